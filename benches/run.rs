@@ -48,7 +48,7 @@ macro_rules! impl_benches {
       let store = init_store();
       let module = decode_module(&bytes);
       let mut vm = instantiate_module(store, module, Default::default(), 65536).unwrap();
-      assert_eq!(vm.run("app_main", vec![]).unwrap(), $expect);
+      assert_eq!(vm.run("app_main", vec![]).unwrap(), vec![$expect]);
       // });
       flame::end($bench_name);
       flame::dump_stdout();
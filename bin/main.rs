@@ -3,10 +3,53 @@ extern crate test;
 extern crate wasvm;
 
 use std::env::args;
+#[cfg(feature = "std")]
+use std::env::vars;
 use std::fs;
 use std::io;
 use std::io::Read;
-use wasvm::{decode_module, init_store, instantiate_module, Values};
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+#[cfg(feature = "std")]
+use std::process::exit;
+#[cfg(feature = "std")]
+use wasvm::{create_wasi, ModuleInstance, ProcExit, Trap, WasmError, WASI_MODULE_NAME};
+use wasvm::{decode_module, init_store, instantiate_module, ExternalModules, Values};
+
+#[cfg(feature = "std")]
+fn external_modules(file_name: &str, arguments: &[String]) -> ExternalModules {
+  let mut wasi_arguments = vec![file_name.to_owned()];
+  wasi_arguments.extend_from_slice(arguments);
+  let mut external_modules = ExternalModules::default();
+  external_modules
+    .register_module(
+      Some(WASI_MODULE_NAME.to_owned()),
+      create_wasi(
+        wasi_arguments,
+        vars().collect(),
+        vec![(".".to_owned(), PathBuf::from("."))],
+      ),
+    )
+    .unwrap();
+  external_modules
+}
+
+#[cfg(not(feature = "std"))]
+fn external_modules(_file_name: &str, _arguments: &[String]) -> ExternalModules {
+  Default::default()
+}
+
+#[cfg(feature = "std")]
+fn run_command(vm: &mut ModuleInstance) -> ! {
+  match vm.run("_start", vec![]) {
+    Ok(_) => exit(0),
+    Err(WasmError::Trap(Trap::HostError(e))) => match e.downcast_ref::<ProcExit>() {
+      Some(ProcExit(code)) => exit(*code as i32),
+      None => panic!("{:?}", e),
+    },
+    Err(e) => panic!("{:?}", e),
+  }
+}
 
 fn main() -> io::Result<()> {
   let arguments = args().collect::<Vec<String>>();
@@ -19,7 +62,14 @@ fn main() -> io::Result<()> {
 
       let store = init_store();
       let module = decode_module(&bytes);
-      let mut vm = instantiate_module(store, module, Default::default(), 65536).unwrap();
+      let external_modules = external_modules(file_name, arguments);
+      let mut vm = instantiate_module(store, module, external_modules, 65536).unwrap();
+      #[cfg(feature = "std")]
+      {
+        if vm.has_export("_start") {
+          run_command(&mut vm);
+        }
+      }
       let result = vm.run(
         "_subject",
         arguments
@@ -29,6 +79,9 @@ fn main() -> io::Result<()> {
           .collect::<Vec<Values>>(),
       );
       println!("{:?}", result);
+      if let Some(trap) = vm.trap_info() {
+        println!("{}", trap);
+      }
     }
     _ => unreachable!("Should specify file-name"),
   };
@@ -3,8 +3,9 @@ set -euxo pipefail
 main() {
 
     if [ $TARGET = x86_64-unknown-linux-gnu ]; then
-        cargo clippy
+        cargo clippy --features std
         cargo test --target $TARGET
+        cargo test --features std --target $TARGET
     else 
       cd discovery
       cargo check --target $TARGET
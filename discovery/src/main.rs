@@ -20,15 +20,30 @@ use cortex_m::asm;
 use cortex_m_rt::{entry, heap_start};
 use cortex_m_semihosting::hprintln;
 use wasvm::{
-    decode_module, init_store, instantiate_module, ExternalModule, ExternalModules,
-    FunctionInstance, FunctionType, ValueTypes, Values,
+    decode_module, init_store, instantiate_module, Caller, ExternalModule, ExternalModules,
+    FunctionInstance, FunctionType, MemoryType, ResourceLimiter, Trap, ValueTypes, Values,
 };
 
 #[global_allocator]
 static ALLOCATOR: CortexMHeap = CortexMHeap::empty();
 
-fn my_hal_function(_arguments: &[Values]) -> alloc::vec::Vec<Values> {
-    [Values::I32(3 * 5)].to_vec()
+// NOTE: Linear memory of the module is placed here rather than on the heap,
+// which is too small to hold its pages.
+static mut LINEAR_MEMORY: [u8; 65536] = [0; 65536];
+
+struct StaticMemory(Option<&'static mut [u8]>);
+
+impl ResourceLimiter for StaticMemory {
+    fn memory_buffer(&mut self, _memory_type: &MemoryType) -> Option<&'static mut [u8]> {
+        self.0.take()
+    }
+}
+
+fn my_hal_function(
+    _caller: &Caller,
+    _arguments: &[Values],
+) -> Result<alloc::vec::Vec<Values>, Trap> {
+    Ok([Values::I32(3 * 5)].to_vec())
 }
 
 #[entry]
@@ -40,7 +55,8 @@ fn main() -> ! {
     hprintln!("Start...").unwrap();
 
     let bytes = include_bytes!("discovery_wasm_bg.wasm");
-    let store = init_store();
+    let memory = unsafe { &mut LINEAR_MEMORY[..] };
+    let store = init_store().with_resource_limiter(StaticMemory(Some(memory)));
     let section = decode_module(bytes);
     let mut external_modules = ExternalModules::default();
     let external_module = ExternalModule::new(
@@ -50,7 +66,7 @@ fn main() -> ! {
                 [ValueTypes::I32, ValueTypes::I32].to_vec(),
                 [ValueTypes::I32].to_vec(),
             ),
-            &my_hal_function,
+            my_hal_function,
         )]
         .to_vec(),
         [].to_vec(),
@@ -59,7 +75,6 @@ fn main() -> ! {
         [].to_vec(),
     );
     external_modules.register_module(Some("./discovery_wasm".to_owned()), external_module).unwrap();
-    // FIXME: Causes OOM.
     let instance = instantiate_module(store, section, external_modules, 128);
     let mut vm = instance.unwrap();
     let result = vm.run(
@@ -0,0 +1,16 @@
+(module
+  (type (;0;) (func (param i32 i32) (result i32)))
+  (type (;1;) (func (result i32)))
+  (import "env" "read_string" (func (;0;) (type 0)))
+  (func (;1;) (type 1) (result i32)
+    i32.const 16
+    i32.const 5
+    call 0
+    i32.const 0
+    i32.load
+    i32.add)
+  (memory (;0;) 1)
+  (global (;0;) (mut i32) (i32.const 0))
+  (export "_subject" (func 1))
+  (export "calls" (global 0))
+  (data (i32.const 16) "hello"))
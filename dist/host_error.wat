@@ -0,0 +1,14 @@
+(module
+  (type (;0;) (func (param i32) (result i32)))
+  (import "env" "checked_double" (func (;0;) (type 0)))
+  (func (;1;) (type 0) (param i32) (result i32)
+    get_local 0
+    call 0)
+  (func (;2;) (type 0) (param i32) (result i32)
+    get_local 0
+    i32.const 0
+    call_indirect (type 0))
+  (table (;0;) 1 anyfunc)
+  (export "direct" (func 1))
+  (export "indirect" (func 2))
+  (elem (i32.const 0) 0))
@@ -0,0 +1,39 @@
+(module
+  (type (;0;) (func (param i32) (result i32 i32)))
+  (type (;1;) (func (result i32 i32)))
+  (type (;2;) (func (param i32 i32) (result i32 i32)))
+  (func (;0;) (type 0) (param i32) (result i32 i32)
+    get_local 0
+    block (type 0)
+      i32.const 10
+    end)
+  (func (;1;) (type 0) (param i32) (result i32 i32)
+    block (type 1)
+      i32.const 99
+      get_local 0
+      get_local 0
+      br 0
+    end)
+  (func (;2;) (type 2) (param i32 i32) (result i32 i32)
+    get_local 0
+    get_local 1
+    loop (type 2)
+      set_local 1
+      tee_local 0
+      i32.eqz
+      if
+        get_local 0
+        get_local 1
+        br 2
+      end
+      get_local 0
+      i32.const 1
+      i32.sub
+      get_local 1
+      get_local 0
+      i32.add
+      br 0
+    end)
+  (export "block_params" (func 0))
+  (export "branch_values" (func 1))
+  (export "loop_params" (func 2)))
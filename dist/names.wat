@@ -0,0 +1,8 @@
+(module $names
+  (type (;0;) (func (param i32) (result i32)))
+  (func $subject (type 0) (param $n i32) (result i32)
+    get_local $n
+    call $boom)
+  (func $boom (type 0) (param i32) (result i32)
+    unreachable)
+  (export "_subject" (func $subject)))
@@ -0,0 +1,54 @@
+(module
+  (type (;0;) (func (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+  (type (;1;) (func (param i32 i32 i32 i32) (result i32)))
+  (type (;2;) (func (param i32) (result i32)))
+  (type (;3;) (func (param i32)))
+  (type (;4;) (func (param i32 i32) (result i32)))
+  (type (;5;) (func))
+  (import "wasi_snapshot_preview1" "path_open" (func (;0;) (type 0)))
+  (import "wasi_snapshot_preview1" "fd_write" (func (;1;) (type 1)))
+  (import "wasi_snapshot_preview1" "fd_close" (func (;2;) (type 2)))
+  (import "wasi_snapshot_preview1" "proc_exit" (func (;3;) (type 3)))
+  (import "wasi_snapshot_preview1" "args_sizes_get" (func (;4;) (type 4)))
+  (func (;5;) (type 5)
+    i32.const 3
+    i32.const 0
+    i32.const 64
+    i32.const 7
+    i32.const 9
+    i64.const 64
+    i64.const 0
+    i32.const 0
+    i32.const 0
+    call 0
+    if
+      i32.const 1
+      call 3
+    end
+    i32.const 0
+    i32.load
+    i32.const 96
+    i32.const 1
+    i32.const 4
+    call 1
+    if
+      i32.const 2
+      call 3
+    end
+    i32.const 0
+    i32.load
+    call 2
+    drop
+    i32.const 8
+    i32.const 12
+    call 4
+    drop
+    i32.const 8
+    i32.load
+    call 3)
+  (memory (;0;) 1)
+  (export "memory" (memory 0))
+  (export "_start" (func 5))
+  (data (i32.const 64) "out.txt")
+  (data (i32.const 80) "hello wasi\0a")
+  (data (i32.const 96) "P\00\00\00\0b\00\00\00"))
@@ -0,0 +1,125 @@
+use error::Trap;
+use function::FunctionInstance;
+use global::GlobalInstance;
+use memory::MemoryInstance;
+use module::ExportDescriptor;
+use store::ModuleRef;
+use table::TableInstance;
+use value::Values;
+
+/// Memory of the caller, by its index or by the name it is exported as.
+#[derive(Debug, Clone, Copy)]
+pub enum MemoryRef<'a> {
+  Index(u32),
+  Export(&'a str),
+}
+
+impl<'a> From<u32> for MemoryRef<'a> {
+  fn from(idx: u32) -> Self {
+    MemoryRef::Index(idx)
+  }
+}
+
+impl<'a> From<&'a str> for MemoryRef<'a> {
+  fn from(name: &'a str) -> Self {
+    MemoryRef::Export(name)
+  }
+}
+
+/// Context of a host function call,
+/// which exposes instances owned by the module calling it.
+pub struct Caller {
+  module: ModuleRef,
+}
+
+impl Caller {
+  pub(crate) fn new(module: ModuleRef) -> Self {
+    Caller { module }
+  }
+
+  /// Size of linear memory by pages, or None if the caller doesn't have it.
+  pub fn memory_size<'a, M: Into<MemoryRef<'a>>>(&self, memory: M) -> Option<u32> {
+    self
+      .memory(memory)
+      .map(|memory_instance| memory_instance.size_by_pages())
+  }
+
+  /// Size of linear memory by bytes, or None if the caller doesn't have it.
+  pub fn memory_data_size<'a, M: Into<MemoryRef<'a>>>(&self, memory: M) -> Option<u32> {
+    self
+      .memory(memory)
+      .map(|memory_instance| memory_instance.data_size())
+  }
+
+  pub fn memory<'a, M: Into<MemoryRef<'a>>>(&self, memory: M) -> Option<MemoryInstance> {
+    match memory.into() {
+      MemoryRef::Index(idx) => self.module.memory_instance(idx),
+      MemoryRef::Export(name) => match self.module.export(name)? {
+        ExportDescriptor::Memory(idx) => self.module.memory_instance(idx.to_u32()),
+        _ => None,
+      },
+    }
+  }
+
+  pub fn read_memory<'a, M: Into<MemoryRef<'a>>>(
+    &self,
+    memory: M,
+    offset: u32,
+    buf: &mut [u8],
+  ) -> Result<(), Trap> {
+    self
+      .memory(memory)
+      .ok_or(Trap::MemoryAccessOutOfBounds)?
+      .read_bytes(offset, buf)?;
+    Ok(())
+  }
+
+  pub fn write_memory<'a, M: Into<MemoryRef<'a>>>(
+    &self,
+    memory: M,
+    offset: u32,
+    bytes: &[u8],
+  ) -> Result<(), Trap> {
+    self
+      .memory(memory)
+      .ok_or(Trap::MemoryAccessOutOfBounds)?
+      .write_bytes(offset, bytes)?;
+    Ok(())
+  }
+
+  /// Find a function which the caller exports by its name.
+  pub fn function(&self, name: &str) -> Option<FunctionInstance> {
+    match self.module.export(name)? {
+      ExportDescriptor::Function(idx) => self.module.function_instance(idx.to_u32()),
+      _ => None,
+    }
+  }
+
+  /// Find a table which the caller exports by its name.
+  pub fn table(&self, name: &str) -> Option<TableInstance> {
+    match self.module.export(name)? {
+      ExportDescriptor::Table(idx) => self.module.table_instance(idx.to_u32()),
+      _ => None,
+    }
+  }
+
+  /// Find a global which the caller exports by its name.
+  pub fn get_global(&self, name: &str) -> Option<Values> {
+    self
+      .global_instance(name)
+      .map(|global_instance| global_instance.get_value())
+  }
+
+  pub fn set_global(&self, name: &str, value: Values) -> Result<(), Trap> {
+    let global_instance = self.global_instance(name).ok_or(Trap::Notfound)?;
+    global_instance.set(value)?;
+    Ok(())
+  }
+
+  pub fn global_instance(&self, name: &str) -> Option<GlobalInstance> {
+    match self.module.export(name)? {
+      ExportDescriptor::Global(idx) => self.module.global_instance(idx.to_u32()),
+      _ => None,
+    }
+  }
+}
@@ -6,6 +6,9 @@ use core::convert::TryFrom;
 use core::default::Default;
 use error::{Result, WasmError, Trap};
 
+// NOTE: Magic words and version dropped at `new_with_drop`.
+const SIZE_OF_HEADER: usize = 8;
+
 impl_decodable!(Byte);
 impl Leb128Decodable for Byte {}
 impl U32Decodable for Byte {}
@@ -44,7 +47,8 @@ impl Byte {
     if end > self.bytes.len() {
       return Err(WasmError::Trap(Trap::LengthOutofBounds));
     }
-    let bytes = self.bytes.drain(start..end).collect::<Vec<_>>();
+    let bytes = self.bytes[start..end].to_vec();
+    self.byte_ptr = end;
     Ok(bytes)
   }
 
@@ -57,7 +61,10 @@ impl Byte {
       match code {
         Type => section.function_types(&mut sec_type::Section::new(bytes).decode()?),
         Function => section.functions(&mut sec_function::Section::new(bytes).decode()?),
-        Code => section.codes(&mut sec_code::Section::new(bytes).decode()?),
+        Code => {
+          let offset_of_section = (SIZE_OF_HEADER + self.byte_ptr - bytes.len()) as u32;
+          section.codes(&mut sec_code::Section::new(bytes).decode_at(offset_of_section)?)
+        }
         Data => section.datas(&mut sec_data::Section::new(bytes).decode()?),
         Memory => section.limits(&mut sec_memory::Section::new(bytes).decode()?),
         Table => section.tables(&mut sec_table::Section::new(bytes).decode()?),
@@ -67,8 +74,15 @@ impl Byte {
         Export => section.exports(sec_export::Section::new(bytes).decode()?),
         Import => section.imports(sec_import::Section::new(bytes).decode()?),
         Start => section.start(sec_start::Section::new(bytes).decode()?),
+        DataCount => section.data_count(sec_data_count::Section::new(bytes).decode()?),
+        Tag => section.tags(&mut sec_tag::Section::new(bytes).decode()?),
       };
     }
+    if let Some(data_count) = section.data_count {
+      if data_count as usize != section.datas.len() {
+        return Err(WasmError::Trap(Trap::DataCountAndDataInconsistent));
+      }
+    }
     Ok(section)
   }
 }
@@ -92,15 +106,12 @@ mod tests {
         let mut file = File::open(format!("./{}.wasm", $file_name)).unwrap();
         let mut buffer = vec![];
         let _ = file.read_to_end(&mut buffer);
-        let mut store = init_store();
-        decode_module(&buffer)
+        let store = init_store();
+        let (module, _) = decode_module(&buffer)
           .unwrap()
-          .complete(&ExternalModules::default(), &mut store)
+          .complete(&ExternalModules::default(), &store)
           .unwrap();
-        assert_eq!(
-          store.get_function_instance(&From::from(0u32)).unwrap(),
-          $fn_insts
-        );
+        assert_eq!(module.function_instance(0).unwrap(), $fn_insts);
       }
     };
   }
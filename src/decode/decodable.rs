@@ -1,7 +1,7 @@
 use alloc::string::String;
 use alloc::vec::Vec;
 use error::{Result, Trap, WasmError};
-use memory::Limit;
+use memory::{Limit, MemoryType};
 
 macro_rules! impl_decode_leb128 {
   ($ty: ty, $conv_fn: path, $fn_name: ident) => {
@@ -88,6 +88,16 @@ pub trait U32Decodable: Leb128Decodable {
     let (buf, _) = self.decode_leb128_u32_internal()?;
     Ok(buf)
   }
+
+  // NOTE: Every item of a vector takes a byte at least,
+  // so that a hostile count can't allocate beyond the size of the module.
+  fn decode_count(&mut self) -> Result<u32> {
+    let count = self.decode_leb128_u32()?;
+    if count as usize > self.bytes().len().saturating_sub(self.byte_ptr()) {
+      return Err(WasmError::Trap(Trap::LengthOutofBounds));
+    }
+    Ok(count)
+  }
 }
 
 pub trait SignedIntegerDecodable: Leb128Decodable {
@@ -97,8 +107,13 @@ pub trait SignedIntegerDecodable: Leb128Decodable {
 
 pub trait LimitDecodable: U32Decodable {
   fn decode_limit(&mut self) -> Result<Limit> {
+    let flag = self.next();
+    self.decode_limit_by_flag(flag)
+  }
+
+  fn decode_limit_by_flag(&mut self, flag: Option<u8>) -> Result<Limit> {
     use self::Limit::*;
-    match self.next() {
+    match flag {
       Some(0x0) => {
         let min = self.decode_leb128_u32()?;
         Ok(NoUpperLimit(min))
@@ -108,9 +123,39 @@ pub trait LimitDecodable: U32Decodable {
         let max = self.decode_leb128_u32()?;
         Ok(HasUpperLimit(min, max))
       }
+      Some(0x3) => {
+        let min = self.decode_leb128_u32()?;
+        let max = self.decode_leb128_u32()?;
+        Ok(Shared(min, max))
+      }
+      // NOTE: Shared memory must have its maximum.
+      Some(0x2) => Err(WasmError::Trap(Trap::Unknown)),
       x => unreachable!("Expected limit code, got {:?}", x),
     }
   }
+
+  // NOTE: Bit 3 of the flag tells that the page size follows as its exponent of 2.
+  fn decode_memory_type(&mut self) -> Result<MemoryType> {
+    let flag = self.next();
+    let limit = self.decode_limit_by_flag(flag.map(|x| x & !0x08))?;
+    match flag {
+      Some(x) if x & 0x08 != 0 => {
+        let page_size = 1u32
+          .checked_shl(self.decode_leb128_u32()?)
+          .ok_or(WasmError::Trap(Trap::Unknown))?;
+        Ok(MemoryType::new(limit, page_size))
+      }
+      _ => Ok(MemoryType::from(limit)),
+    }
+  }
+
+  // NOTE: Only memory can be shared.
+  fn decode_table_limit(&mut self) -> Result<Limit> {
+    match self.decode_limit()? {
+      Limit::Shared(_, _) => Err(WasmError::Trap(Trap::Unknown)),
+      limit => Ok(limit),
+    }
+  }
 }
 
 pub trait NameDecodable: U32Decodable {
@@ -1,7 +1,10 @@
 use super::decodable::{Peekable, SignedIntegerDecodable, U32Decodable};
+use super::source_map::SourceMap;
 use alloc::vec::Vec;
+use core::convert::TryFrom;
 use error::{Result, WasmError, Trap};
-use isa::Isa;
+use isa::{AtomicIsa, Isa, MiscIsa, SimdIsa};
+use value_type::BLOCK_TYPE_INDEX;
 
 macro_rules! impl_decode_float {
   ($buf_ty: ty, $fn_name: ident, $bitwidth: expr) => {
@@ -32,13 +35,18 @@ pub trait InstructionDecodable: U32Decodable + Peekable + SignedIntegerDecodable
   impl_push_raw_bytes!(push_u32_as_bytes, u32, 4);
   impl_push_raw_bytes!(push_u64_as_bytes, u64, 8);
 
-  fn decode_memory_parameter(&mut self) -> Result<(u32, u32)> {
+  // NOTE: Bit 6 of alignment tells an explicit memory index follows.
+  fn decode_memory_parameter(&mut self) -> Result<(u32, u32, u32)> {
     let align = self.decode_leb128_u32();
+    let memory_idx = match align {
+      Ok(align) if align & 0x40 != 0 => self.decode_leb128_u32(),
+      _ => Ok(0),
+    };
     let offset = self.decode_leb128_u32();
-    match (align, offset) {
-      (Ok(align), Ok(offset)) => Ok((align as u32, offset as u32)),
-      (Err(WasmError::Trap(Trap::BitshiftOverflow)), _)
-      | (_, Err(WasmError::Trap(Trap::BitshiftOverflow))) => {
+    match (align, memory_idx, offset) {
+      (Ok(align), Ok(memory_idx), Ok(offset)) => Ok((align & !0x40, offset, memory_idx)),
+      (Err(WasmError::Trap(Trap::BitshiftOverflow)), _, _)
+      | (_, _, Err(WasmError::Trap(Trap::BitshiftOverflow))) => {
         Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds))
       }
       _ => Err(WasmError::Trap(Trap::Unknown)),
@@ -46,60 +54,152 @@ pub trait InstructionDecodable: U32Decodable + Peekable + SignedIntegerDecodable
   }
 
   fn decode_memory(&mut self, inst: u8, expressions: &mut Vec<u8>) -> Result<()> {
-    let (align, offset) = self.decode_memory_parameter()?;
+    let (align, offset, memory_idx) = self.decode_memory_parameter()?;
     expressions.push(inst);
     self.push_u32_as_bytes(align, expressions);
     self.push_u32_as_bytes(offset, expressions);
+    self.push_u32_as_bytes(memory_idx, expressions);
     Ok(())
   }
 
+  // NOTE: Block type is either a single value type or an index of function type encoded as s33.
+  fn decode_block_type(&mut self) -> Result<Vec<u8>> {
+    match self.peek()? {
+      0x40 | 0x7f | 0x7e | 0x7d | 0x7c | 0x7b | 0x70 | 0x6f => Ok(vec![self.next()?]),
+      _ => {
+        let idx = self.decode_leb128_i64()? as i64;
+        if idx < 0 || idx > i64::from(u32::max_value()) {
+          return Err(WasmError::Trap(Trap::Unknown));
+        }
+        let mut block_type = vec![BLOCK_TYPE_INDEX];
+        self.push_u32_as_bytes(idx as u32, &mut block_type);
+        Ok(block_type)
+      }
+    }
+  }
+
   fn decode_instructions(&mut self) -> Result<Vec<u8>> {
+    let (expressions, _) = self.decode_instructions_with_source_map()?;
+    Ok(expressions)
+  }
+
+  fn decode_instructions_with_source_map(&mut self) -> Result<(Vec<u8>, SourceMap)> {
+    let (mut expressions, source_map) = self.decode_instructions_until_end()?;
+    let end_code = self.next()?;
+    match Isa::from(end_code) {
+      Isa::Else | Isa::End => expressions.push(end_code),
+      _ => return Err(WasmError::Trap(Trap::IllegalOpcode)),
+    }
+    Ok((expressions, source_map))
+  }
+
+  // NOTE: Terminator of instructions is left to be consumed by the caller.
+  fn decode_instructions_until_end(&mut self) -> Result<(Vec<u8>, SourceMap)> {
     use self::Isa::*;
     let mut expressions = vec![];
-    while !Isa::is_else_or_end(self.peek()) {
+    let mut source_map = SourceMap::default();
+    while !Isa::is_end_of_block(self.peek()) {
+      source_map.push(expressions.len() as u32, self.byte_ptr() as u32);
       let code = self.next()?;
       match Isa::from(code) {
         // NOTE: Else and End are already consumed at decoding "If" instructions.
-        Reserved | End | Else => unreachable!("{:?}", code),
+        Reserved | End | Else | Catch | CatchAll | Delegate => unreachable!("{:?}", code),
         Unreachable | Nop | Return | DropInst => expressions.push(code),
 
         Block => {
-          let block_type = self.next()?;
-          let mut instructions = self.decode_instructions()?;
-          let size =
-            (2 /* Block inst + Type of block */ + 4 /* size of size */ + instructions.len()) as u32;
+          let mut block_type = self.decode_block_type()?;
+          let (mut instructions, inner) = self.decode_instructions_with_source_map()?;
+          let size = (1 /* Block inst */ + 4 /* size of size */ + block_type.len()
+            + instructions.len()) as u32;
           expressions.push(code);
           self.push_u32_as_bytes(size, &mut expressions);
-          expressions.push(block_type);
+          expressions.append(&mut block_type);
+          source_map.append(inner, expressions.len() as u32);
           expressions.append(&mut instructions);
         }
         Loop => {
-          let block_type = self.next()?;
-          let mut instructions = self.decode_instructions()?;
+          let mut block_type = self.decode_block_type()?;
+          let (mut instructions, inner) = self.decode_instructions_with_source_map()?;
           expressions.push(code);
-          expressions.push(block_type);
+          expressions.append(&mut block_type);
+          source_map.append(inner, expressions.len() as u32);
           expressions.append(&mut instructions);
         }
         If => {
-          let block_type = self.next()?;
-          let mut if_insts = self.decode_instructions()?;
+          let mut block_type = self.decode_block_type()?;
+          let (mut if_insts, if_source_map) = self.decode_instructions_with_source_map()?;
           let last = *if_insts.last()?;
-          let mut else_insts = match Isa::from(last) {
-            Else => self.decode_instructions()?,
-            End => vec![],
-            x => unreachable!("{:?}", x),
+          let (mut else_insts, else_source_map) = match Isa::from(last) {
+            Else => self.decode_instructions_with_source_map()?,
+            End => (vec![], SourceMap::default()),
+            _ => return Err(WasmError::Trap(Trap::IllegalOpcode)),
           };
-          let size_of_if = (2 /* If inst + Type of block */ + 8 + if_insts.len()) as u32;
+          let size_of_if =
+            (1 /* If inst */ + 8 + block_type.len() + if_insts.len()) as u32;
           let size_of_else = else_insts.len() as u32;
           expressions.push(code);
           self.push_u32_as_bytes(size_of_if, &mut expressions);
           self.push_u32_as_bytes(size_of_else, &mut expressions);
-          expressions.push(block_type);
+          expressions.append(&mut block_type);
+          source_map.append(if_source_map, expressions.len() as u32);
           expressions.append(&mut if_insts);
+          source_map.append(else_source_map, expressions.len() as u32);
           expressions.append(&mut else_insts);
         }
+        // NOTE: Handlers are gathered in front of instructions,
+        // so that unwinding can find them without scanning the body.
+        Try => {
+          let mut block_type = self.decode_block_type()?;
+          let mut instructions = vec![];
+          let mut inner_source_maps = vec![];
+          let mut handlers = vec![];
+          let mut delegate = u32::max_value();
+          loop {
+            let (mut body, inner) = self.decode_instructions_until_end()?;
+            inner_source_maps.push((inner, instructions.len()));
+            instructions.append(&mut body);
+            let clause = self.next()?;
+            let is_after_catch_all = handlers.last().map(|(tag, _)| *tag) == Some(u32::max_value());
+            instructions.push(clause);
+            match Isa::from(clause) {
+              Catch | CatchAll if is_after_catch_all => {
+                return Err(WasmError::Trap(Trap::IllegalOpcode))
+              }
+              Catch => {
+                let tag_idx = self.decode_leb128_u32()?;
+                self.push_u32_as_bytes(tag_idx, &mut instructions);
+                handlers.push((tag_idx, instructions.len()));
+              }
+              CatchAll => handlers.push((u32::max_value(), instructions.len())),
+              Delegate if handlers.is_empty() => {
+                delegate = self.decode_leb128_u32()?;
+                self.push_u32_as_bytes(delegate, &mut instructions);
+                break;
+              }
+              End => break,
+              _ => return Err(WasmError::Trap(Trap::IllegalOpcode)),
+            }
+          }
+          let size_of_header = 1 /* Try inst */ + 4 /* size of size */ + block_type.len()
+            + 4 /* count of handlers */ + 8 * handlers.len() + 4 /* delegate */;
+          expressions.push(code);
+          self.push_u32_as_bytes((size_of_header + instructions.len()) as u32, &mut expressions);
+          expressions.append(&mut block_type);
+          self.push_u32_as_bytes(handlers.len() as u32, &mut expressions);
+          for (tag_idx, offset) in handlers {
+            self.push_u32_as_bytes(tag_idx, &mut expressions);
+            self.push_u32_as_bytes((size_of_header + offset) as u32, &mut expressions);
+          }
+          self.push_u32_as_bytes(delegate, &mut expressions);
+          let start_of_instructions = expressions.len();
+          for (inner, offset) in inner_source_maps {
+            source_map.append(inner, (start_of_instructions + offset) as u32);
+          }
+          expressions.append(&mut instructions);
+        }
 
-        GetLocal | SetLocal | TeeLocal | GetGlobal | SetGlobal | Br | BrIf | Call => {
+        GetLocal | SetLocal | TeeLocal | GetGlobal | SetGlobal | Br | BrIf | Call | ReturnCall
+        | TableGet | TableSet | RefFunc | Throw | Rethrow => {
           expressions.push(code);
           let idx = self.decode_leb128_u32()?;
           self.push_u32_as_bytes(idx, &mut expressions);
@@ -116,11 +216,27 @@ pub trait InstructionDecodable: U32Decodable + Peekable + SignedIntegerDecodable
           let idx = self.decode_leb128_u32()?;
           self.push_u32_as_bytes(idx, &mut expressions);
         }
-        CallIndirect => {
+        CallIndirect | ReturnCallIndirect => {
           expressions.push(code);
           let idx = self.decode_leb128_u32()?;
           self.push_u32_as_bytes(idx, &mut expressions);
-          self.next(); // Drop code 0x00.
+          let table_idx = self.decode_leb128_u32()?;
+          self.push_u32_as_bytes(table_idx, &mut expressions);
+        }
+        SelectTyped => {
+          expressions.push(code);
+          let len = self.decode_leb128_u32()?;
+          self.push_u32_as_bytes(len, &mut expressions);
+          for _ in 0..len {
+            expressions.push(self.next()?);
+          }
+        }
+        RefNull => {
+          expressions.push(code);
+          match self.next()? {
+            reference_type @ 0x70 | reference_type @ 0x6f => expressions.push(reference_type),
+            _ => return Err(WasmError::Trap(Trap::Unknown)),
+          }
         }
 
         I32Const => {
@@ -152,8 +268,89 @@ pub trait InstructionDecodable: U32Decodable + Peekable + SignedIntegerDecodable
         }
 
         MemorySize | MemoryGrow => {
-          self.next()?; // Drop 0x00;
+          let memory_idx = self.decode_leb128_u32()?;
+          expressions.push(code);
+          self.push_u32_as_bytes(memory_idx, &mut expressions);
+        }
+
+        MiscPrefix => {
+          let misc_code = self.decode_leb128_u32()?;
+          let misc_isa = MiscIsa::try_from(misc_code)?;
+          expressions.push(code);
+          self.push_u32_as_bytes(misc_code, &mut expressions);
+          match misc_isa {
+            MiscIsa::MemoryInit
+            | MiscIsa::MemoryCopy
+            | MiscIsa::TableInit
+            | MiscIsa::TableCopy => {
+              for _ in 0..2 {
+                let idx = self.decode_leb128_u32()?;
+                self.push_u32_as_bytes(idx, &mut expressions);
+              }
+            }
+            MiscIsa::DataDrop
+            | MiscIsa::MemoryFill
+            | MiscIsa::ElemDrop
+            | MiscIsa::TableGrow
+            | MiscIsa::TableSize
+            | MiscIsa::TableFill => {
+              let idx = self.decode_leb128_u32()?;
+              self.push_u32_as_bytes(idx, &mut expressions);
+            }
+            _ => {}
+          }
+        }
+
+        SimdPrefix => {
+          let simd_code = self.decode_leb128_u32()?;
+          let simd_isa = SimdIsa::try_from(simd_code)?;
           expressions.push(code);
+          self.push_u32_as_bytes(simd_code, &mut expressions);
+          match simd_isa {
+            SimdIsa::Load
+            | SimdIsa::LoadExtend(_, _)
+            | SimdIsa::LoadSplat(_)
+            | SimdIsa::LoadZero(_)
+            | SimdIsa::Store
+            | SimdIsa::LoadLane(_)
+            | SimdIsa::StoreLane(_) => {
+              let (align, offset, memory_idx) = self.decode_memory_parameter()?;
+              self.push_u32_as_bytes(align, &mut expressions);
+              self.push_u32_as_bytes(offset, &mut expressions);
+              self.push_u32_as_bytes(memory_idx, &mut expressions);
+            }
+            _ => {}
+          };
+          match simd_isa {
+            SimdIsa::Const | SimdIsa::Shuffle => {
+              for _ in 0..16 {
+                expressions.push(self.next()?);
+              }
+            }
+            SimdIsa::LoadLane(_)
+            | SimdIsa::StoreLane(_)
+            | SimdIsa::ExtractLane(_, _)
+            | SimdIsa::ReplaceLane(_) => {
+              let lane = u32::from(self.next()?);
+              self.push_u32_as_bytes(lane, &mut expressions);
+            }
+            _ => {}
+          };
+        }
+
+        AtomicPrefix => {
+          let atomic_code = self.decode_leb128_u32()?;
+          let atomic_isa = AtomicIsa::try_from(atomic_code)?;
+          expressions.push(code);
+          self.push_u32_as_bytes(atomic_code, &mut expressions);
+          if atomic_isa == AtomicIsa::Fence {
+            self.next()?; // Drop 0x00;
+          } else {
+            let (align, offset, memory_idx) = self.decode_memory_parameter()?;
+            self.push_u32_as_bytes(align, &mut expressions);
+            self.push_u32_as_bytes(offset, &mut expressions);
+            self.push_u32_as_bytes(memory_idx, &mut expressions);
+          }
         }
 
         I32CountLeadingZero
@@ -279,14 +476,15 @@ pub trait InstructionDecodable: U32Decodable + Peekable + SignedIntegerDecodable
         | I64ReinterpretF64
         | F32ReinterpretI32
         | F64ReinterpretI64
+        | I32Extend8Sign
+        | I32Extend16Sign
+        | I64Extend8Sign
+        | I64Extend16Sign
+        | I64Extend32Sign
+        | RefIsNull
         | Select => expressions.push(code),
       };
     }
-    let end_code = self.next()?;
-    match Isa::from(end_code) {
-      Else | End => expressions.push(end_code),
-      x => unreachable!("{:?}", x),
-    }
-    Ok(expressions)
+    Ok((expressions, source_map))
   }
 }
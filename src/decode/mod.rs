@@ -6,20 +6,26 @@ mod instruction;
 mod sec_code;
 mod sec_custom;
 mod sec_data;
+mod sec_data_count;
 mod sec_element;
 mod sec_export;
 mod sec_function;
 mod sec_global;
 mod sec_import;
 mod sec_memory;
+mod sec_name;
 mod sec_start;
 mod sec_table;
+mod sec_tag;
 mod sec_type;
 mod section;
+mod source_map;
 
 pub use self::byte::Byte;
 pub use self::decodable::{AbstractDecodable, U8Iterator};
 pub use self::sec_data::Data;
-pub use self::sec_element::{Element, ElementType};
+pub use self::sec_element::{Element, ElementInit, ElementMode, ElementType};
+pub use self::sec_name::Names;
 pub use self::sec_table::TableType;
 pub use self::section::Module;
+pub use self::source_map::SourceMap;
@@ -2,11 +2,15 @@ use super::decodable::{
   Decodable, Leb128Decodable, Peekable, SignedIntegerDecodable, U32Decodable, U8Iterator,
 };
 use super::instruction::InstructionDecodable;
+use super::source_map::SourceMap;
 use alloc::vec::Vec;
 use core::convert::From;
-use error::Result;
+use error::{Result, Trap, WasmError};
 use value_type::ValueTypes;
 
+// NOTE: Upper bound of locals of a function, as same as other engines.
+const MAXIMUM_LOCALS: u32 = 50_000;
+
 impl_decodable!(Section);
 
 impl Peekable for Section {}
@@ -15,26 +19,47 @@ impl U32Decodable for Section {}
 impl SignedIntegerDecodable for Section {}
 impl InstructionDecodable for Section {}
 
+impl Section {
+  // NOTE: Offsets decoded are relative to the section, until relocated to the ones in the module.
+  pub fn decode_at(&mut self, offset_of_section: u32) -> Result<<Self as Decodable>::Item> {
+    let mut codes = self.decode()?;
+    for code in codes.iter_mut() {
+      if let Ok((_, _, source_map)) = code {
+        source_map.relocate(offset_of_section);
+      }
+    }
+    Ok(codes)
+  }
+}
+
 impl Decodable for Section {
   // FIXME:
-  type Item = Vec<Result<(Vec<u8>, Vec<ValueTypes>)>>;
+  type Item = Vec<Result<(Vec<u8>, Vec<ValueTypes>, SourceMap)>>;
   fn decode(&mut self) -> Result<Self::Item> {
-    let count_of_section = self.decode_leb128_u32()?;
+    let count_of_section = self.decode_count()?;
     (0..count_of_section)
       .map(|_| {
         let size_of_function = self.decode_leb128_u32()?;
         let end_of_function = self.byte_ptr + (size_of_function as usize);
-        let count_of_locals = self.decode_leb128_u32()? as usize;
-        let mut locals: Vec<ValueTypes> = Vec::with_capacity(count_of_locals);
+        let count_of_locals = self.decode_count()?;
+        let mut declarations = Vec::with_capacity(count_of_locals as usize);
+        let mut total = 0u32;
         for _ in 0..count_of_locals {
           let count_of_type = self.decode_leb128_u32()?;
-          let value_type = ValueTypes::from(self.next()?);
+          total = match total.checked_add(count_of_type) {
+            Some(total) if total <= MAXIMUM_LOCALS => total,
+            _ => return Err(WasmError::Trap(Trap::LengthOutofBounds)),
+          };
+          declarations.push((count_of_type, ValueTypes::from(self.next()?)));
+        }
+        let mut locals: Vec<ValueTypes> = Vec::with_capacity(total as usize);
+        for (count_of_type, value_type) in declarations.into_iter() {
           for _ in 0..count_of_type {
             locals.push(value_type.clone());
           }
         }
-        Ok(match self.decode_instructions() {
-          Ok(expressions) => Ok((expressions, locals)),
+        Ok(match self.decode_instructions_with_source_map() {
+          Ok((expressions, source_map)) => Ok((expressions, locals, source_map)),
           Err(err) => {
             self.byte_ptr = end_of_function;
             Err(err)
@@ -3,17 +3,18 @@ use super::decodable::{
 };
 use super::instruction::InstructionDecodable;
 use alloc::vec::Vec;
-use error::Result;
+use error::{Result, Trap, WasmError};
 
 #[derive(Debug)]
 pub struct Data {
   pub memidx: u32,
-  pub offset: Vec<u8>,
+  // NOTE: None means passive segment, which is copied only by `memory.init`.
+  pub offset: Option<Vec<u8>>,
   pub init: Vec<u8>,
 }
 
 impl Data {
-  pub fn new(memidx: u32, offset: Vec<u8>, init: Vec<u8>) -> Self {
+  pub fn new(memidx: u32, offset: Option<Vec<u8>>, init: Vec<u8>) -> Self {
     Data {
       memidx,
       offset,
@@ -26,6 +27,9 @@ impl Data {
   pub fn get_init(self) -> Vec<u8> {
     self.init
   }
+  pub fn is_passive(&self) -> bool {
+    self.offset.is_none()
+  }
 }
 
 impl_decodable!(Section);
@@ -38,11 +42,15 @@ impl InstructionDecodable for Section {}
 impl Decodable for Section {
   type Item = Vec<Data>;
   fn decode(&mut self) -> Result<Self::Item> {
-    let count_of_section = self.decode_leb128_u32()?;
+    let count_of_section = self.decode_count()?;
     (0..count_of_section)
       .map(|_| {
-        let memidx = self.decode_leb128_u32()?;
-        let offset = self.decode_instructions()?;
+        let (memidx, offset) = match self.decode_leb128_u32()? {
+          0x0 => (0, Some(self.decode_instructions()?)),
+          0x1 => (0, None),
+          0x2 => (self.decode_leb128_u32()?, Some(self.decode_instructions()?)),
+          _ => return Err(WasmError::Trap(Trap::Unknown)),
+        };
         let size_of_data = self.decode_leb128_u32()?;
         let mut init = vec![];
         for _ in 0..size_of_data {
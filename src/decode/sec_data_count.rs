@@ -0,0 +1,15 @@
+use super::decodable::{Decodable, Leb128Decodable, U32Decodable};
+use alloc::vec::Vec;
+use error::Result;
+
+impl_decodable!(Section);
+impl Leb128Decodable for Section {}
+impl U32Decodable for Section {}
+
+impl Decodable for Section {
+  type Item = u32;
+  fn decode(&mut self) -> Result<Self::Item> {
+    let count_of_datas = self.decode_leb128_u32()?;
+    Ok(count_of_datas)
+  }
+}
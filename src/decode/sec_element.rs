@@ -1,62 +1,120 @@
 use super::decodable::{
-  Decodable, Leb128Decodable, Peekable, SignedIntegerDecodable, U32Decodable,
+  Decodable, Leb128Decodable, Peekable, SignedIntegerDecodable, U32Decodable, U8Iterator,
 };
 use super::instruction::InstructionDecodable;
 use alloc::vec::Vec;
-use error::Result;
+use error::{Result, Trap, WasmError};
 use function::FunctionInstance;
+use global::GlobalInstances;
 use indice::Indice;
+use isa::Isa;
+use value::Values;
+use value_type::ValueTypes;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ElementMode {
+  // NOTE: Index of table and offset expression.
+  Active(Indice, Vec<u8>),
+  Passive,
+  Declarative,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ElementInit {
+  Functions(Vec<Indice>),
+  // NOTE: Each of constant expressions produces a reference.
+  Expressions(Vec<Vec<u8>>),
+}
 
 #[derive(Debug, Clone)]
 pub struct Element {
-  pub(crate) table_idx: Indice,
-  pub(crate) offset: Vec<u8>,
-  pub(crate) init: Vec<Indice>,
+  pub(crate) mode: ElementMode,
+  pub(crate) element_type: ElementType,
+  pub(crate) init: ElementInit,
 }
 
 impl Element {
-  pub fn new(table_idx: Indice, offset: Vec<u8>, init: Vec<Indice>) -> Self {
+  pub fn new(mode: ElementMode, element_type: ElementType, init: ElementInit) -> Self {
     Element {
-      table_idx,
-      offset,
+      mode,
+      element_type,
       init,
     }
   }
 
-  pub fn get_table_idx(&self) -> &Indice {
-    &self.table_idx
+  pub fn get_table_idx(&self) -> Option<&Indice> {
+    match &self.mode {
+      ElementMode::Active(table_idx, _) => Some(table_idx),
+      _ => None,
+    }
   }
 
-  pub fn move_init_to(&self) -> Vec<Indice> {
-    self.init.clone()
+  pub fn is_active(&self) -> bool {
+    self.get_table_idx().is_some()
   }
 
-  pub(crate) fn wrap_by_option(
+  pub fn len(&self) -> usize {
+    match &self.init {
+      ElementInit::Functions(xs) => xs.len(),
+      ElementInit::Expressions(xs) => xs.len(),
+    }
+  }
+
+  pub(crate) fn references(
     &self,
+    global_instances: &GlobalInstances,
     function_instances: &[FunctionInstance],
-  ) -> Vec<Option<FunctionInstance>> {
-    self
-      .init
-      .iter()
-      .map(|fn_idx| function_instances.get(fn_idx.to_usize()).cloned())
-      .collect()
+  ) -> Result<Vec<Values>> {
+    match &self.init {
+      ElementInit::Functions(xs) => xs
+        .iter()
+        .map(|fn_idx| {
+          let function_instance = function_instances.get(fn_idx.to_usize())?;
+          Ok(Values::FuncRef(Some(function_instance.clone())))
+        })
+        .collect(),
+      ElementInit::Expressions(xs) => xs
+        .iter()
+        .map(|x| Isa::constant_reference(x, global_instances, function_instances))
+        .collect(),
+    }
   }
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum ElementType {
   AnyFunc,
+  ExternRef,
 }
 
 impl From<Option<u8>> for ElementType {
   fn from(code: Option<u8>) -> Self {
     match code {
       Some(0x70) => ElementType::AnyFunc,
+      Some(0x6f) => ElementType::ExternRef,
       x => unreachable!("Expected element-type code, got {:?}", x),
     }
   }
 }
 
+impl<'a> From<&'a ElementType> for u8 {
+  fn from(element_type: &'a ElementType) -> Self {
+    match element_type {
+      ElementType::AnyFunc => 0x70,
+      ElementType::ExternRef => 0x6f,
+    }
+  }
+}
+
+impl<'a> From<&'a ElementType> for ValueTypes {
+  fn from(element_type: &'a ElementType) -> Self {
+    match element_type {
+      ElementType::AnyFunc => ValueTypes::FuncRef,
+      ElementType::ExternRef => ValueTypes::ExternRef,
+    }
+  }
+}
+
 impl_decodable!(Section);
 impl Peekable for Section {}
 impl Leb128Decodable for Section {}
@@ -73,18 +131,63 @@ impl Section {
     }
     Ok(buf)
   }
+
+  fn decode_expressions(&mut self) -> Result<Vec<Vec<u8>>> {
+    let count = self.decode_count()?;
+    (0..count)
+      .map(|_| self.decode_instructions())
+      .collect::<Result<Vec<_>>>()
+  }
+
+  // NOTE: Only funcref(0x00) is defined as element kind.
+  fn decode_element_kind(&mut self) -> Result<ElementType> {
+    match self.next()? {
+      0x00 => Ok(ElementType::AnyFunc),
+      _ => Err(WasmError::Trap(Trap::Unknown)),
+    }
+  }
+
+  fn decode_reference_type(&mut self) -> Result<ElementType> {
+    match self.next()? {
+      code @ 0x70 | code @ 0x6f => Ok(ElementType::from(Some(code))),
+      _ => Err(WasmError::Trap(Trap::Unknown)),
+    }
+  }
 }
 
 impl Decodable for Section {
   type Item = Vec<Element>;
   fn decode(&mut self) -> Result<Self::Item> {
-    let count_of_section = self.decode_leb128_u32()?;
+    let count_of_section = self.decode_count()?;
     (0..count_of_section)
       .map(|_| {
-        let table_idx = self.decode_leb128_u32()?;
-        let offset = self.decode_instructions()?;
-        let init = self.decode_function_idx()?;
-        Ok(Element::new(From::from(table_idx), offset, init))
+        // NOTE: Bit 0 indicates passive or declarative, bit 1 explicit table index
+        // (or declarative), bit 2 initializer given by expressions.
+        let flags = self.decode_leb128_u32()?;
+        if flags > 7 {
+          return Err(WasmError::Trap(Trap::Unknown));
+        }
+        let mode = match flags & 0b011 {
+          0b000 => ElementMode::Active(Indice::from(0u32), self.decode_instructions()?),
+          0b010 => {
+            let table_idx = Indice::from(self.decode_leb128_u32()?);
+            ElementMode::Active(table_idx, self.decode_instructions()?)
+          }
+          0b001 => ElementMode::Passive,
+          _ => ElementMode::Declarative,
+        };
+        let has_expressions = flags & 0b100 != 0;
+        let element_type = match (flags & 0b011, has_expressions) {
+          (0b000, _) => ElementType::AnyFunc,
+          (_, false) => self.decode_element_kind()?,
+          (_, true) => self.decode_reference_type()?,
+        };
+        let init = if has_expressions {
+          ElementInit::Expressions(self.decode_expressions()?)
+        } else {
+          ElementInit::Functions(self.decode_function_idx()?)
+        };
+        Ok(Element::new(mode, element_type, init))
       })
       .collect::<Result<Vec<_>>>()
   }
@@ -9,7 +9,7 @@ impl U32Decodable for Section {}
 impl Decodable for Section {
   type Item = Vec<u32>;
   fn decode(&mut self) -> Result<Self::Item> {
-    let count_of_section = self.decode_leb128_u32()?;
+    let count_of_section = self.decode_count()?;
     (0..count_of_section)
       .map(|_| Ok(self.decode_leb128_u32()? as u32))
       .collect::<Result<Vec<_>>>()
@@ -17,7 +17,7 @@ impl InstructionDecodable for Section {}
 impl Decodable for Section {
   type Item = Vec<(GlobalType, Vec<u8>)>;
   fn decode(&mut self) -> Result<Self::Item> {
-    let count_of_section = self.decode_leb128_u32()?;
+    let count_of_section = self.decode_count()?;
     (0..count_of_section)
       .map(|_| {
         let value_type = ValueTypes::from(self.next()?);
@@ -4,11 +4,11 @@ use super::decodable::{
 use super::sec_element::ElementType;
 use super::sec_table::TableType;
 use alloc::vec::Vec;
-use error::Result;
+use error::{Result, Trap, WasmError};
 use global::GlobalType;
 use module::{
   ExternalInterface, ExternalInterfaces, ImportDescriptor, ModuleDescriptor, FUNCTION_DESCRIPTOR,
-  GLOBAL_DESCRIPTOR, MEMORY_DESCRIPTOR, TABLE_DESCRIPTOR,
+  GLOBAL_DESCRIPTOR, MEMORY_DESCRIPTOR, TABLE_DESCRIPTOR, TAG_DESCRIPTOR,
 };
 use value_type::ValueTypes;
 
@@ -30,14 +30,18 @@ impl Decodable for Section {
         FUNCTION_DESCRIPTOR => ImportDescriptor::Function(From::from(self.decode_leb128_u32()?)),
         TABLE_DESCRIPTOR => ImportDescriptor::Table(TableType::new(
           ElementType::from(self.next()),
-          self.decode_limit()?,
+          self.decode_table_limit()?,
         )),
-        MEMORY_DESCRIPTOR => ImportDescriptor::Memory(self.decode_limit()?),
+        MEMORY_DESCRIPTOR => ImportDescriptor::Memory(self.decode_memory_type()?),
         GLOBAL_DESCRIPTOR => {
           let value_type = ValueTypes::from(self.next()?);
           let global_type = GlobalType::new(self.next(), value_type)?;
           ImportDescriptor::Global(global_type)
         }
+        TAG_DESCRIPTOR => match self.next()? {
+          0x00 => ImportDescriptor::Tag(From::from(self.decode_leb128_u32()?)),
+          _ => return Err(WasmError::Trap(Trap::Unknown)),
+        },
       };
       imports.push(ExternalInterface::new(
         Some(module_name),
@@ -1,7 +1,7 @@
 use super::decodable::{Decodable, Leb128Decodable, LimitDecodable, U32Decodable};
 use alloc::vec::Vec;
 use error::Result;
-use memory::Limit;
+use memory::MemoryType;
 
 impl_decodable!(Section);
 impl Leb128Decodable for Section {}
@@ -9,11 +9,11 @@ impl U32Decodable for Section {}
 impl LimitDecodable for Section {}
 
 impl Decodable for Section {
-  type Item = Vec<Limit>;
+  type Item = Vec<MemoryType>;
   fn decode(&mut self) -> Result<Self::Item> {
-    let count_of_section = self.decode_leb128_u32()?;
+    let count_of_section = self.decode_count()?;
     (0..count_of_section)
-      .map(|_| self.decode_limit())
+      .map(|_| self.decode_memory_type())
       .collect::<Result<Vec<_>>>()
   }
 }
@@ -0,0 +1,81 @@
+use super::decodable::{Decodable, Leb128Decodable, NameDecodable, U32Decodable, U8Iterator};
+use alloc::string::String;
+use alloc::vec::Vec;
+use error::{Result, Trap, WasmError};
+
+type NameMap = Vec<(u32, String)>;
+
+fn find(name_map: &[(u32, String)], idx: u32) -> Option<&str> {
+  name_map
+    .binary_search_by_key(&idx, |(key, _)| *key)
+    .ok()
+    .map(|position| name_map[position].1.as_str())
+}
+
+/// Names of module, functions and locals given by "name" custom section.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Names {
+  pub module: Option<String>,
+  pub functions: NameMap,
+  pub locals: Vec<(u32, NameMap)>,
+}
+
+impl Names {
+  pub fn function(&self, function_idx: u32) -> Option<&str> {
+    find(&self.functions, function_idx)
+  }
+
+  pub fn local(&self, function_idx: u32, local_idx: u32) -> Option<&str> {
+    let position = self
+      .locals
+      .binary_search_by_key(&function_idx, |(key, _)| *key)
+      .ok()?;
+    find(&self.locals[position].1, local_idx)
+  }
+}
+
+impl_decodable!(Section);
+impl Leb128Decodable for Section {}
+impl U32Decodable for Section {}
+impl NameDecodable for Section {}
+
+impl Section {
+  fn decode_name_map(&mut self) -> Result<NameMap> {
+    let count = self.decode_count()?;
+    (0..count)
+      .map(|_| Ok((self.decode_leb128_u32()?, self.decode_name()?)))
+      .collect::<Result<Vec<_>>>()
+  }
+}
+
+impl Decodable for Section {
+  type Item = Names;
+
+  // NOTE: Unknown subsections are skipped.
+  fn decode(&mut self) -> Result<Self::Item> {
+    let mut names = Names::default();
+    while self.byte_ptr < self.bytes.len() {
+      let id = self.next()?;
+      let size_of_subsection = self.decode_leb128_u32()? as usize;
+      let end_of_subsection = self.byte_ptr + size_of_subsection;
+      if end_of_subsection > self.bytes.len() {
+        return Err(WasmError::Trap(Trap::LengthOutofBounds));
+      }
+      match id {
+        0x0 => names.module = Some(self.decode_name()?),
+        0x1 => names.functions = self.decode_name_map()?,
+        0x2 => {
+          let count = self.decode_leb128_u32()?;
+          for _ in 0..count {
+            let function_idx = self.decode_leb128_u32()?;
+            let name_map = self.decode_name_map()?;
+            names.locals.push((function_idx, name_map));
+          }
+        }
+        _ => {}
+      };
+      self.byte_ptr = end_of_subsection;
+    }
+    Ok(names)
+  }
+}
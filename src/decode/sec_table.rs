@@ -6,7 +6,7 @@ use memory::Limit;
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct TableType {
-  element_type: ElementType,
+  pub(crate) element_type: ElementType,
   pub(crate) limit: Limit,
 }
 
@@ -27,11 +27,11 @@ impl LimitDecodable for Section {}
 impl Decodable for Section {
   type Item = Vec<TableType>;
   fn decode(&mut self) -> Result<Self::Item> {
-    let count_of_section = self.decode_leb128_u32()?;
+    let count_of_section = self.decode_count()?;
     (0..count_of_section)
       .map(|_| {
         let element_type = ElementType::from(self.next());
-        let limit = self.decode_limit()?;
+        let limit = self.decode_table_limit()?;
         Ok(TableType::new(element_type, limit))
       })
       .collect::<Result<Vec<_>>>()
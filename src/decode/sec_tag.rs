@@ -0,0 +1,21 @@
+use super::decodable::{Decodable, Leb128Decodable, U32Decodable, U8Iterator};
+use alloc::vec::Vec;
+use error::{Result, Trap, WasmError};
+
+impl_decodable!(Section);
+impl Leb128Decodable for Section {}
+impl U32Decodable for Section {}
+
+// NOTE: Only exception is defined as an attribute of tag.
+impl Decodable for Section {
+  type Item = Vec<u32>;
+  fn decode(&mut self) -> Result<Self::Item> {
+    let count_of_section = self.decode_count()?;
+    (0..count_of_section)
+      .map(|_| match self.next()? {
+        0x00 => Ok(self.decode_leb128_u32()?),
+        _ => Err(WasmError::Trap(Trap::Unknown)),
+      })
+      .collect::<Result<Vec<_>>>()
+  }
+}
@@ -13,7 +13,7 @@ impl U32Decodable for Section {}
 impl Decodable for Section {
   type Item = Vec<FunctionType>;
   fn decode(&mut self) -> Result<Self::Item> {
-    let count_of_type = self.decode_leb128_u32()?;
+    let count_of_type = self.decode_count()?;
     (0..count_of_type)
       .map(|_| {
         let mut parameters = vec![];
@@ -1,5 +1,8 @@
+use super::decodable::Decodable;
 use super::sec_element::Element;
+use super::sec_name::{self, Names};
 use super::sec_table::TableType;
+use super::source_map::SourceMap;
 use super::Data;
 #[cfg(not(test))]
 use alloc::prelude::*;
@@ -10,13 +13,15 @@ use core::default::Default;
 use error::{Result, Trap, WasmError};
 use function::{FunctionInstance, FunctionType};
 use global::{GlobalInstances, GlobalType};
-use memory::{Limit, MemoryInstance, MemoryInstances};
+use limiter::Limiter;
+use memory::{DataInstances, MemoryInstance, MemoryInstances, MemoryType};
 use module::{
   ExternalInterface, ExternalInterfaces, ExternalModules, InternalModule, FUNCTION_DESCRIPTOR,
-  GLOBAL_DESCRIPTOR, MEMORY_DESCRIPTOR, TABLE_DESCRIPTOR,
+  GLOBAL_DESCRIPTOR, MEMORY_DESCRIPTOR, TABLE_DESCRIPTOR, TAG_DESCRIPTOR,
 };
-use store::Store;
-use table::{TableInstance, TableInstances};
+use store::{ModuleContext, ModuleRef, Store};
+use table::{ElementInstances, TableInstance, TableInstances};
+use tag::TagInstance;
 use value_type::ValueTypes;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -33,6 +38,8 @@ pub enum SectionCode {
   Element,
   Code,
   Data,
+  DataCount,
+  Tag,
 }
 
 impl TryFrom<Option<u8>> for SectionCode {
@@ -52,6 +59,8 @@ impl TryFrom<Option<u8>> for SectionCode {
       Some(0x9) => Ok(Element),
       Some(0xa) => Ok(Code),
       Some(0xb) => Ok(Data),
+      Some(0xc) => Ok(DataCount),
+      Some(0xd) => Ok(Tag),
       _ => Err(Trap::InvalidSectionId),
     }
   }
@@ -62,15 +71,18 @@ pub struct Module {
   pub(crate) function_types: Vec<FunctionType>,
   pub(crate) functions: Vec<u32>,
   pub(crate) exports: ExternalInterfaces,
-  pub(crate) codes: Vec<Result<(Vec<u8>, Vec<ValueTypes>)>>,
+  pub(crate) codes: Vec<Result<(Vec<u8>, Vec<ValueTypes>, SourceMap)>>,
   pub(crate) datas: Vec<Data>,
-  pub(crate) limits: Vec<Limit>,
+  pub(crate) limits: Vec<MemoryType>,
   pub(crate) tables: Vec<TableType>,
   pub(crate) globals: Vec<(GlobalType, Vec<u8>)>,
+  // NOTE: Indices of function types which tags are defined by.
+  pub(crate) tags: Vec<u32>,
   pub(crate) elements: Vec<Element>,
   pub(crate) customs: Vec<(String, Vec<u8>)>,
   pub(crate) imports: ExternalInterfaces,
   pub(crate) start: Option<u32>,
+  pub(crate) data_count: Option<u32>,
 }
 
 impl Default for Module {
@@ -84,10 +96,12 @@ impl Default for Module {
       limits: vec![],
       tables: vec![],
       globals: vec![],
+      tags: vec![],
       elements: vec![],
       customs: vec![],
       imports: ExternalInterfaces::default(),
       start: None,
+      data_count: None,
     }
   }
 }
@@ -104,11 +118,12 @@ macro_rules! impl_builder {
 impl Module {
   impl_builder!(function_types, function_types, FunctionType);
   impl_builder!(functions, functions, u32);
-  impl_builder!(codes, codes, Result<(Vec<u8>, Vec<ValueTypes>)>);
+  impl_builder!(codes, codes, Result<(Vec<u8>, Vec<ValueTypes>, SourceMap)>);
   impl_builder!(datas, datas, Data);
-  impl_builder!(limits, limits, Limit);
+  impl_builder!(limits, limits, MemoryType);
   impl_builder!(tables, tables, TableType);
   impl_builder!(globals, globals, (GlobalType, Vec<u8>));
+  impl_builder!(tags, tags, u32);
   impl_builder!(elements, elements, Element);
   impl_builder!(customs, customs, (String, Vec<u8>));
 
@@ -127,144 +142,80 @@ impl Module {
     self
   }
 
-  fn validate_memory(
-    datas: &[Data],
-    limits: &[Limit],
-    imports: &[ExternalInterface],
-    external_modules: &ExternalModules,
-    global_instances: &GlobalInstances,
-  ) -> Result<()> {
-    let memory_idx = 0;
-    let external_memory_instances = imports
-      .get(memory_idx as usize)
-      .map(|key| external_modules.find_memory_instances(key));
-
-    if limits.get(memory_idx as usize).is_some() && external_memory_instances.is_none() {
-      return Ok(());
-    }
-    if external_memory_instances.is_some() {
-      MemoryInstances::validate(
-        &external_memory_instances??,
-        &limits
-          .get(memory_idx as usize)
-          .map(|limit| limit.to_owned()),
-        imports.get(memory_idx as usize)?,
-        datas,
-        global_instances,
-      )
-    } else {
-      Ok(())
-    }
+  pub fn data_count(&mut self, x: u32) -> &mut Self {
+    self.data_count = Some(x);
+    self
   }
 
-  fn validate_table(
-    elements: &[Element],
-    tables: &[TableType],
+  fn memory_instances(
+    limits: Vec<MemoryType>,
+    exports: &ExternalInterfaces,
     imports: &[ExternalInterface],
     external_modules: &ExternalModules,
-    global_instances: &GlobalInstances,
-    function_instances: &[FunctionInstance],
-  ) -> Result<()> {
-    if !tables.is_empty() {
-      tables
-        .iter()
-        .map(|table_type| {
-          TableInstance::validate(elements, table_type, global_instances, function_instances)
-        })
-        .collect::<Result<Vec<_>>>()
-        .and_then(|_| Ok(()))
-    } else {
-      match imports.first() {
-        Some(import) => external_modules
-          .find_table_instances(import)
-          .and_then(|table_instances| {
-            table_instances.validate(elements, global_instances, function_instances)
-          }),
-        None => Ok(()),
-      }
+    limiter: &Limiter,
+  ) -> Result<Vec<MemoryInstance>> {
+    // NOTE: Imported memories precede memories defined in the module.
+    let mut memory_instances = imports
+      .iter()
+      .map(|import| external_modules.find_memory_instance(import))
+      .collect::<Result<Vec<_>>>()?;
+    let count_of_imports = memory_instances.len();
+    for (idx, memory_type) in limits.into_iter().enumerate() {
+      let export_name = exports
+        .find_kind_by_idx((count_of_imports + idx) as u32, &MEMORY_DESCRIPTOR)
+        .map(|x| x.name.to_owned());
+      let memory_instance =
+        MemoryInstance::new_with_limiter(memory_type, export_name, limiter.clone())?;
+      memory_instances.push(memory_instance);
     }
+    Ok(memory_instances)
   }
 
-  fn memory_instances(
-    datas: Vec<Data>,
-    limits: &[Limit],
+  fn table_instances(
+    tables: Vec<TableType>,
     exports: &ExternalInterfaces,
     imports: &[ExternalInterface],
     external_modules: &ExternalModules,
-    global_instances: &GlobalInstances,
-  ) -> Result<MemoryInstances> {
-    // NOTE: Currently WASM specification assumed only one memory instance;
-    let memory_idx = 0;
-    let export_name = exports
-      .find_kind_by_idx(memory_idx, &MEMORY_DESCRIPTOR)
-      .map(|x| x.name.to_owned());
-
-    let external_memory_instances = imports
-      .get(memory_idx as usize)
-      .map(|key| external_modules.find_memory_instances(key));
-
-    if let Some(limit) = limits.get(memory_idx as usize) {
-      if external_memory_instances.is_none() {
-        return Ok(MemoryInstances::new(vec![MemoryInstance::new(
-          datas,
-          limit.to_owned(),
-          export_name,
-          global_instances,
-        )?]));
-      }
-    }
-    if external_memory_instances.is_some() {
-      MemoryInstances::from(
-        &external_memory_instances??,
-        limits
-          .get(memory_idx as usize)
-          .map(|limit| limit.to_owned()),
-        datas,
-        global_instances,
-      )
-    } else {
-      Ok(MemoryInstances::empty())
+    limiter: &Limiter,
+  ) -> Result<Vec<TableInstance>> {
+    // NOTE: Imported tables precede tables defined in the module.
+    let mut table_instances = imports
+      .iter()
+      .map(|import| external_modules.find_table_instance(import))
+      .collect::<Result<Vec<_>>>()?;
+    let count_of_imports = table_instances.len();
+    for (idx, table_type) in tables.into_iter().enumerate() {
+      let export_name = exports
+        .find_kind_by_idx((count_of_imports + idx) as u32, &TABLE_DESCRIPTOR)
+        .map(|x| x.name.to_owned());
+      let table_instance =
+        TableInstance::new_with_limiter(table_type, export_name, limiter.clone())?;
+      table_instances.push(table_instance);
     }
+    Ok(table_instances)
   }
 
-  fn table_instances(
-    elements: &[Element],
-    tables: Vec<TableType>,
+  fn tag_instances(
+    tags: Vec<u32>,
+    function_types: &[FunctionType],
     exports: &ExternalInterfaces,
     imports: &[ExternalInterface],
     external_modules: &ExternalModules,
-    global_instances: &GlobalInstances,
-    function_instances: &[FunctionInstance],
-  ) -> Result<TableInstances> {
-    if !tables.is_empty() {
-      tables
-        .into_iter()
-        .map(|table_type| {
-          let export_name = exports
-            .find_kind_by_idx(0, &TABLE_DESCRIPTOR)
-            .map(|x| x.name.to_owned());
-          TableInstance::new(
-            elements.to_vec(),
-            table_type,
-            export_name,
-            global_instances,
-            function_instances,
-          )
-        })
-        .collect::<Result<Vec<_>>>()
-        .map(TableInstances::new)
-    } else {
-      // NOTE: Only one table instance allowed.
-      match imports.first() {
-        Some(import) => external_modules
-          .find_table_instances(import)
-          .map(|table_instances| {
-            table_instances.link(&elements, global_instances, function_instances)?;
-            Ok(table_instances)
-          })?,
-        None => Ok(TableInstances::empty()),
-      }
+  ) -> Result<Vec<TagInstance>> {
+    // NOTE: Imported tags precede tags defined in the module.
+    let mut tag_instances = imports
+      .iter()
+      .map(|import| external_modules.find_tag_instance(import, function_types))
+      .collect::<Result<Vec<_>>>()?;
+    let count_of_imports = tag_instances.len();
+    for (idx, index_of_type) in tags.into_iter().enumerate() {
+      let export_name = exports
+        .find_kind_by_idx((count_of_imports + idx) as u32, &TAG_DESCRIPTOR)
+        .map(|x| x.name.to_owned());
+      let function_type = Module::function_type(index_of_type as usize, function_types);
+      tag_instances.push(TagInstance::new(function_type, export_name));
     }
+    Ok(tag_instances)
   }
 
   fn function_type(idx: usize, function_types: &[FunctionType]) -> FunctionType {
@@ -278,8 +229,8 @@ impl Module {
     function_types: &[FunctionType],
     functions: &[u32],
     exports: &ExternalInterfaces,
-    codes: Vec<Result<(Vec<u8>, Vec<ValueTypes>)>>,
-  ) -> Result<Vec<FunctionInstance>> {
+    codes: Vec<Result<(Vec<u8>, Vec<ValueTypes>, SourceMap)>>,
+  ) -> Result<Vec<(FunctionInstance, SourceMap)>> {
     codes
       .into_iter()
       .enumerate()
@@ -292,17 +243,23 @@ impl Module {
           None => return Err(WasmError::Trap(Trap::FunctionAndCodeInconsitent)),
         };
         let function_type = Module::function_type(index_of_type as usize, function_types);
-        let (expressions, locals) = code?;
-        Ok(FunctionInstance::new(
-          export_name,
-          function_type,
-          locals,
-          expressions,
-        ))
+        let (expressions, locals, source_map) = code?;
+        let function_instance =
+          FunctionInstance::new(export_name, function_type, locals, expressions);
+        Ok((function_instance, source_map))
       })
       .collect::<Result<Vec<_>>>()
   }
 
+  // NOTE: Malformed name section is ignored, as same as other custom sections.
+  fn names(customs: &[(String, Vec<u8>)]) -> Names {
+    customs
+      .iter()
+      .find(|(name, _)| name == "name")
+      .and_then(|(_, bytes)| sec_name::Section::new(bytes.to_owned()).decode().ok())
+      .unwrap_or_default()
+  }
+
   fn external_function_instances(
     function_types: &[FunctionType],
     imports: &[ExternalInterface],
@@ -314,11 +271,11 @@ impl Module {
       .collect::<Result<Vec<_>>>()
   }
 
-  pub fn complete(
+  pub(crate) fn complete(
     self,
     external_modules: &ExternalModules,
-    store: &mut Store,
-  ) -> Result<InternalModule> {
+    store: &Store,
+  ) -> Result<(ModuleRef, InternalModule)> {
     match self {
       Module {
         function_types,
@@ -330,8 +287,10 @@ impl Module {
         tables,
         elements,
         globals,
+        tags,
         imports,
         start,
+        customs,
         ..
       } => {
         let grouped_imports = imports.group_by_kind()?;
@@ -339,9 +298,19 @@ impl Module {
         let imports_table = grouped_imports.get(&TABLE_DESCRIPTOR)?;
         let imports_memory = grouped_imports.get(&MEMORY_DESCRIPTOR)?;
         let imports_global = grouped_imports.get(&GLOBAL_DESCRIPTOR)?;
+        let imports_tag = grouped_imports.get(&TAG_DESCRIPTOR)?;
 
-        let mut internal_function_instances =
-          Module::function_instances(&function_types, &functions, &exports, codes)?;
+        // NOTE: Consult the limiter before allocating anything the module defines.
+        let limiter = store.limiter();
+        limiter.allocate(|limiter| limiter.functions_allocating(codes.len()))?;
+        limiter.allocate(|limiter| limiter.tables_allocating(tables.len()))?;
+        limiter.allocate(|limiter| limiter.memories_allocating(limits.len()))?;
+        limiter.allocate(|limiter| limiter.globals_allocating(globals.len()))?;
+
+        let (mut internal_function_instances, source_maps): (Vec<_>, Vec<_>) =
+          Module::function_instances(&function_types, &functions, &exports, codes)?
+            .into_iter()
+            .unzip();
 
         let mut function_instances = Module::external_function_instances(
           &function_types,
@@ -349,6 +318,16 @@ impl Module {
           &external_modules,
         )?;
 
+        let names = Module::names(&customs);
+        for (idx, name) in names.functions.iter() {
+          if *idx as usize >= function_instances.len() {
+            if let Some(function_instance) = internal_function_instances
+              .get(*idx as usize - function_instances.len())
+            {
+              function_instance.set_name(name);
+            }
+          }
+        }
         function_instances.append(&mut internal_function_instances);
 
         let global_instances = GlobalInstances::new_with_external(
@@ -356,55 +335,75 @@ impl Module {
           &exports,
           &imports_global,
           &external_modules,
+          &function_instances,
         )?;
 
-        // TODO: Move to context mod.
-        let (validate_memory, validate_table) = (
-          Module::validate_memory(
-            &datas,
-            &limits,
-            &imports_memory,
-            &external_modules,
-            &global_instances,
-          ),
-          Module::validate_table(
-            &elements,
-            &tables,
-            &imports_table,
-            &external_modules,
-            &global_instances,
-            &function_instances,
-          ),
-        );
-        validate_memory?;
-        validate_table?;
+        let table_instances = Module::table_instances(
+          tables,
+          &exports,
+          &imports_table,
+          &external_modules,
+          &limiter,
+        )?;
 
         let memory_instances = Module::memory_instances(
-          datas,
-          &limits,
+          limits,
           &exports,
           &imports_memory,
           &external_modules,
-          &global_instances,
+          &limiter,
         )?;
 
-        let mut table_instances = Module::table_instances(
-          &elements,
-          tables,
+        let tag_instances = Module::tag_instances(
+          tags,
+          &function_types,
           &exports,
-          &imports_table,
+          &imports_tag,
           &external_modules,
-          &global_instances,
-          &function_instances,
         )?;
 
-        store.function_instances = function_instances;
-        store.function_types = function_types;
-        store.memory_instances = memory_instances;
-        store.table_instances = table_instances;
-        store.global_instances = global_instances;
-        let internal_module = InternalModule::new(exports, start);
-        Ok(internal_module)
+        // TODO: Move to context mod.
+        let memories = MemoryInstances::new(memory_instances.clone());
+        let tables = TableInstances::new(table_instances.clone());
+        let (validate_memory, validate_table) = (
+          memories.validate(&datas, &global_instances),
+          tables.validate(&elements, &global_instances),
+        );
+        validate_memory?;
+        validate_table?;
+
+        let data_instances = DataInstances::new(&datas);
+        let element_instances =
+          ElementInstances::new(&elements, &global_instances, &function_instances)?;
+
+        tables.link(&elements, &global_instances, &function_instances)?;
+        memories.link(&datas, &global_instances)?;
+
+        let mut linked_stores: Vec<Store> = vec![];
+        for function_instance in function_instances[..imports_function.len()].iter() {
+          if let Some(other) = function_instance.module().map(|module| module.store()) {
+            if !other.ptr_eq(store) && !linked_stores.iter().any(|x| x.ptr_eq(&other)) {
+              linked_stores.push(other);
+            }
+          }
+        }
+        let module = store.allocate_module(ModuleContext {
+          function_types,
+          function_addrs: store.allocate_functions(&function_instances, imports_function.len()),
+          table_addrs: store.allocate_tables(&table_instances, imports_table.len()),
+          memory_addrs: store.allocate_memories(&memory_instances, imports_memory.len()),
+          global_addrs: store.allocate_globals(&global_instances.to_vec(), imports_global.len()),
+          tag_addrs: store.allocate_tags(&tag_instances, imports_tag.len()),
+          data_instances,
+          element_instances,
+          exports,
+        });
+        for function_instance in function_instances[imports_function.len()..].iter() {
+          function_instance.set_module(&module);
+        }
+        let internal_module =
+          InternalModule::new(start, names, source_maps, linked_stores);
+        Ok((module, internal_module))
       }
     }
   }
@@ -0,0 +1,63 @@
+use alloc::vec::Vec;
+
+/// Offsets of instructions in a function body re-encoded while decoding,
+/// paired with the ones in the original module.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceMap(Vec<(u32, u32)>);
+
+impl SourceMap {
+  pub(crate) fn push(&mut self, internal_offset: u32, original_offset: u32) {
+    self.0.push((internal_offset, original_offset));
+  }
+
+  // NOTE: Instructions of a nested block are decoded ahead of its header,
+  // so their internal offsets are shifted by where the block is placed.
+  pub(crate) fn append(&mut self, other: SourceMap, base: u32) {
+    self
+      .0
+      .extend(other.0.into_iter().map(|(internal, original)| (internal + base, original)));
+  }
+
+  pub(crate) fn relocate(&mut self, base: u32) {
+    for (_, original) in self.0.iter_mut() {
+      *original += base;
+    }
+  }
+
+  /// Offset in the original module of an instruction which covers `internal_offset`.
+  pub fn original_offset(&self, internal_offset: u32) -> Option<u32> {
+    let position = match self
+      .0
+      .binary_search_by_key(&internal_offset, |(internal, _)| *internal)
+    {
+      Ok(position) => position,
+      Err(0) => return None,
+      Err(position) => position - 1,
+    };
+    Some(self.0[position].1)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn original_offset_of_nested_block() {
+    let mut inner = SourceMap::default();
+    inner.push(0, 10);
+    inner.push(5, 12);
+    let mut source_map = SourceMap::default();
+    source_map.push(0, 7);
+    source_map.append(inner, 6);
+    source_map.push(12, 14);
+    source_map.relocate(100);
+
+    assert_eq!(source_map.original_offset(0), Some(107));
+    assert_eq!(source_map.original_offset(5), Some(107));
+    assert_eq!(source_map.original_offset(6), Some(110));
+    assert_eq!(source_map.original_offset(11), Some(112));
+    assert_eq!(source_map.original_offset(12), Some(114));
+    assert_eq!(SourceMap::default().original_offset(0), None);
+  }
+}
@@ -1,22 +1,34 @@
 #[cfg(not(test))]
 use alloc::prelude::*;
 use decode::{Byte, Module};
-use error::Result;
+use encode;
+use error::{Result, Trap, WasmError};
 use frame::Frame;
 use module::ExternalModules;
 use stack::Stack;
 use store::Store;
 use validate::Context;
 use vm::ModuleInstance;
+use wat::{is_textform, parse_wat};
 
 pub fn init_store() -> Store {
   Default::default()
 }
 
+// NOTE: Module in text format is translated into binary format before decoding.
 pub fn decode_module(bytes: &[u8]) -> Result<Module> {
+  if is_textform(bytes) {
+    let source =
+      core::str::from_utf8(bytes).map_err(|_| WasmError::Trap(Trap::InvalidUTF8Encoding))?;
+    return Byte::new_with_drop(&parse_wat(source)?)?.decode();
+  }
   Byte::new_with_drop(&bytes)?.decode()
 }
 
+pub fn encode_module(module: &Module) -> Result<Vec<u8>> {
+  encode::encode(module)
+}
+
 pub fn validate_module(module: &Result<Module>) -> Result<()> {
   match module {
     Ok(module) => Context::new(module)?.validate(),
@@ -24,16 +36,16 @@ pub fn validate_module(module: &Result<Module>) -> Result<()> {
   }
 }
 
+/// Instances of the module are allocated in the store,
+/// which other modules instantiated in clones of it share.
 pub fn instantiate_module(
-  mut store: Store,
+  store: Store,
   section: Result<Module>, // module: Module(PreVm)
   external_modules: ExternalModules,
   max_stack_height: usize,
 ) -> Result<ModuleInstance> {
-  // TODO: Return pair of (Store, Vm) by using Rc<Store> type.
-  let internal_module = section?.complete(&external_modules, &mut store)?;
-  let mut vm =
-    ModuleInstance::new_from(store, internal_module, external_modules, max_stack_height)?;
+  let (module, internal_module) = section?.complete(&external_modules, &store)?;
+  let mut vm = ModuleInstance::new_from(module, internal_module, max_stack_height)?;
   if let Some(idx) = vm.start_index().clone() {
     let function_instance = vm.get_function_instance(&idx)?;
     let frame = Frame::new(
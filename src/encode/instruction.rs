@@ -0,0 +1,196 @@
+use super::{write_sleb128, write_uleb128};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use error::{Result, Trap, WasmError};
+use isa::{AtomicIsa, Isa, MiscIsa, SimdIsa};
+use value_type::BLOCK_TYPE_INDEX;
+
+struct Reader<'a> {
+  source: &'a [u8],
+  ptr: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn has_next(&self) -> bool {
+    self.ptr < self.source.len()
+  }
+
+  fn next(&mut self) -> Result<u8> {
+    let byte = *self
+      .source
+      .get(self.ptr)
+      .ok_or(WasmError::Trap(Trap::UnexpectedEnd))?;
+    self.ptr += 1;
+    Ok(byte)
+  }
+
+  fn skip(&mut self, count: usize) {
+    self.ptr += count;
+  }
+
+  fn take(&mut self, count: usize) -> Result<&'a [u8]> {
+    let end = self.ptr + count;
+    if end > self.source.len() {
+      return Err(WasmError::Trap(Trap::UnexpectedEnd));
+    }
+    let bytes = &self.source[self.ptr..end];
+    self.ptr = end;
+    Ok(bytes)
+  }
+
+  fn u32(&mut self) -> Result<u32> {
+    let mut buf = [0; 4];
+    buf.clone_from_slice(self.take(4)?);
+    Ok(u32::from_ne_bytes(buf))
+  }
+
+  fn u64(&mut self) -> Result<u64> {
+    let mut buf = [0; 8];
+    buf.clone_from_slice(self.take(8)?);
+    Ok(u64::from_ne_bytes(buf))
+  }
+
+  fn block_type(&mut self, buf: &mut Vec<u8>) -> Result<()> {
+    match self.next()? {
+      BLOCK_TYPE_INDEX => write_sleb128(buf, i64::from(self.u32()?)),
+      value_type => buf.push(value_type),
+    };
+    Ok(())
+  }
+
+  fn memory_argument(&mut self, buf: &mut Vec<u8>) -> Result<()> {
+    let (align, offset, memory_idx) = (self.u32()?, self.u32()?, self.u32()?);
+    // NOTE: Bit 6 of alignment tells an explicit memory index follows.
+    if memory_idx == 0 {
+      write_uleb128(buf, u64::from(align));
+    } else {
+      write_uleb128(buf, u64::from(align | 0x40));
+      write_uleb128(buf, u64::from(memory_idx));
+    }
+    write_uleb128(buf, u64::from(offset));
+    Ok(())
+  }
+}
+
+// NOTE: Translate fixed-width representation made by `decode_instructions` into the binary format.
+// Size of block prepended for jumping is dropped.
+pub fn encode_instructions(source: &[u8], buf: &mut Vec<u8>) -> Result<()> {
+  use self::Isa::*;
+  let mut reader = Reader { source, ptr: 0 };
+  while reader.has_next() {
+    let code = reader.next()?;
+    buf.push(code);
+    match Isa::from(code) {
+      Reserved => unreachable!("{:?}", code),
+      Block => {
+        reader.skip(4);
+        reader.block_type(buf)?;
+      }
+      Loop => reader.block_type(buf)?,
+      If => {
+        reader.skip(8);
+        reader.block_type(buf)?;
+      }
+      // NOTE: Handlers and delegation are written again at each clause.
+      Try => {
+        reader.skip(4);
+        reader.block_type(buf)?;
+        let count_of_handlers = reader.u32()?;
+        reader.skip(count_of_handlers as usize * 8 + 4);
+      }
+
+      GetLocal | SetLocal | TeeLocal | GetGlobal | SetGlobal | Br | BrIf | Call | ReturnCall
+      | TableGet | TableSet | RefFunc | Throw | Rethrow | Catch | Delegate => {
+        write_uleb128(buf, u64::from(reader.u32()?));
+      }
+      BrTable => {
+        let len = reader.u32()?;
+        write_uleb128(buf, u64::from(len));
+        for _ in 0..=len {
+          write_uleb128(buf, u64::from(reader.u32()?));
+        }
+      }
+      CallIndirect | ReturnCallIndirect => {
+        write_uleb128(buf, u64::from(reader.u32()?));
+        write_uleb128(buf, u64::from(reader.u32()?));
+      }
+      SelectTyped => {
+        let len = reader.u32()?;
+        write_uleb128(buf, u64::from(len));
+        buf.extend_from_slice(reader.take(len as usize)?);
+      }
+      RefNull => buf.push(reader.next()?),
+
+      I32Const => write_sleb128(buf, i64::from(reader.u32()? as i32)),
+      I64Const => write_sleb128(buf, reader.u64()? as i64),
+      F32Const => buf.extend_from_slice(&reader.u32()?.to_le_bytes()),
+      F64Const => buf.extend_from_slice(&reader.u64()?.to_le_bytes()),
+
+      I32Load | I64Load | F32Load | F64Load | I32Load8Sign | I32Load8Unsign | I32Load16Sign
+      | I32Load16Unsign | I64Load8Sign | I64Load8Unsign | I64Load16Sign | I64Load16Unsign
+      | I64Load32Sign | I64Load32Unsign | I32Store | I64Store | F32Store | F64Store
+      | I32Store8 | I32Store16 | I64Store8 | I64Store16 | I64Store32 => {
+        reader.memory_argument(buf)?
+      }
+
+      MemorySize | MemoryGrow => write_uleb128(buf, u64::from(reader.u32()?)),
+
+      MiscPrefix => {
+        let misc_code = reader.u32()?;
+        write_uleb128(buf, u64::from(misc_code));
+        match MiscIsa::try_from(misc_code)? {
+          MiscIsa::MemoryInit
+          | MiscIsa::MemoryCopy
+          | MiscIsa::TableInit
+          | MiscIsa::TableCopy => {
+            write_uleb128(buf, u64::from(reader.u32()?));
+            write_uleb128(buf, u64::from(reader.u32()?));
+          }
+          MiscIsa::DataDrop
+          | MiscIsa::MemoryFill
+          | MiscIsa::ElemDrop
+          | MiscIsa::TableGrow
+          | MiscIsa::TableSize
+          | MiscIsa::TableFill => write_uleb128(buf, u64::from(reader.u32()?)),
+          _ => {}
+        }
+      }
+
+      SimdPrefix => {
+        let simd_code = reader.u32()?;
+        write_uleb128(buf, u64::from(simd_code));
+        let simd_isa = SimdIsa::try_from(simd_code)?;
+        match simd_isa {
+          SimdIsa::Load
+          | SimdIsa::LoadExtend(_, _)
+          | SimdIsa::LoadSplat(_)
+          | SimdIsa::LoadZero(_)
+          | SimdIsa::Store
+          | SimdIsa::LoadLane(_)
+          | SimdIsa::StoreLane(_) => reader.memory_argument(buf)?,
+          _ => {}
+        };
+        match simd_isa {
+          SimdIsa::Const | SimdIsa::Shuffle => buf.extend_from_slice(reader.take(16)?),
+          SimdIsa::LoadLane(_)
+          | SimdIsa::StoreLane(_)
+          | SimdIsa::ExtractLane(_, _)
+          | SimdIsa::ReplaceLane(_) => buf.push(reader.u32()? as u8),
+          _ => {}
+        };
+      }
+
+      AtomicPrefix => {
+        let atomic_code = reader.u32()?;
+        write_uleb128(buf, u64::from(atomic_code));
+        match AtomicIsa::try_from(atomic_code)? {
+          AtomicIsa::Fence => buf.push(0x00),
+          _ => reader.memory_argument(buf)?,
+        }
+      }
+
+      _ => {}
+    };
+  }
+  Ok(())
+}
@@ -0,0 +1,557 @@
+mod instruction;
+
+use self::instruction::encode_instructions;
+use alloc::vec::Vec;
+use decode::{ElementInit, ElementMode, ElementType, Module, TableType};
+use error::Result;
+use global::GlobalType;
+use memory::{Limit, MemoryType, PAGE_SIZE};
+use module::{ExportDescriptor, ImportDescriptor, ModuleDescriptor};
+use value_type::ValueTypes;
+
+pub fn write_uleb128(buf: &mut Vec<u8>, mut value: u64) {
+  loop {
+    let byte = (value & 0x7f) as u8;
+    value >>= 7;
+    if value == 0 {
+      buf.push(byte);
+      return;
+    }
+    buf.push(byte | 0x80);
+  }
+}
+
+pub fn write_sleb128(buf: &mut Vec<u8>, mut value: i64) {
+  loop {
+    let byte = (value & 0x7f) as u8;
+    value >>= 7;
+    let is_last = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+    if is_last {
+      buf.push(byte);
+      return;
+    }
+    buf.push(byte | 0x80);
+  }
+}
+
+pub fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+  write_uleb128(buf, bytes.len() as u64);
+  buf.extend_from_slice(bytes);
+}
+
+fn write_vec<T>(
+  buf: &mut Vec<u8>,
+  xs: &[T],
+  mut write: impl FnMut(&mut Vec<u8>, &T) -> Result<()>,
+) -> Result<()> {
+  write_uleb128(buf, xs.len() as u64);
+  for x in xs {
+    write(buf, x)?;
+  }
+  Ok(())
+}
+
+fn write_limit(buf: &mut Vec<u8>, limit: &Limit) {
+  match limit {
+    Limit::NoUpperLimit(min) => {
+      buf.push(0x00);
+      write_uleb128(buf, u64::from(*min));
+    }
+    Limit::HasUpperLimit(min, max) => {
+      buf.push(0x01);
+      write_uleb128(buf, u64::from(*min));
+      write_uleb128(buf, u64::from(*max));
+    }
+    Limit::Shared(min, max) => {
+      buf.push(0x03);
+      write_uleb128(buf, u64::from(*min));
+      write_uleb128(buf, u64::from(*max));
+    }
+  }
+}
+
+// NOTE: Bit 3 of the flag tells the exponent of page size follows,
+// which is omitted for the default one.
+fn write_memory_type(buf: &mut Vec<u8>, memory_type: &MemoryType) {
+  let flag = buf.len();
+  write_limit(buf, &memory_type.limit);
+  if memory_type.page_size != PAGE_SIZE {
+    buf[flag] |= 0x08;
+    write_uleb128(buf, u64::from(memory_type.page_size.trailing_zeros()));
+  }
+}
+
+fn write_table_type(buf: &mut Vec<u8>, table_type: &TableType) {
+  buf.push(u8::from(&table_type.element_type));
+  write_limit(buf, &table_type.limit);
+}
+
+fn write_global_type(buf: &mut Vec<u8>, global_type: &GlobalType) {
+  buf.push(u8::from(global_type.value_type()));
+  buf.push(if global_type.is_mutable() { 0x01 } else { 0x00 });
+}
+
+fn write_value_types(buf: &mut Vec<u8>, value_types: &[ValueTypes]) {
+  write_uleb128(buf, value_types.len() as u64);
+  for value_type in value_types {
+    buf.push(u8::from(value_type));
+  }
+}
+
+fn write_expression(buf: &mut Vec<u8>, expression: &[u8]) -> Result<()> {
+  encode_instructions(expression, buf)
+}
+
+// NOTE: Locals are expanded while decoding, so consecutive ones are grouped again.
+fn write_code(buf: &mut Vec<u8>, expression: &[u8], locals: &[ValueTypes]) -> Result<()> {
+  let mut groups: Vec<(u32, &ValueTypes)> = vec![];
+  for value_type in locals {
+    if let Some((count, last)) = groups.last_mut() {
+      if *last == value_type {
+        *count += 1;
+        continue;
+      }
+    }
+    groups.push((1, value_type));
+  }
+  let mut body = vec![];
+  write_uleb128(&mut body, groups.len() as u64);
+  for (count, value_type) in groups {
+    write_uleb128(&mut body, u64::from(count));
+    body.push(u8::from(value_type));
+  }
+  write_expression(&mut body, expression)?;
+  write_bytes(buf, &body);
+  Ok(())
+}
+
+fn write_section(buf: &mut Vec<u8>, id: u8, content: &[u8]) {
+  buf.push(id);
+  write_bytes(buf, content);
+}
+
+/// Serialize a decoded module into the binary format.
+// NOTE: Position of custom sections isn't kept while decoding, so all of them are placed at last.
+pub fn encode(module: &Module) -> Result<Vec<u8>> {
+  let mut buf = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+  if !module.function_types.is_empty() {
+    let mut section = vec![];
+    write_vec(&mut section, &module.function_types, |buf, function_type| {
+      buf.push(0x60);
+      write_value_types(buf, function_type.parameters());
+      write_value_types(buf, function_type.returns());
+      Ok(())
+    })?;
+    write_section(&mut buf, 0x1, &section);
+  }
+
+  if module.imports.len() > 0 {
+    let mut section = vec![];
+    write_uleb128(&mut section, module.imports.len() as u64);
+    for import in module.imports.iter() {
+      let module_name = import.module_name.as_ref().map(|x| x.as_bytes());
+      write_bytes(&mut section, module_name.unwrap_or(&[]));
+      write_bytes(&mut section, import.name.as_bytes());
+      match &import.descriptor {
+        ModuleDescriptor::ImportDescriptor(ImportDescriptor::Function(idx)) => {
+          section.push(0x00);
+          write_uleb128(&mut section, u64::from(idx.to_u32()));
+        }
+        ModuleDescriptor::ImportDescriptor(ImportDescriptor::Table(table_type)) => {
+          section.push(0x01);
+          write_table_type(&mut section, table_type);
+        }
+        ModuleDescriptor::ImportDescriptor(ImportDescriptor::Memory(memory_type)) => {
+          section.push(0x02);
+          write_memory_type(&mut section, memory_type);
+        }
+        ModuleDescriptor::ImportDescriptor(ImportDescriptor::Global(global_type)) => {
+          section.push(0x03);
+          write_global_type(&mut section, global_type);
+        }
+        ModuleDescriptor::ImportDescriptor(ImportDescriptor::Tag(idx)) => {
+          section.push(0x04);
+          section.push(0x00);
+          write_uleb128(&mut section, u64::from(idx.to_u32()));
+        }
+        x => unreachable!("Expected import descriptor, got {:?}", x),
+      }
+    }
+    write_section(&mut buf, 0x2, &section);
+  }
+
+  if !module.functions.is_empty() {
+    let mut section = vec![];
+    write_vec(&mut section, &module.functions, |buf, idx| {
+      write_uleb128(buf, u64::from(*idx));
+      Ok(())
+    })?;
+    write_section(&mut buf, 0x3, &section);
+  }
+
+  if !module.tables.is_empty() {
+    let mut section = vec![];
+    write_vec(&mut section, &module.tables, |buf, table_type| {
+      write_table_type(buf, table_type);
+      Ok(())
+    })?;
+    write_section(&mut buf, 0x4, &section);
+  }
+
+  if !module.limits.is_empty() {
+    let mut section = vec![];
+    write_vec(&mut section, &module.limits, |buf, memory_type| {
+      write_memory_type(buf, memory_type);
+      Ok(())
+    })?;
+    write_section(&mut buf, 0x5, &section);
+  }
+
+  // NOTE: Tag section is placed between memory and global sections.
+  if !module.tags.is_empty() {
+    let mut section = vec![];
+    write_vec(&mut section, &module.tags, |buf, idx| {
+      buf.push(0x00);
+      write_uleb128(buf, u64::from(*idx));
+      Ok(())
+    })?;
+    write_section(&mut buf, 0xd, &section);
+  }
+
+  if !module.globals.is_empty() {
+    let mut section = vec![];
+    write_vec(&mut section, &module.globals, |buf, (global_type, init)| {
+      write_global_type(buf, global_type);
+      write_expression(buf, init)
+    })?;
+    write_section(&mut buf, 0x6, &section);
+  }
+
+  if module.exports.len() > 0 {
+    let mut section = vec![];
+    write_uleb128(&mut section, module.exports.len() as u64);
+    for export in module.exports.iter() {
+      write_bytes(&mut section, export.name.as_bytes());
+      let (kind, idx) = match &export.descriptor {
+        ModuleDescriptor::ExportDescriptor(ExportDescriptor::Function(idx)) => (0x00, idx),
+        ModuleDescriptor::ExportDescriptor(ExportDescriptor::Table(idx)) => (0x01, idx),
+        ModuleDescriptor::ExportDescriptor(ExportDescriptor::Memory(idx)) => (0x02, idx),
+        ModuleDescriptor::ExportDescriptor(ExportDescriptor::Global(idx)) => (0x03, idx),
+        ModuleDescriptor::ExportDescriptor(ExportDescriptor::Tag(idx)) => (0x04, idx),
+        x => unreachable!("Expected export descriptor, got {:?}", x),
+      };
+      section.push(kind);
+      write_uleb128(&mut section, u64::from(idx.to_u32()));
+    }
+    write_section(&mut buf, 0x7, &section);
+  }
+
+  if let Some(start) = module.start {
+    let mut section = vec![];
+    write_uleb128(&mut section, u64::from(start));
+    write_section(&mut buf, 0x8, &section);
+  }
+
+  if !module.elements.is_empty() {
+    let mut section = vec![];
+    write_vec(&mut section, &module.elements, |buf, element| {
+      let (expressions_flag, element_kind) = match element.init {
+        ElementInit::Functions(_) => (0b000, 0x00),
+        ElementInit::Expressions(_) => (0b100, u8::from(&element.element_type)),
+      };
+      match &element.mode {
+        ElementMode::Active(table_idx, offset)
+          if table_idx.to_u32() == 0 && element.element_type == ElementType::AnyFunc =>
+        {
+          write_uleb128(buf, expressions_flag);
+          write_expression(buf, offset)?;
+        }
+        ElementMode::Active(table_idx, offset) => {
+          write_uleb128(buf, 0b010 | expressions_flag);
+          write_uleb128(buf, u64::from(table_idx.to_u32()));
+          write_expression(buf, offset)?;
+          buf.push(element_kind);
+        }
+        ElementMode::Passive => {
+          write_uleb128(buf, 0b001 | expressions_flag);
+          buf.push(element_kind);
+        }
+        ElementMode::Declarative => {
+          write_uleb128(buf, 0b011 | expressions_flag);
+          buf.push(element_kind);
+        }
+      };
+      match &element.init {
+        ElementInit::Functions(xs) => write_vec(buf, xs, |buf, idx| {
+          write_uleb128(buf, u64::from(idx.to_u32()));
+          Ok(())
+        }),
+        ElementInit::Expressions(xs) => write_vec(buf, xs, |buf, x| write_expression(buf, x)),
+      }
+    })?;
+    write_section(&mut buf, 0x9, &section);
+  }
+
+  if let Some(data_count) = module.data_count {
+    let mut section = vec![];
+    write_uleb128(&mut section, u64::from(data_count));
+    write_section(&mut buf, 0xc, &section);
+  }
+
+  if !module.codes.is_empty() {
+    let mut section = vec![];
+    write_vec(&mut section, &module.codes, |buf, code| match code {
+      Ok((expression, locals, _)) => write_code(buf, expression, locals),
+      Err(err) => Err(err.clone()),
+    })?;
+    write_section(&mut buf, 0xa, &section);
+  }
+
+  if !module.datas.is_empty() {
+    let mut section = vec![];
+    write_vec(&mut section, &module.datas, |buf, data| {
+      match (&data.offset, data.memidx) {
+        (None, _) => buf.push(0x01),
+        (Some(offset), 0) => {
+          buf.push(0x00);
+          write_expression(buf, offset)?;
+        }
+        (Some(offset), memidx) => {
+          buf.push(0x02);
+          write_uleb128(buf, u64::from(memidx));
+          write_expression(buf, offset)?;
+        }
+      }
+      write_bytes(buf, &data.init);
+      Ok(())
+    })?;
+    write_section(&mut buf, 0xb, &section);
+  }
+
+  for (name, bytes) in module.customs.iter() {
+    let mut section = vec![];
+    write_bytes(&mut section, name.as_bytes());
+    section.extend_from_slice(bytes);
+    write_section(&mut buf, 0x0, &section);
+  }
+
+  Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use embedder::decode_module;
+  use wat::parse_wat;
+  use std::fs::File;
+  use std::io::Read;
+
+  macro_rules! test_round_trip {
+    ($fn_name:ident, $file_name:expr) => {
+      #[test]
+      fn $fn_name() {
+        let mut file = File::open(format!("./dist/{}.wasm", $file_name)).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+        let module = decode_module(&bytes).unwrap();
+        assert_eq!(encode(&module).unwrap(), bytes);
+      }
+    };
+  }
+
+  test_round_trip!(encode_fib, "fib");
+  test_round_trip!(encode_count, "count");
+  test_round_trip!(encode_signed, "signed");
+  test_round_trip!(encode_host_error, "host_error");
+  test_round_trip!(encode_multi_value, "multi_value");
+  test_round_trip!(encode_wasi, "wasi");
+  test_round_trip!(encode_names, "names");
+
+  #[test]
+  fn encode_instructions_of_each_immediate() {
+    let bytes = vec![
+      0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // Header
+      0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7c, // Type section
+      0x03, 0x02, 0x01, 0x00, // Function section
+      0x04, 0x04, 0x01, 0x70, 0x00, 0x01, // Table section
+      0x05, 0x04, 0x01, 0x01, 0x01, 0x02, // Memory section
+      0x06, 0x0a, 0x01, 0x7e, 0x01, 0x42, 0x80, 0x80, 0x80, 0x80, 0x78, 0x0b, // Global section
+      0x0a, 0x3d, 0x01, 0x3b, 0x01, 0x02, 0x7f, // Code section
+      0x02, 0x40, 0x0e, 0x02, 0x00, 0x00, 0x00, 0x0b, // block, br_table
+      0x41, 0xc0, 0xbb, 0x78, 0x11, 0x00, 0x00, // i32.const -123456, call_indirect
+      0x41, 0x00, 0x28, 0x02, 0x80, 0x01, 0x1a, // i32.load offset=128
+      0x3f, 0x00, 0x40, 0x00, 0x21, 0x00, // memory.size, memory.grow
+      0x41, 0x01, 0x04, 0x7c, 0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x3f, // if f64.const
+      0x05, 0x03, 0x7c, 0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0b, // else loop
+      0x0b, 0x0b, // end, end
+      0x00, 0x05, 0x03, 0x66, 0x6f, 0x6f, 0x2a, // Custom section
+    ];
+    let module = decode_module(&bytes).unwrap();
+    assert_eq!(encode(&module).unwrap(), bytes);
+  }
+
+  #[test]
+  fn encode_prefixed_instructions() {
+    let bytes = parse_wat(
+      r#"(module (func (param f64) (result i64) (i64.trunc_sat_f64_u (local.get 0))))"#,
+    )
+    .unwrap();
+    let module = decode_module(&bytes).unwrap();
+    assert_eq!(encode(&module).unwrap(), bytes);
+  }
+
+  #[test]
+  fn encode_bulk_memory() {
+    let bytes = parse_wat(
+      r#"(module
+        (memory 2)
+        (data (i32.const 8) "active")
+        (data "passive")
+        (func
+          (memory.init 1 (i32.const 0) (i32.const 0) (i32.const 4))
+          (data.drop 1)
+          (memory.copy (i32.const 0) (i32.const 8) (i32.const 4))
+          (memory.fill (i32.const 0) (i32.const 0) (i32.const 4))))"#,
+    )
+    .unwrap();
+    let module = decode_module(&bytes).unwrap();
+    assert_eq!(module.data_count, Some(2));
+    assert_eq!(encode(&module).unwrap(), bytes);
+  }
+
+  #[test]
+  fn encode_multiple_memories() {
+    let bytes = parse_wat(
+      r#"(module
+        (memory $a 1)
+        (memory $b 1)
+        (data (memory $b) (i32.const 0) "b")
+        (data "passive")
+        (func
+          (i32.store $b offset=4 (i32.const 0) (i32.load $a (i32.const 0)))
+          (drop (memory.grow $b (memory.size $b)))
+          (memory.init $b 1 (i32.const 0) (i32.const 0) (i32.const 1))
+          (memory.copy $a $b (i32.const 0) (i32.const 0) (i32.const 1))
+          (memory.fill $b (i32.const 0) (i32.const 0) (i32.const 1))))"#,
+    )
+    .unwrap();
+    let module = decode_module(&bytes).unwrap();
+    assert_eq!(module.limits.len(), 2);
+    assert_eq!(encode(&module).unwrap(), bytes);
+  }
+
+  #[test]
+  fn encode_custom_page_size() {
+    let bytes = parse_wat(
+      r#"(module
+        (import "env" "memory" (memory 1 (pagesize 1)))
+        (memory $a 16 1024 (pagesize 1))
+        (memory $b 1 1 shared (pagesize 1))
+        (func (drop (memory.grow $a (memory.size $b)))))"#,
+    )
+    .unwrap();
+    let module = decode_module(&bytes).unwrap();
+    assert_eq!(module.limits[0].page_size, 1);
+    assert_eq!(encode(&module).unwrap(), bytes);
+  }
+
+  #[test]
+  fn encode_atomics() {
+    let bytes = parse_wat(
+      r#"(module
+        (memory $a 1 1 shared)
+        (memory $b 1 2 shared)
+        (func (result i32)
+          (atomic.fence)
+          (i64.atomic.store32 $b offset=8 (i32.const 0) (i64.atomic.load8_u (i32.const 0)))
+          (drop (i32.atomic.rmw16.cmpxchg_u (i32.const 0) (i32.const 0) (i32.const 1)))
+          (drop (memory.atomic.wait64 $b (i32.const 0) (i64.const 0) (i64.const -1)))
+          (memory.atomic.notify (i32.const 0) (i32.const 1))))"#,
+    )
+    .unwrap();
+    let module = decode_module(&bytes).unwrap();
+    assert_eq!(encode(&module).unwrap(), bytes);
+  }
+
+  #[test]
+  fn encode_tail_calls() {
+    let bytes = parse_wat(
+      r#"(module
+        (table 1 funcref)
+        (func $f (param i32) (result i32)
+          (if (result i32) (local.get 0)
+            (then (return_call $f (i32.const 0)))
+            (else (return_call_indirect (param i32) (result i32) (i32.const 1) (i32.const 0))))))"#,
+    )
+    .unwrap();
+    let module = decode_module(&bytes).unwrap();
+    assert_eq!(encode(&module).unwrap(), bytes);
+  }
+
+  #[test]
+  fn encode_exceptions() {
+    let bytes = parse_wat(
+      r#"(module
+        (import "env" "error" (tag $error (param i32)))
+        (tag $empty (export "empty"))
+        (func (param i32) (result i32)
+          (try $outer (result i32)
+            (do
+              (try (result i32)
+                (do (throw $error (local.get 0)))
+                (delegate $outer)))
+            (catch $error)
+            (catch_all (try (do (rethrow 1)) (catch $empty)) (i32.const 0)))))"#,
+    )
+    .unwrap();
+    let module = decode_module(&bytes).unwrap();
+    assert_eq!(encode(&module).unwrap(), bytes);
+  }
+
+  #[test]
+  fn encode_simd() {
+    let bytes = parse_wat(
+      r#"(module
+        (memory $a 1)
+        (memory $b 1)
+        (global v128 (v128.const f32x4 1.5 -0 nan inf))
+        (func (param v128) (result v128)
+          (v128.store64_lane $b offset=8 1 (i32.const 0) (local.get 0))
+          (drop (v128.load32_zero align=2 (i32.const 0)))
+          (drop (i8x16.replace_lane 15 (local.get 0) (i32.const -128)))
+          (i8x16.shuffle 0 1 2 3 4 5 6 7 24 25 26 27 28 29 30 31
+            (v128.load16_lane 3 (i32.const 0) (local.get 0))
+            (f64x2.promote_low_f32x4 (v128.const i16x8 0 1 2 3 4 5 6 65535)))))"#,
+    )
+    .unwrap();
+    let module = decode_module(&bytes).unwrap();
+    assert_eq!(encode(&module).unwrap(), bytes);
+  }
+
+  #[test]
+  fn encode_reference_types() {
+    let bytes = parse_wat(
+      r#"(module
+        (table $f 2 funcref)
+        (table $e 2 externref)
+        (elem (i32.const 0) $g)
+        (elem (table $e) (i32.const 0) externref (ref.null extern))
+        (elem $p funcref (ref.func $g) (ref.null func))
+        (elem declare func $g)
+        (func $g (result funcref)
+          (table.init $f $p (i32.const 0) (i32.const 0) (i32.const 1))
+          (elem.drop $p)
+          (table.copy $e $e (i32.const 0) (i32.const 1) (i32.const 1))
+          (drop (table.grow $e (ref.null extern) (i32.const 1)))
+          (table.fill $e (i32.const 0) (table.get $e (i32.const 0)) (table.size $e))
+          (call_indirect $f (result funcref) (i32.const 0))
+          (select (result funcref) (ref.func $g) (ref.null func) (i32.const 0))
+          (drop (ref.is_null))))"#,
+    )
+    .unwrap();
+    let module = decode_module(&bytes).unwrap();
+    assert_eq!(module.elements.len(), 4);
+    assert_eq!(encode(&module).unwrap(), bytes);
+  }
+}
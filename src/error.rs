@@ -1,5 +1,41 @@
+use alloc::rc::Rc;
+use core::any::Any;
 use core::convert::From;
+use core::fmt;
 use core::option::NoneError;
+use tag::Exception;
+
+// NOTE: Payload of an error raised by host function.
+// It is handed back to embedder as it is through `Trap::HostError`.
+#[derive(Clone)]
+pub struct HostError(Rc<Any>);
+
+impl HostError {
+  pub fn new<T: Any>(payload: T) -> Self {
+    HostError(Rc::new(payload))
+  }
+
+  pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+    self.0.downcast_ref::<T>()
+  }
+
+  pub fn is<T: Any>(&self) -> bool {
+    self.0.is::<T>()
+  }
+}
+
+impl fmt::Debug for HostError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "HostError(..)")
+  }
+}
+
+// NOTE: Payload can't be compared, so it is equal only to itself.
+impl PartialEq for HostError {
+  fn eq(&self, other: &HostError) -> bool {
+    Rc::ptr_eq(&self.0, &other.0)
+  }
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Trap {
@@ -8,6 +44,9 @@ pub enum Trap {
   DataSegmentDoesNotFit,
   ElementSegmentDoesNotFit,
   MemoryAccessOutOfBounds,
+  TableAccessOutOfBounds,
+  UnalignedAtomic,
+  ExpectedSharedMemory,
   BitshiftOverflow,
   IntegerOverflow,
   InvalidConversionToInt,
@@ -30,10 +69,23 @@ pub enum Trap {
   IncompatibleImportType,
   MagicHeaderNotDetected,
   UnsupportedTextform,
+  // NOTE: Line number where the text format couldn't be read.
+  MalformedTextform(u32),
   IntegerRepresentationTooLong,
+  IllegalOpcode,
   FunctionAndCodeInconsitent,
+  DataCountAndDataInconsistent,
   InvalidUTF8Encoding,
   LinearMapOverflowed,
+  // NOTE: Allocation which `ResourceLimiter` denied.
+  ResourceLimitExceeded,
+  HostError(HostError),
+  OutOfFuel,
+  // NOTE: Host function can suspend an invocation by returning it.
+  Yield,
+  NotSuspended,
+  // NOTE: Exception which isn't caught by any handler.
+  Exception(Exception),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -46,7 +98,9 @@ pub enum TypeError {
   IncompatibleImportType,
   InvalidResultArity,
   InvalidAlignment,
+  InvalidLaneIndex,
   InvalidMemorySize,
+  InvalidPageSize,
   InvalidStartFunction,
   UnknownLabel,
   UnknownLocal,
@@ -55,6 +109,12 @@ pub enum TypeError {
   UnknownFunction(u32),
   UnknownTable(u32),
   UnknownGlobal(u32),
+  UnknownData(u32),
+  UnknownElement(u32),
+  UnknownTag(u32),
+  InvalidRethrowLabel,
+  DataCountRequired,
+  UndeclaredFunctionReference,
   ConstantExpressionRequired,
   DuplicateExportName,
   GlobalIsImmutable,
@@ -82,7 +142,8 @@ impl From<WasmError> for self::Trap {
   fn from(wasm_error: WasmError) -> Self {
     match wasm_error {
       WasmError::Trap(e) => e,
-      _ => unreachable!(),
+      // NOTE: Type errors are reported by validation, so that one raised later traps as it is.
+      WasmError::TypeError(_) => Trap::TypeMismatch,
     }
   }
 }
@@ -93,6 +154,18 @@ impl From<Trap> for WasmError {
   }
 }
 
+impl From<HostError> for Trap {
+  fn from(host_error: HostError) -> Self {
+    Trap::HostError(host_error)
+  }
+}
+
+impl From<Exception> for Trap {
+  fn from(exception: Exception) -> Self {
+    Trap::Exception(exception)
+  }
+}
+
 impl From<TypeError> for WasmError {
   fn from(type_error: TypeError) -> Self {
     WasmError::TypeError(type_error)
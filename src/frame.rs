@@ -4,9 +4,8 @@ use core::fmt;
 use core::ops::Sub;
 use error::Result;
 use function::FunctionInstance;
-use indice::Indice;
 use stack::StackEntry;
-use value_type::ValueTypes;
+use value_type::{BlockType, ValueTypes, BLOCK_TYPE_INDEX};
 
 macro_rules! impl_pop_bytes {
   ($name: ident, $ty: ty, $width: expr) => {
@@ -31,6 +30,8 @@ pub struct Frame {
   local_variables: RefCell<Vec<StackEntry>>,
   pub(crate) function_instance: FunctionInstance,
   ptr: Cell<u32>,
+  is_fresh: Cell<bool>,
+  is_tail_called: Cell<bool>,
   pub last_ptr: u32,
   pub return_ptr: usize,
   pub prev_return_ptr: usize,
@@ -39,6 +40,7 @@ pub struct Frame {
 impl Frame {
   impl_pop_bytes!(pop_raw_u32, u32, 4);
   impl_pop_bytes!(pop_raw_u64, u64, 8);
+  impl_pop_bytes!(pop_raw_u128, u128, 16);
 
   pub fn new(
     return_ptr: usize,
@@ -56,6 +58,8 @@ impl Frame {
           return_ptr,
           prev_return_ptr,
           ptr: Cell::new(0),
+          is_fresh: Cell::new(true),
+          is_tail_called: Cell::new(false),
         }
       }
       FunctionInstance::HostFn(_) => Frame {
@@ -65,6 +69,8 @@ impl Frame {
         return_ptr,
         prev_return_ptr,
         ptr: Cell::new(0),
+        is_fresh: Cell::new(true),
+        is_tail_called: Cell::new(false),
       },
     }
   }
@@ -73,8 +79,23 @@ impl Frame {
     self.ptr.get().ge(&self.last_ptr)
   }
 
+  // NOTE: Frame suspended before its first instruction is not fresh anymore.
   pub fn is_fresh(&self) -> bool {
-    self.ptr.get().eq(&0)
+    self.is_fresh.get()
+  }
+
+  pub fn initialized(&self) {
+    self.is_fresh.set(false);
+  }
+
+  // NOTE: Frame which made a tail call is replaced by its callee, so it returns nothing by itself.
+  pub fn is_tail_called(&self) -> bool {
+    self.is_tail_called.get()
+  }
+
+  pub fn tail_called(&self) {
+    self.is_tail_called.set(true);
+    self.jump_to_last();
   }
 
   pub fn get_local_variables(&self) -> RefMut<Vec<StackEntry>> {
@@ -99,6 +120,10 @@ impl Frame {
     self.function_instance.get_return_count()
   }
 
+  pub(crate) fn ptr(&self) -> u32 {
+    self.ptr.get()
+  }
+
   pub fn get_start_of_label(&self) -> u32 {
     self.ptr.get().sub(1)
   }
@@ -118,13 +143,19 @@ impl Frame {
     head
   }
 
-  pub fn pop_runtime_type(&self) -> Option<ValueTypes> {
-    match self.pop_ref() {
-      Some(byte) => Some(ValueTypes::from(*byte)),
-      None => None,
+  pub fn pop_block_type(&self) -> Result<BlockType> {
+    match *self.pop_ref()? {
+      BLOCK_TYPE_INDEX => Ok(BlockType::Index(self.pop_raw_u32()?)),
+      byte => Ok(BlockType::Value(ValueTypes::from(byte))),
     }
   }
 
+  // NOTE: Put back an instruction which has been popped but not evaluated yet.
+  pub fn rewind(&self) {
+    let ptr = self.ptr.get();
+    self.ptr.set(ptr - 1);
+  }
+
   pub fn is_next_empty(&self) -> bool {
     match self.peek() {
       None => true,
@@ -140,15 +171,12 @@ impl Frame {
     let last_ptr = self.last_ptr;
     self.jump_to(last_ptr);
   }
-
-  pub fn get_table_address(&self) -> Indice {
-    From::from(0u32)
-  }
 }
 
 impl fmt::Debug for Frame {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     f.debug_struct("Frame")
+      .field("function", &self.function_instance.name())
       .field(
         "type",
         &format!("{:?}", self.function_instance.get_function_type()),
@@ -1,13 +1,15 @@
 #[cfg(not(test))]
 use alloc::prelude::*;
+use alloc::boxed::Box;
 use alloc::rc::Rc;
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 use core::fmt;
-use error::{Result, TypeError, WasmError};
-use module::ModuleName;
+use caller::Caller;
+use error::{Result, Trap, TypeError, WasmError};
 use stack::StackEntry;
+use store::{ModuleRef, WeakModuleRef};
 use value::Values;
 use value_type::ValueTypes;
 
@@ -64,13 +66,26 @@ impl fmt::Debug for FunctionType {
   }
 }
 
-#[derive(PartialEq)]
 pub struct FunctionInstanceImpl {
   export_name: Option<String>,
   function_type: FunctionType,
   local_variables: Vec<StackEntry>,
   body: Vec<u8>,
-  source_module_name: RefCell<Option<String>>,
+  // NOTE: Module instance which the function is defined in, given on instantiation.
+  module: RefCell<Option<WeakModuleRef>>,
+  // NOTE: Name given by "name" section.
+  name: RefCell<Option<String>>,
+}
+
+// NOTE: Functions are compared by their definitions, regardless of module instances.
+impl PartialEq for FunctionInstanceImpl {
+  fn eq(&self, other: &FunctionInstanceImpl) -> bool {
+    self.export_name == other.export_name
+      && self.function_type == other.function_type
+      && self.local_variables == other.local_variables
+      && self.body == other.body
+      && self.name == other.name
+  }
 }
 
 impl FunctionInstanceImpl {
@@ -91,25 +106,34 @@ impl FunctionInstanceImpl {
   }
 }
 
+type HostCallable = FnMut(&Caller, &[Values]) -> core::result::Result<Vec<Values>, Trap>;
+
 pub struct HostFunction {
   export_name: Option<String>,
   function_type: FunctionType,
-  source_module_name: RefCell<Option<String>>,
-  callable: &'static Fn(&[Values]) -> Vec<Values>,
+  callable: RefCell<Box<HostCallable>>,
 }
 
 impl HostFunction {
-  pub(crate) fn call(&self, arguments: &[Values]) -> Vec<Values> {
-    let callable = self.callable;
-    callable(arguments)
+  pub(crate) fn call(&self, caller: &Caller, arguments: &[Values]) -> Result<Vec<Values>> {
+    let mut callable = self.callable.borrow_mut();
+    let results = (&mut **callable)(caller, arguments)?;
+    let returns = self.function_type.returns();
+    if results.len() != returns.len()
+      || results
+        .iter()
+        .zip(returns.iter())
+        .any(|(result, ty)| &ValueTypes::from(result) != ty)
+    {
+      return Err(WasmError::Trap(Trap::TypeMismatch));
+    }
+    Ok(results)
   }
 }
 
 impl PartialEq for HostFunction {
   fn eq(&self, other: &HostFunction) -> bool {
-    self.export_name == other.export_name
-      && self.function_type == other.function_type
-      && self.source_module_name == other.source_module_name
+    self.export_name == other.export_name && self.function_type == other.function_type
   }
 }
 
@@ -136,23 +160,23 @@ impl FunctionInstance {
       function_type,
       local_variables,
       body,
-      source_module_name: RefCell::new(None),
+      module: RefCell::new(None),
+      name: RefCell::new(None),
     }))
   }
 
   pub fn new_host_fn<F>(
     export_name: Option<String>,
     function_type: FunctionType,
-    callable: &'static F,
+    callable: F,
   ) -> Self
   where
-    F: Fn(&[Values]) -> Vec<Values>,
+    F: FnMut(&Caller, &[Values]) -> core::result::Result<Vec<Values>, Trap> + 'static,
   {
     FunctionInstance::HostFn(Rc::new(HostFunction {
       export_name,
       function_type,
-      source_module_name: RefCell::new(None),
-      callable,
+      callable: RefCell::new(Box::new(callable)),
     }))
   }
 
@@ -163,20 +187,39 @@ impl FunctionInstance {
     }
   }
 
-  pub fn set_source_module_name(&self, name: &ModuleName) {
-    if let Some(name) = name {
-      let mut source_module_name = match self {
-        FunctionInstance::LocalFn(f) => f.source_module_name.borrow_mut(),
-        FunctionInstance::HostFn(f) => f.source_module_name.borrow_mut(),
-      };
-      source_module_name.replace(name.to_owned());
-    };
+  pub(crate) fn set_module(&self, module: &ModuleRef) {
+    if let FunctionInstance::LocalFn(f) = self {
+      f.module.borrow_mut().replace(module.downgrade());
+    }
   }
 
-  pub fn get_source_module_name(&self) -> Option<String> {
+  // NOTE: Host functions don't belong to any module, but evaluated with the one of its caller.
+  pub(crate) fn module(&self) -> Option<ModuleRef> {
     match self {
-      FunctionInstance::LocalFn(f) => f.source_module_name.borrow().to_owned(),
-      FunctionInstance::HostFn(f) => f.source_module_name.borrow().to_owned(),
+      FunctionInstance::LocalFn(f) => f.module.borrow().as_ref()?.upgrade(),
+      FunctionInstance::HostFn(_) => None,
+    }
+  }
+
+  pub(crate) fn set_name(&self, name: &str) {
+    if let FunctionInstance::LocalFn(f) = self {
+      f.name.borrow_mut().replace(name.to_owned());
+    }
+  }
+
+  /// Name of local function given by "name" section, or name of host function.
+  pub fn name(&self) -> Option<String> {
+    match self {
+      FunctionInstance::LocalFn(f) => f.name.borrow().to_owned(),
+      FunctionInstance::HostFn(f) => f.export_name.to_owned(),
+    }
+  }
+
+  pub(crate) fn ptr_eq(&self, other: &FunctionInstance) -> bool {
+    match (self, other) {
+      (FunctionInstance::LocalFn(x), FunctionInstance::LocalFn(y)) => Rc::ptr_eq(x, y),
+      (FunctionInstance::HostFn(x), FunctionInstance::HostFn(y)) => Rc::ptr_eq(x, y),
+      _ => false,
     }
   }
 
@@ -230,6 +273,7 @@ impl fmt::Debug for FunctionInstance {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     let empty: Vec<u8> = vec![];
     f.debug_struct("FunctionInstance")
+      .field("name", &self.name())
       .field(
         "export_name",
         &match self {
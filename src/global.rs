@@ -5,6 +5,7 @@ use alloc::string::String;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 use error::{Result, Trap, WasmError};
+use function::FunctionInstance;
 use indice::Indice;
 use isa::Isa;
 use module::{
@@ -28,6 +29,19 @@ impl GlobalType {
       _ => Err(WasmError::Trap(Trap::InvalidMutability)),
     }
   }
+
+  pub fn value_type(&self) -> &ValueTypes {
+    match self {
+      GlobalType::Const(ty) | GlobalType::Var(ty) => ty,
+    }
+  }
+
+  pub fn is_mutable(&self) -> bool {
+    match self {
+      GlobalType::Const(_) => false,
+      GlobalType::Var(_) => true,
+    }
+  }
 }
 
 #[derive(Debug)]
@@ -53,17 +67,38 @@ impl GlobalInstance {
     self.0.borrow().value.clone()
   }
 
-  pub fn set_value(&self, value: Values) {
+  pub(crate) fn set_value(&self, value: Values) {
     self.0.borrow_mut().value = value;
   }
 
+  /// Write the value to the global, which must be mutable and of the same type.
+  pub fn set(&self, value: Values) -> Result<()> {
+    let global_type = self.global_type();
+    if !global_type.is_mutable() {
+      return Err(WasmError::Trap(Trap::InvalidMutability));
+    }
+    if global_type.value_type() != &ValueTypes::from(&value) {
+      return Err(WasmError::Trap(Trap::TypeMismatch));
+    }
+    self.set_value(value);
+    Ok(())
+  }
+
+  pub fn global_type(&self) -> GlobalType {
+    self.0.borrow().global_type.clone()
+  }
+
   fn is_same_name(&self, name: &str) -> bool {
     self.0.borrow().export_name == Some(name.to_string())
   }
 
-  fn is_same_type(&self, ty: &GlobalType) -> bool {
+  pub(crate) fn is_same_type(&self, ty: &GlobalType) -> bool {
     &self.0.borrow().global_type == ty
   }
+
+  pub(crate) fn ptr_eq(&self, other: &GlobalInstance) -> bool {
+    Rc::ptr_eq(&self.0, &other.0)
+  }
 }
 
 #[derive(Debug, Clone)]
@@ -83,30 +118,27 @@ impl GlobalInstances {
     exports: &ExternalInterfaces,
     imports: &[ExternalInterface],
     external_modules: &ExternalModules,
+    function_instances: &[FunctionInstance],
   ) -> Result<Self> {
     let mut global_instances: Vec<GlobalInstance> = vec![];
+    // NOTE: Imported globals are shared with the exporting module,
+    // so that mutations are visible to both of them.
     for import in imports.iter() {
       match import {
         ExternalInterface {
-          descriptor: ModuleDescriptor::ImportDescriptor(ImportDescriptor::Global(ty)),
-          name,
-          module_name,
+          descriptor: ModuleDescriptor::ImportDescriptor(ImportDescriptor::Global(_)),
+          ..
         } => {
-          let global_instance = external_modules
-            .find_global_instances(module_name)?
-            .find(name)
-            .ok_or(Trap::UnknownImport)?;
-          if !global_instance.is_same_type(ty) {
-            return Err(WasmError::Trap(Trap::IncompatibleImportType));
-          }
+          let global_instance = external_modules.find_global_instance(import)?;
           global_instances.push(global_instance);
         }
         x => unreachable!("Expected global descriptor, got {:?}", x),
       };
     }
+    let count_of_imports = global_instances.len();
     for (idx, (global_type, init)) in globals.into_iter().enumerate() {
       let export_name = exports
-        .find_kind_by_idx(idx as u32, &GLOBAL_DESCRIPTOR)
+        .find_kind_by_idx((count_of_imports + idx) as u32, &GLOBAL_DESCRIPTOR)
         .map(|x| x.name.to_owned());
       let init_first = init.first()?;
       let value = match Isa::from(*init_first) {
@@ -140,6 +172,19 @@ impl GlobalInstances {
           let idx = Indice::from(unsafe { core::mem::transmute::<_, u32>(buf) });
           global_instances.get(idx.to_usize())?.get_value()
         }
+        // NOTE: Only `v128.const` is allowed, which is followed by 16 bytes.
+        Isa::SimdPrefix => {
+          let mut buf = [0; 16];
+          buf.clone_from_slice(&init[5..21]);
+          Values::V128(unsafe { core::mem::transmute::<_, u128>(buf) })
+        }
+        Isa::RefNull => Values::from(ValueTypes::from(*init.get(1)?)),
+        Isa::RefFunc => {
+          let mut buf = [0; 4];
+          buf.clone_from_slice(&init[1..5]);
+          let idx = unsafe { core::mem::transmute::<_, u32>(buf) } as usize;
+          Values::FuncRef(Some(function_instances.get(idx)?.clone()))
+        }
         x => unreachable!("Expected initial value of global, got {:?}", x),
       };
       global_instances.push(GlobalInstance::new(global_type, value, export_name));
@@ -156,6 +201,14 @@ impl GlobalInstances {
       .cloned()
   }
 
+  pub(crate) fn to_vec(&self) -> Vec<GlobalInstance> {
+    self.0.borrow().clone()
+  }
+
+  pub fn get_global_instance(&self, idx: &Indice) -> Option<GlobalInstance> {
+    self.0.borrow().get(idx.to_usize()).cloned()
+  }
+
   pub fn get_global(&self, idx: &Indice) -> Result<Values> {
     self
       .0
@@ -1,8 +1,12 @@
 use core::convert::From;
 use core::convert::Into;
+use core::convert::TryFrom;
 use error::{Result, Trap, WasmError};
+use function::FunctionInstance;
 use global::GlobalInstances;
 use indice::Indice;
+use value::Values;
+use value_type::ValueTypes;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Isa {
@@ -14,13 +18,23 @@ pub enum Isa {
   If,
   Else,
   End,
+  Try,
+  Catch,
+  Throw,
+  Rethrow,
+  Delegate,
+  CatchAll,
   Br,
   BrIf,
   BrTable,
   Return,
   Call,
   CallIndirect,
+  ReturnCall,
+  ReturnCallIndirect,
   Select,
+  // NOTE: Followed by a value type code.
+  SelectTyped,
   DropInst,
   I32Const,
   I64Const,
@@ -31,6 +45,8 @@ pub enum Isa {
   SetLocal,
   GetGlobal,
   SetGlobal,
+  TableGet,
+  TableSet,
   I32Load,
   I64Load,
   F32Load,
@@ -179,6 +195,503 @@ pub enum Isa {
   I64ReinterpretF64,
   F32ReinterpretI32,
   F64ReinterpretI64,
+  I32Extend8Sign,
+  I32Extend16Sign,
+  I64Extend8Sign,
+  I64Extend16Sign,
+  I64Extend32Sign,
+  // NOTE: Followed by a reference type code.
+  RefNull,
+  RefIsNull,
+  RefFunc,
+  // NOTE: Followed by u32 which identifies an instruction of `MiscIsa`.
+  MiscPrefix,
+  // NOTE: Followed by u32 which identifies an instruction of `SimdIsa`.
+  SimdPrefix,
+  // NOTE: Followed by u32 which identifies an instruction of `AtomicIsa`.
+  AtomicPrefix,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum MiscIsa {
+  I32TruncSatSignF32,
+  I32TruncSatUnsignF32,
+  I32TruncSatSignF64,
+  I32TruncSatUnsignF64,
+  I64TruncSatSignF32,
+  I64TruncSatUnsignF32,
+  I64TruncSatSignF64,
+  I64TruncSatUnsignF64,
+  MemoryInit,
+  DataDrop,
+  MemoryCopy,
+  MemoryFill,
+  TableInit,
+  ElemDrop,
+  TableCopy,
+  TableGrow,
+  TableSize,
+  TableFill,
+}
+
+impl TryFrom<u32> for MiscIsa {
+  type Error = Trap;
+  fn try_from(code: u32) -> core::result::Result<Self, Self::Error> {
+    use self::MiscIsa::*;
+    match code {
+      0x00 => Ok(I32TruncSatSignF32),
+      0x01 => Ok(I32TruncSatUnsignF32),
+      0x02 => Ok(I32TruncSatSignF64),
+      0x03 => Ok(I32TruncSatUnsignF64),
+      0x04 => Ok(I64TruncSatSignF32),
+      0x05 => Ok(I64TruncSatUnsignF32),
+      0x06 => Ok(I64TruncSatSignF64),
+      0x07 => Ok(I64TruncSatUnsignF64),
+      0x08 => Ok(MemoryInit),
+      0x09 => Ok(DataDrop),
+      0x0a => Ok(MemoryCopy),
+      0x0b => Ok(MemoryFill),
+      0x0c => Ok(TableInit),
+      0x0d => Ok(ElemDrop),
+      0x0e => Ok(TableCopy),
+      0x0f => Ok(TableGrow),
+      0x10 => Ok(TableSize),
+      0x11 => Ok(TableFill),
+      _ => Err(Trap::IllegalOpcode),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum AtomicRmw {
+  Add,
+  Sub,
+  And,
+  Or,
+  Xor,
+  Xchg,
+  Cmpxchg,
+}
+
+// NOTE: Accessing instructions hold type of value and bit width of memory they access.
+#[derive(Debug, PartialEq, Clone)]
+pub enum AtomicIsa {
+  Notify,
+  Wait32,
+  Wait64,
+  Fence,
+  Load(ValueTypes, u32),
+  Store(ValueTypes, u32),
+  Rmw(AtomicRmw, ValueTypes, u32),
+}
+
+impl TryFrom<u32> for AtomicIsa {
+  type Error = Trap;
+  fn try_from(code: u32) -> core::result::Result<Self, Self::Error> {
+    use self::AtomicIsa::*;
+    use self::AtomicRmw::*;
+    // NOTE: Each group of accessing instructions is ordered by these operands.
+    let operand = |code: u32| match (code - 0x10) % 7 {
+      0 => (ValueTypes::I32, 32),
+      1 => (ValueTypes::I64, 64),
+      2 => (ValueTypes::I32, 8),
+      3 => (ValueTypes::I32, 16),
+      4 => (ValueTypes::I64, 8),
+      5 => (ValueTypes::I64, 16),
+      _ => (ValueTypes::I64, 32),
+    };
+    match code {
+      0x00 => Ok(Notify),
+      0x01 => Ok(Wait32),
+      0x02 => Ok(Wait64),
+      0x03 => Ok(Fence),
+      0x10...0x4e => {
+        let (ty, width) = operand(code);
+        Ok(match (code - 0x10) / 7 {
+          0 => Load(ty, width),
+          1 => Store(ty, width),
+          2 => Rmw(Add, ty, width),
+          3 => Rmw(Sub, ty, width),
+          4 => Rmw(And, ty, width),
+          5 => Rmw(Or, ty, width),
+          6 => Rmw(Xor, ty, width),
+          7 => Rmw(Xchg, ty, width),
+          _ => Rmw(Cmpxchg, ty, width),
+        })
+      }
+      _ => Err(Trap::IllegalOpcode),
+    }
+  }
+}
+
+// NOTE: Interpretation of 128-bit vector as lanes.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Shape {
+  I8x16,
+  I16x8,
+  I32x4,
+  I64x2,
+  F32x4,
+  F64x2,
+}
+
+impl Shape {
+  pub fn lane_width(self) -> u32 {
+    match self {
+      Shape::I8x16 => 8,
+      Shape::I16x8 => 16,
+      Shape::I32x4 | Shape::F32x4 => 32,
+      Shape::I64x2 | Shape::F64x2 => 64,
+    }
+  }
+
+  pub fn lanes(self) -> u32 {
+    128 / self.lane_width()
+  }
+
+  pub fn is_float(self) -> bool {
+    match self {
+      Shape::F32x4 | Shape::F64x2 => true,
+      _ => false,
+    }
+  }
+
+  // NOTE: Type of a value which is splatted to or extracted from lanes.
+  pub fn scalar_type(self) -> ValueTypes {
+    match self {
+      Shape::I8x16 | Shape::I16x8 | Shape::I32x4 => ValueTypes::I32,
+      Shape::I64x2 => ValueTypes::I64,
+      Shape::F32x4 => ValueTypes::F32,
+      Shape::F64x2 => ValueTypes::F64,
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Half {
+  Low,
+  High,
+}
+
+// NOTE: Shape of instructions which change it is the one of their result.
+// Boolean tells the lanes are signed, and is always true for floats.
+#[derive(Debug, PartialEq, Clone)]
+pub enum SimdUnary {
+  Not,
+  Abs(Shape),
+  Neg(Shape),
+  Popcnt,
+  Sqrt(Shape),
+  Ceil(Shape),
+  Floor(Shape),
+  Trunc(Shape),
+  Nearest(Shape),
+  ExtaddPairwise(Shape, bool),
+  Extend(Shape, Half, bool),
+  TruncSatF32x4(bool),
+  TruncSatF64x2Zero(bool),
+  ConvertI32x4(bool),
+  ConvertLowI32x4(bool),
+  DemoteF64x2Zero,
+  PromoteLowF32x4,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SimdBinary {
+  And,
+  AndNot,
+  Or,
+  Xor,
+  Swizzle,
+  Eq(Shape),
+  Ne(Shape),
+  Lt(Shape, bool),
+  Gt(Shape, bool),
+  Le(Shape, bool),
+  Ge(Shape, bool),
+  Add(Shape),
+  Sub(Shape),
+  Mul(Shape),
+  Div(Shape),
+  AddSat(Shape, bool),
+  SubSat(Shape, bool),
+  Min(Shape, bool),
+  Max(Shape, bool),
+  Pmin(Shape),
+  Pmax(Shape),
+  Avgr(Shape),
+  Narrow(Shape, bool),
+  Q15MulrSat,
+  Dot,
+  ExtMul(Shape, Half, bool),
+}
+
+// NOTE: Instructions are grouped by operands they take from stack.
+// Loads and stores of a part of vector hold bit width of memory they access,
+// except that extending loads hold shape of the result.
+#[derive(Debug, PartialEq, Clone)]
+pub enum SimdIsa {
+  // NOTE: Followed by memory argument.
+  Load,
+  LoadExtend(Shape, bool),
+  LoadSplat(u32),
+  LoadZero(u32),
+  Store,
+  // NOTE: Followed by memory argument and index of lane.
+  LoadLane(u32),
+  StoreLane(u32),
+  // NOTE: Followed by 16 bytes of vector.
+  Const,
+  // NOTE: Followed by 16 bytes of lane indices.
+  Shuffle,
+  Splat(Shape),
+  // NOTE: Followed by index of lane.
+  ExtractLane(Shape, bool),
+  ReplaceLane(Shape),
+  Unary(SimdUnary),
+  Binary(SimdBinary),
+  Bitselect,
+  Shl(Shape),
+  Shr(Shape, bool),
+  AnyTrue,
+  AllTrue(Shape),
+  Bitmask(Shape),
+}
+
+impl TryFrom<u32> for SimdIsa {
+  type Error = Trap;
+  fn try_from(code: u32) -> core::result::Result<Self, Self::Error> {
+    use self::Half::*;
+    use self::Shape::*;
+    use self::SimdBinary::*;
+    use self::SimdIsa::*;
+    use self::SimdUnary::*;
+    let integers = [I8x16, I16x8, I32x4];
+    let floats = [F32x4, F64x2];
+    let instruction = match code {
+      0x00 => Load,
+      0x01 => LoadExtend(I16x8, true),
+      0x02 => LoadExtend(I16x8, false),
+      0x03 => LoadExtend(I32x4, true),
+      0x04 => LoadExtend(I32x4, false),
+      0x05 => LoadExtend(I64x2, true),
+      0x06 => LoadExtend(I64x2, false),
+      0x07 => LoadSplat(8),
+      0x08 => LoadSplat(16),
+      0x09 => LoadSplat(32),
+      0x0a => LoadSplat(64),
+      0x0b => Store,
+      0x0c => Const,
+      0x0d => Shuffle,
+      0x0e => Binary(Swizzle),
+      0x0f => Splat(I8x16),
+      0x10 => Splat(I16x8),
+      0x11 => Splat(I32x4),
+      0x12 => Splat(I64x2),
+      0x13 => Splat(F32x4),
+      0x14 => Splat(F64x2),
+      0x15 => ExtractLane(I8x16, true),
+      0x16 => ExtractLane(I8x16, false),
+      0x17 => ReplaceLane(I8x16),
+      0x18 => ExtractLane(I16x8, true),
+      0x19 => ExtractLane(I16x8, false),
+      0x1a => ReplaceLane(I16x8),
+      0x1b => ExtractLane(I32x4, true),
+      0x1c => ReplaceLane(I32x4),
+      0x1d => ExtractLane(I64x2, true),
+      0x1e => ReplaceLane(I64x2),
+      0x1f => ExtractLane(F32x4, true),
+      0x20 => ReplaceLane(F32x4),
+      0x21 => ExtractLane(F64x2, true),
+      0x22 => ReplaceLane(F64x2),
+      // NOTE: Comparisons of integers are ordered as eq, ne, lt_s, lt_u, ... ge_u.
+      0x23...0x40 => {
+        let shape = integers[((code - 0x23) / 10) as usize];
+        let sign = (code - 0x23) % 2 == 0;
+        Binary(match (code - 0x23) % 10 {
+          0 => Eq(shape),
+          1 => Ne(shape),
+          2 | 3 => Lt(shape, sign),
+          4 | 5 => Gt(shape, sign),
+          6 | 7 => Le(shape, sign),
+          _ => Ge(shape, sign),
+        })
+      }
+      // NOTE: Comparisons of floats are ordered as eq, ne, lt, gt, le, ge.
+      0x41...0x4c => {
+        let shape = floats[((code - 0x41) / 6) as usize];
+        Binary(match (code - 0x41) % 6 {
+          0 => Eq(shape),
+          1 => Ne(shape),
+          2 => Lt(shape, true),
+          3 => Gt(shape, true),
+          4 => Le(shape, true),
+          _ => Ge(shape, true),
+        })
+      }
+      0x4d => Unary(Not),
+      0x4e => Binary(And),
+      0x4f => Binary(AndNot),
+      0x50 => Binary(Or),
+      0x51 => Binary(Xor),
+      0x52 => Bitselect,
+      0x53 => AnyTrue,
+      0x54 => LoadLane(8),
+      0x55 => LoadLane(16),
+      0x56 => LoadLane(32),
+      0x57 => LoadLane(64),
+      0x58 => StoreLane(8),
+      0x59 => StoreLane(16),
+      0x5a => StoreLane(32),
+      0x5b => StoreLane(64),
+      0x5c => LoadZero(32),
+      0x5d => LoadZero(64),
+      0x5e => Unary(DemoteF64x2Zero),
+      0x5f => Unary(PromoteLowF32x4),
+      0x60 => Unary(Abs(I8x16)),
+      0x61 => Unary(Neg(I8x16)),
+      0x62 => Unary(Popcnt),
+      0x63 => AllTrue(I8x16),
+      0x64 => Bitmask(I8x16),
+      0x65 => Binary(Narrow(I8x16, true)),
+      0x66 => Binary(Narrow(I8x16, false)),
+      0x67 => Unary(Ceil(F32x4)),
+      0x68 => Unary(Floor(F32x4)),
+      0x69 => Unary(Trunc(F32x4)),
+      0x6a => Unary(Nearest(F32x4)),
+      0x6b => Shl(I8x16),
+      0x6c => Shr(I8x16, true),
+      0x6d => Shr(I8x16, false),
+      0x6e => Binary(Add(I8x16)),
+      0x6f => Binary(AddSat(I8x16, true)),
+      0x70 => Binary(AddSat(I8x16, false)),
+      0x71 => Binary(Sub(I8x16)),
+      0x72 => Binary(SubSat(I8x16, true)),
+      0x73 => Binary(SubSat(I8x16, false)),
+      0x74 => Unary(Ceil(F64x2)),
+      0x75 => Unary(Floor(F64x2)),
+      0x76 => Binary(Min(I8x16, true)),
+      0x77 => Binary(Min(I8x16, false)),
+      0x78 => Binary(Max(I8x16, true)),
+      0x79 => Binary(Max(I8x16, false)),
+      0x7a => Unary(Trunc(F64x2)),
+      0x7b => Binary(Avgr(I8x16)),
+      0x7c => Unary(ExtaddPairwise(I16x8, true)),
+      0x7d => Unary(ExtaddPairwise(I16x8, false)),
+      0x7e => Unary(ExtaddPairwise(I32x4, true)),
+      0x7f => Unary(ExtaddPairwise(I32x4, false)),
+      0x80 => Unary(Abs(I16x8)),
+      0x81 => Unary(Neg(I16x8)),
+      0x82 => Binary(Q15MulrSat),
+      0x83 => AllTrue(I16x8),
+      0x84 => Bitmask(I16x8),
+      0x85 => Binary(Narrow(I16x8, true)),
+      0x86 => Binary(Narrow(I16x8, false)),
+      0x87 => Unary(Extend(I16x8, Low, true)),
+      0x88 => Unary(Extend(I16x8, High, true)),
+      0x89 => Unary(Extend(I16x8, Low, false)),
+      0x8a => Unary(Extend(I16x8, High, false)),
+      0x8b => Shl(I16x8),
+      0x8c => Shr(I16x8, true),
+      0x8d => Shr(I16x8, false),
+      0x8e => Binary(Add(I16x8)),
+      0x8f => Binary(AddSat(I16x8, true)),
+      0x90 => Binary(AddSat(I16x8, false)),
+      0x91 => Binary(Sub(I16x8)),
+      0x92 => Binary(SubSat(I16x8, true)),
+      0x93 => Binary(SubSat(I16x8, false)),
+      0x94 => Unary(Nearest(F64x2)),
+      0x95 => Binary(Mul(I16x8)),
+      0x96 => Binary(Min(I16x8, true)),
+      0x97 => Binary(Min(I16x8, false)),
+      0x98 => Binary(Max(I16x8, true)),
+      0x99 => Binary(Max(I16x8, false)),
+      0x9b => Binary(Avgr(I16x8)),
+      0x9c => Binary(ExtMul(I16x8, Low, true)),
+      0x9d => Binary(ExtMul(I16x8, High, true)),
+      0x9e => Binary(ExtMul(I16x8, Low, false)),
+      0x9f => Binary(ExtMul(I16x8, High, false)),
+      0xa0 => Unary(Abs(I32x4)),
+      0xa1 => Unary(Neg(I32x4)),
+      0xa3 => AllTrue(I32x4),
+      0xa4 => Bitmask(I32x4),
+      0xa7 => Unary(Extend(I32x4, Low, true)),
+      0xa8 => Unary(Extend(I32x4, High, true)),
+      0xa9 => Unary(Extend(I32x4, Low, false)),
+      0xaa => Unary(Extend(I32x4, High, false)),
+      0xab => Shl(I32x4),
+      0xac => Shr(I32x4, true),
+      0xad => Shr(I32x4, false),
+      0xae => Binary(Add(I32x4)),
+      0xb1 => Binary(Sub(I32x4)),
+      0xb5 => Binary(Mul(I32x4)),
+      0xb6 => Binary(Min(I32x4, true)),
+      0xb7 => Binary(Min(I32x4, false)),
+      0xb8 => Binary(Max(I32x4, true)),
+      0xb9 => Binary(Max(I32x4, false)),
+      0xba => Binary(Dot),
+      0xbc => Binary(ExtMul(I32x4, Low, true)),
+      0xbd => Binary(ExtMul(I32x4, High, true)),
+      0xbe => Binary(ExtMul(I32x4, Low, false)),
+      0xbf => Binary(ExtMul(I32x4, High, false)),
+      0xc0 => Unary(Abs(I64x2)),
+      0xc1 => Unary(Neg(I64x2)),
+      0xc3 => AllTrue(I64x2),
+      0xc4 => Bitmask(I64x2),
+      0xc7 => Unary(Extend(I64x2, Low, true)),
+      0xc8 => Unary(Extend(I64x2, High, true)),
+      0xc9 => Unary(Extend(I64x2, Low, false)),
+      0xca => Unary(Extend(I64x2, High, false)),
+      0xcb => Shl(I64x2),
+      0xcc => Shr(I64x2, true),
+      0xcd => Shr(I64x2, false),
+      0xce => Binary(Add(I64x2)),
+      0xd1 => Binary(Sub(I64x2)),
+      0xd5 => Binary(Mul(I64x2)),
+      0xd6 => Binary(Eq(I64x2)),
+      0xd7 => Binary(Ne(I64x2)),
+      0xd8 => Binary(Lt(I64x2, true)),
+      0xd9 => Binary(Gt(I64x2, true)),
+      0xda => Binary(Le(I64x2, true)),
+      0xdb => Binary(Ge(I64x2, true)),
+      0xdc => Binary(ExtMul(I64x2, Low, true)),
+      0xdd => Binary(ExtMul(I64x2, High, true)),
+      0xde => Binary(ExtMul(I64x2, Low, false)),
+      0xdf => Binary(ExtMul(I64x2, High, false)),
+      0xe0 => Unary(Abs(F32x4)),
+      0xe1 => Unary(Neg(F32x4)),
+      0xe3 => Unary(Sqrt(F32x4)),
+      0xe4 => Binary(Add(F32x4)),
+      0xe5 => Binary(Sub(F32x4)),
+      0xe6 => Binary(Mul(F32x4)),
+      0xe7 => Binary(Div(F32x4)),
+      0xe8 => Binary(Min(F32x4, true)),
+      0xe9 => Binary(Max(F32x4, true)),
+      0xea => Binary(Pmin(F32x4)),
+      0xeb => Binary(Pmax(F32x4)),
+      0xec => Unary(Abs(F64x2)),
+      0xed => Unary(Neg(F64x2)),
+      0xef => Unary(Sqrt(F64x2)),
+      0xf0 => Binary(Add(F64x2)),
+      0xf1 => Binary(Sub(F64x2)),
+      0xf2 => Binary(Mul(F64x2)),
+      0xf3 => Binary(Div(F64x2)),
+      0xf4 => Binary(Min(F64x2, true)),
+      0xf5 => Binary(Max(F64x2, true)),
+      0xf6 => Binary(Pmin(F64x2)),
+      0xf7 => Binary(Pmax(F64x2)),
+      0xf8 => Unary(TruncSatF32x4(true)),
+      0xf9 => Unary(TruncSatF32x4(false)),
+      0xfa => Unary(ConvertI32x4(true)),
+      0xfb => Unary(ConvertI32x4(false)),
+      0xfc => Unary(TruncSatF64x2Zero(true)),
+      0xfd => Unary(TruncSatF64x2Zero(false)),
+      0xfe => Unary(ConvertLowI32x4(true)),
+      0xff => Unary(ConvertLowI32x4(false)),
+      _ => return Err(Trap::IllegalOpcode),
+    };
+    Ok(instruction)
+  }
 }
 
 impl Isa {
@@ -207,6 +720,31 @@ impl Isa {
       _ => unreachable!("Expected offset value of memory"),
     }
   }
+
+  pub(crate) fn constant_reference(
+    source: &[u8],
+    global_instances: &GlobalInstances,
+    function_instances: &[FunctionInstance],
+  ) -> Result<Values> {
+    use self::Isa::*;
+    let constant_instruction = Isa::from(*source.first()?);
+    match constant_instruction {
+      RefNull => Ok(Values::from(ValueTypes::from(*source.get(1)?))),
+      RefFunc => {
+        let mut buf = [0; 4];
+        buf.clone_from_slice(&source[1..5]);
+        let idx = unsafe { core::mem::transmute::<_, u32>(buf) } as usize;
+        Ok(Values::FuncRef(Some(function_instances.get(idx)?.clone())))
+      }
+      GetGlobal => {
+        let mut buf = [0; 4];
+        buf.clone_from_slice(&source[1..5]);
+        let idx = Indice::from(unsafe { core::mem::transmute::<_, u32>(buf) });
+        global_instances.get_global(&idx)
+      }
+      x => unreachable!("Expected reference, got {:?}", x),
+    }
+  }
 }
 
 impl From<u8> for Isa {
@@ -219,7 +757,11 @@ impl From<u8> for Isa {
       0x3 => Loop,
       0x4 => If,
       0x5 => Else,
-      0x06 | 0x07 | 0x08 | 0x09 | 0x0A => Reserved,
+      0x06 => Try,
+      0x07 => Catch,
+      0x08 => Throw,
+      0x09 => Rethrow,
+      0x0A => Reserved,
       0x0b => End,
       0x0C => Br,
       0x0D => BrIf,
@@ -227,15 +769,22 @@ impl From<u8> for Isa {
       0x0f => Return,
       0x10 => Call,
       0x11 => CallIndirect,
-      0x12 | 0x13 | 0x14 | 0x15 | 0x16 | 0x17 | 0x18 | 0x19 => Reserved,
+      0x12 => ReturnCall,
+      0x13 => ReturnCallIndirect,
+      0x14 | 0x15 | 0x16 | 0x17 => Reserved,
+      0x18 => Delegate,
+      0x19 => CatchAll,
       0x1a => DropInst,
       0x1b => Select,
+      0x1c => SelectTyped,
       0x20 => GetLocal,
       0x21 => SetLocal,
       0x22 => TeeLocal,
       0x23 => GetGlobal,
       0x24 => SetGlobal,
-      0x25 | 0x26 | 0x27 => Reserved,
+      0x25 => TableGet,
+      0x26 => TableSet,
+      0x27 => Reserved,
       0x28 => I32Load,
       0x29 => I64Load,
       0x2a => F32Load,
@@ -388,6 +937,17 @@ impl From<u8> for Isa {
       0xbd => I64ReinterpretF64,
       0xbe => F32ReinterpretI32,
       0xbf => F64ReinterpretI64,
+      0xc0 => I32Extend8Sign,
+      0xc1 => I32Extend16Sign,
+      0xc2 => I64Extend8Sign,
+      0xc3 => I64Extend16Sign,
+      0xc4 => I64Extend32Sign,
+      0xd0 => RefNull,
+      0xd1 => RefIsNull,
+      0xd2 => RefFunc,
+      0xfc => MiscPrefix,
+      0xfd => SimdPrefix,
+      0xfe => AtomicPrefix,
       x => unreachable!("Code {:x?} does not supported yet.", x),
     }
   }
@@ -405,19 +965,30 @@ impl Into<u8> for Isa {
       If => 0x4,
       Else => 0x5,
       End => 0x0b,
+      Try => 0x06,
+      Catch => 0x07,
+      Throw => 0x08,
+      Rethrow => 0x09,
+      Delegate => 0x18,
+      CatchAll => 0x19,
       Br => 0x0C,
       BrIf => 0x0D,
       BrTable => 0x0e,
       Return => 0x0f,
       Call => 0x10,
       CallIndirect => 0x11,
+      ReturnCall => 0x12,
+      ReturnCallIndirect => 0x13,
       DropInst => 0x1a,
       Select => 0x1b,
+      SelectTyped => 0x1c,
       GetLocal => 0x20,
       SetLocal => 0x21,
       TeeLocal => 0x22,
       GetGlobal => 0x23,
       SetGlobal => 0x24,
+      TableGet => 0x25,
+      TableSet => 0x26,
       I32Load => 0x28,
       I64Load => 0x29,
       F32Load => 0x2a,
@@ -570,6 +1141,17 @@ impl Into<u8> for Isa {
       I64ReinterpretF64 => 0xbd,
       F32ReinterpretI32 => 0xbe,
       F64ReinterpretI64 => 0xbf,
+      I32Extend8Sign => 0xc0,
+      I32Extend16Sign => 0xc1,
+      I64Extend8Sign => 0xc2,
+      I64Extend16Sign => 0xc3,
+      I64Extend32Sign => 0xc4,
+      RefNull => 0xd0,
+      RefIsNull => 0xd1,
+      RefFunc => 0xd2,
+      MiscPrefix => 0xfc,
+      SimdPrefix => 0xfd,
+      AtomicPrefix => 0xfe,
     }
   }
 }
@@ -592,9 +1174,10 @@ pub fn into_vec_u8(this: &[ComposedCode]) -> alloc::vec::Vec<u8> {
 }
 
 impl Isa {
-  pub fn is_else_or_end(code: Option<u8>) -> bool {
+  // NOTE: Instructions of a block are terminated by either of else, end, or clauses of try.
+  pub fn is_end_of_block(code: Option<u8>) -> bool {
     match code {
-      Some(0x5) | Some(0x0b) => true,
+      Some(0x5) | Some(0x0b) | Some(0x07) | Some(0x18) | Some(0x19) => true,
       _ => false,
     }
   }
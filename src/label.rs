@@ -1,3 +1,5 @@
+use alloc::vec::Vec;
+use tag::Exception;
 use value_type::ValueTypes;
 
 #[derive(PartialEq, Debug, Clone)]
@@ -7,12 +9,36 @@ pub enum LabelKind {
   Loop,
   Block,
   Frame,
+  Try,
+  Catch,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Handler {
+  // NOTE: Pairs of an index of tag, None for catch_all, and where the handler starts.
+  Catch(Vec<(Option<u32>, u32)>),
+  // NOTE: Depth of label which exceptions are delegated to.
+  Delegate(u32),
+  // NOTE: Exception being handled, which `rethrow` throws again.
+  Caught(Exception),
 }
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct Label {
   pub(crate) source_instruction: LabelKind,
-  // FIXME: To Vec type
-  pub(crate) return_type: ValueTypes,
+  pub(crate) parameter_type: Vec<ValueTypes>,
+  pub(crate) return_type: Vec<ValueTypes>,
   pub(crate) continuation: u32,
+  pub(crate) handler: Option<Handler>,
+}
+
+impl Label {
+  /// Count of values which a branch to the label carries.
+  /// Branch to loop re-enters it, so that it takes parameters instead of results.
+  pub fn arity(&self) -> usize {
+    match self.source_instruction {
+      LabelKind::Loop => self.parameter_type.len(),
+      _ => self.return_type.len(),
+    }
+  }
 }
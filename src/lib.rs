@@ -3,7 +3,7 @@
 #![feature(int_to_from_bytes)]
 #![feature(alloc)]
 #![feature(core_intrinsics)]
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 #![allow(clippy::needless_range_loop)]
 
 #[cfg(test)]
@@ -15,16 +15,18 @@ extern crate std as alloc;
 #[macro_use]
 extern crate alloc;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "std"))]
 #[macro_use]
 extern crate core;
 
 extern crate heapless;
 extern crate libm;
 
+mod caller;
 #[macro_use]
 mod decode;
 mod embedder;
+mod encode;
 mod error;
 mod frame;
 mod function;
@@ -32,31 +34,54 @@ mod global;
 mod indice;
 mod isa;
 mod label;
+mod limiter;
 mod memory;
 mod module;
+mod simd;
 mod spectest;
 mod stack;
 mod store;
 mod table;
+mod tag;
 mod validate;
 mod value;
 mod value_type;
 mod vm;
+#[cfg(feature = "std")]
+mod wasi;
+mod wat;
 
-pub use self::embedder::{decode_module, init_store, instantiate_module, validate_module};
-pub use self::error::{Trap, WasmError};
+pub use self::caller::{Caller, MemoryRef};
+pub use self::decode::Names;
+pub use self::embedder::{
+  decode_module, encode_module, init_store, instantiate_module, validate_module,
+};
+pub use self::error::{HostError, Trap, WasmError};
 pub use self::function::{FunctionInstance, FunctionType};
+pub use self::global::{GlobalInstance, GlobalType};
+pub use self::limiter::ResourceLimiter;
+pub use self::memory::{Limit, MemoryInstance, MemoryType};
+#[cfg(feature = "std")]
+pub use self::memory::SharedMemory;
 pub use self::module::{ExternalModule, ExternalModules};
 pub use self::spectest::create_spectest;
+pub use self::store::Store;
+pub use self::table::TableInstance;
+pub use self::tag::{Exception, TagInstance};
 pub use self::value::Values;
 pub use self::value_type::ValueTypes;
-pub use self::vm::ModuleInstance;
+pub use self::vm::{Invocation, ModuleInstance, Suspended, TrapInfo, TrapLocation};
+pub use self::wat::{parse_wat, script};
+#[cfg(feature = "std")]
+pub use self::wasi::{create_wasi, ProcExit, WASI_MODULE_NAME};
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use error::TypeError;
     use std::fs::File;
     use std::io::Read;
+    use std::{f32, f64};
 
     #[test]
     fn test_repl() {
@@ -79,13 +104,16 @@ mod tests {
                 let section = decode_module(&bytes);
                 let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
                 let actual = vm.run("_subject", $call_arguments).unwrap();
-                assert_eq!(actual, Values::I32($expect_value));
+                assert_eq!(actual, vec![Values::I32($expect_value)]);
             }
         };
     }
 
-    fn my_hal_function(_arguments: &[Values]) -> alloc::vec::Vec<Values> {
-        [Values::I32(3 * 5)].to_vec()
+    fn my_hal_function(
+        _caller: &Caller,
+        _arguments: &[Values],
+    ) -> Result<alloc::vec::Vec<Values>, Trap> {
+        Ok([Values::I32(3 * 5)].to_vec())
     }
 
     #[test]
@@ -105,7 +133,7 @@ mod tests {
                     [ValueTypes::I32, ValueTypes::I32].to_vec(),
                     [ValueTypes::I32].to_vec(),
                 ),
-                &my_hal_function,
+                my_hal_function,
             )]
             .to_vec(),
             [].to_vec(),
@@ -124,7 +152,2057 @@ mod tests {
                 [Values::I32(3), Values::I32(5)].to_vec(),
             )
             .unwrap();
-        assert_eq!(actual, Values::I32(25));
+        assert_eq!(actual, vec![Values::I32(25)]);
+    }
+
+    #[test]
+    fn eval_host_function_with_caller() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut file = File::open("./dist/caller.wasm").unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let received = Rc::new(RefCell::new(vec![]));
+        let received_by_host = received.clone();
+        let mut external_modules = ExternalModules::default();
+        let external_module = ExternalModule::new(
+            vec![FunctionInstance::new_host_fn(
+                Some("read_string".to_owned()),
+                FunctionType::new(vec![ValueTypes::I32, ValueTypes::I32], vec![ValueTypes::I32]),
+                move |caller: &Caller, arguments: &[Values]| {
+                    let (ptr, len) = match arguments {
+                        [Values::I32(ptr), Values::I32(len)] => (*ptr as u32, *len as usize),
+                        _ => return Err(Trap::TypeMismatch),
+                    };
+                    let mut buf = vec![0; len];
+                    caller.read_memory(0, ptr, &mut buf)?;
+                    received_by_host.borrow_mut().push(buf);
+                    caller.write_memory(0, 0, &(len as u32).to_le_bytes())?;
+                    let calls = match caller.get_global("calls") {
+                        Some(Values::I32(calls)) => calls,
+                        _ => return Err(Trap::Notfound),
+                    };
+                    caller.set_global("calls", Values::I32(calls + 1))?;
+                    Ok(vec![Values::I32(len as i32)])
+                },
+            )],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        );
+        external_modules
+            .register_module(Some("env".to_owned()), external_module)
+            .unwrap();
+
+        let store = init_store();
+        let section = decode_module(&bytes);
+        let mut vm = instantiate_module(store, section, external_modules, 65536).unwrap();
+        assert_eq!(vm.run("_subject", vec![]).unwrap(), vec![Values::I32(10)]);
+        assert_eq!(vm.run("_subject", vec![]).unwrap(), vec![Values::I32(10)]);
+        assert_eq!(vm.run("calls", vec![]).unwrap(), vec![Values::I32(2)]);
+        assert_eq!(
+            *received.borrow(),
+            vec![b"hello".to_vec(), b"hello".to_vec()]
+        );
+    }
+
+    #[test]
+    fn eval_host_function_with_caller_exports() {
+        let mut external_modules = ExternalModules::default();
+        let external_module = ExternalModule::new(
+            vec![FunctionInstance::new_host_fn(
+                Some("inspect".to_owned()),
+                FunctionType::new(vec![], vec![ValueTypes::I32]),
+                |caller: &Caller, _: &[Values]| {
+                    assert!(caller.function("double").is_some());
+                    assert!(caller.function("mem").is_none());
+                    assert!(caller.table("tbl").is_some());
+                    assert_eq!(caller.memory_size("mem"), Some(1));
+                    assert_eq!(
+                        caller.read_memory("missing", 0, &mut [0]),
+                        Err(Trap::MemoryAccessOutOfBounds)
+                    );
+                    let mut buf = [0];
+                    caller.read_memory("mem", 0, &mut buf)?;
+                    caller.write_memory(MemoryRef::Index(0), 1, &buf)?;
+                    Ok(vec![Values::I32(i32::from(buf[0]))])
+                },
+            )],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        );
+        external_modules
+            .register_module(Some("env".to_owned()), external_module)
+            .unwrap();
+
+        let section = decode_module(
+            br#"(module
+              (import "env" "inspect" (func $inspect (result i32)))
+              (memory (export "mem") 1)
+              (data (i32.const 0) "\07")
+              (table (export "tbl") 1 funcref)
+              (func (export "double") (param i32) (result i32)
+                (i32.mul (local.get 0) (i32.const 2)))
+              (func (export "run") (result i32)
+                (i32.add (call $inspect) (i32.load8_u (i32.const 1)))))"#,
+        );
+        let mut vm = instantiate_module(init_store(), section, external_modules, 65536).unwrap();
+        assert_eq!(vm.run("run", vec![]), Ok(vec![Values::I32(14)]));
+    }
+
+    #[test]
+    fn eval_host_error() {
+        #[derive(Debug, PartialEq)]
+        struct NegativeInput(i32);
+
+        let mut file = File::open("./dist/host_error.wasm").unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let mut external_modules = ExternalModules::default();
+        let external_module = ExternalModule::new(
+            vec![FunctionInstance::new_host_fn(
+                Some("checked_double".to_owned()),
+                FunctionType::new(vec![ValueTypes::I32], vec![ValueTypes::I32]),
+                |_: &Caller, arguments: &[Values]| match arguments {
+                    [Values::I32(n)] if *n < 0 => Err(HostError::new(NegativeInput(*n)).into()),
+                    [Values::I32(n)] => Ok(vec![Values::I32(n * 2)]),
+                    _ => Err(Trap::TypeMismatch),
+                },
+            )],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        );
+        external_modules
+            .register_module(Some("env".to_owned()), external_module)
+            .unwrap();
+
+        let store = init_store();
+        let section = decode_module(&bytes);
+        let mut vm = instantiate_module(store, section, external_modules, 65536).unwrap();
+        for invoke in ["direct", "indirect"].iter() {
+            match vm.run(invoke, vec![Values::I32(-3)]) {
+                Err(WasmError::Trap(Trap::HostError(e))) => {
+                    assert_eq!(e.downcast_ref::<NegativeInput>(), Some(&NegativeInput(-3)))
+                }
+                x => panic!("{:?}", x),
+            }
+            assert_eq!(
+                vm.run(invoke, vec![Values::I32(21)]).unwrap(),
+                vec![Values::I32(42)]
+            );
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn eval_wasi_command() {
+        use std::env::temp_dir;
+        use std::fs::{create_dir_all, read_to_string};
+
+        let mut file = File::open("./dist/wasi.wasm").unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let sandbox = temp_dir().join("wasvm-wasi-command");
+        create_dir_all(&sandbox).unwrap();
+        let mut external_modules = ExternalModules::default();
+        external_modules
+            .register_module(
+                Some(WASI_MODULE_NAME.to_owned()),
+                create_wasi(
+                    vec!["wasi".to_owned(), "a".to_owned(), "b".to_owned()],
+                    vec![],
+                    vec![(".".to_owned(), sandbox.clone())],
+                ),
+            )
+            .unwrap();
+
+        let store = init_store();
+        let section = decode_module(&bytes);
+        let mut vm = instantiate_module(store, section, external_modules, 65536).unwrap();
+        match vm.run("_start", vec![]) {
+            Err(WasmError::Trap(Trap::HostError(e))) => {
+                assert_eq!(e.downcast_ref::<ProcExit>(), Some(&ProcExit(3)))
+            }
+            x => panic!("{:?}", x),
+        }
+        assert_eq!(
+            read_to_string(sandbox.join("out.txt")).unwrap(),
+            "hello wasi\n"
+        );
+    }
+
+    #[cfg(all(feature = "std", unix))]
+    #[test]
+    fn eval_wasi_faults() {
+        use std::env::temp_dir;
+        use std::fs::{create_dir_all, remove_file, write};
+        use std::os::unix::fs::symlink;
+
+        let root = temp_dir().join("wasvm-wasi-faults");
+        let sandbox = root.join("sandbox");
+        create_dir_all(&sandbox).unwrap();
+        create_dir_all(root.join("outside")).unwrap();
+        let _ = remove_file(sandbox.join("link"));
+        symlink(&root, sandbox.join("link")).unwrap();
+        write(sandbox.join("read-only"), "").unwrap();
+        let mut external_modules = ExternalModules::default();
+        external_modules
+            .register_module(
+                Some(WASI_MODULE_NAME.to_owned()),
+                create_wasi(vec![], vec![], vec![(".".to_owned(), sandbox)]),
+            )
+            .unwrap();
+
+        let store = init_store();
+        let section = decode_module(
+            br#"(module
+                (import "wasi_snapshot_preview1" "random_get"
+                    (func $random_get (param i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "fd_write"
+                    (func $fd_write (param i32 i32 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "path_open"
+                    (func $path_open
+                        (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "fd_fdstat_get"
+                    (func $fd_fdstat_get (param i32 i32) (result i32)))
+                (memory 1)
+                (data (i32.const 16) "link/outside")
+                (data (i32.const 32) "read-only")
+                (data (i32.const 48) "\20\00\00\00\09\00\00\00")
+                (func (export "random_get") (result i32)
+                    (call $random_get (i32.const 65500) (i32.const -1)))
+                (func (export "fd_write") (result i32)
+                    (call $fd_write (i32.const 1) (i32.const -8) (i32.const 2) (i32.const 0)))
+                (func (export "path_open") (param i32) (result i32)
+                    (call $path_open (i32.const 3) (i32.const 0) (i32.const 16) (get_local 0)
+                        (i32.const 2) (i64.const 0) (i64.const 0) (i32.const 0) (i32.const 0)))
+                (func (export "open_read_only") (result i32)
+                    (call $path_open (i32.const 3) (i32.const 0) (i32.const 32) (i32.const 9)
+                        (i32.const 0) (i64.const 2) (i64.const 0) (i32.const 0) (i32.const 0)))
+                (func (export "write_read_only") (result i32)
+                    (call $fd_write (i32.load (i32.const 0)) (i32.const 48) (i32.const 1)
+                        (i32.const 4)))
+                (func (export "rights_read_only") (result i64)
+                    (drop (call $fd_fdstat_get (i32.load (i32.const 0)) (i32.const 64)))
+                    (i64.load (i32.const 72))))"#,
+        );
+        let mut vm = instantiate_module(store, section, external_modules, 65536).unwrap();
+        // NOTE: 21 is ERRNO_FAULT and 76 is ERRNO_NOTCAPABLE.
+        assert_eq!(vm.run("random_get", vec![]), Ok(vec![Values::I32(21)]));
+        assert_eq!(vm.run("fd_write", vec![]), Ok(vec![Values::I32(21)]));
+        assert_eq!(
+            vm.run("path_open", vec![Values::I32(4)]),
+            Ok(vec![Values::I32(76)])
+        );
+        assert_eq!(
+            vm.run("path_open", vec![Values::I32(12)]),
+            Ok(vec![Values::I32(76)])
+        );
+
+        // NOTE: Descriptor opened only with fd_read right rejects writes.
+        assert_eq!(vm.run("open_read_only", vec![]), Ok(vec![Values::I32(0)]));
+        assert_eq!(vm.run("write_read_only", vec![]), Ok(vec![Values::I32(76)]));
+        assert_eq!(vm.run("rights_read_only", vec![]), Ok(vec![Values::I64(2)]));
+    }
+
+    #[test]
+    fn eval_with_fuel() {
+        let mut file = File::open("./dist/fib.wasm").unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let store = init_store();
+        let section = decode_module(&bytes);
+        let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
+        assert_eq!(vm.remaining_fuel(), None);
+        assert_eq!(vm.resume(), Err(WasmError::Trap(Trap::NotSuspended)));
+
+        vm.add_fuel(7);
+        let mut result = vm.run("_subject", vec![Values::I32(15)]);
+        let mut count_of_slices = 1;
+        while let Err(WasmError::Trap(Trap::OutOfFuel)) = result {
+            assert_eq!(vm.remaining_fuel(), Some(0));
+            vm.add_fuel(7);
+            result = vm.resume();
+            count_of_slices += 1;
+        }
+        assert_eq!(result, Ok(vec![Values::I32(610)]));
+        assert!(count_of_slices > 100);
+
+        vm.add_fuel(1000);
+        assert_eq!(
+            vm.run("_subject", vec![Values::I32(1)]),
+            Ok(vec![Values::I32(1)])
+        );
+        assert!(vm.remaining_fuel().unwrap() < 1007);
+    }
+
+    #[test]
+    fn eval_resumable_invocations() {
+        let mut file = File::open("./dist/fib.wasm").unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let store = init_store();
+        let section = decode_module(&bytes);
+        let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
+        vm.add_fuel(10);
+        let mut invocations = vec![
+            (0, vm.invoke_resumable("_subject", vec![Values::I32(15)]).unwrap()),
+            (1, vm.invoke_resumable("_subject", vec![Values::I32(10)]).unwrap()),
+        ];
+        let mut results = vec![vec![], vec![]];
+        while let Some((i, invocation)) = invocations.pop() {
+            match invocation {
+                Invocation::Suspended(suspended) => {
+                    assert_eq!(suspended.reason(), &Trap::OutOfFuel);
+                    vm.add_fuel(10);
+                    // NOTE: Round robin between invocations.
+                    invocations.insert(0, (i, suspended.resume(&mut vm).unwrap()));
+                }
+                Invocation::Returned(values) => results[i] = values,
+            }
+        }
+        assert_eq!(results, vec![vec![Values::I32(610)], vec![Values::I32(55)]]);
+    }
+
+    #[test]
+    fn eval_host_function_yield() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let mut file = File::open("./dist/host_error.wasm").unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let ready = Rc::new(Cell::new(false));
+        let ready_of_host = ready.clone();
+        let mut external_modules = ExternalModules::default();
+        let external_module = ExternalModule::new(
+            vec![FunctionInstance::new_host_fn(
+                Some("checked_double".to_owned()),
+                FunctionType::new(vec![ValueTypes::I32], vec![ValueTypes::I32]),
+                move |_: &Caller, arguments: &[Values]| match arguments {
+                    _ if !ready_of_host.get() => Err(Trap::Yield),
+                    [Values::I32(n)] => Ok(vec![Values::I32(n * 2)]),
+                    _ => Err(Trap::TypeMismatch),
+                },
+            )],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        );
+        external_modules
+            .register_module(Some("env".to_owned()), external_module)
+            .unwrap();
+
+        let store = init_store();
+        let section = decode_module(&bytes);
+        let mut vm = instantiate_module(store, section, external_modules, 65536).unwrap();
+        let suspended = match vm.invoke_resumable("indirect", vec![Values::I32(4)]) {
+            Ok(Invocation::Suspended(s)) => s,
+            x => panic!("{:?}", x),
+        };
+        assert_eq!(suspended.reason(), &Trap::Yield);
+        let suspended = match suspended.resume(&mut vm) {
+            Ok(Invocation::Suspended(s)) => s,
+            x => panic!("{:?}", x),
+        };
+        ready.set(true);
+        match suspended.resume(&mut vm) {
+            Ok(Invocation::Returned(values)) => assert_eq!(values, vec![Values::I32(8)]),
+            x => panic!("{:?}", x),
+        };
+    }
+
+    #[test]
+    fn eval_trap_location() {
+        let mut file = File::open("./dist/names.wasm").unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let store = init_store();
+        let section = decode_module(&bytes);
+        let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
+        assert_eq!(vm.names().module, Some("names".to_owned()));
+        assert_eq!(vm.names().function(0), Some("subject"));
+        assert_eq!(vm.names().local(0, 0), Some("n"));
+        assert_eq!(
+            vm.run("_subject", vec![Values::I32(1)]),
+            Err(WasmError::Trap(Trap::Unreachable))
+        );
+        let location = vm.last_trap_location().unwrap();
+        assert_eq!(location.function_index, 1);
+        assert_eq!(format!("{}", location), "boom");
+    }
+
+    #[test]
+    fn eval_trap_backtrace() {
+        let mut file = File::open("./dist/names.wasm").unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let store = init_store();
+        let section = decode_module(&bytes);
+        let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
+        assert!(vm.run("_subject", vec![Values::I32(1)]).is_err());
+        let trap = vm.trap_info().unwrap();
+        assert_eq!(trap.error, WasmError::Trap(Trap::Unreachable));
+        assert_eq!(
+            trap.backtrace,
+            vec![
+                TrapLocation {
+                    function_index: 1,
+                    name: Some("boom".to_owned()),
+                    offset: Some(0x2f),
+                },
+                TrapLocation {
+                    function_index: 0,
+                    name: Some("subject".to_owned()),
+                    offset: Some(0x2a),
+                },
+            ]
+        );
+        assert_eq!(
+            format!("{}", trap),
+            "Trap(Unreachable)\nwasm backtrace:\n  0: 0x2f - boom\n  1: 0x2a - subject"
+        );
+    }
+
+    #[test]
+    fn eval_trap_backtrace_in_nested_block() {
+        let bytes = parse_wat(
+            r#"
+          (module
+            (func (export "_subject") (param i32) (result i32)
+              (block (result i32)
+                (if (result i32) (get_local 0)
+                  (then (i32.const 1))
+                  (else (i32.div_s (i32.const 1) (get_local 0)))))))
+        "#,
+        )
+        .unwrap();
+        let offset_of_div = bytes.iter().rposition(|byte| *byte == 0x6d).unwrap() as u32;
+
+        let store = init_store();
+        let section = decode_module(&bytes);
+        let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
+        assert_eq!(
+            vm.run("_subject", vec![Values::I32(0)]),
+            Err(WasmError::Trap(Trap::DivisionByZero))
+        );
+        let location = vm.last_trap_location().unwrap();
+        assert_eq!(location.function_index, 0);
+        assert_eq!(location.offset, Some(offset_of_div));
+    }
+
+    #[test]
+    fn evaluate_textform() {
+        let bytes = br#"
+          (module
+            (func $fib (export "_subject") (param $n i32) (result i32)
+              (if (result i32) (i32.lt_u (local.get $n) (i32.const 2))
+                (then (local.get $n))
+                (else
+                  (i32.add
+                    (call $fib (i32.sub (local.get $n) (i32.const 1)))
+                    (call $fib (i32.sub (local.get $n) (i32.const 2))))))))
+        "#;
+        let store = init_store();
+        let section = decode_module(bytes);
+        let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
+        assert_eq!(
+            vm.run("_subject", vec![Values::I32(15)]).unwrap(),
+            vec![Values::I32(610)]
+        );
+    }
+
+    #[test]
+    fn evaluate_sign_extension() {
+        let bytes = br#"
+          (module
+            (func (export "i32.extend8_s") (param i32) (result i32)
+              (i32.extend8_s (local.get 0)))
+            (func (export "i32.extend16_s") (param i32) (result i32)
+              (i32.extend16_s (local.get 0)))
+            (func (export "i64.extend8_s") (param i64) (result i64)
+              (i64.extend8_s (local.get 0)))
+            (func (export "i64.extend16_s") (param i64) (result i64)
+              (i64.extend16_s (local.get 0)))
+            (func (export "i64.extend32_s") (param i64) (result i64)
+              (i64.extend32_s (local.get 0))))
+        "#;
+        let store = init_store();
+        let section = decode_module(bytes);
+        let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
+        let cases = vec![
+            ("i32.extend8_s", Values::I32(0x7f), Values::I32(127)),
+            ("i32.extend8_s", Values::I32(0x80), Values::I32(-128)),
+            ("i32.extend8_s", Values::I32(0x1234_5680), Values::I32(-0x80)),
+            ("i32.extend16_s", Values::I32(0x7fff), Values::I32(32767)),
+            ("i32.extend16_s", Values::I32(0x8000), Values::I32(-32768)),
+            ("i32.extend16_s", Values::I32(0x1234_8000), Values::I32(-0x8000)),
+            ("i64.extend8_s", Values::I64(0x80), Values::I64(-128)),
+            ("i64.extend8_s", Values::I64(-0x0123_4567_89ab_cd01), Values::I64(-1)),
+            ("i64.extend16_s", Values::I64(0x8000), Values::I64(-32768)),
+            ("i64.extend16_s", Values::I64(0x0123_4567_89ab_7fff), Values::I64(32767)),
+            ("i64.extend32_s", Values::I64(0x7fff_ffff), Values::I64(2_147_483_647)),
+            ("i64.extend32_s", Values::I64(0x8000_0000), Values::I64(-2_147_483_648)),
+            ("i64.extend32_s", Values::I64(0x0123_4567_0000_0000), Values::I64(0)),
+        ];
+        for (invoke, argument, expect) in cases {
+            assert_eq!(vm.run(invoke, vec![argument]).unwrap(), vec![expect]);
+        }
+    }
+
+    #[test]
+    fn validate_sign_extension() {
+        let section = decode_module(
+            br#"(module (func (param i64) (result i64) (i64.extend32_s (local.get 0))))"#,
+        );
+        assert_eq!(validate_module(&section), Ok(()));
+        let section = decode_module(
+            br#"(module (func (param i64) (result i32) (i32.extend8_s (local.get 0))))"#,
+        );
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::TypeMismatch))
+        );
+    }
+
+    #[test]
+    fn evaluate_saturating_truncation() {
+        let bytes = br#"
+          (module
+            (func (export "i32.trunc_sat_f32_s") (param f32) (result i32)
+              (i32.trunc_sat_f32_s (local.get 0)))
+            (func (export "i32.trunc_sat_f32_u") (param f32) (result i32)
+              (i32.trunc_sat_f32_u (local.get 0)))
+            (func (export "i32.trunc_sat_f64_s") (param f64) (result i32)
+              (i32.trunc_sat_f64_s (local.get 0)))
+            (func (export "i32.trunc_sat_f64_u") (param f64) (result i32)
+              (i32.trunc_sat_f64_u (local.get 0)))
+            (func (export "i64.trunc_sat_f32_s") (param f32) (result i64)
+              (i64.trunc_sat_f32_s (local.get 0)))
+            (func (export "i64.trunc_sat_f32_u") (param f32) (result i64)
+              (i64.trunc_sat_f32_u (local.get 0)))
+            (func (export "i64.trunc_sat_f64_s") (param f64) (result i64)
+              (i64.trunc_sat_f64_s (local.get 0)))
+            (func (export "i64.trunc_sat_f64_u") (param f64) (result i64)
+              (i64.trunc_sat_f64_u (local.get 0))))
+        "#;
+        let store = init_store();
+        let section = decode_module(bytes);
+        let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
+        let cases = vec![
+            ("i32.trunc_sat_f32_s", Values::F32(-1.9), Values::I32(-1)),
+            ("i32.trunc_sat_f32_s", Values::F32(3e9), Values::I32(i32::max_value())),
+            ("i32.trunc_sat_f32_s", Values::F32(-3e9), Values::I32(i32::min_value())),
+            ("i32.trunc_sat_f32_s", Values::F32(f32::NAN), Values::I32(0)),
+            ("i32.trunc_sat_f32_u", Values::F32(3e9), Values::I32(-1_294_967_296)),
+            ("i32.trunc_sat_f32_u", Values::F32(5e9), Values::I32(-1)),
+            ("i32.trunc_sat_f32_u", Values::F32(-1.0), Values::I32(0)),
+            ("i32.trunc_sat_f64_s", Values::F64(f64::INFINITY), Values::I32(i32::max_value())),
+            ("i32.trunc_sat_f64_s", Values::F64(-2_147_483_648.9), Values::I32(i32::min_value())),
+            ("i32.trunc_sat_f64_u", Values::F64(4_294_967_295.9), Values::I32(-1)),
+            ("i32.trunc_sat_f64_u", Values::F64(f64::NEG_INFINITY), Values::I32(0)),
+            ("i64.trunc_sat_f32_s", Values::F32(1e19), Values::I64(i64::max_value())),
+            ("i64.trunc_sat_f32_s", Values::F32(-1e19), Values::I64(i64::min_value())),
+            ("i64.trunc_sat_f32_u", Values::F32(2e19), Values::I64(-1)),
+            ("i64.trunc_sat_f32_u", Values::F32(f32::NAN), Values::I64(0)),
+            ("i64.trunc_sat_f64_s", Values::F64(-4.5), Values::I64(-4)),
+            ("i64.trunc_sat_f64_s", Values::F64(1e300), Values::I64(i64::max_value())),
+            ("i64.trunc_sat_f64_u", Values::F64(1e300), Values::I64(-1)),
+            ("i64.trunc_sat_f64_u", Values::F64(-0.9), Values::I64(0)),
+        ];
+        for (invoke, argument, expect) in cases {
+            assert_eq!(vm.run(invoke, vec![argument]).unwrap(), vec![expect], "{}", invoke);
+        }
+    }
+
+    #[test]
+    fn decode_unknown_prefixed_instruction() {
+        let mut bytes = parse_wat(
+            r#"(module (func (result i32) (i32.trunc_sat_f32_s (f32.const 0))))"#,
+        )
+        .unwrap();
+        let position = bytes.iter().position(|byte| *byte == 0xfc).unwrap();
+        bytes[position + 1] = 0x7f;
+        assert_eq!(
+            decode_module(&bytes).unwrap().codes[0].clone().map(|_| ()),
+            Err(WasmError::Trap(Trap::IllegalOpcode))
+        );
+    }
+
+    #[test]
+    fn evaluate_bulk_memory() {
+        let bytes = br#"
+          (module
+            (memory 1)
+            (data (i32.const 0) "abcdef")
+            (data $passive "hello")
+            (func (export "fill") (param i32 i32 i32)
+              (memory.fill (local.get 0) (local.get 1) (local.get 2)))
+            (func (export "copy") (param i32 i32 i32)
+              (memory.copy (local.get 0) (local.get 1) (local.get 2)))
+            (func (export "init") (param i32 i32 i32)
+              (memory.init $passive (local.get 0) (local.get 1) (local.get 2)))
+            (func (export "drop")
+              (data.drop $passive))
+            (func (export "load8") (param i32) (result i32)
+              (i32.load8_u (local.get 0))))
+        "#;
+        let store = init_store();
+        let section = decode_module(bytes);
+        let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
+        let i32s = |xs: &[i32]| xs.iter().map(|x| Values::I32(*x)).collect::<Vec<_>>();
+        let load8 =
+            |vm: &mut ModuleInstance, offset: i32| vm.run("load8", i32s(&[offset])).unwrap();
+
+        vm.run("copy", i32s(&[1, 0, 5])).unwrap();
+        assert_eq!(load8(&mut vm, 1), i32s(&[b'a' as i32]));
+        assert_eq!(load8(&mut vm, 5), i32s(&[b'e' as i32]));
+        vm.run("copy", i32s(&[0, 1, 5])).unwrap();
+        assert_eq!(load8(&mut vm, 0), i32s(&[b'a' as i32]));
+        assert_eq!(load8(&mut vm, 4), i32s(&[b'e' as i32]));
+
+        vm.run("fill", i32s(&[100, 0x1ff, 3])).unwrap();
+        assert_eq!(load8(&mut vm, 102), i32s(&[0xff]));
+        assert_eq!(load8(&mut vm, 103), i32s(&[0]));
+
+        vm.run("init", i32s(&[200, 1, 3])).unwrap();
+        assert_eq!(load8(&mut vm, 200), i32s(&[b'e' as i32]));
+        assert_eq!(load8(&mut vm, 202), i32s(&[b'l' as i32]));
+
+        let out_of_bounds = Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds));
+        assert_eq!(vm.run("fill", i32s(&[65535, 0, 2])), out_of_bounds);
+        assert_eq!(vm.run("copy", i32s(&[0, 65535, 2])), out_of_bounds);
+        assert_eq!(vm.run("init", i32s(&[0, 3, 3])), out_of_bounds);
+        assert_eq!(vm.run("fill", i32s(&[65536, 0, 0])), Ok(vec![]));
+
+        vm.run("drop", vec![]).unwrap();
+        assert_eq!(vm.run("init", i32s(&[0, 0, 1])), out_of_bounds);
+        assert_eq!(vm.run("init", i32s(&[0, 0, 0])), Ok(vec![]));
+    }
+
+    #[test]
+    fn validate_bulk_memory() {
+        let section = decode_module(
+            br#"(module (memory 1) (data "x")
+              (func (memory.init 0 (i32.const 0) (i32.const 0) (i32.const 1))))"#,
+        );
+        assert_eq!(validate_module(&section), Ok(()));
+        let section = decode_module(br#"(module (memory 1) (data "x") (func (data.drop 1)))"#);
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::UnknownData(1)))
+        );
+        let section = decode_module(
+            br#"(module (func (memory.fill (i32.const 0) (i32.const 0) (i32.const 0))))"#,
+        );
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::UnknownMemory))
+        );
+
+        let mut bytes =
+            parse_wat(r#"(module (memory 1) (data "x") (func (data.drop 0)))"#).unwrap();
+        let data_count = [0x0c, 0x01, 0x01];
+        let position = bytes.windows(3).position(|x| x == data_count).unwrap();
+        bytes.drain(position..position + 3);
+        assert_eq!(
+            validate_module(&decode_module(&bytes)),
+            Err(WasmError::TypeError(TypeError::DataCountRequired))
+        );
+    }
+
+    #[test]
+    fn evaluate_multiple_memories() {
+        let exporter = decode_module(
+            br#"(module
+              (memory (export "ccm") 1 2)
+              (func (export "load") (param i32) (result i32) (i32.load8_u (local.get 0)))
+              (func (export "size") (result i32) (memory.size)))"#,
+        );
+        let mut exporter =
+            instantiate_module(init_store(), exporter, Default::default(), 65536).unwrap();
+        let mut external_modules = ExternalModules::default();
+        external_modules
+            .register_module(Some("board".to_owned()), exporter.export_module())
+            .unwrap();
+
+        let bytes = br#"
+          (module
+            (import "board" "ccm" (memory $ccm 1 2))
+            (memory $sram 1)
+            (data (memory $ccm) (i32.const 0) "ccm")
+            (data (memory $sram) (i32.const 0) "sram")
+            (data $passive "xyz")
+            (func (export "load_ccm") (param i32) (result i32)
+              (i32.load8_u $ccm (local.get 0)))
+            (func (export "load_sram") (param i32) (result i32)
+              (i32.load8_u $sram (local.get 0)))
+            (func (export "store_ccm") (param i32 i32)
+              (i32.store8 $ccm (local.get 0) (local.get 1)))
+            (func (export "copy") (param i32 i32 i32)
+              (memory.copy $sram $ccm (local.get 0) (local.get 1) (local.get 2)))
+            (func (export "fill") (param i32 i32 i32)
+              (memory.fill $ccm (local.get 0) (local.get 1) (local.get 2)))
+            (func (export "init") (param i32 i32 i32)
+              (memory.init $ccm $passive (local.get 0) (local.get 1) (local.get 2)))
+            (func (export "grow") (param i32) (result i32)
+              (memory.grow $ccm (local.get 0)))
+            (func (export "size") (result i32)
+              (memory.size $sram)))
+        "#;
+        let section = decode_module(bytes);
+        let mut vm = instantiate_module(init_store(), section, external_modules, 65536).unwrap();
+        let i32s = |xs: &[i32]| xs.iter().map(|x| Values::I32(*x)).collect::<Vec<_>>();
+
+        assert_eq!(vm.run("load_ccm", i32s(&[0])), Ok(i32s(&[b'c' as i32])));
+        assert_eq!(vm.run("load_sram", i32s(&[0])), Ok(i32s(&[b's' as i32])));
+        assert_eq!(exporter.run("load", i32s(&[1])), Ok(i32s(&[b'c' as i32])));
+
+        vm.run("copy", i32s(&[100, 0, 3])).unwrap();
+        assert_eq!(vm.run("load_sram", i32s(&[102])), Ok(i32s(&[b'm' as i32])));
+        vm.run("init", i32s(&[10, 0, 3])).unwrap();
+        vm.run("store_ccm", i32s(&[20, 7])).unwrap();
+        assert_eq!(exporter.run("load", i32s(&[10])), Ok(i32s(&[b'x' as i32])));
+        assert_eq!(exporter.run("load", i32s(&[20])), Ok(i32s(&[7])));
+        vm.run("fill", i32s(&[0, 0xff, 4])).unwrap();
+        assert_eq!(vm.run("load_ccm", i32s(&[3])), Ok(i32s(&[0xff])));
+        assert_eq!(vm.run("load_sram", i32s(&[3])), Ok(i32s(&[b'm' as i32])));
+
+        assert_eq!(vm.run("grow", i32s(&[1])), Ok(i32s(&[1])));
+        assert_eq!(vm.run("grow", i32s(&[1])), Ok(i32s(&[-1])));
+        assert_eq!(exporter.run("size", vec![]), Ok(i32s(&[2])));
+        assert_eq!(vm.run("size", vec![]), Ok(i32s(&[1])));
+        assert_eq!(vm.run("load_ccm", i32s(&[65536])), Ok(i32s(&[0])));
+        assert_eq!(
+            vm.run("load_sram", i32s(&[65536])),
+            Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds))
+        );
+    }
+
+    #[test]
+    fn validate_multiple_memories() {
+        let section = decode_module(
+            br#"(module (memory 1) (memory 1)
+              (func (drop (i32.load 1 offset=4 (i32.const 0)))))"#,
+        );
+        assert_eq!(validate_module(&section), Ok(()));
+        let section = decode_module(
+            br#"(module (import "m" "a" (memory 1)) (memory 1)
+              (data (memory 1) (i32.const 0) "x"))"#,
+        );
+        assert_eq!(validate_module(&section), Ok(()));
+        let section = decode_module(br#"(module (memory 1) (func (drop (memory.size 1))))"#);
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::UnknownMemory))
+        );
+        let section = decode_module(
+            br#"(module (memory 1)
+              (func (memory.copy 0 1 (i32.const 0) (i32.const 0) (i32.const 0))))"#,
+        );
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::UnknownMemory))
+        );
+    }
+
+    #[test]
+    fn evaluate_atomics() {
+        let bytes = br#"
+          (module
+            (memory 1 1)
+            (func (export "add") (param i32 i32) (result i32)
+              (i32.atomic.rmw.add (local.get 0) (local.get 1)))
+            (func (export "sub8") (param i32 i32) (result i32)
+              (i32.atomic.rmw8.sub_u (local.get 0) (local.get 1)))
+            (func (export "cmpxchg") (param i32 i64 i64) (result i64)
+              (i64.atomic.rmw.cmpxchg (local.get 0) (local.get 1) (local.get 2)))
+            (func (export "store16") (param i32 i32)
+              (atomic.fence)
+              (i32.atomic.store16 (local.get 0) (local.get 1)))
+            (func (export "load") (param i32) (result i64)
+              (i64.atomic.load (local.get 0)))
+            (func (export "notify") (result i32)
+              (memory.atomic.notify (i32.const 0) (i32.const 1)))
+            (func (export "wait") (result i32)
+              (memory.atomic.wait32 (i32.const 0) (i32.const 0) (i64.const 0))))
+        "#;
+        let section = decode_module(bytes);
+        let mut vm =
+            instantiate_module(init_store(), section, Default::default(), 65536).unwrap();
+
+        assert_eq!(
+            vm.run("add", vec![Values::I32(0), Values::I32(5)]),
+            Ok(vec![Values::I32(0)])
+        );
+        assert_eq!(
+            vm.run("add", vec![Values::I32(0), Values::I32(-1)]),
+            Ok(vec![Values::I32(5)])
+        );
+        assert_eq!(
+            vm.run("sub8", vec![Values::I32(0), Values::I32(6)]),
+            Ok(vec![Values::I32(4)])
+        );
+        assert_eq!(
+            vm.run("load", vec![Values::I32(0)]),
+            Ok(vec![Values::I64(0xfe)])
+        );
+        assert_eq!(
+            vm.run(
+                "cmpxchg",
+                vec![Values::I32(0), Values::I64(0), Values::I64(1)]
+            ),
+            Ok(vec![Values::I64(0xfe)])
+        );
+        assert_eq!(
+            vm.run(
+                "cmpxchg",
+                vec![Values::I32(0), Values::I64(0xfe), Values::I64(1)]
+            ),
+            Ok(vec![Values::I64(0xfe)])
+        );
+        vm.run("store16", vec![Values::I32(2), Values::I32(0x1_0002)])
+            .unwrap();
+        assert_eq!(
+            vm.run("load", vec![Values::I32(0)]),
+            Ok(vec![Values::I64(0x0002_0001)])
+        );
+        assert_eq!(
+            vm.run("load", vec![Values::I32(4)]),
+            Err(WasmError::Trap(Trap::UnalignedAtomic))
+        );
+        assert_eq!(
+            vm.run("load", vec![Values::I32(65536)]),
+            Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds))
+        );
+        assert_eq!(vm.run("notify", vec![]), Ok(vec![Values::I32(0)]));
+        assert_eq!(
+            vm.run("wait", vec![]),
+            Err(WasmError::Trap(Trap::ExpectedSharedMemory))
+        );
+    }
+
+    #[test]
+    fn validate_atomics() {
+        let section = decode_module(
+            br#"(module (memory 1 1 shared)
+              (func (drop (i64.atomic.rmw16.xchg_u (i32.const 0) (i64.const 0)))))"#,
+        );
+        assert_eq!(validate_module(&section), Ok(()));
+        let section = decode_module(
+            br#"(module (memory 1)
+              (func (drop (i32.atomic.load align=2 (i32.const 0)))))"#,
+        );
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::InvalidAlignment))
+        );
+        let section = decode_module(
+            br#"(module (memory 1)
+              (func (drop (memory.atomic.wait64 (i32.const 0) (i32.const 0) (i64.const 0)))))"#,
+        );
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::TypeMismatch))
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn evaluate_shared_memory_between_threads() {
+        use std::thread;
+        use std::time::Duration;
+
+        let bytes = br#"
+          (module
+            (import "env" "memory" (memory 1 1 shared))
+            (func (export "count") (param i32)
+              (block $done
+                (loop $continue
+                  (br_if $done (i32.eqz (local.get 0)))
+                  (drop (i32.atomic.rmw.add (i32.const 0) (i32.const 1)))
+                  (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+                  (br $continue))))
+            (func (export "load") (result i32)
+              (i32.atomic.load (i32.const 0)))
+            (func (export "wait") (param i32 i64) (result i32)
+              (memory.atomic.wait32 (i32.const 4) (local.get 0) (local.get 1)))
+            (func (export "notify") (result i32)
+              (memory.atomic.notify (i32.const 4) (i32.const 1))))
+        "#;
+        let shared_memory = SharedMemory::new(1, 1);
+        let instantiate = move |shared_memory: SharedMemory| {
+            let mut external_modules = ExternalModules::default();
+            external_modules
+                .register_module(
+                    Some("env".to_owned()),
+                    ExternalModule::from_shared_memory("memory", shared_memory),
+                )
+                .unwrap();
+            instantiate_module(init_store(), decode_module(bytes), external_modules, 65536)
+                .unwrap()
+        };
+
+        let counters = (0..4)
+            .map(|_| {
+                let shared_memory = shared_memory.clone();
+                thread::spawn(move || {
+                    let mut vm = instantiate(shared_memory);
+                    vm.run("count", vec![Values::I32(1000)]).unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+        for counter in counters {
+            counter.join().unwrap();
+        }
+        let mut vm = instantiate(shared_memory.clone());
+        assert_eq!(vm.run("load", vec![]), Ok(vec![Values::I32(4000)]));
+
+        assert_eq!(
+            vm.run("wait", vec![Values::I32(1), Values::I64(-1)]),
+            Ok(vec![Values::I32(1)])
+        );
+        assert_eq!(
+            vm.run("wait", vec![Values::I32(0), Values::I64(1_000_000)]),
+            Ok(vec![Values::I32(2)])
+        );
+        let waiter = {
+            let shared_memory = shared_memory.clone();
+            thread::spawn(move || {
+                let mut vm = instantiate(shared_memory);
+                // NOTE: Values can not be sent between threads, as they might hold references.
+                vm.run("wait", vec![Values::I32(0), Values::I64(-1)]) == Ok(vec![Values::I32(0)])
+            })
+        };
+        // NOTE: Notification is lost until the waiter is enqueued.
+        while vm.run("notify", vec![]) != Ok(vec![Values::I32(1)]) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(waiter.join().unwrap());
+    }
+
+    #[test]
+    fn evaluate_simd() {
+        let bytes = br#"
+          (module
+            (memory 1 1)
+            (data (i32.const 0) "\01\02\03\04\05\06\07\08\09\0a\0b\0c\0d\0e\0f\10")
+            (global $lanes v128 (v128.const i32x4 1 2 3 4))
+            (func (export "add") (param i32 i32) (result i32)
+              (i32x4.extract_lane 2
+                (i32x4.add (i32x4.splat (local.get 0)) (i32x4.splat (local.get 1)))))
+            (func (export "global") (result v128) (global.get $lanes))
+            (func (export "not") (param v128) (result v128) (v128.not (local.get 0)))
+            (func (export "shuffle") (result v128)
+              (i8x16.shuffle 0 16 1 17 2 18 3 19 4 20 5 21 6 22 7 23
+                (v128.load (i32.const 0))
+                (v128.const i64x2 0 0)))
+            (func (export "extend") (result v128) (v128.load8x8_u (i32.const 0)))
+            (func (export "saturate") (result i32)
+              (i8x16.extract_lane_s 3
+                (i8x16.add_sat_s (i8x16.splat (i32.const 127)) (i8x16.splat (i32.const 1)))))
+            (func (export "unsigned") (result i32)
+              (i8x16.extract_lane_u 0 (i8x16.splat (i32.const -1))))
+            (func (export "load_lane") (result i32)
+              (i32x4.extract_lane 0 (v128.load16_lane 1 (i32.const 2) (v128.const i64x2 0 0))))
+            (func (export "store_lane") (result i32)
+              (v128.store32_lane 3 (i32.const 16) (v128.const i32x4 0 0 0 7))
+              (i32.load (i32.const 16)))
+            (func (export "sqrt") (result f32)
+              (f32x4.extract_lane 3 (f32x4.sqrt (v128.const f32x4 1 4 9 16))))
+            (func (export "bitmask") (result i32)
+              (i8x16.bitmask (i8x16.lt_s (v128.load (i32.const 0)) (i8x16.splat (i32.const 5)))))
+            (func (export "all_true") (result i32)
+              (i32x4.all_true (v128.const i32x4 1 2 3 0)))
+            (func (export "choose") (param i32) (result v128)
+              (if (result v128) (local.get 0)
+                (then (v128.const i32x4 1 0 0 0))
+                (else (block (result v128) (v128.const i32x4 2 0 0 0))))))
+        "#;
+        let section = decode_module(bytes);
+        let mut vm =
+            instantiate_module(init_store(), section, Default::default(), 65536).unwrap();
+
+        assert_eq!(
+            vm.run("add", vec![Values::I32(3), Values::I32(4)]),
+            Ok(vec![Values::I32(7)])
+        );
+        assert_eq!(
+            vm.run("global", vec![]),
+            Ok(vec![Values::V128(0x4_0000_0003_0000_0002_0000_0001)])
+        );
+        assert_eq!(
+            vm.run("not", vec![Values::V128(0xff)]),
+            Ok(vec![Values::V128(!0xff)])
+        );
+        let extended = Values::V128(0x0008_0007_0006_0005_0004_0003_0002_0001);
+        assert_eq!(vm.run("shuffle", vec![]), Ok(vec![extended.clone()]));
+        assert_eq!(vm.run("extend", vec![]), Ok(vec![extended]));
+        assert_eq!(vm.run("saturate", vec![]), Ok(vec![Values::I32(127)]));
+        assert_eq!(vm.run("unsigned", vec![]), Ok(vec![Values::I32(255)]));
+        assert_eq!(
+            vm.run("load_lane", vec![]),
+            Ok(vec![Values::I32(0x0403_0000)])
+        );
+        assert_eq!(vm.run("store_lane", vec![]), Ok(vec![Values::I32(7)]));
+        assert_eq!(vm.run("sqrt", vec![]), Ok(vec![Values::F32(4.0)]));
+        assert_eq!(vm.run("bitmask", vec![]), Ok(vec![Values::I32(0b1111)]));
+        assert_eq!(vm.run("all_true", vec![]), Ok(vec![Values::I32(0)]));
+        assert_eq!(
+            vm.run("choose", vec![Values::I32(1)]),
+            Ok(vec![Values::V128(1)])
+        );
+        assert_eq!(
+            vm.run("choose", vec![Values::I32(0)]),
+            Ok(vec![Values::V128(2)])
+        );
+    }
+
+    #[test]
+    fn evaluate_signed_zeros() {
+        let bytes = br#"
+          (module
+            (func (export "f32x4.max") (result f32)
+              (f32x4.extract_lane 0
+                (f32x4.max (f32x4.splat (f32.const -0)) (f32x4.splat (f32.const 0)))))
+            (func (export "f64x2.min") (result f64)
+              (f64x2.extract_lane 1
+                (f64x2.min (f64x2.splat (f64.const 0)) (f64x2.splat (f64.const -0)))))
+            (func (export "f64x2.nearest") (result f64)
+              (f64x2.extract_lane 0 (f64x2.nearest (f64x2.splat (f64.const -0.5)))))
+            (func (export "f32.min") (result f32) (f32.min (f32.const 0) (f32.const -0)))
+            (func (export "f32.max") (result f32) (f32.max (f32.const -0) (f32.const 0))))
+        "#;
+        let section = decode_module(bytes);
+        let mut vm =
+            instantiate_module(init_store(), section, Default::default(), 65536).unwrap();
+        let sign_of = |result: Result<Vec<Values>, WasmError>| match result.unwrap()[0] {
+            Values::F32(n) => n.is_sign_negative(),
+            Values::F64(n) => n.is_sign_negative(),
+            ref x => panic!("{:?}", x),
+        };
+        assert!(!sign_of(vm.run("f32x4.max", vec![])));
+        assert!(sign_of(vm.run("f64x2.min", vec![])));
+        assert!(sign_of(vm.run("f64x2.nearest", vec![])));
+        assert!(sign_of(vm.run("f32.min", vec![])));
+        assert!(!sign_of(vm.run("f32.max", vec![])));
+    }
+
+    #[test]
+    fn validate_simd() {
+        let section = decode_module(
+            br#"(module (func (drop (i32x4.extract_lane 4 (v128.const i64x2 0 0)))))"#,
+        );
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::InvalidLaneIndex))
+        );
+        let section = decode_module(
+            br#"(module (memory 1) (func (drop (v128.load align=32 (i32.const 0)))))"#,
+        );
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::InvalidAlignment))
+        );
+        let section = decode_module(
+            br#"(module (func (drop (i32x4.add (i32.const 0) (v128.const i64x2 0 0)))))"#,
+        );
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::TypeMismatch))
+        );
+    }
+
+    #[test]
+    fn evaluate_tail_calls() {
+        let bytes = br#"
+          (module
+            (type $predicate (func (param i64) (result i32)))
+            (table 2 funcref)
+            (elem (i32.const 0) $even $odd)
+            (func $even (export "even") (param i64) (result i32)
+              (if (result i32) (i64.eqz (local.get 0))
+                (then (i32.const 1))
+                (else (return_call_indirect (type $predicate)
+                  (i64.sub (local.get 0) (i64.const 1)) (i32.const 1)))))
+            (func $odd (param i64) (result i32)
+              (if (result i32) (i64.eqz (local.get 0))
+                (then (i32.const 0))
+                (else (return_call $even (i64.sub (local.get 0) (i64.const 1))))))
+            (func $sum (export "sum") (param i64 i64) (result i64)
+              (if (result i64) (i64.eqz (local.get 0))
+                (then (local.get 1))
+                (else (return_call $sum
+                  (i64.sub (local.get 0) (i64.const 1))
+                  (i64.add (local.get 0) (local.get 1))))))
+            (func $count (export "count") (param i64) (result i64)
+              (if (result i64) (i64.eqz (local.get 0))
+                (then (i64.const 0))
+                (else (i64.add (i64.const 1)
+                  (call $count (i64.sub (local.get 0) (i64.const 1))))))))
+        "#;
+        let section = decode_module(bytes);
+        let mut vm = instantiate_module(init_store(), section, Default::default(), 1024).unwrap();
+
+        assert_eq!(
+            vm.run("sum", vec![Values::I64(100_000), Values::I64(0)]),
+            Ok(vec![Values::I64(5_000_050_000)])
+        );
+        assert_eq!(
+            vm.run("even", vec![Values::I64(100_001)]),
+            Ok(vec![Values::I32(0)])
+        );
+        assert_eq!(
+            vm.run("even", vec![Values::I64(100_000)]),
+            Ok(vec![Values::I32(1)])
+        );
+        assert_eq!(
+            vm.run("count", vec![Values::I64(100_000)]),
+            Err(WasmError::Trap(Trap::StackOverflow))
+        );
+    }
+
+    #[test]
+    fn validate_tail_calls() {
+        let section = decode_module(
+            br#"(module
+              (func $f (result i64) (i64.const 0))
+              (func (result i32) (return_call $f)))"#,
+        );
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::TypeMismatch))
+        );
+    }
+
+    #[test]
+    fn evaluate_exceptions() {
+        let error = TagInstance::new(
+            FunctionType::new(vec![ValueTypes::I32], vec![]),
+            Some("error".to_owned()),
+        );
+        let error_of_host = error.clone();
+        let mut external_modules = ExternalModules::default();
+        let external_module = ExternalModule::new(
+            vec![FunctionInstance::new_host_fn(
+                Some("fail".to_owned()),
+                FunctionType::new(vec![ValueTypes::I32], vec![]),
+                move |_caller: &Caller, arguments: &[Values]| {
+                    Err(Exception::new(error_of_host.clone(), arguments.to_vec()).into())
+                },
+            )],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        )
+        .with_tag_instances(vec![error]);
+        external_modules
+            .register_module(Some("env".to_owned()), external_module)
+            .unwrap();
+
+        let bytes = br#"
+          (module
+            (import "env" "error" (tag $error (param i32)))
+            (import "env" "fail" (func $fail (param i32)))
+            (tag $overflow (export "overflow") (param i32 i32))
+            (tag $empty)
+            (func $check (param i32) (result i32)
+              (if (i32.gt_s (local.get 0) (i32.const 100))
+                (then (throw $overflow (local.get 0) (i32.const 100))))
+              (local.get 0))
+            (func (export "catch") (param i32) (result i32)
+              (try (result i32)
+                (do (call $check (local.get 0)))
+                (catch $overflow (i32.sub))))
+            (func (export "catch_all") (param i32) (result i32)
+              (try (result i32)
+                (do (call $check (local.get 0)))
+                (catch $empty (i32.const -1))
+                (catch_all (i32.const 0))))
+            (func (export "rethrow") (param i32) (result i32)
+              (try (result i32)
+                (do
+                  (try (result i32)
+                    (do (call $check (local.get 0)))
+                    (catch_all (rethrow 0))))
+                (catch $overflow (drop))))
+            (func (export "delegate") (param i32) (result i32)
+              (try $outer (result i32)
+                (do
+                  (try (result i32)
+                    (do
+                      (try (result i32)
+                        (do (call $check (local.get 0)))
+                        (delegate $outer)))
+                    (catch $overflow (drop) (drop) (i32.const -1))))
+                (catch $overflow (i32.add))))
+            (func (export "uncaught") (param i32) (result i32)
+              (call $check (local.get 0)))
+            (func (export "host") (param i32) (result i32)
+              (try (result i32)
+                (do (call $fail (local.get 0)) (i32.const 0))
+                (catch $error))))
+        "#;
+        let section = decode_module(bytes);
+        let mut vm = instantiate_module(init_store(), section, external_modules, 1024).unwrap();
+
+        assert_eq!(
+            vm.run("catch", vec![Values::I32(250)]),
+            Ok(vec![Values::I32(150)])
+        );
+        assert_eq!(
+            vm.run("catch_all", vec![Values::I32(250)]),
+            Ok(vec![Values::I32(0)])
+        );
+        assert_eq!(
+            vm.run("catch_all", vec![Values::I32(5)]),
+            Ok(vec![Values::I32(5)])
+        );
+        assert_eq!(
+            vm.run("rethrow", vec![Values::I32(250)]),
+            Ok(vec![Values::I32(250)])
+        );
+        assert_eq!(
+            vm.run("delegate", vec![Values::I32(250)]),
+            Ok(vec![Values::I32(350)])
+        );
+        assert_eq!(vm.run("host", vec![Values::I32(7)]), Ok(vec![Values::I32(7)]));
+
+        let exception = match vm.run("uncaught", vec![Values::I32(250)]) {
+            Err(WasmError::Trap(Trap::Exception(exception))) => exception,
+            x => panic!("{:?}", x),
+        };
+        assert_eq!(exception.values(), &[Values::I32(250), Values::I32(100)]);
+        assert!(exception.tag() == &vm.export_module().tag_instances()[1]);
+        assert_eq!(vm.trap_info().unwrap().backtrace.len(), 2);
+        assert_eq!(
+            vm.run("catch", vec![Values::I32(50)]),
+            Ok(vec![Values::I32(50)])
+        );
+    }
+
+    #[test]
+    fn validate_exceptions() {
+        let section = decode_module(br#"(module (func (block (rethrow 0))))"#);
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::InvalidRethrowLabel))
+        );
+        let section = decode_module(br#"(module (tag) (func (throw 5)))"#);
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::UnknownTag(5)))
+        );
+        let section = decode_module(br#"(module (tag (param i32) (result i32)))"#);
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::TypeMismatch))
+        );
+    }
+
+    #[test]
+    fn evaluate_mutable_globals() {
+        let counter = GlobalInstance::new(
+            GlobalType::Var(ValueTypes::I32),
+            Values::I32(0),
+            Some("counter".to_owned()),
+        );
+        let mut external_modules = ExternalModules::default();
+        external_modules
+            .register_module(
+                Some("env".to_owned()),
+                ExternalModule::new(vec![], vec![], vec![], vec![], vec![counter.clone()]),
+            )
+            .unwrap();
+
+        let section = decode_module(
+            br#"(module
+              (import "env" "counter" (global $counter (mut i32)))
+              (global $sp (export "sp") (mut i32) (i32.const 1024))
+              (global (export "version") i32 (i32.const 1))
+              (func (export "bump") (result i32)
+                (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+                (global.get $counter))
+              (func (export "alloc") (param i32) (result i32)
+                (global.set $sp (i32.sub (global.get $sp) (local.get 0)))
+                (global.get $sp)))"#,
+        );
+        let mut vm = instantiate_module(init_store(), section, external_modules.clone(), 1024)
+            .unwrap();
+        assert_eq!(vm.run("bump", vec![]), Ok(vec![Values::I32(1)]));
+        assert_eq!(counter.get_value(), Values::I32(1));
+        assert_eq!(counter.set(Values::I32(10)), Ok(()));
+        assert_eq!(vm.run("bump", vec![]), Ok(vec![Values::I32(11)]));
+
+        assert_eq!(vm.get_global("sp"), Some(Values::I32(1024)));
+        assert_eq!(vm.set_global("sp", Values::I32(512)), Ok(()));
+        assert_eq!(vm.run("alloc", vec![Values::I32(16)]), Ok(vec![Values::I32(496)]));
+        assert_eq!(vm.get_global("sp"), Some(Values::I32(496)));
+        assert_eq!(
+            vm.set_global("sp", Values::I64(0)),
+            Err(WasmError::Trap(Trap::TypeMismatch))
+        );
+        assert_eq!(
+            vm.set_global("version", Values::I32(2)),
+            Err(WasmError::Trap(Trap::InvalidMutability))
+        );
+        assert_eq!(vm.get_global("bump"), None);
+
+        external_modules
+            .register_module(Some("a".to_owned()), vm.export_module())
+            .unwrap();
+        let section = decode_module(
+            br#"(module
+              (import "a" "sp" (global $sp (mut i32)))
+              (import "a" "bump" (func $bump (result i32)))
+              (func (export "reset") (global.set $sp (i32.const 2048)))
+              (func (export "bump") (result i32) (call $bump)))"#,
+        );
+        let mut other = instantiate_module(init_store(), section, external_modules, 1024).unwrap();
+        assert_eq!(other.run("reset", vec![]), Ok(vec![]));
+        assert_eq!(vm.get_global("sp"), Some(Values::I32(2048)));
+        assert_eq!(other.run("bump", vec![]), Ok(vec![Values::I32(12)]));
+        assert_eq!(vm.get_global("sp"), Some(Values::I32(2048)));
+    }
+
+    #[test]
+    fn validate_imported_globals() {
+        let section = decode_module(
+            br#"(module
+              (import "env" "version" (global i32))
+              (global (mut i32) (i32.const 0))
+              (func (global.set 0 (i32.const 1))))"#,
+        );
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::GlobalIsImmutable))
+        );
+    }
+
+    #[test]
+    fn evaluate_memory_api() {
+        let section = decode_module(
+            br#"(module
+              (memory (export "memory") 1 3)
+              (func (export "load") (param i32) (result i32) (i32.load (local.get 0)))
+              (func (export "store") (param i32 f64) (f64.store (local.get 0) (local.get 1))))"#,
+        );
+        let mut vm = instantiate_module(init_store(), section, Default::default(), 1024).unwrap();
+        let memory = vm.memory_instance("memory").unwrap();
+        assert!(vm.memory_instance("load").is_none());
+
+        memory.write_bytes(16, b"hello").unwrap();
+        let mut buf = [0; 5];
+        memory.read_bytes(16, &mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+        assert_eq!(memory.read_u8(17), Ok(b'e'));
+
+        memory.write_u32(8, 0xdead_beef).unwrap();
+        assert_eq!(memory.read_u16(8), Ok(0xbeef));
+        assert_eq!(
+            vm.run("load", vec![Values::I32(8)]),
+            Ok(vec![Values::I32(0xdead_beef_u32 as i32)])
+        );
+        vm.run("store", vec![Values::I32(32), Values::F64(-1.5)]).unwrap();
+        assert_eq!(memory.read_f64(32), Ok(-1.5));
+        assert_eq!(memory.read_u64(32), Ok((-1.5f64).to_bits()));
+
+        assert_eq!(memory.size_by_pages(), 1);
+        assert_eq!(
+            memory.read_u32(65534),
+            Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds))
+        );
+        assert_eq!(
+            memory.write_bytes(65535, b"ab"),
+            Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds))
+        );
+        assert_eq!(memory.memory_grow(2), Ok(()));
+        assert_eq!(memory.data_size(), 65536 * 3);
+        assert_eq!(memory.memory_grow(1), Err(WasmError::Trap(Trap::FailToGrow)));
+        memory.write_f32(65534, 2.5).unwrap();
+        assert_eq!(memory.read_f32(65534), Ok(2.5));
+    }
+
+    #[test]
+    fn evaluate_memory_on_buffer() {
+        let buffer = Box::leak(vec![0xff; 65536 * 2 + 100].into_boxed_slice());
+        let ptr = buffer.as_ptr();
+        let memory = MemoryInstance::from_buffer(
+            buffer,
+            Limit::NoUpperLimit(1),
+            Some("memory".to_owned()),
+        )
+        .unwrap();
+        assert_eq!(memory.limit(), Limit::HasUpperLimit(1, 2));
+        assert_eq!(memory.read_u8(65535), Ok(0));
+
+        let mut external_modules = ExternalModules::default();
+        external_modules
+            .register_module(
+                Some("env".to_owned()),
+                ExternalModule::new(vec![], vec![], vec![memory.clone()], vec![], vec![]),
+            )
+            .unwrap();
+        let section = decode_module(
+            br#"(module
+              (import "env" "memory" (memory 1))
+              (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0)))
+              (func (export "store") (param i32 i32) (i32.store (local.get 0) (local.get 1))))"#,
+        );
+        let mut vm = instantiate_module(init_store(), section, external_modules, 1024).unwrap();
+        assert_eq!(vm.run("grow", vec![Values::I32(1)]), Ok(vec![Values::I32(1)]));
+        assert_eq!(vm.run("grow", vec![Values::I32(1)]), Ok(vec![Values::I32(-1)]));
+        assert_eq!(memory.memory_grow(1), Err(WasmError::Trap(Trap::FailToGrow)));
+        assert_eq!(memory.size_by_pages(), 2);
+
+        vm.run("store", vec![Values::I32(131068), Values::I32(0x0403_0201)])
+            .unwrap();
+        assert_eq!(unsafe { *ptr.add(131068) }, 0x01);
+        assert_eq!(unsafe { *ptr.add(131071) }, 0x04);
+        assert_eq!(memory.read_u32(131068), Ok(0x0403_0201));
+        assert_eq!(
+            vm.run("store", vec![Values::I32(131069), Values::I32(0)]),
+            Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds))
+        );
+
+        // NOTE: Memory defined in a module is placed on the buffer which the limiter gives.
+        struct OnBuffer(Option<&'static mut [u8]>);
+        impl ResourceLimiter for OnBuffer {
+            fn memory_buffer(&mut self, _: &MemoryType) -> Option<&'static mut [u8]> {
+                self.0.take()
+            }
+        }
+        let buffer = Box::leak(vec![0xff; 65536 * 2].into_boxed_slice());
+        let ptr = buffer.as_ptr();
+        let store = init_store().with_resource_limiter(OnBuffer(Some(buffer)));
+        let section = decode_module(
+            br#"(module
+              (memory (export "memory") 1)
+              (data (i32.const 0) "\2a")
+              (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0))))"#,
+        );
+        let mut vm = instantiate_module(store.clone(), section, Default::default(), 1024).unwrap();
+        assert_eq!(unsafe { *ptr }, 0x2a);
+        assert_eq!(unsafe { *ptr.add(1) }, 0);
+        assert_eq!(vm.run("grow", vec![Values::I32(1)]), Ok(vec![Values::I32(1)]));
+        assert_eq!(vm.run("grow", vec![Values::I32(1)]), Ok(vec![Values::I32(-1)]));
+
+        // NOTE: Memory is allocated on the heap once the limiter gives no buffer.
+        let section = decode_module(br#"(module (memory 1))"#);
+        assert!(instantiate_module(store, section, Default::default(), 1024).is_ok());
+
+        let buffer = Box::leak(vec![0; 100].into_boxed_slice());
+        assert_eq!(
+            MemoryInstance::from_buffer(buffer, Limit::NoUpperLimit(1), None).map(|_| ()),
+            Err(WasmError::Trap(Trap::FailToGrow))
+        );
+    }
+
+    #[test]
+    fn evaluate_custom_page_size() {
+        let bytes = br#"
+          (module
+            (memory (export "memory") 16 64 (pagesize 1))
+            (func (export "size") (result i32) (memory.size))
+            (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0)))
+            (func (export "load") (param i32) (result i32) (i32.load (local.get 0))))
+        "#;
+        assert_eq!(validate_module(&decode_module(bytes)), Ok(()));
+        let section = decode_module(bytes);
+        let mut vm = instantiate_module(init_store(), section, Default::default(), 1024).unwrap();
+        let memory = vm.memory_instance("memory").unwrap();
+        assert_eq!(memory.page_size(), 1);
+        assert_eq!(vm.run("size", vec![]), Ok(vec![Values::I32(16)]));
+        assert_eq!(vm.run("grow", vec![Values::I32(8)]), Ok(vec![Values::I32(16)]));
+        assert_eq!(vm.run("grow", vec![Values::I32(41)]), Ok(vec![Values::I32(-1)]));
+        assert_eq!(memory.data_size(), 24);
+        assert_eq!(vm.run("load", vec![Values::I32(20)]), Ok(vec![Values::I32(0)]));
+        assert_eq!(
+            vm.run("load", vec![Values::I32(21)]),
+            Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds))
+        );
+
+        let mut external_modules = ExternalModules::default();
+        let memory = MemoryInstance::new(
+            MemoryType::new(Limit::NoUpperLimit(4), 1),
+            Some("memory".to_owned()),
+        );
+        external_modules
+            .register_module(
+                Some("env".to_owned()),
+                ExternalModule::new(vec![], vec![], vec![memory], vec![], vec![]),
+            )
+            .unwrap();
+        let section =
+            decode_module(br#"(module (import "env" "memory" (memory 4 (pagesize 1))))"#);
+        assert!(instantiate_module(init_store(), section, external_modules.clone(), 1024).is_ok());
+        let section = decode_module(br#"(module (import "env" "memory" (memory 0)))"#);
+        assert_eq!(
+            instantiate_module(init_store(), section, external_modules, 1024).map(|_| ()),
+            Err(WasmError::Trap(Trap::IncompatibleImportType))
+        );
+    }
+
+    #[test]
+    fn evaluate_resource_limiter() {
+        struct Budget {
+            bytes: usize,
+            elements: u32,
+        }
+        impl ResourceLimiter for Budget {
+            fn memory_growing(&mut self, _: usize, desired: usize, _: Option<usize>) -> bool {
+                desired <= self.bytes
+            }
+            fn table_growing(&mut self, _: u32, desired: u32, _: Option<u32>) -> bool {
+                desired <= self.elements
+            }
+        }
+        let budget = || Budget {
+            bytes: 100_000,
+            elements: 4,
+        };
+
+        let bytes = br#"
+          (module
+            (memory 1)
+            (table 2 funcref)
+            (func (export "grow_memory") (param i32) (result i32) (memory.grow (local.get 0)))
+            (func (export "grow_table") (param i32) (result i32)
+              (table.grow (ref.null func) (local.get 0))))
+        "#;
+        let store = init_store().with_resource_limiter(budget());
+        let mut vm = instantiate_module(store, decode_module(bytes), Default::default(), 1024)
+            .unwrap();
+        assert_eq!(vm.run("grow_memory", vec![Values::I32(1)]), Ok(vec![Values::I32(-1)]));
+        assert_eq!(vm.run("grow_memory", vec![Values::I32(0)]), Ok(vec![Values::I32(1)]));
+        assert_eq!(vm.run("grow_table", vec![Values::I32(2)]), Ok(vec![Values::I32(2)]));
+        assert_eq!(vm.run("grow_table", vec![Values::I32(1)]), Ok(vec![Values::I32(-1)]));
+
+        let store = init_store().with_resource_limiter(budget());
+        let section = decode_module(br#"(module (memory 2))"#);
+        assert_eq!(
+            instantiate_module(store, section, Default::default(), 1024).map(|_| ()),
+            Err(WasmError::Trap(Trap::ResourceLimitExceeded))
+        );
+        let store = init_store().with_resource_limiter(budget());
+        let section = decode_module(br#"(module (memory 100000 (pagesize 1)))"#);
+        assert!(instantiate_module(store, section, Default::default(), 1024).is_ok());
+        let store = init_store().with_resource_limiter(budget());
+        let section = decode_module(br#"(module (table 5 funcref))"#);
+        assert_eq!(
+            instantiate_module(store, section, Default::default(), 1024).map(|_| ()),
+            Err(WasmError::Trap(Trap::ResourceLimitExceeded))
+        );
+    }
+
+    #[test]
+    fn evaluate_resource_limiter_on_counts() {
+        struct Budget;
+        impl ResourceLimiter for Budget {
+            fn functions_allocating(&mut self, count: usize) -> bool {
+                count <= 2
+            }
+            fn globals_allocating(&mut self, count: usize) -> bool {
+                count <= 1
+            }
+        }
+
+        let section = decode_module(br#"(module (func) (func) (global i32 (i32.const 0)))"#);
+        let store = init_store().with_resource_limiter(Budget);
+        assert!(instantiate_module(store, section, Default::default(), 1024).is_ok());
+
+        let section = decode_module(br#"(module (func) (func) (func))"#);
+        let store = init_store().with_resource_limiter(Budget);
+        assert_eq!(
+            instantiate_module(store, section, Default::default(), 1024).map(|_| ()),
+            Err(WasmError::Trap(Trap::ResourceLimitExceeded))
+        );
+        let section = decode_module(
+            br#"(module (global i32 (i32.const 0)) (global i32 (i32.const 1)))"#,
+        );
+        let store = init_store().with_resource_limiter(Budget);
+        assert_eq!(
+            instantiate_module(store, section, Default::default(), 1024).map(|_| ()),
+            Err(WasmError::Trap(Trap::ResourceLimitExceeded))
+        );
+    }
+
+    #[test]
+    fn decode_hostile_count() {
+        let mut bytes = parse_wat(r#"(module (func))"#).unwrap();
+        // NOTE: Function section which claims 2^32 - 1 functions.
+        let position = bytes.windows(3).position(|x| x == [0x03, 0x02, 0x01]).unwrap();
+        bytes.splice(
+            position + 1..position + 3,
+            vec![0x06, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x00],
+        );
+        assert_eq!(
+            decode_module(&bytes).map(|_| ()),
+            Err(WasmError::Trap(Trap::LengthOutofBounds))
+        );
+    }
+
+    #[test]
+    fn decode_hostile_locals() {
+        // NOTE: Function body which declares 2^32 - 1 locals of i32.
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00, 0x0a, 0x0a, 0x01, 0x08, 0x01, 0xff, 0xff, 0xff, 0xff, 0x0f,
+            0x7f, 0x0b,
+        ];
+        assert_eq!(
+            decode_module(&bytes).map(|_| ()),
+            Err(WasmError::Trap(Trap::LengthOutofBounds))
+        );
+    }
+
+    #[test]
+    fn validate_custom_page_size() {
+        let section = decode_module(br#"(module (memory 1 (pagesize 2)))"#);
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::InvalidPageSize))
+        );
+        let section = decode_module(br#"(module (memory 65537))"#);
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::InvalidMemorySize))
+        );
+        let section = decode_module(br#"(module (memory 65537 4294967295 (pagesize 1)))"#);
+        assert_eq!(validate_module(&section), Ok(()));
+    }
+
+    #[test]
+    fn evaluate_shared_store() {
+        let store = init_store();
+        let mut external_modules = ExternalModules::default();
+        let section = decode_module(
+            br#"(module
+              (memory 1)
+              (data (i32.const 0) "\01")
+              (table (export "table") 2 funcref)
+              (global $count (mut i32) (i32.const 0))
+              (func (export "count") (result i32)
+                (global.set $count (i32.add (global.get $count) (i32.const 1)))
+                (global.get $count))
+              (func (export "call") (param i32) (result i32)
+                (call_indirect (result i32) (local.get 0))))"#,
+        );
+        let mut a = instantiate_module(store.clone(), section, external_modules.clone(), 1024)
+            .unwrap();
+        external_modules
+            .register_module(Some("a".to_owned()), a.export_module())
+            .unwrap();
+
+        // NOTE: Function placed in the table of other module is evaluated with its own memory.
+        let section = decode_module(
+            br#"(module
+              (import "a" "table" (table 2 funcref))
+              (import "a" "count" (func $count (result i32)))
+              (export "count" (func $count))
+              (memory 1)
+              (data (i32.const 0) "\02")
+              (func $load (result i32) (i32.load8_u (i32.const 0)))
+              (elem (i32.const 0) $load))"#,
+        );
+        let b = instantiate_module(store.clone(), section, external_modules.clone(), 1024)
+            .unwrap();
+        assert_eq!(a.run("call", vec![Values::I32(0)]), Ok(vec![Values::I32(2)]));
+        external_modules
+            .register_module(Some("b".to_owned()), b.export_module())
+            .unwrap();
+
+        // NOTE: Function re-exported by other module is evaluated with the module defining it.
+        let section = decode_module(
+            br#"(module
+              (import "b" "count" (func $count (result i32)))
+              (func (export "count") (result i32) (call $count)))"#,
+        );
+        let mut c = instantiate_module(store, section, external_modules.clone(), 1024).unwrap();
+        assert_eq!(c.run("count", vec![]), Ok(vec![Values::I32(1)]));
+        assert_eq!(a.run("count", vec![]), Ok(vec![Values::I32(2)]));
+
+        // NOTE: Store which instances are imported from is kept alive along with the importer.
+        drop(a);
+        drop(b);
+        drop(c);
+        let section = decode_module(
+            br#"(module
+              (import "b" "count" (func $count (result i32)))
+              (func (export "count") (result i32) (call $count)))"#,
+        );
+        let mut d = instantiate_module(init_store(), section, external_modules, 1024).unwrap();
+        assert_eq!(d.run("count", vec![]), Ok(vec![Values::I32(3)]));
+    }
+
+    #[test]
+    fn evaluate_stores_importing_each_other() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        struct Dropped(Rc<Cell<u32>>);
+        impl ResourceLimiter for Dropped {}
+        impl Drop for Dropped {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let dropped = Rc::new(Cell::new(0));
+        let s = init_store().with_resource_limiter(Dropped(dropped.clone()));
+        let t = init_store().with_resource_limiter(Dropped(dropped.clone()));
+        let mut external_modules = ExternalModules::default();
+        let exporter = br#"(module (func (export "one") (result i32) (i32.const 1)))"#;
+        let a = instantiate_module(s.clone(), decode_module(exporter), Default::default(), 1024)
+            .unwrap();
+        let b = instantiate_module(t.clone(), decode_module(exporter), Default::default(), 1024)
+            .unwrap();
+        external_modules
+            .register_module(Some("a".to_owned()), a.export_module())
+            .unwrap();
+        external_modules
+            .register_module(Some("b".to_owned()), b.export_module())
+            .unwrap();
+
+        // NOTE: Each store has a module which imports from the other one.
+        let importer = |name: &str| {
+            let bytes = format!(
+                r#"(module
+                  (import "{}" "one" (func $one (result i32)))
+                  (func (export "two") (result i32) (i32.add (call $one) (call $one))))"#,
+                name
+            );
+            decode_module(bytes.as_bytes())
+        };
+        let mut c = instantiate_module(s, importer("b"), external_modules.clone(), 1024).unwrap();
+        let mut d = instantiate_module(t, importer("a"), external_modules.clone(), 1024).unwrap();
+        drop(external_modules);
+        drop(a);
+        drop(b);
+        assert_eq!(c.run("two", vec![]), Ok(vec![Values::I32(2)]));
+        assert_eq!(d.run("two", vec![]), Ok(vec![Values::I32(2)]));
+
+        drop(c);
+        drop(d);
+        assert_eq!(dropped.get(), 2);
+    }
+
+    #[test]
+    fn evaluate_reexports() {
+        let store = init_store();
+        let mut external_modules = ExternalModules::default();
+        let section = decode_module(
+            br#"(module
+              (func $f (export "f") (result i32) (i32.const 42))
+              (memory (export "m") 1)
+              (data (i32.const 0) "\2a")
+              (table (export "t") 1 funcref)
+              (elem (i32.const 0) $f)
+              (global (export "g") i32 (i32.const 7)))"#,
+        );
+        let a = instantiate_module(store.clone(), section, external_modules.clone(), 1024)
+            .unwrap();
+        external_modules
+            .register_module(Some("a".to_owned()), a.export_module())
+            .unwrap();
+
+        // NOTE: Imports are re-exported by other names, and the function by two of them.
+        let section = decode_module(
+            br#"(module
+              (import "a" "f" (func $f (result i32)))
+              (import "a" "m" (memory $m 1))
+              (import "a" "t" (table $t 1 funcref))
+              (import "a" "g" (global $g i32))
+              (export "f2" (func $f))
+              (export "f3" (func $f))
+              (export "m2" (memory $m))
+              (export "t2" (table $t))
+              (export "h" (global $g)))"#,
+        );
+        let b = instantiate_module(store.clone(), section, external_modules.clone(), 1024)
+            .unwrap();
+        external_modules
+            .register_module(Some("b".to_owned()), b.export_module())
+            .unwrap();
+
+        let section = decode_module(
+            br#"(module
+              (import "b" "f2" (func $f2 (result i32)))
+              (import "b" "f3" (func $f3 (result i32)))
+              (import "b" "m2" (memory 1))
+              (import "b" "t2" (table 1 funcref))
+              (import "b" "h" (global $h i32))
+              (func (export "f") (result i32) (i32.add (call $f2) (call $f3)))
+              (func (export "m") (result i32) (i32.load8_u (i32.const 0)))
+              (func (export "t") (result i32) (call_indirect (result i32) (i32.const 0)))
+              (func (export "h") (result i32) (global.get $h)))"#,
+        );
+        let mut c = instantiate_module(store.clone(), section, external_modules.clone(), 1024)
+            .unwrap();
+        assert_eq!(c.run("f", vec![]), Ok(vec![Values::I32(84)]));
+        assert_eq!(c.run("m", vec![]), Ok(vec![Values::I32(42)]));
+        assert_eq!(c.run("t", vec![]), Ok(vec![Values::I32(42)]));
+        assert_eq!(c.run("h", vec![]), Ok(vec![Values::I32(7)]));
+
+        // NOTE: Names of the instances in the exporting module aren't visible through the other.
+        let section = decode_module(br#"(module (import "b" "f" (func (result i32))))"#);
+        assert_eq!(
+            instantiate_module(store, section, external_modules, 1024).map(|_| ()),
+            Err(WasmError::Trap(Trap::UnknownImport))
+        );
+    }
+
+    #[test]
+    fn evaluate_reexported_mutable_global() {
+        let store = init_store();
+        let mut external_modules = ExternalModules::default();
+        let section = decode_module(
+            br#"(module
+              (global $g (export "g") (mut i32) (i32.const 0))
+              (func (export "inc") (global.set $g (i32.add (global.get $g) (i32.const 1)))))"#,
+        );
+        let mut a = instantiate_module(store.clone(), section, external_modules.clone(), 1024)
+            .unwrap();
+        external_modules
+            .register_module(Some("a".to_owned()), a.export_module())
+            .unwrap();
+        let section = decode_module(
+            br#"(module
+              (import "a" "g" (global $g (mut i32)))
+              (export "h" (global $g)))"#,
+        );
+        let b = instantiate_module(store.clone(), section, external_modules.clone(), 1024)
+            .unwrap();
+        external_modules
+            .register_module(Some("b".to_owned()), b.export_module())
+            .unwrap();
+        let section = decode_module(
+            br#"(module
+              (import "b" "h" (global $h (mut i32)))
+              (func (export "get") (result i32) (global.get $h))
+              (func (export "set") (param i32) (global.set $h (local.get 0))))"#,
+        );
+        let mut c = instantiate_module(store, section, external_modules, 1024).unwrap();
+
+        // NOTE: All of them share the global defined in the first module.
+        assert_eq!(a.run("inc", vec![]), Ok(vec![]));
+        assert_eq!(c.run("get", vec![]), Ok(vec![Values::I32(1)]));
+        assert_eq!(c.run("set", vec![Values::I32(5)]), Ok(vec![]));
+        assert_eq!(a.get_global("g"), Some(Values::I32(5)));
+        assert_eq!(b.get_global("h"), Some(Values::I32(5)));
+    }
+
+    #[test]
+    fn decode_inconsistent_data_count() {
+        let mut bytes =
+            parse_wat(r#"(module (memory 1) (data "x") (func (data.drop 0)))"#).unwrap();
+        let position = bytes.windows(3).position(|x| x == [0x0c, 0x01, 0x01]).unwrap();
+        bytes[position + 2] = 0x02;
+        assert_eq!(
+            decode_module(&bytes).map(|_| ()),
+            Err(WasmError::Trap(Trap::DataCountAndDataInconsistent))
+        );
+    }
+
+    #[test]
+    fn evaluate_reference_types() {
+        let bytes = br#"
+          (module
+            (table $ext 2 externref)
+            (table $fun 3 funcref)
+            (elem $passive funcref (ref.func $one) (ref.null func))
+            (elem declare func $two)
+            (func $one (result i32) (i32.const 1))
+            (func $two (result i32) (i32.const 2))
+            (func (export "set") (param i32 externref)
+              (table.set $ext (local.get 0) (local.get 1)))
+            (func (export "get") (param i32) (result externref)
+              (table.get $ext (local.get 0)))
+            (func (export "is_null") (param externref) (result i32)
+              (ref.is_null (local.get 0)))
+            (func (export "grow") (param i32 externref) (result i32)
+              (table.grow $ext (local.get 1) (local.get 0)))
+            (func (export "size") (result i32)
+              (table.size $ext))
+            (func (export "fill") (param i32 externref i32)
+              (table.fill $ext (local.get 0) (local.get 1) (local.get 2)))
+            (func (export "copy") (param i32 i32 i32)
+              (table.copy $ext $ext (local.get 0) (local.get 1) (local.get 2)))
+            (func (export "init") (param i32 i32 i32)
+              (table.init $fun $passive (local.get 0) (local.get 1) (local.get 2)))
+            (func (export "drop")
+              (elem.drop $passive))
+            (func (export "call") (param i32) (result i32)
+              (call_indirect $fun (result i32) (local.get 0)))
+            (func (export "store_two")
+              (table.set $fun (i32.const 2) (ref.func $two)))
+            (func (export "select") (param externref externref i32) (result externref)
+              (select (result externref) (local.get 0) (local.get 1) (local.get 2))))
+        "#;
+        let store = init_store();
+        let section = decode_module(bytes);
+        let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
+        let handle = |x: u32| Values::ExternRef(Some(x));
+        let null = Values::ExternRef(None);
+
+        assert_eq!(vm.run("get", vec![Values::I32(0)]), Ok(vec![null.clone()]));
+        vm.run("set", vec![Values::I32(1), handle(42)]).unwrap();
+        assert_eq!(vm.run("get", vec![Values::I32(1)]), Ok(vec![handle(42)]));
+        assert_eq!(vm.run("is_null", vec![handle(42)]), Ok(vec![Values::I32(0)]));
+        assert_eq!(vm.run("is_null", vec![null.clone()]), Ok(vec![Values::I32(1)]));
+        assert_eq!(
+            vm.run("select", vec![handle(1), handle(2), Values::I32(0)]),
+            Ok(vec![handle(2)])
+        );
+
+        assert_eq!(vm.run("grow", vec![Values::I32(2), handle(7)]), Ok(vec![Values::I32(2)]));
+        assert_eq!(vm.run("size", vec![]), Ok(vec![Values::I32(4)]));
+        assert_eq!(vm.run("get", vec![Values::I32(3)]), Ok(vec![handle(7)]));
+        vm.run("fill", vec![Values::I32(0), handle(9), Values::I32(2)])
+            .unwrap();
+        vm.run("copy", vec![Values::I32(1), Values::I32(2), Values::I32(2)])
+            .unwrap();
+        assert_eq!(vm.run("get", vec![Values::I32(0)]), Ok(vec![handle(9)]));
+        assert_eq!(vm.run("get", vec![Values::I32(2)]), Ok(vec![handle(7)]));
+
+        vm.run("init", vec![Values::I32(0), Values::I32(0), Values::I32(2)])
+            .unwrap();
+        vm.run("store_two", vec![]).unwrap();
+        assert_eq!(vm.run("call", vec![Values::I32(0)]), Ok(vec![Values::I32(1)]));
+        assert_eq!(vm.run("call", vec![Values::I32(2)]), Ok(vec![Values::I32(2)]));
+        assert_eq!(
+            vm.run("call", vec![Values::I32(1)]),
+            Err(WasmError::Trap(Trap::UninitializedElement))
+        );
+
+        let out_of_bounds = Err(WasmError::Trap(Trap::TableAccessOutOfBounds));
+        assert_eq!(vm.run("get", vec![Values::I32(4)]), out_of_bounds);
+        assert_eq!(
+            vm.run("fill", vec![Values::I32(3), null, Values::I32(2)]),
+            out_of_bounds
+        );
+        vm.run("drop", vec![]).unwrap();
+        assert_eq!(
+            vm.run("init", vec![Values::I32(0), Values::I32(0), Values::I32(1)]),
+            out_of_bounds
+        );
+    }
+
+    #[test]
+    fn validate_reference_types() {
+        let section = decode_module(br#"(module (table 1 funcref) (table 1 externref))"#);
+        assert_eq!(validate_module(&section), Ok(()));
+        let section = decode_module(
+            br#"(module (table 1 externref)
+              (func (result funcref) (table.get 0 (i32.const 0))))"#,
+        );
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::TypeMismatch))
+        );
+        let section = decode_module(
+            br#"(module (func (param funcref funcref i32)
+              (drop (select (local.get 0) (local.get 1) (local.get 2)))))"#,
+        );
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::TypeMismatch))
+        );
+        let section = decode_module(
+            br#"(module (func (param funcref funcref i32)
+              (drop (select (result funcref) (local.get 0) (local.get 1) (local.get 2)))))"#,
+        );
+        assert_eq!(validate_module(&section), Ok(()));
+        let section = decode_module(br#"(module (func $f (drop (ref.func $f))))"#);
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::UndeclaredFunctionReference))
+        );
+        let section =
+            decode_module(br#"(module (elem declare func $f) (func $f (drop (ref.func $f))))"#);
+        assert_eq!(validate_module(&section), Ok(()));
+        let section = decode_module(br#"(module (func (elem.drop 0)))"#);
+        assert_eq!(
+            validate_module(&section),
+            Err(WasmError::TypeError(TypeError::UnknownElement(0)))
+        );
+    }
+
+    #[test]
+    fn evaluate_multi_value() {
+        let mut file = File::open("./dist/multi_value.wasm").unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let store = init_store();
+        let section = decode_module(&bytes);
+        let mut vm = instantiate_module(store, section, Default::default(), 65536).unwrap();
+        assert_eq!(
+            vm.run("block_params", vec![Values::I32(3)]).unwrap(),
+            vec![Values::I32(3), Values::I32(10)]
+        );
+        assert_eq!(
+            vm.run("branch_values", vec![Values::I32(7)]).unwrap(),
+            vec![Values::I32(7), Values::I32(7)]
+        );
+        assert_eq!(
+            vm.run("loop_params", vec![Values::I32(5), Values::I32(0)])
+                .unwrap(),
+            vec![Values::I32(0), Values::I32(15)]
+        );
+    }
+
+    #[test]
+    fn validate_multi_value() {
+        let valid: &[&[u8]] = &[
+            br#"(module (func (result i32) (block (result i32) (i32.const 1))))"#,
+            br#"(module (func (result i32 i32)
+              (block (result i32 i32) (i32.const 1) (i32.const 2))))"#,
+            br#"(module (func (param i32) (result i32 i64)
+              (local.get 0)
+              (block (param i32) (result i32 i64) (i64.const 2))))"#,
+            br#"(module (func (result i32 i32)
+              (i32.const 1)
+              (loop (param i32) (result i32 i32) (i32.const 2))))"#,
+            br#"(module (func (param i32) (result f32 i32)
+              (if (result f32 i32) (local.get 0)
+                (then (f32.const 1) (i32.const 1))
+                (else (f32.const 0) (i32.const 0)))))"#,
+        ];
+        for bytes in valid.iter() {
+            assert_eq!(validate_module(&decode_module(bytes)), Ok(()));
+        }
+        let invalid: &[&[u8]] = &[
+            br#"(module (func (result i32) (block (result i32) (i32.const 1) (i32.const 2))))"#,
+            br#"(module (func (result i32 i32) (block (result i32 i32) (i32.const 1))))"#,
+            br#"(module (func (result i32) (block (result i32 i32) (i32.const 1) (i32.const 2))))"#,
+            br#"(module (func (param i32) (result i32)
+              (if (result i32) (local.get 0)
+                (then (i32.const 1) (i32.const 1))
+                (else (i32.const 0)))))"#,
+        ];
+        for bytes in invalid.iter() {
+            assert_eq!(
+                validate_module(&decode_module(bytes)),
+                Err(WasmError::TypeError(TypeError::TypeMismatch))
+            );
+        }
     }
 
     test_eval!(evaluate_cons8, "cons8", vec![], 42);
@@ -0,0 +1,94 @@
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::fmt;
+use error::{Result, Trap, WasmError};
+use memory::MemoryType;
+
+/// Policy of an embedder to limit resources which modules allocate.
+/// Every request is accepted unless the method is overridden.
+/// Denied allocation on instantiation fails with `Trap::ResourceLimitExceeded`,
+/// while denied growth fails as same as growth beyond the maximum.
+pub trait ResourceLimiter {
+  /// Consulted when linear memory is allocated or grows.
+  /// Sizes are by bytes, and `maximum` is the one its type declares.
+  fn memory_growing(&mut self, _current: usize, _desired: usize, _maximum: Option<usize>) -> bool {
+    true
+  }
+
+  /// Consulted when a table is allocated or grows. Sizes are by elements.
+  fn table_growing(&mut self, _current: u32, _desired: u32, _maximum: Option<u32>) -> bool {
+    true
+  }
+
+  /// Consulted with the number of functions which a module defines, before instantiating it.
+  fn functions_allocating(&mut self, _count: usize) -> bool {
+    true
+  }
+
+  fn tables_allocating(&mut self, _count: usize) -> bool {
+    true
+  }
+
+  fn memories_allocating(&mut self, _count: usize) -> bool {
+    true
+  }
+
+  fn globals_allocating(&mut self, _count: usize) -> bool {
+    true
+  }
+
+  /// Consulted when a module defines linear memory, to place it on the buffer
+  /// instead of the heap. The memory can't grow beyond pages the buffer holds.
+  fn memory_buffer(&mut self, _memory_type: &MemoryType) -> Option<&'static mut [u8]> {
+    None
+  }
+}
+
+// NOTE: Shared by instances which are allocated through the same store.
+#[derive(Clone, Default)]
+pub struct Limiter(Option<Rc<RefCell<ResourceLimiter>>>);
+
+impl Limiter {
+  pub fn new<T: ResourceLimiter + 'static>(resource_limiter: T) -> Self {
+    Limiter(Some(Rc::new(RefCell::new(resource_limiter))))
+  }
+
+  pub fn allocate<F: FnOnce(&mut ResourceLimiter) -> bool>(&self, f: F) -> Result<()> {
+    let accepted = match &self.0 {
+      Some(limiter) => f(&mut *limiter.borrow_mut()),
+      None => true,
+    };
+    if accepted {
+      Ok(())
+    } else {
+      Err(WasmError::Trap(Trap::ResourceLimitExceeded))
+    }
+  }
+
+  pub fn memory_growing(&self, current: usize, desired: usize, maximum: Option<usize>) -> bool {
+    match &self.0 {
+      Some(limiter) => limiter.borrow_mut().memory_growing(current, desired, maximum),
+      None => true,
+    }
+  }
+
+  pub fn memory_buffer(&self, memory_type: &MemoryType) -> Option<&'static mut [u8]> {
+    match &self.0 {
+      Some(limiter) => limiter.borrow_mut().memory_buffer(memory_type),
+      None => None,
+    }
+  }
+
+  pub fn table_growing(&self, current: u32, desired: u32, maximum: Option<u32>) -> bool {
+    match &self.0 {
+      Some(limiter) => limiter.borrow_mut().table_growing(current, desired, maximum),
+      None => true,
+    }
+  }
+}
+
+impl fmt::Debug for Limiter {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Limiter(..)")
+  }
+}
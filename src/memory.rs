@@ -7,16 +7,21 @@ use core::cell::RefCell;
 use core::cmp::{Ordering, PartialOrd};
 use core::fmt;
 use core::mem::transmute;
+use core::ops::{Deref, DerefMut};
 use core::u32;
+#[cfg(feature = "std")]
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
 use decode::Data;
 use error::{Result, Trap, WasmError};
 use global::GlobalInstances;
 use isa::Isa;
-use module::{ExternalInterface, ImportDescriptor, ModuleDescriptor};
+use limiter::Limiter;
 use value::Values;
 
-// NOTE: 65536(64KiB) is constant data size per page.
-const PAGE_SIZE: u32 = 65536;
+// NOTE: 65536(64KiB) is data size per page unless the memory type customizes it.
+pub(crate) const PAGE_SIZE: u32 = 65536;
 
 // Prefer to rename MemoryType
 #[derive(Clone, PartialEq)]
@@ -25,21 +30,73 @@ pub enum Limit {
   NoUpperLimit(u32),
   // (min, max)
   HasUpperLimit(u32, u32),
+  // (min, max) of memory shared between threads
+  Shared(u32, u32),
 }
 
 impl Limit {
+  pub fn is_shared(&self) -> bool {
+    match self {
+      Limit::Shared(_, _) => true,
+      _ => false,
+    }
+  }
+
+  fn unshared(&self) -> Limit {
+    match self {
+      Limit::Shared(min, max) => Limit::HasUpperLimit(*min, *max),
+      x => x.clone(),
+    }
+  }
+}
+/// Limit of linear memory by pages, and the size of a page by bytes.
+/// Page size is 64KiB by default, or 1 byte for modules which need only a little memory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryType {
+  pub limit: Limit,
+  pub page_size: u32,
+}
+
+impl MemoryType {
+  pub fn new(limit: Limit, page_size: u32) -> Self {
+    MemoryType { limit, page_size }
+  }
+
+  fn min(&self) -> u32 {
+    match self.limit {
+      Limit::NoUpperLimit(min) | Limit::HasUpperLimit(min, _) | Limit::Shared(min, _) => min,
+    }
+  }
+
+  fn max(&self) -> Option<u32> {
+    match self.limit {
+      Limit::NoUpperLimit(_) => None,
+      Limit::HasUpperLimit(_, max) | Limit::Shared(_, max) => Some(max),
+    }
+  }
+
   fn initial_min_size(&self) -> usize {
-    let min_size = match self {
-      Limit::NoUpperLimit(min) => min,
-      Limit::HasUpperLimit(min, _) => min,
-    };
-    (PAGE_SIZE * min_size) as usize
+    (self.page_size * self.min()) as usize
+  }
+
+  fn maximum_size(&self) -> Option<usize> {
+    self
+      .max()
+      .map(|max| (max as usize).saturating_mul(self.page_size as usize))
   }
 }
+
+impl From<Limit> for MemoryType {
+  fn from(limit: Limit) -> Self {
+    MemoryType::new(limit, PAGE_SIZE)
+  }
+}
+
 impl PartialOrd for Limit {
   fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
     use self::Limit::*;
-    match (self, other) {
+    // NOTE: Whether shared or not is compared apart from the range.
+    match (&self.unshared(), &other.unshared()) {
       // NOTE: In the mean of Limit, to compare min bound `A.min < B.min` represents self > other.
       // However, `A.max < B.max` self < other.
       // It seems the specification assumes to compare range of limitations.
@@ -82,6 +139,7 @@ impl PartialOrd for Limit {
         }
         Some(Ordering::Equal)
       }
+      _ => unreachable!(),
     }
   }
 }
@@ -98,24 +156,62 @@ impl fmt::Debug for Limit {
         .debug_tuple("Limit")
         .field(&format!("min:{},max:{}", min, max))
         .finish(),
+      Shared(min, max) => f
+        .debug_tuple("Limit")
+        .field(&format!("min:{},max:{},shared", min, max))
+        .finish(),
     }
   }
 }
 
-#[derive(Clone)]
-pub struct MemoryInstance {
-  data: Vec<u8>,
+// NOTE: Owned data is lazily resized up to the surface size,
+// while borrowed one has been placed by the embedder in advance.
+enum Buffer {
+  Owned(Vec<u8>),
+  Borrowed(&'static mut [u8]),
+}
+
+impl Buffer {
+  fn reserve(&mut self, size: usize) {
+    if let Buffer::Owned(data) = self {
+      if size > data.len() {
+        data.resize(size, 0);
+      }
+    }
+  }
+}
+
+impl Deref for Buffer {
+  type Target = [u8];
+
+  fn deref(&self) -> &[u8] {
+    match self {
+      Buffer::Owned(data) => data,
+      Buffer::Borrowed(data) => data,
+    }
+  }
+}
+
+impl DerefMut for Buffer {
+  fn deref_mut(&mut self) -> &mut [u8] {
+    match self {
+      Buffer::Owned(data) => data,
+      Buffer::Borrowed(data) => data,
+    }
+  }
+}
+
+struct MemoryInstanceImpl {
+  data: Buffer,
   limit: Limit,
-  export_name: Option<String>,
+  page_size: u32,
   surface_size: u32,
 }
 
 macro_rules! impl_load_data {
   ($name: ident, $ty: ty, $conv_fn: path) => {
-    pub fn $name(&mut self, from: u32, to: u32) -> $ty {
-      if (to as usize) > self.data.len() {
-        self.data.resize(to as usize, 0);
-      };
+    fn $name(&mut self, from: u32, to: u32) -> $ty {
+      self.data.reserve(to as usize);
       let data = &self.data[(from as usize)..(to as usize)];
       let mut bit_buf: $ty = 0;
       for (idx, d) in data.iter().enumerate() {
@@ -131,150 +227,151 @@ macro_rules! impl_store_data {
   ($name: ident, $length: expr, $ty: ty) => {
     fn $name (&mut self, v: $ty, from: u32, to: u32) {
         let bytes: [u8; $length] = unsafe { transmute(v) };
-        let data: &mut Vec<u8> = self.data.as_mut();
-        MemoryInstance::allocate(data, &bytes[0..(to - from) as usize], from as usize);
+        let allocatable = &bytes[0..(to - from) as usize];
+        MemoryInstanceImpl::allocate(&mut self.data, allocatable, from as usize);
     }
   };
 }
 
-impl MemoryInstance {
+impl MemoryInstanceImpl {
   impl_load_data!(load_data_32, u32, u32::from);
   impl_load_data!(load_data_64, u64, u64::from);
+  impl_load_data!(load_data_128, u128, u128::from);
 
-  fn allocate(data: &mut Vec<u8>, allocatable: &[u8], offset: usize) {
+  fn allocate(data: &mut Buffer, allocatable: &[u8], offset: usize) {
     let end = offset + allocatable.len();
-    if end > data.len() {
-      data.resize(end, 0);
-    }
+    data.reserve(end);
     data[offset..end].copy_from_slice(allocatable);
   }
 
-  pub fn new(
-    datas: Vec<Data>,
-    limit: Limit,
-    export_name: Option<String>,
-    global_instances: &GlobalInstances,
-  ) -> Result<Self> {
-    let initial_size = limit.initial_min_size();
-    let mut data = Vec::new();
-    for Data { offset, init, .. } in datas.into_iter() {
-      let offset = Isa::constant_expression(&offset, global_instances)?;
-      let size = offset + init.len();
-      if size > initial_size {
-        return Err(WasmError::Trap(Trap::DataSegmentDoesNotFit));
-      }
-      MemoryInstance::allocate(&mut data, &init, offset);
+  fn new(memory_type: MemoryType) -> Self {
+    MemoryInstanceImpl {
+      data: Buffer::Owned(Vec::new()),
+      surface_size: memory_type.initial_min_size() as u32,
+      limit: memory_type.limit,
+      page_size: memory_type.page_size,
     }
-
-    Ok(MemoryInstance {
-      data,
-      limit,
-      export_name,
-      surface_size: initial_size as u32,
-    })
   }
 
-  fn link(
-    &mut self,
-    datas: Vec<Data>,
-    limit: Option<Limit>,
-    global_instances: &GlobalInstances,
-  ) -> Result<()> {
-    if let Some(limit) = limit {
-      self.limit = limit;
-    };
-    let data: &mut Vec<u8> = self.data.as_mut();
-    for Data { offset, init, .. } in datas.into_iter() {
-      let offset = Isa::constant_expression(&offset, global_instances)?;
-      MemoryInstance::allocate(data, &init, offset);
+  // NOTE: Maximum is capped by pages which the buffer can hold,
+  // and the buffer is zeroed as linear memory is initially.
+  fn from_buffer(buffer: &'static mut [u8], memory_type: MemoryType) -> Result<Self> {
+    let page_size = memory_type.page_size;
+    let capacity = (buffer.len() / page_size as usize).min(u32::MAX as usize) as u32;
+    let (min, max) = (memory_type.min(), memory_type.max().unwrap_or(capacity));
+    let max = max.min(capacity);
+    if min > max {
+      return Err(WasmError::Trap(Trap::FailToGrow));
     }
-    Ok(())
-  }
-
-  fn validate(
-    &mut self,
-    datas: &[Data],
-    limit: &Option<Limit>,
-    global_instances: &GlobalInstances,
-  ) -> Result<()> {
-    let initial_size = match limit {
-      Some(limit) => limit.initial_min_size(),
-      None => self.limit.initial_min_size(),
-    };
-    for Data { offset, init, .. } in datas.iter() {
-      let offset = Isa::constant_expression(&offset, global_instances)?;
-      let size = offset + init.len();
-      if size > initial_size {
-        return Err(WasmError::Trap(Trap::DataSegmentDoesNotFit));
-      }
+    for byte in buffer.iter_mut() {
+      *byte = 0;
     }
-    Ok(())
+    let memory_type = MemoryType::new(Limit::HasUpperLimit(min, max), page_size);
+    Ok(MemoryInstanceImpl {
+      surface_size: memory_type.initial_min_size() as u32,
+      data: Buffer::Borrowed(buffer),
+      limit: memory_type.limit,
+      page_size,
+    })
   }
 
   fn data_size(&self) -> u32 {
     self.surface_size
   }
 
-  pub fn data_size_smaller_than(&self, ptr: u32) -> bool {
-    ptr > self.data_size()
+  fn size_by_pages(&self) -> u32 {
+    self.data_size() / self.page_size
   }
 
-  pub fn size_by_pages(&self) -> u32 {
-    self.data_size() / PAGE_SIZE
+  fn memory_type(&self) -> MemoryType {
+    MemoryType::new(self.limit.clone(), self.page_size)
   }
 
-  pub fn memory_grow(&mut self, increase_page: u32) -> Result<()> {
-    match self.limit {
-      Limit::HasUpperLimit(_, max) if self.size_by_pages() + increase_page > max => {
-        Err(WasmError::Trap(Trap::FailToGrow))
-      }
-      _ => {
-        let current_size = self.data_size();
-        match increase_page.checked_mul(PAGE_SIZE) {
-          Some(growing_size) => match current_size.checked_add(growing_size) {
-            Some(next_size) => {
-              self.surface_size = next_size;
-              Ok(())
-            }
-            None => Err(WasmError::Trap(Trap::FailToGrow)),
-          },
-          None => Err(WasmError::Trap(Trap::FailToGrow)),
-        }
-      }
+  fn memory_grow(&mut self, increase_page: u32, limiter: &Limiter) -> Result<()> {
+    let memory_type = self.memory_type();
+    let next_size = self
+      .size_by_pages()
+      .checked_add(increase_page)
+      .filter(|pages| memory_type.max().map_or(true, |max| *pages <= max))
+      .and_then(|pages| pages.checked_mul(self.page_size))
+      .ok_or(WasmError::Trap(Trap::FailToGrow))?;
+    let current_size = self.data_size() as usize;
+    if !limiter.memory_growing(current_size, next_size as usize, memory_type.maximum_size()) {
+      return Err(WasmError::Trap(Trap::FailToGrow));
     }
+    self.surface_size = next_size;
+    Ok(())
   }
 
-  pub fn load_data_f32(&mut self, from: u32, to: u32) -> f32 {
-    f32::from_bits(self.load_data_32(from, to))
+  fn range_of(&self, offset: u32, length: usize) -> Result<(usize, usize)> {
+    let from = offset as usize;
+    match from.checked_add(length) {
+      Some(to) if to <= self.data_size() as usize => Ok((from, to)),
+      _ => Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds)),
+    }
   }
 
-  pub fn load_data_f64(&mut self, from: u32, to: u32) -> f64 {
-    f64::from_bits(self.load_data_64(from, to))
+  fn read_bytes(&mut self, offset: u32, buf: &mut [u8]) -> Result<()> {
+    let (from, to) = self.range_of(offset, buf.len())?;
+    self.data.reserve(to);
+    buf.copy_from_slice(&self.data[from..to]);
+    Ok(())
+  }
+
+  fn write_bytes(&mut self, offset: u32, bytes: &[u8]) -> Result<()> {
+    let (from, _) = self.range_of(offset, bytes.len())?;
+    MemoryInstanceImpl::allocate(&mut self.data, bytes, from);
+    Ok(())
+  }
+
+  fn fill(&mut self, offset: u32, value: u8, length: u32) -> Result<()> {
+    let (from, to) = self.range_of(offset, length as usize)?;
+    self.data.reserve(to);
+    for byte in self.data[from..to].iter_mut() {
+      *byte = value;
+    }
+    Ok(())
+  }
+
+  // NOTE: Regions of source and destination may overlap.
+  fn copy(&mut self, destination: u32, source: u32, length: u32) -> Result<()> {
+    let (from, to) = self.range_of(source, length as usize)?;
+    let (destination, end) = self.range_of(destination, length as usize)?;
+    self.data.reserve(end.max(to));
+    let data = self.data.as_mut_ptr();
+    unsafe { core::ptr::copy(data.add(from), data.add(destination), to - from) };
+    Ok(())
   }
 
   impl_store_data!(store_data_i32, 4, i32);
   impl_store_data!(store_data_f32, 4, f32);
   impl_store_data!(store_data_i64, 8, i64);
   impl_store_data!(store_data_f64, 8, f64);
+  impl_store_data!(store_data_v128, 16, u128);
 
-  pub fn store_data(&mut self, from: u32, to: u32, value: &Values) {
+  fn store_data(&mut self, from: u32, to: u32, value: &Values) {
     match value {
       Values::I32(v) => self.store_data_i32(*v, from, to),
       Values::F32(v) => self.store_data_f32(*v, from, to),
       Values::I64(v) => self.store_data_i64(*v, from, to),
       Values::F64(v) => self.store_data_f64(*v, from, to),
+      Values::V128(v) => self.store_data_v128(*v, from, to),
+      x => unreachable!("Expected numeric value, got {:?}", x),
     };
   }
 
-  pub fn limit_gt(&self, other_limit: &Limit) -> bool {
-    &self.limit > other_limit
+  // NOTE: Replaces `to - from` bytes by the result of `f`, and returns the previous bytes.
+  fn read_modify_write<F: FnOnce(u64) -> u64>(&mut self, from: u32, to: u32, f: F) -> u64 {
+    let previous = self.load_data_64(from, to);
+    let bytes = f(previous).to_le_bytes();
+    MemoryInstanceImpl::allocate(&mut self.data, &bytes[0..(to - from) as usize], from as usize);
+    previous
   }
 }
 
-impl fmt::Debug for MemoryInstance {
+impl fmt::Debug for MemoryInstanceImpl {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     f.debug_struct("MemoryInstance")
-      .field("export_name", &self.export_name)
       .field(
         "data",
         &self
@@ -291,146 +388,503 @@ impl fmt::Debug for MemoryInstance {
   }
 }
 
+#[cfg(feature = "std")]
+#[derive(Debug)]
+struct Waiter {
+  address: u32,
+  notified: Mutex<bool>,
+  condvar: Condvar,
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug)]
+struct SharedMemoryImpl {
+  memory: Mutex<MemoryInstanceImpl>,
+  // NOTE: Waiters are queued by arrival, and removed once notified or timed out.
+  waiters: Mutex<Vec<Arc<Waiter>>>,
+}
+
+#[cfg(feature = "std")]
+impl SharedMemoryImpl {
+  fn memory(&self) -> MutexGuard<MemoryInstanceImpl> {
+    self.memory.lock().expect("Shared memory is poisoned")
+  }
+
+  fn waiters(&self) -> MutexGuard<Vec<Arc<Waiter>>> {
+    self.waiters.lock().expect("Waiters of shared memory are poisoned")
+  }
+
+  fn wait(&self, from: u32, to: u32, expected: u64, timeout: i64) -> u32 {
+    // NOTE: Comparison and enqueueing is atomic, as notifier writes memory before.
+    let waiter = {
+      let mut memory = self.memory();
+      if memory.load_data_64(from, to) != expected {
+        return 1;
+      }
+      let waiter = Arc::new(Waiter {
+        address: from,
+        notified: Mutex::new(false),
+        condvar: Condvar::new(),
+      });
+      self.waiters().push(waiter.clone());
+      waiter
+    };
+    let deadline = if timeout < 0 {
+      None
+    } else {
+      Some(Instant::now() + Duration::from_nanos(timeout as u64))
+    };
+    {
+      let mut notified = waiter.notified.lock().expect("Waiter is poisoned");
+      while !*notified {
+        notified = match deadline {
+          None => waiter.condvar.wait(notified).expect("Waiter is poisoned"),
+          Some(deadline) => {
+            let now = Instant::now();
+            if now >= deadline {
+              break;
+            }
+            let (notified, _) = waiter
+              .condvar
+              .wait_timeout(notified, deadline - now)
+              .expect("Waiter is poisoned");
+            notified
+          }
+        };
+      }
+      if *notified {
+        return 0;
+      }
+    }
+    self.waiters().retain(|x| !Arc::ptr_eq(x, &waiter));
+    // NOTE: Notification might arrive before the waiter is removed.
+    let notified = *waiter.notified.lock().expect("Waiter is poisoned");
+    if notified {
+      0
+    } else {
+      2
+    }
+  }
+
+  fn notify(&self, address: u32, count: u32) -> u32 {
+    let mut woken = 0;
+    self.waiters().retain(|waiter| {
+      if woken >= count || waiter.address != address {
+        return true;
+      }
+      woken += 1;
+      *waiter.notified.lock().expect("Waiter is poisoned") = true;
+      waiter.condvar.notify_one();
+      false
+    });
+    woken
+  }
+}
+
+/// Linear memory which module instances on several threads can share.
+/// It is imported through `ExternalModule::from_shared_memory`.
+#[cfg(feature = "std")]
 #[derive(Debug, Clone)]
-pub struct MemoryInstances(Rc<RefCell<Vec<MemoryInstance>>>);
+pub struct SharedMemory(Arc<SharedMemoryImpl>);
+
+#[cfg(feature = "std")]
+impl SharedMemory {
+  pub fn new(min: u32, max: u32) -> Self {
+    SharedMemory(Arc::new(SharedMemoryImpl {
+      memory: Mutex::new(MemoryInstanceImpl::new(Limit::Shared(min, max).into())),
+      waiters: Mutex::new(vec![]),
+    }))
+  }
+}
 
-impl MemoryInstances {
-  pub fn new(memory_instances: Vec<MemoryInstance>) -> Self {
-    MemoryInstances(Rc::new(RefCell::new(memory_instances)))
+#[derive(Debug, Clone)]
+enum MemoryCell {
+  Unshared(Rc<RefCell<MemoryInstanceImpl>>),
+  #[cfg(feature = "std")]
+  Shared(SharedMemory),
+}
+
+impl MemoryCell {
+  fn with<T, F: FnOnce(&mut MemoryInstanceImpl) -> T>(&self, f: F) -> T {
+    match self {
+      MemoryCell::Unshared(memory) => f(&mut memory.borrow_mut()),
+      #[cfg(feature = "std")]
+      MemoryCell::Shared(SharedMemory(memory)) => f(&mut memory.memory()),
+    }
   }
 
-  pub fn empty() -> Self {
-    MemoryInstances(Rc::new(RefCell::new(vec![])))
+  fn is_same(&self, other: &MemoryCell) -> bool {
+    match (self, other) {
+      (MemoryCell::Unshared(x), MemoryCell::Unshared(y)) => Rc::ptr_eq(x, y),
+      #[cfg(feature = "std")]
+      (MemoryCell::Shared(SharedMemory(x)), MemoryCell::Shared(SharedMemory(y))) => {
+        Arc::ptr_eq(x, y)
+      }
+      #[cfg(feature = "std")]
+      _ => false,
+    }
   }
+}
+
+// NOTE: Integers are little-endian as wasm loads and stores them.
+// Signed ones can be read or written by casting with `as`.
+macro_rules! impl_read_le {
+  ($name: ident, $ty: ty, $length: expr) => {
+    pub fn $name(&self, offset: u32) -> Result<$ty> {
+      let mut buf = [0; $length];
+      self.read_bytes(offset, &mut buf)?;
+      Ok(<$ty>::from_le_bytes(buf))
+    }
+  };
+}
+
+macro_rules! impl_write_le {
+  ($name: ident, $ty: ty) => {
+    pub fn $name(&self, offset: u32, value: $ty) -> Result<()> {
+      self.write_bytes(offset, &value.to_le_bytes())
+    }
+  };
+}
 
-  pub fn from(
-    that: &MemoryInstances,
-    limit: Option<Limit>,
-    datas: Vec<Data>,
-    global_instances: &GlobalInstances,
+#[derive(Debug, Clone)]
+pub struct MemoryInstance {
+  cell: MemoryCell,
+  export_name: Option<String>,
+  limiter: Limiter,
+}
+
+impl MemoryInstance {
+  /// Memory type is either of `Limit` with 64KiB pages or `MemoryType`.
+  pub fn new<T: Into<MemoryType>>(memory_type: T, export_name: Option<String>) -> Self {
+    MemoryInstance::from_cell(MemoryInstance::new_cell(memory_type.into()), export_name)
+  }
+
+  // NOTE: Without std, shared memory is backed as same as others,
+  // as there is no other thread to share it.
+  fn new_cell(memory_type: MemoryType) -> MemoryCell {
+    #[cfg(feature = "std")]
+    {
+      if memory_type.limit.is_shared() {
+        return MemoryCell::Shared(SharedMemory(Arc::new(SharedMemoryImpl {
+          memory: Mutex::new(MemoryInstanceImpl::new(memory_type)),
+          waiters: Mutex::new(vec![]),
+        })));
+      }
+    }
+    MemoryCell::Unshared(Rc::new(RefCell::new(MemoryInstanceImpl::new(memory_type))))
+  }
+
+  fn from_cell(cell: MemoryCell, export_name: Option<String>) -> Self {
+    MemoryInstance {
+      cell,
+      export_name,
+      limiter: Limiter::default(),
+    }
+  }
+
+  // NOTE: Memory defined in a module is allocated under the limiter of the store.
+  pub(crate) fn new_with_limiter(
+    memory_type: MemoryType,
+    export_name: Option<String>,
+    limiter: Limiter,
   ) -> Result<Self> {
-    that
-      .0
-      .borrow_mut()
-      .get_mut(0)
-      .ok_or(Trap::UnknownImport)?
-      .link(datas, limit, global_instances)?;
-    Ok(that.clone())
-  }
-
-  pub fn validate(
-    that: &MemoryInstances,
-    limit: &Option<Limit>,
-    import: &ExternalInterface,
-    datas: &[Data],
-    global_instances: &GlobalInstances,
-  ) -> Result<()> {
-    that
-      .0
-      .borrow_mut()
-      .get_mut(0)
-      .ok_or(Trap::UnknownImport)
-      .and_then(|instance| {
-        if instance.export_name.as_ref() != Some(&import.name) {
-          Err(Trap::UnknownImport)
-        } else {
-          Ok(instance)
-        }
-      })
-      .and_then(|instance| match import {
-        ExternalInterface {
-          descriptor: ModuleDescriptor::ImportDescriptor(ImportDescriptor::Memory(limit)),
-          ..
-        } => {
-          if instance.limit_gt(limit) {
-            Err(Trap::IncompatibleImportType)
-          } else {
-            Ok(instance)
-          }
-        }
-        x => unreachable!("Expected memory descriptor, got {:?}", x),
-      })?
-      .validate(datas, limit, global_instances)
+    let initial_size = memory_type.initial_min_size();
+    let maximum_size = memory_type.maximum_size();
+    limiter.allocate(|limiter| limiter.memory_growing(0, initial_size, maximum_size))?;
+    // NOTE: Shared memory is never placed on a buffer, as other threads may access it.
+    let buffer = if memory_type.limit.is_shared() {
+      None
+    } else {
+      limiter.memory_buffer(&memory_type)
+    };
+    let memory_instance = match buffer {
+      Some(buffer) => MemoryInstance::from_buffer(buffer, memory_type, export_name)?,
+      None => MemoryInstance::new(memory_type, export_name),
+    };
+    Ok(MemoryInstance {
+      limiter,
+      ..memory_instance
+    })
   }
 
-  pub fn data_size_small_than(&self, ptr: u32) -> bool {
-    self
-      .0
-      .borrow()
-      .get(0)
-      .expect("At least one memory instance expected")
-      .data_size_smaller_than(ptr)
+  /// Linear memory on the buffer, which is never reallocated.
+  /// It can't grow beyond pages the buffer holds, and fails with `Trap::FailToGrow`
+  /// if even the minimum of the limit doesn't fit in.
+  pub fn from_buffer<T: Into<MemoryType>>(
+    buffer: &'static mut [u8],
+    memory_type: T,
+    export_name: Option<String>,
+  ) -> Result<Self> {
+    let memory = MemoryInstanceImpl::from_buffer(buffer, memory_type.into())?;
+    Ok(MemoryInstance::from_cell(
+      MemoryCell::Unshared(Rc::new(RefCell::new(memory))),
+      export_name,
+    ))
   }
 
-  pub fn load_data_32(&self, from: u32, to: u32) -> u32 {
-    self
-      .0
-      .borrow_mut()
-      .get_mut(0)
-      .expect("At least one memory instance expected")
-      .load_data_32(from, to)
+  #[cfg(feature = "std")]
+  pub fn from_shared(shared_memory: SharedMemory, export_name: Option<String>) -> Self {
+    MemoryInstance::from_cell(MemoryCell::Shared(shared_memory), export_name)
   }
 
-  pub fn load_data_64(&self, from: u32, to: u32) -> u64 {
-    self
-      .0
-      .borrow_mut()
-      .get_mut(0)
-      .expect("At least one memory instance expected")
-      .load_data_64(from, to)
+  // NOTE: Minimum of limit reflects the current size, as it may be grown.
+  pub fn limit(&self) -> Limit {
+    self.cell.with(|memory| {
+      let size = memory.size_by_pages();
+      match memory.limit {
+        Limit::NoUpperLimit(_) => Limit::NoUpperLimit(size),
+        Limit::HasUpperLimit(_, max) => Limit::HasUpperLimit(size, max),
+        Limit::Shared(_, max) => Limit::Shared(size, max),
+      }
+    })
   }
 
-  pub fn load_data_f32(&self, from: u32, to: u32) -> f32 {
-    self
-      .0
-      .borrow_mut()
-      .get_mut(0)
-      .expect("At least one memory instance expected")
-      .load_data_f32(from, to)
+  pub fn page_size(&self) -> u32 {
+    self.cell.with(|memory| memory.page_size)
   }
 
-  pub fn load_data_f64(&self, from: u32, to: u32) -> f64 {
-    self
-      .0
-      .borrow_mut()
-      .get_mut(0)
-      .expect("At least one memory instance expected")
-      .load_data_f64(from, to)
+  pub(crate) fn limit_gt(&self, other_limit: &Limit) -> bool {
+    &self.limit() > other_limit
+  }
+
+  fn is_same_name(&self, name: &str) -> bool {
+    self.export_name == Some(name.to_owned())
+  }
+
+  pub(crate) fn ptr_eq(&self, other: &MemoryInstance) -> bool {
+    self.cell.is_same(&other.cell)
+  }
+
+  /// Size of linear memory by bytes.
+  pub fn data_size(&self) -> u32 {
+    self.cell.with(|memory| memory.data_size())
+  }
+
+  pub(crate) fn data_size_smaller_than(&self, ptr: u32) -> bool {
+    ptr > self.data_size()
   }
 
   pub fn size_by_pages(&self) -> u32 {
-    self
-      .0
-      .borrow()
-      .get(0)
-      .expect("At least one memory instance expected")
-      .size_by_pages()
+    self.cell.with(|memory| memory.size_by_pages())
+  }
+
+  /// Grows linear memory by pages, or fails with `Trap::FailToGrow` beyond the maximum.
+  pub fn memory_grow(&self, increase_page: u32) -> Result<()> {
+    self.cell.with(|memory| memory.memory_grow(increase_page, &self.limiter))
+  }
+
+  pub(crate) fn load_data_32(&self, from: u32, to: u32) -> u32 {
+    self.cell.with(|memory| memory.load_data_32(from, to))
+  }
+
+  pub(crate) fn load_data_64(&self, from: u32, to: u32) -> u64 {
+    self.cell.with(|memory| memory.load_data_64(from, to))
+  }
+
+  pub(crate) fn load_data_128(&self, from: u32, to: u32) -> u128 {
+    self.cell.with(|memory| memory.load_data_128(from, to))
   }
 
-  pub fn store_data(&self, from: u32, to: u32, value: &Values) {
+  pub(crate) fn load_data_f32(&self, from: u32, to: u32) -> f32 {
+    f32::from_bits(self.load_data_32(from, to))
+  }
+
+  pub(crate) fn load_data_f64(&self, from: u32, to: u32) -> f64 {
+    f64::from_bits(self.load_data_64(from, to))
+  }
+
+  pub(crate) fn store_data(&self, from: u32, to: u32, value: &Values) {
+    self.cell.with(|memory| memory.store_data(from, to, value))
+  }
+
+  pub(crate) fn read_modify_write<F: FnOnce(u64) -> u64>(&self, from: u32, to: u32, f: F) -> u64 {
+    self.cell.with(|memory| memory.read_modify_write(from, to, f))
+  }
+
+  /// Fills the buffer from the offset,
+  /// or fails with `Trap::MemoryAccessOutOfBounds` without reading anything.
+  pub fn read_bytes(&self, offset: u32, buf: &mut [u8]) -> Result<()> {
+    self.cell.with(|memory| memory.read_bytes(offset, buf))
+  }
+
+  pub fn write_bytes(&self, offset: u32, bytes: &[u8]) -> Result<()> {
+    self.cell.with(|memory| memory.write_bytes(offset, bytes))
+  }
+
+  impl_read_le!(read_u8, u8, 1);
+  impl_read_le!(read_u16, u16, 2);
+  impl_read_le!(read_u32, u32, 4);
+  impl_read_le!(read_u64, u64, 8);
+  impl_write_le!(write_u8, u8);
+  impl_write_le!(write_u16, u16);
+  impl_write_le!(write_u32, u32);
+  impl_write_le!(write_u64, u64);
+
+  pub fn read_f32(&self, offset: u32) -> Result<f32> {
+    self.read_u32(offset).map(f32::from_bits)
+  }
+
+  pub fn read_f64(&self, offset: u32) -> Result<f64> {
+    self.read_u64(offset).map(f64::from_bits)
+  }
+
+  pub fn write_f32(&self, offset: u32, value: f32) -> Result<()> {
+    self.write_u32(offset, value.to_bits())
+  }
+
+  pub fn write_f64(&self, offset: u32, value: f64) -> Result<()> {
+    self.write_u64(offset, value.to_bits())
+  }
+
+  pub(crate) fn fill(&self, offset: u32, value: u8, length: u32) -> Result<()> {
+    self.cell.with(|memory| memory.fill(offset, value, length))
+  }
+
+  pub(crate) fn copy(
+    &self,
+    destination: u32,
+    source: &MemoryInstance,
+    offset: u32,
+    length: u32,
+  ) -> Result<()> {
+    if self.cell.is_same(&source.cell) {
+      return self
+        .cell
+        .with(|memory| memory.copy(destination, offset, length));
+    }
+    source
+      .cell
+      .with(|memory| memory.range_of(offset, length as usize))?;
     self
-      .0
-      .borrow_mut()
-      .get_mut(0)
-      .expect("At least one memory instance expected")
-      .store_data(from, to, value)
+      .cell
+      .with(|memory| memory.range_of(destination, length as usize))?;
+    let mut bytes = vec![0; length as usize];
+    source.read_bytes(offset, &mut bytes)?;
+    self.write_bytes(destination, &bytes)
   }
 
-  pub fn memory_grow(&self, increase_page: u32) -> Result<()> {
+  // NOTE: Returns 0 when woken by notification, 1 when the value isn't expected one
+  // and 2 when timed out.
+  #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+  pub(crate) fn wait(&self, from: u32, to: u32, expected: u64, timeout: i64) -> Result<u32> {
+    match &self.cell {
+      #[cfg(feature = "std")]
+      MemoryCell::Shared(SharedMemory(memory)) => Ok(memory.wait(from, to, expected, timeout)),
+      _ => Err(WasmError::Trap(Trap::ExpectedSharedMemory)),
+    }
+  }
+
+  // NOTE: Returns the number of woken waiters, which is always 0 for unshared memory.
+  #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+  pub(crate) fn notify(&self, address: u32, count: u32) -> u32 {
+    match &self.cell {
+      #[cfg(feature = "std")]
+      MemoryCell::Shared(SharedMemory(memory)) => memory.notify(address, count),
+      _ => 0,
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryInstances(Rc<RefCell<Vec<MemoryInstance>>>);
+
+impl MemoryInstances {
+  pub fn new(memory_instances: Vec<MemoryInstance>) -> Self {
+    MemoryInstances(Rc::new(RefCell::new(memory_instances)))
+  }
+
+  pub fn empty() -> Self {
+    MemoryInstances::new(vec![])
+  }
+
+  pub fn find_by_name(&self, name: &str) -> Option<MemoryInstance> {
     self
       .0
-      .borrow_mut()
-      .get_mut(0)
-      .expect("At least one memory instance expected")
-      .memory_grow(increase_page)
+      .borrow()
+      .iter()
+      .find(|memory_instance| memory_instance.is_same_name(name))
+      .cloned()
   }
 
-  pub fn clone_instance_by_name(&self, name: &str) -> Option<MemoryInstance> {
-    let instance = self.0.borrow().get(0)?.clone();
-    if instance.export_name == Some(name.to_owned()) {
-      Some(instance)
-    } else {
-      None
+  pub fn get_memory_at(&self, idx: u32) -> Option<MemoryInstance> {
+    self.0.borrow().get(idx as usize).cloned()
+  }
+
+  fn active_datas<'a>(
+    &'a self,
+    datas: &'a [Data],
+    global_instances: &'a GlobalInstances,
+  ) -> impl Iterator<Item = Result<(MemoryInstance, usize, &'a Data)>> + 'a {
+    datas.iter().filter(|data| !data.is_passive()).map(move |data| {
+      let memory_instance = self.get_memory_at(data.memidx)?;
+      let offset = match &data.offset {
+        Some(offset) => Isa::constant_expression(offset, global_instances)?,
+        None => unreachable!("Expected active data segment"),
+      };
+      Ok((memory_instance, offset, data))
+    })
+  }
+
+  pub fn link(&self, datas: &[Data], global_instances: &GlobalInstances) -> Result<()> {
+    for x in self.active_datas(datas, global_instances) {
+      let (memory_instance, offset, data) = x?;
+      memory_instance.write_bytes(offset as u32, &data.init)?;
+    }
+    Ok(())
+  }
+
+  pub fn validate(&self, datas: &[Data], global_instances: &GlobalInstances) -> Result<()> {
+    for x in self.active_datas(datas, global_instances) {
+      let (memory_instance, offset, data) = x?;
+      let size = offset + data.init.len();
+      if size > memory_instance.data_size() as usize {
+        return Err(WasmError::Trap(Trap::DataSegmentDoesNotFit));
+      }
+    }
+    Ok(())
+  }
+}
+
+// NOTE: Bytes of data segments which `memory.init` copies from.
+// Active segments are dropped at instantiation, and passive ones by `data.drop`.
+#[derive(Debug, Clone)]
+pub struct DataInstances(Rc<RefCell<Vec<Vec<u8>>>>);
+
+impl DataInstances {
+  pub fn new(datas: &[Data]) -> Self {
+    let data_instances = datas
+      .iter()
+      .map(|data| {
+        if data.is_passive() {
+          data.init.clone()
+        } else {
+          vec![]
+        }
+      })
+      .collect();
+    DataInstances(Rc::new(RefCell::new(data_instances)))
+  }
+
+  pub fn init(
+    &self,
+    memory_instance: &MemoryInstance,
+    idx: u32,
+    destination: u32,
+    source: u32,
+    length: u32,
+  ) -> Result<()> {
+    let data_instances = self.0.borrow();
+    let data = data_instances.get(idx as usize)?;
+    let from = source as usize;
+    match from.checked_add(length as usize) {
+      Some(to) if to <= data.len() => memory_instance.write_bytes(destination, &data[from..to]),
+      _ => Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds)),
     }
   }
+
+  pub fn drop_data(&self, idx: u32) -> Result<()> {
+    let mut data_instances = self.0.borrow_mut();
+    *data_instances.get_mut(idx as usize)? = vec![];
+    Ok(())
+  }
 }
@@ -7,23 +7,27 @@ use core::default::Default;
 use core::fmt;
 use core::iter::Iterator;
 use core::slice::Iter;
-use decode::TableType;
+use decode::{Names, SourceMap, TableType};
 use error::{Result, Trap, WasmError};
 use function::{FunctionInstance, FunctionType};
 use global::{GlobalInstance, GlobalInstances, GlobalType};
-use heapless::consts::{U32, U4};
+use heapless::consts::{U32, U8};
 use heapless::LinearMap;
 use indice::Indice;
-use memory::{Limit, MemoryInstance, MemoryInstances};
-use store::Store;
+#[cfg(feature = "std")]
+use memory::SharedMemory;
+use memory::{MemoryInstance, MemoryInstances, MemoryType};
+use store::{ModuleRef, Store};
 use table::{TableInstance, TableInstances};
+use tag::TagInstance;
 
 #[derive(Debug, Clone)]
 pub enum ImportDescriptor {
   Function(Indice), // NOTE: Index of FunctionTypes
   Table(TableType),
-  Memory(Limit),
+  Memory(MemoryType),
   Global(GlobalType),
+  Tag(Indice), // NOTE: Index of FunctionTypes
 }
 
 #[derive(Debug, Clone)]
@@ -32,6 +36,7 @@ pub enum ExportDescriptor {
   Table(Indice),
   Memory(Indice),
   Global(Indice),
+  Tag(Indice),
 }
 
 impl From<(Option<u8>, u32)> for ExportDescriptor {
@@ -42,6 +47,7 @@ impl From<(Option<u8>, u32)> for ExportDescriptor {
       Some(0x01) => Table(From::from(codes.1)),
       Some(0x02) => Memory(From::from(codes.1)),
       Some(0x03) => Global(From::from(codes.1)),
+      Some(0x04) => Tag(From::from(codes.1)),
       x => unreachable!("Expected exports descriptor, got {:?}", x),
     }
   }
@@ -59,12 +65,14 @@ pub enum ModuleDescriptorKind {
   Table,
   Memory,
   Global,
+  Tag,
 }
 
 pub const FUNCTION_DESCRIPTOR: ModuleDescriptorKind = ModuleDescriptorKind::Function;
 pub const TABLE_DESCRIPTOR: ModuleDescriptorKind = ModuleDescriptorKind::Table;
 pub const MEMORY_DESCRIPTOR: ModuleDescriptorKind = ModuleDescriptorKind::Memory;
 pub const GLOBAL_DESCRIPTOR: ModuleDescriptorKind = ModuleDescriptorKind::Global;
+pub const TAG_DESCRIPTOR: ModuleDescriptorKind = ModuleDescriptorKind::Tag;
 
 impl From<Option<u8>> for ModuleDescriptorKind {
   fn from(code: Option<u8>) -> Self {
@@ -74,6 +82,7 @@ impl From<Option<u8>> for ModuleDescriptorKind {
       Some(0x1) => Table,
       Some(0x2) => Memory,
       Some(0x3) => Global,
+      Some(0x4) => Tag,
       x => unreachable!("Expected import descriptor, got {:x?}", x),
     }
   }
@@ -99,7 +108,7 @@ impl ExternalInterface {
   }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ExternalInterfaces(Vec<ExternalInterface>);
 
 impl ExternalInterfaces {
@@ -132,6 +141,9 @@ impl ExternalInterfaces {
         ModuleDescriptor::ExportDescriptor(ExportDescriptor::Global(x)) => {
           &GLOBAL_DESCRIPTOR == kind && x.to_u32() == idx
         }
+        ModuleDescriptor::ExportDescriptor(ExportDescriptor::Tag(x)) => {
+          &TAG_DESCRIPTOR == kind && x.to_u32() == idx
+        }
         _ => unreachable!(),
       })
   }
@@ -140,13 +152,25 @@ impl ExternalInterfaces {
     self.0.iter()
   }
 
+  pub fn find_export(&self, name: &str) -> Option<&ExportDescriptor> {
+    self
+      .0
+      .iter()
+      .find(|x| x.name == name)
+      .and_then(|x| match &x.descriptor {
+        ModuleDescriptor::ExportDescriptor(descriptor) => Some(descriptor),
+        _ => None,
+      })
+  }
+
   pub fn group_by_kind(
     &self,
-  ) -> Result<LinearMap<ModuleDescriptorKind, Vec<ExternalInterface>, U4>> {
+  ) -> Result<LinearMap<ModuleDescriptorKind, Vec<ExternalInterface>, U8>> {
     let mut buf_function = vec![];
     let mut buf_table = vec![];
     let mut buf_memory = vec![];
     let mut buf_global = vec![];
+    let mut buf_tag = vec![];
     let mut buf = LinearMap::new();
 
     for x in self.iter() {
@@ -163,6 +187,9 @@ impl ExternalInterfaces {
         ModuleDescriptor::ImportDescriptor(ImportDescriptor::Global(_)) => {
           buf_global.push(x.clone());
         }
+        ModuleDescriptor::ImportDescriptor(ImportDescriptor::Tag(_)) => {
+          buf_tag.push(x.clone());
+        }
         y => unimplemented!("{:?}", y),
       };
     }
@@ -178,6 +205,9 @@ impl ExternalInterfaces {
     buf
       .insert(ModuleDescriptorKind::Global, buf_global)
       .map_err(|_| Trap::LinearMapOverflowed)?;
+    buf
+      .insert(ModuleDescriptorKind::Tag, buf_tag)
+      .map_err(|_| Trap::LinearMapOverflowed)?;
     Ok(buf)
   }
 }
@@ -190,30 +220,44 @@ impl Default for ExternalInterfaces {
 
 #[derive(Debug)]
 pub struct InternalModule {
-  exports: ExternalInterfaces,
   pub start: Option<Indice>,
+  pub(crate) names: Names,
+  // NOTE: Indexed by functions defined in the module, excluding imported ones.
+  pub(crate) source_maps: Vec<SourceMap>,
+  // NOTE: Stores which imported functions are evaluated in, kept alive as long as the instance.
+  // Stores don't hold each other, as stores importing from each other would never be freed.
+  linked_stores: Vec<Store>,
 }
 
 impl InternalModule {
-  pub fn new(exports: ExternalInterfaces, start: Option<u32>) -> Self {
+  pub fn new(
+    start: Option<u32>,
+    names: Names,
+    source_maps: Vec<SourceMap>,
+    linked_stores: Vec<Store>,
+  ) -> Self {
     InternalModule {
-      exports,
       start: start.map(Indice::from),
+      names,
+      source_maps,
+      linked_stores,
     }
   }
-
-  pub fn get_export_by_key(&self, invoke: &str) -> Option<&ExternalInterface> {
-    self.exports.0.iter().find(|x| x.name == invoke)
-  }
 }
 
 #[derive(Debug, Clone)]
 pub struct ExternalModule {
   pub function_instances: Vec<FunctionInstance>,
   function_types: Vec<FunctionType>,
-  pub(crate) memory_instances: MemoryInstances,
+  memory_instances: MemoryInstances,
   table_instances: TableInstances,
   global_instances: GlobalInstances,
+  tag_instances: Vec<TagInstance>,
+  // NOTE: Export section of the instantiated module, which resolves names to the instances.
+  // Host modules have none, so that their instances are found by their own names.
+  exports: Option<ExternalInterfaces>,
+  // NOTE: Keeps the store alive, which exported functions are evaluated in.
+  store: Option<Store>,
 }
 
 impl ExternalModule {
@@ -230,9 +274,33 @@ impl ExternalModule {
       memory_instances: MemoryInstances::new(memory_instances),
       table_instances: TableInstances::new(table_instances),
       global_instances: GlobalInstances::new(global_instances),
+      tag_instances: vec![],
+      exports: None,
+      store: None,
     }
   }
 
+  /// Module which exports only the shared memory by the name.
+  #[cfg(feature = "std")]
+  pub fn from_shared_memory(name: &str, shared_memory: SharedMemory) -> Self {
+    let memory_instance = MemoryInstance::from_shared(shared_memory, Some(name.to_owned()));
+    ExternalModule::new(vec![], vec![], vec![memory_instance], vec![], vec![])
+  }
+
+  /// Tags are given separately, as most of host modules have nothing to do with exceptions.
+  pub fn with_tag_instances(mut self, tag_instances: Vec<TagInstance>) -> Self {
+    self.tag_instances = tag_instances;
+    self
+  }
+
+  pub fn tag_instances(&self) -> &[TagInstance] {
+    &self.tag_instances
+  }
+
+  fn find_export(&self, name: &str) -> Option<Option<&ExportDescriptor>> {
+    self.exports.as_ref().map(|exports| exports.find_export(name))
+  }
+
   // FIXME: Consider to rename import-function-instance
   fn find_function_instance(
     &self,
@@ -243,21 +311,25 @@ impl ExternalModule {
       ExternalInterface {
         descriptor: ModuleDescriptor::ImportDescriptor(ImportDescriptor::Function(idx)),
         name,
-        module_name,
+        ..
       } => {
         let expected_type = function_types.get(idx.to_usize())?;
-        let instance = self
-          .function_instances
-          .iter()
-          .find(|instance| instance.is_same_name(name))
-          .ok_or(Trap::UnknownImport)
-          .map(|x| x.clone())?;
+        let instance = match self.find_export(name) {
+          Some(Some(ExportDescriptor::Function(idx))) => {
+            self.function_instances.get(idx.to_usize()).cloned()
+          }
+          Some(_) => None,
+          None => self
+            .function_instances
+            .iter()
+            .find(|instance| instance.is_same_name(name))
+            .cloned(),
+        }
+        .ok_or(Trap::UnknownImport)?;
 
         instance
           .validate_type(expected_type)
           .map_err(|_| Trap::IncompatibleImportType)?;
-
-        instance.set_source_module_name(module_name);
         Ok(instance)
       }
       x => unreachable!("Expected function descriptor, got {:?}", x),
@@ -267,24 +339,119 @@ impl ExternalModule {
   fn find_table_instance(
     &self,
     key: &ExternalInterface, // import section of table
-  ) -> Result<TableInstances> {
+  ) -> Result<TableInstance> {
     match key {
       ExternalInterface {
         descriptor: ModuleDescriptor::ImportDescriptor(ImportDescriptor::Table(table_type)),
         name,
         ..
       } => {
-        if !self.table_instances.find_by_name(name) {
-          return Err(WasmError::Trap(Trap::UnknownImport));
+        let table_instance = match self.find_export(name) {
+          Some(Some(ExportDescriptor::Table(idx))) => self.table_instances.get_table_at(idx),
+          Some(_) => None,
+          None => self.table_instances.find_by_name(name),
         }
-        if self.table_instances.gt_table_type(table_type) {
+        .ok_or(Trap::UnknownImport)?;
+        let actual_table_type = table_instance.table_type();
+        if actual_table_type.element_type != table_type.element_type
+          || &actual_table_type > table_type
+        {
           return Err(WasmError::Trap(Trap::IncompatibleImportType));
         }
-        Ok(self.table_instances.clone())
+        Ok(table_instance)
       }
       x => unreachable!("Expected table descriptor, got {:?}", x),
     }
   }
+
+  fn find_memory_instance(
+    &self,
+    key: &ExternalInterface, // import section of memory
+  ) -> Result<MemoryInstance> {
+    match key {
+      ExternalInterface {
+        descriptor: ModuleDescriptor::ImportDescriptor(ImportDescriptor::Memory(memory_type)),
+        name,
+        ..
+      } => {
+        let memory_instance = match self.find_export(name) {
+          Some(Some(ExportDescriptor::Memory(idx))) => {
+            self.memory_instances.get_memory_at(idx.to_u32())
+          }
+          Some(_) => None,
+          None => self.memory_instances.find_by_name(name),
+        }
+        .ok_or(Trap::UnknownImport)?;
+        let limit = &memory_type.limit;
+        if memory_instance.limit().is_shared() != limit.is_shared()
+          || memory_instance.page_size() != memory_type.page_size
+          || memory_instance.limit_gt(limit)
+        {
+          return Err(WasmError::Trap(Trap::IncompatibleImportType));
+        }
+        Ok(memory_instance)
+      }
+      x => unreachable!("Expected memory descriptor, got {:?}", x),
+    }
+  }
+
+  fn find_tag_instance(
+    &self,
+    key: &ExternalInterface, // import section of tag
+    function_types: &[FunctionType],
+  ) -> Result<TagInstance> {
+    match key {
+      ExternalInterface {
+        descriptor: ModuleDescriptor::ImportDescriptor(ImportDescriptor::Tag(idx)),
+        name,
+        ..
+      } => {
+        let expected_type = function_types.get(idx.to_usize())?;
+        let tag_instance = match self.find_export(name) {
+          Some(Some(ExportDescriptor::Tag(idx))) => self.tag_instances.get(idx.to_usize()).cloned(),
+          Some(_) => None,
+          None => self
+            .tag_instances
+            .iter()
+            .find(|instance| instance.is_same_name(name))
+            .cloned(),
+        }
+        .ok_or(Trap::UnknownImport)?;
+        if tag_instance.function_type() != expected_type {
+          return Err(WasmError::Trap(Trap::IncompatibleImportType));
+        }
+        Ok(tag_instance)
+      }
+      x => unreachable!("Expected tag descriptor, got {:?}", x),
+    }
+  }
+
+  fn find_global_instance(
+    &self,
+    key: &ExternalInterface, // import section of global
+  ) -> Result<GlobalInstance> {
+    match key {
+      ExternalInterface {
+        descriptor: ModuleDescriptor::ImportDescriptor(ImportDescriptor::Global(global_type)),
+        name,
+        ..
+      } => {
+        let global_instance = match self.find_export(name) {
+          Some(Some(ExportDescriptor::Global(idx))) => {
+            self.global_instances.get_global_instance(idx)
+          }
+          Some(_) => None,
+          None => self.global_instances.find(name),
+        }
+        .ok_or(Trap::UnknownImport)?;
+        if !global_instance.is_same_type(global_type) {
+          return Err(WasmError::Trap(Trap::IncompatibleImportType));
+        }
+        Ok(global_instance)
+      }
+      x => unreachable!("Expected global descriptor, got {:?}", x),
+    }
+  }
 }
 
 impl Default for ExternalModule {
@@ -295,18 +462,25 @@ impl Default for ExternalModule {
       memory_instances: MemoryInstances::empty(),
       table_instances: TableInstances::empty(),
       global_instances: GlobalInstances::empty(),
+      tag_instances: vec![],
+      exports: None,
+      store: None,
     }
   }
 }
 
-impl From<&Store> for ExternalModule {
-  fn from(store: &Store) -> Self {
+// NOTE: Instances are the same ones as the module refers in the store.
+impl From<&ModuleRef> for ExternalModule {
+  fn from(module: &ModuleRef) -> Self {
     ExternalModule {
-      function_instances: store.function_instances.clone(),
-      function_types: store.function_types.clone(),
-      memory_instances: store.memory_instances.clone(),
-      table_instances: store.table_instances.clone(),
-      global_instances: store.global_instances.clone(),
+      function_instances: module.function_instances(),
+      function_types: module.function_types(),
+      memory_instances: MemoryInstances::new(module.memory_instances()),
+      table_instances: TableInstances::new(module.table_instances()),
+      global_instances: GlobalInstances::new(module.global_instances()),
+      tag_instances: module.tag_instances(),
+      exports: Some(module.exports()),
+      store: Some(module.store()),
     }
   }
 }
@@ -398,16 +572,16 @@ impl ExternalModules {
       .find_function_instance(import, function_types)
   }
 
-  pub fn find_memory_instances(&self, import: &ExternalInterface) -> Result<MemoryInstances> {
+  pub fn find_memory_instance(&self, import: &ExternalInterface) -> Result<MemoryInstance> {
     self
       .0
       .borrow()
       .get(&import.module_name)
-      .ok_or(WasmError::Trap(Trap::UnknownImport))
-      .map(|x| x.memory_instances.clone())
+      .ok_or(Trap::UnknownImport)?
+      .find_memory_instance(import)
   }
 
-  pub fn find_table_instances(&self, import: &ExternalInterface) -> Result<TableInstances> {
+  pub fn find_table_instance(&self, import: &ExternalInterface) -> Result<TableInstance> {
     self
       .0
       .borrow()
@@ -416,12 +590,37 @@ impl ExternalModules {
       .find_table_instance(import)
   }
 
-  pub fn find_global_instances(&self, module_name: &ModuleName) -> Result<GlobalInstances> {
+  pub fn find_tag_instance(
+    &self,
+    import: &ExternalInterface,
+    function_types: &[FunctionType],
+  ) -> Result<TagInstance> {
+    self
+      .0
+      .borrow()
+      .get(&import.module_name)
+      .ok_or(Trap::UnknownImport)?
+      .find_tag_instance(import, function_types)
+  }
+
+  pub fn get_tag_instance(&self, module_name: &ModuleName, idx: u32) -> Result<TagInstance> {
     self
       .0
       .borrow()
       .get(module_name)
-      .ok_or(WasmError::Trap(Trap::UnknownImport))
-      .map(|x| x.global_instances.clone())
+      .ok_or(Trap::UnknownImport)?
+      .tag_instances
+      .get(idx as usize)
+      .cloned()
+      .ok_or(WasmError::Trap(Trap::Notfound))
+  }
+
+  pub fn find_global_instance(&self, import: &ExternalInterface) -> Result<GlobalInstance> {
+    self
+      .0
+      .borrow()
+      .get(&import.module_name)
+      .ok_or(Trap::UnknownImport)?
+      .find_global_instance(import)
   }
 }
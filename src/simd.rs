@@ -0,0 +1,366 @@
+use isa::{Half, Shape, SimdBinary, SimdUnary};
+use value::Values;
+
+// NOTE: Lanes are handled as bits zero extended into u64, so that
+// each of instructions is implemented in portable way without SIMD of host.
+fn mask(width: u32) -> u128 {
+  (1 << width) - 1
+}
+
+fn lane(v: u128, width: u32, idx: u32) -> u64 {
+  ((v >> (idx * width)) & mask(width)) as u64
+}
+
+fn with_lane(v: u128, width: u32, idx: u32, bits: u64) -> u128 {
+  let shift = idx * width;
+  (v & !(mask(width) << shift)) | ((u128::from(bits) & mask(width)) << shift)
+}
+
+fn from_lanes<F: Fn(u32) -> u64>(width: u32, f: F) -> u128 {
+  (0..128 / width).fold(0, |v, idx| with_lane(v, width, idx, f(idx)))
+}
+
+fn extend(bits: u64, width: u32, sign: bool) -> i64 {
+  if sign {
+    let shift = 64 - width;
+    ((bits << shift) as i64) >> shift
+  } else {
+    bits as i64
+  }
+}
+
+fn saturate(value: i64, width: u32, sign: bool) -> u64 {
+  let (min, max) = if sign {
+    (-(1 << (width - 1)), (1 << (width - 1)) - 1)
+  } else {
+    (0, (1 << width) - 1)
+  };
+  value.max(min).min(max) as u64
+}
+
+fn to_float(bits: u64, width: u32) -> f64 {
+  if width == 32 {
+    f64::from(f32::from_bits(bits as u32))
+  } else {
+    f64::from_bits(bits)
+  }
+}
+
+fn to_value(shape: Shape, bits: u64, sign: bool) -> Values {
+  match shape {
+    Shape::I8x16 | Shape::I16x8 | Shape::I32x4 => {
+      Values::I32(extend(bits, shape.lane_width(), sign) as i32)
+    }
+    Shape::I64x2 => Values::I64(bits as i64),
+    Shape::F32x4 => Values::F32(f32::from_bits(bits as u32)),
+    Shape::F64x2 => Values::F64(f64::from_bits(bits)),
+  }
+}
+
+fn to_bits(value: &Values) -> u64 {
+  match value {
+    Values::I32(v) => u64::from(*v as u32),
+    Values::I64(v) => *v as u64,
+    Values::F32(v) => u64::from(v.to_bits()),
+    Values::F64(v) => v.to_bits(),
+    x => unreachable!("Expected numeric value, got {:?}", x),
+  }
+}
+
+// NOTE: Floats are delegated to scalar instructions, to share handling of NaN and rounding.
+fn map_values<F: Fn(Values) -> Values>(shape: Shape, v: u128, f: F) -> u128 {
+  let width = shape.lane_width();
+  from_lanes(width, |idx| {
+    to_bits(&f(to_value(shape, lane(v, width, idx), true)))
+  })
+}
+
+fn zip_values<F: Fn(Values, Values) -> Values>(shape: Shape, a: u128, b: u128, f: F) -> u128 {
+  let width = shape.lane_width();
+  from_lanes(width, |idx| {
+    let l = to_value(shape, lane(a, width, idx), true);
+    let r = to_value(shape, lane(b, width, idx), true);
+    to_bits(&f(l, r))
+  })
+}
+
+fn zip_integers<F: Fn(i64, i64) -> u64>(shape: Shape, sign: bool, a: u128, b: u128, f: F) -> u128 {
+  let width = shape.lane_width();
+  from_lanes(width, |idx| {
+    f(
+      extend(lane(a, width, idx), width, sign),
+      extend(lane(b, width, idx), width, sign),
+    )
+  })
+}
+
+fn compare<F: Fn(i64, i64) -> bool, G: Fn(f64, f64) -> bool>(
+  shape: Shape,
+  sign: bool,
+  a: u128,
+  b: u128,
+  f: F,
+  g: G,
+) -> u128 {
+  let width = shape.lane_width();
+  from_lanes(width, |idx| {
+    let (l, r) = (lane(a, width, idx), lane(b, width, idx));
+    let is_truthy = if shape.is_float() {
+      g(to_float(l, width), to_float(r, width))
+    } else {
+      f(extend(l, width, sign), extend(r, width, sign))
+    };
+    if is_truthy {
+      !0
+    } else {
+      0
+    }
+  })
+}
+
+// NOTE: Index of the first lane of source, which is narrower than the one of result.
+fn half_offset(shape: Shape, half: Half) -> u32 {
+  match half {
+    Half::Low => 0,
+    Half::High => shape.lanes(),
+  }
+}
+
+pub fn splat(shape: Shape, value: &Values) -> u128 {
+  let bits = to_bits(value);
+  from_lanes(shape.lane_width(), |_| bits)
+}
+
+pub fn extract_lane(v: u128, shape: Shape, sign: bool, idx: u32) -> Values {
+  to_value(shape, lane(v, shape.lane_width(), idx), sign)
+}
+
+pub fn replace_lane(v: u128, shape: Shape, idx: u32, value: &Values) -> u128 {
+  with_lane(v, shape.lane_width(), idx, to_bits(value))
+}
+
+// NOTE: Bits of memory are placed into a lane as they are.
+pub fn replace_lane_bits(v: u128, width: u32, idx: u32, bits: u64) -> u128 {
+  with_lane(v, width, idx, bits)
+}
+
+pub fn lane_bits(v: u128, width: u32, idx: u32) -> u64 {
+  lane(v, width, idx)
+}
+
+pub fn splat_bits(width: u32, bits: u64) -> u128 {
+  from_lanes(width, |_| bits)
+}
+
+pub fn shuffle(a: u128, b: u128, indices: u128) -> u128 {
+  from_lanes(8, |idx| match lane(indices, 8, idx) as u32 {
+    i if i < 16 => lane(a, 8, i),
+    i => lane(b, 8, i - 16),
+  })
+}
+
+pub fn bitselect(a: u128, b: u128, c: u128) -> u128 {
+  (a & c) | (b & !c)
+}
+
+pub fn shift_left(shape: Shape, v: u128, amount: u32) -> u128 {
+  let width = shape.lane_width();
+  let amount = amount % width;
+  from_lanes(width, |idx| lane(v, width, idx) << amount)
+}
+
+pub fn shift_right(shape: Shape, sign: bool, v: u128, amount: u32) -> u128 {
+  let width = shape.lane_width();
+  let amount = amount % width;
+  from_lanes(width, |idx| {
+    (extend(lane(v, width, idx), width, sign) >> amount) as u64
+  })
+}
+
+pub fn all_true(shape: Shape, v: u128) -> bool {
+  let width = shape.lane_width();
+  (0..shape.lanes()).all(|idx| lane(v, width, idx) != 0)
+}
+
+pub fn bitmask(shape: Shape, v: u128) -> u32 {
+  let width = shape.lane_width();
+  (0..shape.lanes()).fold(0, |bits, idx| {
+    bits | ((lane(v, width, idx) >> (width - 1)) as u32) << idx
+  })
+}
+
+pub fn unary(op: &SimdUnary, v: u128) -> u128 {
+  use self::SimdUnary::*;
+  match op {
+    Not => !v,
+    Abs(shape) if shape.is_float() => {
+      let width = shape.lane_width();
+      from_lanes(width, |idx| lane(v, width, idx) & !(1 << (width - 1)))
+    }
+    Abs(shape) => {
+      let width = shape.lane_width();
+      from_lanes(width, |idx| {
+        extend(lane(v, width, idx), width, true).wrapping_abs() as u64
+      })
+    }
+    Neg(shape) if shape.is_float() => {
+      let width = shape.lane_width();
+      from_lanes(width, |idx| lane(v, width, idx) ^ (1 << (width - 1)))
+    }
+    Neg(shape) => {
+      let width = shape.lane_width();
+      from_lanes(width, |idx| lane(v, width, idx).wrapping_neg())
+    }
+    Popcnt => from_lanes(8, |idx| u64::from(lane(v, 8, idx).count_ones())),
+    Sqrt(shape) => map_values(*shape, v, |x| x.sqrt()),
+    Ceil(shape) => map_values(*shape, v, |x| x.ceil()),
+    Floor(shape) => map_values(*shape, v, |x| x.floor()),
+    Trunc(shape) => map_values(*shape, v, |x| x.trunc()),
+    Nearest(shape) => map_values(*shape, v, |x| x.nearest()),
+    ExtaddPairwise(shape, sign) => {
+      let width = shape.lane_width();
+      let source = width / 2;
+      from_lanes(width, |idx| {
+        let l = extend(lane(v, source, idx * 2), source, *sign);
+        let r = extend(lane(v, source, idx * 2 + 1), source, *sign);
+        (l + r) as u64
+      })
+    }
+    Extend(shape, half, sign) => {
+      let (width, offset) = (shape.lane_width(), half_offset(*shape, *half));
+      from_lanes(width, |idx| {
+        extend(lane(v, width / 2, offset + idx), width / 2, *sign) as u64
+      })
+    }
+    TruncSatF32x4(sign) => map_values(Shape::F32x4, v, |x| {
+      if *sign {
+        x.trunc_sat_f32_to_sign_i32()
+      } else {
+        x.trunc_sat_f32_to_unsign_i32()
+      }
+    }),
+    TruncSatF64x2Zero(sign) => from_lanes(32, |idx| match idx {
+      0 | 1 => {
+        let x = Values::F64(f64::from_bits(lane(v, 64, idx)));
+        to_bits(&if *sign {
+          x.trunc_sat_f64_to_sign_i32()
+        } else {
+          x.trunc_sat_f64_to_unsign_i32()
+        })
+      }
+      _ => 0,
+    }),
+    ConvertI32x4(sign) => map_values(Shape::I32x4, v, |x| {
+      if *sign {
+        x.convert_sign_i32_to_f32()
+      } else {
+        x.convert_unsign_i32_to_f32()
+      }
+    }),
+    ConvertLowI32x4(sign) => from_lanes(64, |idx| {
+      let x = Values::I32(lane(v, 32, idx) as u32 as i32);
+      to_bits(&if *sign {
+        x.convert_sign_i32_to_f64()
+      } else {
+        x.convert_unsign_i32_to_f64()
+      })
+    }),
+    DemoteF64x2Zero => from_lanes(32, |idx| match idx {
+      0 | 1 => to_bits(&Values::F64(f64::from_bits(lane(v, 64, idx))).demote_f64_to_f32()),
+      _ => 0,
+    }),
+    PromoteLowF32x4 => from_lanes(64, |idx| {
+      to_bits(&Values::F32(f32::from_bits(lane(v, 32, idx) as u32)).promote_f32_to_f64())
+    }),
+  }
+}
+
+pub fn binary(op: &SimdBinary, a: u128, b: u128) -> u128 {
+  use self::SimdBinary::*;
+  match op {
+    And => a & b,
+    AndNot => a & !b,
+    Or => a | b,
+    Xor => a ^ b,
+    Swizzle => from_lanes(8, |idx| match lane(b, 8, idx) as u32 {
+      i if i < 16 => lane(a, 8, i),
+      _ => 0,
+    }),
+    Eq(shape) => compare(*shape, false, a, b, |l, r| l == r, |l, r| l == r),
+    Ne(shape) => compare(*shape, false, a, b, |l, r| l != r, |l, r| l != r),
+    Lt(shape, sign) => compare(*shape, *sign, a, b, |l, r| l < r, |l, r| l < r),
+    Gt(shape, sign) => compare(*shape, *sign, a, b, |l, r| l > r, |l, r| l > r),
+    Le(shape, sign) => compare(*shape, *sign, a, b, |l, r| l <= r, |l, r| l <= r),
+    Ge(shape, sign) => compare(*shape, *sign, a, b, |l, r| l >= r, |l, r| l >= r),
+    Add(shape) if shape.is_float() => zip_values(*shape, a, b, |l, r| l.add(&r)),
+    Add(shape) => zip_integers(*shape, false, a, b, |l, r| l.wrapping_add(r) as u64),
+    Sub(shape) if shape.is_float() => zip_values(*shape, a, b, |l, r| l.sub(&r)),
+    Sub(shape) => zip_integers(*shape, false, a, b, |l, r| l.wrapping_sub(r) as u64),
+    Mul(shape) if shape.is_float() => zip_values(*shape, a, b, |l, r| l.mul(&r)),
+    Mul(shape) => zip_integers(*shape, false, a, b, |l, r| l.wrapping_mul(r) as u64),
+    Div(shape) => zip_values(*shape, a, b, |l, r| l.div_f(&r)),
+    AddSat(shape, sign) => {
+      let width = shape.lane_width();
+      zip_integers(*shape, *sign, a, b, |l, r| saturate(l + r, width, *sign))
+    }
+    SubSat(shape, sign) => {
+      let width = shape.lane_width();
+      zip_integers(*shape, *sign, a, b, |l, r| saturate(l - r, width, *sign))
+    }
+    Min(shape, _) if shape.is_float() => zip_values(*shape, a, b, |l, r| l.min(&r)),
+    Min(shape, sign) => zip_integers(*shape, *sign, a, b, |l, r| l.min(r) as u64),
+    Max(shape, _) if shape.is_float() => zip_values(*shape, a, b, |l, r| l.max(&r)),
+    Max(shape, sign) => zip_integers(*shape, *sign, a, b, |l, r| l.max(r) as u64),
+    Pmin(shape) => {
+      let width = shape.lane_width();
+      from_lanes(width, |idx| {
+        let (l, r) = (lane(a, width, idx), lane(b, width, idx));
+        if to_float(r, width) < to_float(l, width) {
+          r
+        } else {
+          l
+        }
+      })
+    }
+    Pmax(shape) => {
+      let width = shape.lane_width();
+      from_lanes(width, |idx| {
+        let (l, r) = (lane(a, width, idx), lane(b, width, idx));
+        if to_float(l, width) < to_float(r, width) {
+          r
+        } else {
+          l
+        }
+      })
+    }
+    Avgr(shape) => zip_integers(*shape, false, a, b, |l, r| ((l + r + 1) / 2) as u64),
+    Narrow(shape, sign) => {
+      let width = shape.lane_width();
+      let (source, lanes) = (width * 2, shape.lanes() / 2);
+      from_lanes(width, |idx| {
+        let x = if idx < lanes {
+          lane(a, source, idx)
+        } else {
+          lane(b, source, idx - lanes)
+        };
+        saturate(extend(x, source, true), width, *sign)
+      })
+    }
+    Q15MulrSat => zip_integers(Shape::I16x8, true, a, b, |l, r| {
+      saturate((l * r + 0x4000) >> 15, 16, true)
+    }),
+    Dot => from_lanes(32, |idx| {
+      let product = |idx: u32| extend(lane(a, 16, idx), 16, true) * extend(lane(b, 16, idx), 16, true);
+      (product(idx * 2) + product(idx * 2 + 1)) as u64
+    }),
+    ExtMul(shape, half, sign) => {
+      let (width, offset) = (shape.lane_width(), half_offset(*shape, *half));
+      let source = width / 2;
+      from_lanes(width, |idx| {
+        let l = extend(lane(a, source, offset + idx), source, *sign);
+        let r = extend(lane(b, source, offset + idx), source, *sign);
+        l.wrapping_mul(r) as u64
+      })
+    }
+  }
+}
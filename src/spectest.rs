@@ -1,16 +1,18 @@
 #[cfg(not(test))]
 use alloc::prelude::*;
+use caller::Caller;
 use decode::{ElementType, TableType};
+use error::Trap;
 use function::{FunctionInstance, FunctionType};
-use global::{GlobalInstance, GlobalInstances, GlobalType};
+use global::{GlobalInstance, GlobalType};
 use memory::{Limit, MemoryInstance};
 use module::ExternalModule;
 use table::TableInstance;
 use value::Values;
 use value_type::{TYPE_F32, TYPE_F64, TYPE_I32};
 
-fn host_function(_values: &[Values]) -> Vec<Values> {
-  vec![]
+fn host_function(_caller: &Caller, _values: &[Values]) -> Result<Vec<Values>, Trap> {
+  Ok(vec![])
 }
 
 pub fn create_spectest() -> ExternalModule {
@@ -19,57 +21,50 @@ pub fn create_spectest() -> ExternalModule {
       FunctionInstance::new_host_fn(
         Some("print".to_owned()),
         FunctionType::new(vec![], vec![]),
-        &host_function,
+        host_function,
       ),
       // 4
       FunctionInstance::new_host_fn(
         Some("print_i32".to_owned()),
         FunctionType::new(vec![TYPE_I32], vec![]),
-        &host_function,
+        host_function,
       ),
       // 5
       FunctionInstance::new_host_fn(
         Some("print_i32_f32".to_owned()),
         FunctionType::new(vec![TYPE_I32, TYPE_F32], vec![]),
-        &host_function,
+        host_function,
       ),
       // 6
       FunctionInstance::new_host_fn(
         Some("print_f64_f64".to_owned()),
         FunctionType::new(vec![TYPE_F64, TYPE_F64], vec![]),
-        &host_function,
+        host_function,
       ),
       // 2
       FunctionInstance::new_host_fn(
         Some("print_f32".to_owned()),
         FunctionType::new(vec![TYPE_F32], vec![]),
-        &host_function,
+        host_function,
       ),
       // 3
       FunctionInstance::new_host_fn(
         Some("print_f64".to_owned()),
         FunctionType::new(vec![TYPE_F64], vec![]),
-        &host_function,
+        host_function,
       ),
     ],
     vec![],
     // MemoryInstances
     vec![MemoryInstance::new(
-      vec![],
       Limit::HasUpperLimit(1, 2),
       Some("memory".to_owned()),
-      &GlobalInstances::empty(),
-    )
-    .unwrap()],
+    )],
     // TableInstances
     vec![TableInstance::new(
-      vec![],
       TableType::new(ElementType::AnyFunc, Limit::HasUpperLimit(10, 20)),
       Some("table".to_owned()),
-      &GlobalInstances::empty(),
-      &[],
-    )
-    .unwrap()],
+    )],
     // GlobalInstances
     vec![
       GlobalInstance::new(
@@ -2,12 +2,13 @@
 use alloc::prelude::*;
 use alloc::rc::Rc;
 use alloc::vec::Vec;
-use core::cell::{Cell, RefCell};
+use core::cell::{Cell, Ref, RefCell};
 use core::fmt;
 use error::{Result, Trap, WasmError};
 use frame::Frame;
 use indice::Indice;
-use label::{Label, LabelKind};
+use label::{Handler, Label, LabelKind};
+use store::ModuleRef;
 use value::Values;
 use value_type::ValueTypes;
 
@@ -48,13 +49,32 @@ impl StackEntry {
 
   pub fn new_label(
     continuation: u32,
-    return_type: ValueTypes,
+    parameter_type: Vec<ValueTypes>,
+    return_type: Vec<ValueTypes>,
     source_instruction: LabelKind,
+  ) -> Self {
+    StackEntry::new_label_with_handler(
+      continuation,
+      parameter_type,
+      return_type,
+      source_instruction,
+      None,
+    )
+  }
+
+  pub(crate) fn new_label_with_handler(
+    continuation: u32,
+    parameter_type: Vec<ValueTypes>,
+    return_type: Vec<ValueTypes>,
+    source_instruction: LabelKind,
+    handler: Option<Handler>,
   ) -> Self {
     StackEntry::new(StackEntryImpl::Label(Label {
       continuation,
+      parameter_type,
       return_type,
       source_instruction,
+      handler,
     }))
   }
 
@@ -212,6 +232,20 @@ impl Stack {
     calls.pop()
   }
 
+  // NOTE: While a frame is evaluated, its caller is placed at top of call stack.
+  pub(crate) fn get_caller_module(&self) -> Option<ModuleRef> {
+    self
+      .call_stack
+      .borrow()
+      .last()
+      .and_then(|frame| frame.function_instance.module())
+  }
+
+  // NOTE: Callers of a frame being evaluated, innermost at last.
+  pub(crate) fn frames(&self) -> Ref<Vec<Frame>> {
+    self.call_stack.borrow()
+  }
+
   pub fn call_stack_is_empty(&self) -> bool {
     let calls = self.call_stack.borrow();
     calls.is_empty()
@@ -273,38 +307,33 @@ impl Stack {
       buf_values.append(&mut bufs);
       label = Some(self.pop_label_ext());
     }
-    let continuation = match label {
-      Some(Label {
-        return_type: ValueTypes::Unit,
-        continuation,
-        ..
-      }) => continuation,
-      Some(Label {
-        continuation,
-        source_instruction,
-        ..
-      }) => {
-        match source_instruction {
-          LabelKind::Loop => {
-            self.push_entries(&mut buf_values)?;
-          }
-          _ => {
-            // FIXME: Prefer to pop and push with count of return_types.
-            let return_val = buf_values
-              .first()
-              .expect("At least one return value should exists.")
-              .to_owned();
-            self.push(return_val)?;
-          }
-        };
-        continuation
+    match label {
+      Some(label) => {
+        // NOTE: Values are buffered from top of the stack,
+        // so that leading entries are what the branch carries.
+        buf_values.truncate(label.arity());
+        self.push_entries(&mut buf_values)?;
+        Ok(label.continuation)
       }
       x => unreachable!("At least one label should exists.\n{:?}", x),
-    };
-    Ok(continuation)
+    }
+  }
+
+  // NOTE: Find a label without unwinding the stack.
+  pub(crate) fn get_label(&self, depth_of_label: &Indice) -> Option<Label> {
+    self.operand_stack.borrow()[..self.stack_ptr()]
+      .iter()
+      .rev()
+      .filter_map(|entry| match *entry.0 {
+        StackEntryImpl::Label(ref label) => Some(label),
+        _ => None,
+      })
+      .nth(depth_of_label.to_usize())
+      .cloned()
   }
 
   impl_pop_value_ext!(pop_value_ext_i32, Values::I32, i32);
+  impl_pop_value_ext!(pop_value_ext_v128, Values::V128, u128);
 
   pub fn update_frame_ptr(&self, frame: &Frame) {
     self.stack_ptr.set(self.frame_ptr());
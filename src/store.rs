@@ -1,74 +1,215 @@
+use alloc::rc::{Rc, Weak};
 use alloc::vec::Vec;
+use core::cell::RefCell;
 use core::default::Default;
-use error::Result;
 use function::{FunctionInstance, FunctionType};
-use global::GlobalInstances;
-use indice::Indice;
-use memory::MemoryInstances;
-use table::{TableInstance, TableInstances};
-use value::Values;
+use global::GlobalInstance;
+use limiter::{Limiter, ResourceLimiter};
+use memory::{DataInstances, MemoryInstance};
+use module::{ExportDescriptor, ExternalInterfaces};
+use table::{ElementInstances, TableInstance};
+use tag::TagInstance;
 
+// NOTE: Addresses of instances in a store, as the specification defines.
+pub type FuncAddr = usize;
+pub type TableAddr = usize;
+pub type MemAddr = usize;
+pub type GlobalAddr = usize;
+pub type TagAddr = usize;
+pub type ModuleAddr = usize;
+
+// NOTE: Index spaces of a module instance, which map indices to addresses.
+// Segments are never shared between modules, so that the module owns them.
 #[derive(Debug)]
-pub struct Store {
-  pub function_instances: Vec<FunctionInstance>,
-  pub function_types: Vec<FunctionType>,
-  pub memory_instances: MemoryInstances,
-  pub table_instances: TableInstances,
-  pub global_instances: GlobalInstances,
+pub(crate) struct ModuleContext {
+  pub(crate) function_types: Vec<FunctionType>,
+  pub(crate) function_addrs: Vec<FuncAddr>,
+  pub(crate) table_addrs: Vec<TableAddr>,
+  pub(crate) memory_addrs: Vec<MemAddr>,
+  pub(crate) global_addrs: Vec<GlobalAddr>,
+  pub(crate) tag_addrs: Vec<TagAddr>,
+  pub(crate) data_instances: DataInstances,
+  pub(crate) element_instances: ElementInstances,
+  pub(crate) exports: ExternalInterfaces,
 }
 
-impl Store {
-  pub fn new(
-    function_instances: Vec<FunctionInstance>,
-    function_types: Vec<FunctionType>,
-    memory_instances: MemoryInstances,
-    table_instances: TableInstances,
-    global_instances: GlobalInstances,
-  ) -> Self {
-    Store {
-      function_instances,
-      function_types,
-      memory_instances,
-      table_instances,
-      global_instances,
-    }
-  }
+#[derive(Debug)]
+struct StoreImpl {
+  function_instances: Vec<FunctionInstance>,
+  table_instances: Vec<TableInstance>,
+  memory_instances: Vec<MemoryInstance>,
+  global_instances: Vec<GlobalInstance>,
+  tag_instances: Vec<TagInstance>,
+  modules: Vec<ModuleContext>,
+  limiter: Limiter,
+}
 
-  pub fn get_function_instance(&self, fn_idx: &Indice) -> Option<FunctionInstance> {
-    self.function_instances.get(fn_idx.to_usize()).cloned()
-  }
+/// Instances of modules which are instantiated in it.
+/// Modules instantiated in the same store refer to instances they import by the same addresses.
+#[derive(Debug, Clone)]
+pub struct Store(Rc<RefCell<StoreImpl>>);
 
-  pub fn get_function_type(&self, idx: &Indice) -> Option<&FunctionType> {
-    self.function_types.get(idx.to_usize())
-  }
+macro_rules! impl_allocate {
+  ($name: ident, $instances: ident, $ty: ty) => {
+    // NOTE: Imported instances keep their addresses if they have already been allocated.
+    pub(crate) fn $name(&self, instances: &[$ty], count_of_imports: usize) -> Vec<usize> {
+      let mut store = self.0.borrow_mut();
+      let mut addrs = vec![];
+      for (idx, instance) in instances.iter().enumerate() {
+        let allocated = if idx < count_of_imports {
+          store.$instances.iter().position(|x| x.ptr_eq(instance))
+        } else {
+          None
+        };
+        let addr = match allocated {
+          Some(addr) => addr,
+          None => {
+            store.$instances.push(instance.clone());
+            store.$instances.len() - 1
+          }
+        };
+        addrs.push(addr);
+      }
+      addrs
+    }
+  };
+}
 
-  pub fn get_function_type_by_instance(&self, idx: &Indice) -> Option<FunctionType> {
+impl Store {
+  /// Memories and tables defined in modules are allocated and grow under the limiter.
+  pub fn with_resource_limiter<T: ResourceLimiter + 'static>(self, limiter: T) -> Self {
+    self.0.borrow_mut().limiter = Limiter::new(limiter);
     self
-      .get_function_instance(idx)
-      .map(|x| x.get_function_type())
   }
 
-  pub fn get_global(&self, idx: &Indice) -> Result<Values> {
-    self.global_instances.get_global(idx)
+  pub(crate) fn limiter(&self) -> Limiter {
+    self.0.borrow().limiter.clone()
   }
 
-  pub fn set_global(&mut self, idx: &Indice, value: Values) {
-    self.global_instances.set_global(idx, value)
+  impl_allocate!(allocate_functions, function_instances, FunctionInstance);
+  impl_allocate!(allocate_tables, table_instances, TableInstance);
+  impl_allocate!(allocate_memories, memory_instances, MemoryInstance);
+  impl_allocate!(allocate_globals, global_instances, GlobalInstance);
+  impl_allocate!(allocate_tags, tag_instances, TagInstance);
+
+  pub(crate) fn allocate_module(&self, module: ModuleContext) -> ModuleRef {
+    let mut store = self.0.borrow_mut();
+    store.modules.push(module);
+    ModuleRef {
+      store: self.clone(),
+      addr: store.modules.len() - 1,
+    }
   }
 
-  pub fn get_table_at(&self, idx: &Indice) -> Option<TableInstance> {
-    self.table_instances.get_table_at(idx)
+  pub(crate) fn ptr_eq(&self, other: &Store) -> bool {
+    Rc::ptr_eq(&self.0, &other.0)
   }
 }
 
 impl Default for Store {
   fn default() -> Self {
-    Store {
+    Store(Rc::new(RefCell::new(StoreImpl {
       function_instances: Vec::new(),
-      function_types: Vec::new(),
-      memory_instances: MemoryInstances::empty(),
-      table_instances: TableInstances::empty(),
-      global_instances: GlobalInstances::empty(),
+      table_instances: Vec::new(),
+      memory_instances: Vec::new(),
+      global_instances: Vec::new(),
+      tag_instances: Vec::new(),
+      modules: Vec::new(),
+      limiter: Limiter::default(),
+    })))
+  }
+}
+
+macro_rules! impl_resolve {
+  ($name: ident, $all: ident, $addrs: ident, $instances: ident, $ty: ty) => {
+    pub(crate) fn $name(&self, idx: u32) -> Option<$ty> {
+      let store = self.store.0.borrow();
+      let addr = store.modules[self.addr].$addrs.get(idx as usize)?;
+      store.$instances.get(*addr).cloned()
     }
+
+    pub(crate) fn $all(&self) -> Vec<$ty> {
+      let store = self.store.0.borrow();
+      store.modules[self.addr]
+        .$addrs
+        .iter()
+        .map(|addr| store.$instances[*addr].clone())
+        .collect()
+    }
+  };
+}
+
+// NOTE: Module instance in a store, which functions defined in it are evaluated with.
+#[derive(Debug, Clone)]
+pub(crate) struct ModuleRef {
+  store: Store,
+  addr: ModuleAddr,
+}
+
+impl ModuleRef {
+  impl_resolve!(
+    function_instance,
+    function_instances,
+    function_addrs,
+    function_instances,
+    FunctionInstance
+  );
+  impl_resolve!(table_instance, table_instances, table_addrs, table_instances, TableInstance);
+  impl_resolve!(memory_instance, memory_instances, memory_addrs, memory_instances, MemoryInstance);
+  impl_resolve!(global_instance, global_instances, global_addrs, global_instances, GlobalInstance);
+  impl_resolve!(tag_instance, tag_instances, tag_addrs, tag_instances, TagInstance);
+
+  pub(crate) fn function_type(&self, idx: u32) -> Option<FunctionType> {
+    let store = self.store.0.borrow();
+    store.modules[self.addr].function_types.get(idx as usize).cloned()
+  }
+
+  pub(crate) fn function_types(&self) -> Vec<FunctionType> {
+    self.store.0.borrow().modules[self.addr].function_types.clone()
+  }
+
+  // NOTE: Exports map names to indices of the module, rather than to addresses.
+  pub(crate) fn export(&self, name: &str) -> Option<ExportDescriptor> {
+    let store = self.store.0.borrow();
+    store.modules[self.addr].exports.find_export(name).cloned()
+  }
+
+  pub(crate) fn exports(&self) -> ExternalInterfaces {
+    self.store.0.borrow().modules[self.addr].exports.clone()
+  }
+
+  pub(crate) fn data_instances(&self) -> DataInstances {
+    self.store.0.borrow().modules[self.addr].data_instances.clone()
+  }
+
+  pub(crate) fn element_instances(&self) -> ElementInstances {
+    self.store.0.borrow().modules[self.addr].element_instances.clone()
+  }
+
+  pub(crate) fn store(&self) -> Store {
+    self.store.clone()
+  }
+
+  pub(crate) fn downgrade(&self) -> WeakModuleRef {
+    WeakModuleRef {
+      store: Rc::downgrade(&self.store.0),
+      addr: self.addr,
+    }
+  }
+}
+
+// NOTE: Held by functions, as the store owns them in turn.
+#[derive(Debug, Clone)]
+pub(crate) struct WeakModuleRef {
+  store: Weak<RefCell<StoreImpl>>,
+  addr: ModuleAddr,
+}
+
+impl WeakModuleRef {
+  pub(crate) fn upgrade(&self) -> Option<ModuleRef> {
+    Some(ModuleRef {
+      store: Store(self.store.upgrade()?),
+      addr: self.addr,
+    })
   }
 }
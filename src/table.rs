@@ -5,77 +5,159 @@ use alloc::string::String;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 use core::clone::Clone;
-use decode::{Element, TableType};
+use decode::{Element, ElementMode, TableType};
 use error::{Result, Trap, WasmError};
 use function::FunctionInstance;
 use global::GlobalInstances;
 use indice::Indice;
 use isa::Isa;
+use limiter::Limiter;
 use memory::Limit;
+use value::Values;
+use value_type::ValueTypes;
 
-#[derive(Debug, Clone)]
-pub struct TableInstance {
-  pub(crate) function_elements: Vec<Option<FunctionInstance>>,
-  pub(crate) export_name: Option<String>,
+// NOTE: Upper bound of elements of a table which doesn't declare its maximum.
+const MAXIMUM_TABLE_SIZE: u32 = 10_000_000;
+
+#[derive(Debug)]
+struct TableInstanceImpl {
+  elements: Vec<Values>,
+  export_name: Option<String>,
   table_type: TableType,
+  limiter: Limiter,
 }
 
+#[derive(Debug, Clone)]
+pub struct TableInstance(Rc<RefCell<TableInstanceImpl>>);
+
 impl TableInstance {
-  pub fn new(
-    elements: Vec<Element>,
+  pub fn new(table_type: TableType, export_name: Option<String>) -> Self {
+    TableInstance::new_with_limiter(table_type, export_name, Limiter::default())
+      .expect("Allocation without limiter must not fail")
+  }
+
+  pub(crate) fn new_with_limiter(
     table_type: TableType,
     export_name: Option<String>,
-    global_instances: &GlobalInstances,
-    function_instances: &[FunctionInstance],
+    limiter: Limiter,
   ) -> Result<Self> {
-    let table_size = match table_type.limit {
-      Limit::NoUpperLimit(min) | Limit::HasUpperLimit(min, _) => min,
-    } as usize;
-    let mut function_elements = vec![None; table_size];
-    for el in elements.into_iter() {
-      let offset = Isa::constant_expression(&el.offset, global_instances)?;
-      let mut function_addresses = el.wrap_by_option(function_instances);
-      let end = offset + function_addresses.len();
-      if end > function_elements.len() {
-        return Err(WasmError::Trap(Trap::ElementSegmentDoesNotFit));
-      }
-      function_addresses.swap_with_slice(&mut function_elements[offset..end]);
-    }
-    Ok(TableInstance {
-      function_elements,
+    let (table_size, declared_maximum) = match table_type.limit {
+      Limit::NoUpperLimit(min) => (min, None),
+      Limit::HasUpperLimit(min, max) | Limit::Shared(min, max) => (min, Some(max)),
+    };
+    limiter.allocate(|limiter| limiter.table_growing(0, table_size, declared_maximum))?;
+    let null = Values::from(ValueTypes::from(&table_type.element_type));
+    Ok(TableInstance(Rc::new(RefCell::new(TableInstanceImpl {
+      elements: vec![null; table_size as usize],
       export_name,
       table_type,
-    })
+      limiter,
+    }))))
   }
 
-  pub fn validate(
-    elements: &[Element],
-    table_type: &TableType,
-    global_instances: &GlobalInstances,
-    function_instances: &[FunctionInstance],
-  ) -> Result<()> {
-    let table_size = match table_type.limit {
-      Limit::NoUpperLimit(min) | Limit::HasUpperLimit(min, _) => min,
-    } as usize;
-    for el in elements.iter() {
-      let offset = Isa::constant_expression(&el.offset, global_instances)?;
-      let mut function_addresses = el.wrap_by_option(function_instances);
-      let end = offset + function_addresses.len();
-      if end > table_size {
-        return Err(WasmError::Trap(Trap::ElementSegmentDoesNotFit));
-      }
+  pub fn len(&self) -> usize {
+    self.0.borrow().elements.len()
+  }
+
+  // NOTE: Minimum of limit reflects the current size, as it may be grown.
+  pub fn table_type(&self) -> TableType {
+    let table = self.0.borrow();
+    let size = table.elements.len() as u32;
+    let limit = match table.table_type.limit {
+      Limit::NoUpperLimit(_) => Limit::NoUpperLimit(size),
+      Limit::HasUpperLimit(_, max) | Limit::Shared(_, max) => Limit::HasUpperLimit(size, max),
+    };
+    TableType::new(table.table_type.element_type.clone(), limit)
+  }
+
+  fn is_same_name(&self, name: &str) -> bool {
+    self.0.borrow().export_name == Some(name.to_owned())
+  }
+
+  pub(crate) fn ptr_eq(&self, other: &TableInstance) -> bool {
+    Rc::ptr_eq(&self.0, &other.0)
+  }
+
+  fn range_of(&self, offset: u32, length: u32) -> Result<(usize, usize)> {
+    let start = offset as usize;
+    let end = start + length as usize;
+    if end > self.len() {
+      return Err(WasmError::Trap(Trap::TableAccessOutOfBounds));
+    }
+    Ok((start, end))
+  }
+
+  pub fn get(&self, idx: u32) -> Result<Values> {
+    self
+      .0
+      .borrow()
+      .elements
+      .get(idx as usize)
+      .cloned()
+      .ok_or(WasmError::Trap(Trap::TableAccessOutOfBounds))
+  }
+
+  pub fn set(&self, idx: u32, value: Values) -> Result<()> {
+    let (start, _) = self.range_of(idx, 1)?;
+    self.0.borrow_mut().elements[start] = value;
+    Ok(())
+  }
+
+  // NOTE: Returns the previous size, or None when the table couldn't grow.
+  pub fn grow(&self, delta: u32, init: Values) -> Option<u32> {
+    let mut table = self.0.borrow_mut();
+    let size = table.elements.len() as u32;
+    let declared_maximum = match table.table_type.limit {
+      Limit::NoUpperLimit(_) => None,
+      Limit::HasUpperLimit(_, max) | Limit::Shared(_, max) => Some(max),
+    };
+    let new_size = size.checked_add(delta)?;
+    if new_size > declared_maximum.unwrap_or(MAXIMUM_TABLE_SIZE)
+      || !table.limiter.table_growing(size, new_size, declared_maximum)
+    {
+      return None;
+    }
+    table.elements.resize(new_size as usize, init);
+    Some(size)
+  }
+
+  pub fn fill(&self, offset: u32, value: Values, length: u32) -> Result<()> {
+    let (start, end) = self.range_of(offset, length)?;
+    for element in self.0.borrow_mut().elements[start..end].iter_mut() {
+      *element = value.clone();
     }
     Ok(())
   }
 
-  pub fn len(&self) -> usize {
-    self.function_elements.len()
+  pub fn copy(
+    &self,
+    destination: u32,
+    source: &TableInstance,
+    offset: u32,
+    length: u32,
+  ) -> Result<()> {
+    let (src_start, src_end) = source.range_of(offset, length)?;
+    let (dst_start, _) = self.range_of(destination, length)?;
+    let references = source.0.borrow().elements[src_start..src_end].to_vec();
+    self.write(dst_start, references);
+    Ok(())
+  }
+
+  pub fn init(&self, destination: u32, references: &[Values]) -> Result<()> {
+    let (start, _) = self.range_of(destination, references.len() as u32)?;
+    self.write(start, references.to_vec());
+    Ok(())
+  }
+
+  fn write(&self, start: usize, mut references: Vec<Values>) {
+    let end = start + references.len();
+    references.swap_with_slice(&mut self.0.borrow_mut().elements[start..end]);
   }
 
   pub fn get_function_instance(&self, idx: u32) -> Result<FunctionInstance> {
-    match self.function_elements.get(idx as usize) {
-      Some(Some(x)) => Ok(x.clone()),
-      Some(None) => Err(WasmError::Trap(Trap::UninitializedElement)),
+    match self.0.borrow().elements.get(idx as usize) {
+      Some(Values::FuncRef(Some(x))) => Ok(x.clone()),
+      Some(_) => Err(WasmError::Trap(Trap::UninitializedElement)),
       None => Err(WasmError::Trap(Trap::UndefinedElement)),
     }
   }
@@ -93,19 +175,13 @@ impl TableInstances {
     TableInstances::new(vec![])
   }
 
-  pub fn find_by_name(&self, name: &str) -> bool {
-    match self.0.borrow().first() {
-      Some(table_instance) => table_instance.export_name == Some(name.to_owned()),
-      None => false,
-    }
-  }
-
-  // NOTE: It represents `self.table_type > other_table_type`
-  pub fn gt_table_type(&self, other: &TableType) -> bool {
-    match self.0.borrow().first() {
-      Some(table_instance) => &table_instance.table_type > other,
-      None => false,
-    }
+  pub fn find_by_name(&self, name: &str) -> Option<TableInstance> {
+    self
+      .0
+      .borrow()
+      .iter()
+      .find(|table_instance| table_instance.is_same_name(name))
+      .cloned()
   }
 
   pub fn get_table_at(&self, idx: &Indice) -> Option<TableInstance> {
@@ -113,40 +189,43 @@ impl TableInstances {
     table_instances.get(idx.to_usize()).cloned()
   }
 
+  fn active_elements<'a>(
+    &'a self,
+    elements: &'a [Element],
+  ) -> impl Iterator<Item = Result<(TableInstance, &'a Element)>> + 'a {
+    elements.iter().filter(|el| el.is_active()).map(move |el| {
+      let table_instance = self.get_table_at(el.get_table_idx()?)?;
+      Ok((table_instance, el))
+    })
+  }
+
+  fn offset_of(element: &Element, global_instances: &GlobalInstances) -> Result<usize> {
+    match &element.mode {
+      ElementMode::Active(_, offset) => Isa::constant_expression(offset, global_instances),
+      _ => unreachable!("Expected active element segment"),
+    }
+  }
+
   pub fn link(
     &self,
     elements: &[Element],
     global_instances: &GlobalInstances,
     function_instances: &[FunctionInstance],
   ) -> Result<()> {
-    let mut table_instances = self.0.borrow_mut();
-    let table_instance = table_instances.first_mut()?;
-    let function_elements = &mut table_instance.function_elements;
-
-    for el in elements.iter() {
-      let offset = Isa::constant_expression(&el.offset, global_instances)?;
-      let mut function_addresses = el.wrap_by_option(function_instances);
-      let end = offset + function_addresses.len();
-      function_addresses.swap_with_slice(&mut function_elements[offset..end]);
+    for x in self.active_elements(elements) {
+      let (table_instance, el) = x?;
+      let offset = TableInstances::offset_of(el, global_instances)?;
+      let references = el.references(global_instances, function_instances)?;
+      table_instance.init(offset as u32, &references)?;
     }
     Ok(())
   }
 
-  pub fn validate(
-    &self,
-    elements: &[Element],
-    global_instances: &GlobalInstances,
-    function_instances: &[FunctionInstance],
-  ) -> Result<()> {
-    let mut table_instances = self.0.borrow_mut();
-    let table_instance = table_instances.first_mut()?;
-    let function_elements = &mut table_instance.function_elements;
-
-    for el in elements.iter() {
-      let offset = Isa::constant_expression(&el.offset, global_instances)?;
-      let mut function_addresses = el.wrap_by_option(function_instances);
-      let end = offset + function_addresses.len();
-      if end > function_elements.len() {
+  pub fn validate(&self, elements: &[Element], global_instances: &GlobalInstances) -> Result<()> {
+    for x in self.active_elements(elements) {
+      let (table_instance, el) = x?;
+      let offset = TableInstances::offset_of(el, global_instances)?;
+      if offset + el.len() > table_instance.len() {
         return Err(WasmError::Trap(Trap::ElementSegmentDoesNotFit));
       }
     }
@@ -159,3 +238,48 @@ impl Clone for TableInstances {
     TableInstances(self.0.clone())
   }
 }
+
+// NOTE: Passive element segments of a module, which are emptied once dropped.
+#[derive(Debug, Clone)]
+pub struct ElementInstances(Rc<RefCell<Vec<Vec<Values>>>>);
+
+impl ElementInstances {
+  pub fn new(
+    elements: &[Element],
+    global_instances: &GlobalInstances,
+    function_instances: &[FunctionInstance],
+  ) -> Result<Self> {
+    let element_instances = elements
+      .iter()
+      .map(|el| match el.mode {
+        ElementMode::Passive => el.references(global_instances, function_instances),
+        _ => Ok(vec![]),
+      })
+      .collect::<Result<Vec<_>>>()?;
+    Ok(ElementInstances(Rc::new(RefCell::new(element_instances))))
+  }
+
+  pub fn init(
+    &self,
+    table_instance: &TableInstance,
+    idx: u32,
+    destination: u32,
+    source: u32,
+    length: u32,
+  ) -> Result<()> {
+    let element_instances = self.0.borrow();
+    let references = element_instances.get(idx as usize)?;
+    let start = source as usize;
+    let end = start + length as usize;
+    if end > references.len() {
+      return Err(WasmError::Trap(Trap::TableAccessOutOfBounds));
+    }
+    table_instance.init(destination, &references[start..end])
+  }
+
+  pub fn drop_element(&self, idx: u32) -> Result<()> {
+    let mut element_instances = self.0.borrow_mut();
+    *element_instances.get_mut(idx as usize)? = vec![];
+    Ok(())
+  }
+}
@@ -0,0 +1,81 @@
+#[cfg(not(test))]
+use alloc::prelude::*;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use function::FunctionType;
+use value::Values;
+
+#[derive(Debug)]
+struct TagInstanceImpl {
+  function_type: FunctionType,
+  export_name: Option<String>,
+}
+
+// NOTE: Tags are distinguished by their instances, even though they have the same type.
+#[derive(Clone)]
+pub struct TagInstance(Rc<TagInstanceImpl>);
+
+impl TagInstance {
+  /// Tag whose exceptions carry values of parameters of the type.
+  pub fn new(function_type: FunctionType, export_name: Option<String>) -> Self {
+    TagInstance(Rc::new(TagInstanceImpl {
+      function_type,
+      export_name,
+    }))
+  }
+
+  pub fn function_type(&self) -> &FunctionType {
+    &self.0.function_type
+  }
+
+  pub fn name(&self) -> Option<&String> {
+    self.0.export_name.as_ref()
+  }
+
+  pub(crate) fn is_same_name(&self, name: &str) -> bool {
+    self.0.export_name == Some(name.to_owned())
+  }
+
+  pub(crate) fn ptr_eq(&self, other: &TagInstance) -> bool {
+    Rc::ptr_eq(&self.0, &other.0)
+  }
+}
+
+impl PartialEq for TagInstance {
+  fn eq(&self, other: &TagInstance) -> bool {
+    Rc::ptr_eq(&self.0, &other.0)
+  }
+}
+
+impl fmt::Debug for TagInstance {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.name() {
+      Some(name) => write!(f, "tag {} {:?}", name, self.function_type()),
+      None => write!(f, "tag {:?}", self.function_type()),
+    }
+  }
+}
+
+/// Exception thrown by `throw` or host function,
+/// which is handed to embedder through `Trap::Exception` unless it is caught.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exception {
+  tag: TagInstance,
+  values: Vec<Values>,
+}
+
+impl Exception {
+  pub fn new(tag: TagInstance, values: Vec<Values>) -> Self {
+    Exception { tag, values }
+  }
+
+  pub fn tag(&self) -> &TagInstance {
+    &self.tag
+  }
+
+  pub fn values(&self) -> &[Values] {
+    &self.values
+  }
+}
@@ -3,19 +3,48 @@ use alloc::collections::VecDeque;
 use alloc::prelude::*;
 use alloc::vec::Vec;
 use core::cell::{Cell, RefCell};
-use decode::{Data, Element, Module, TableType};
+use core::convert::TryFrom;
+use decode::{Data, Element, ElementInit, ElementMode, ElementType, Module, TableType};
 use error::{Result, TypeError, WasmError};
 use function::FunctionType;
 use global::GlobalType;
 use indice::Indice;
-use isa::Isa;
-use memory::Limit;
+use isa::{AtomicIsa, AtomicRmw, Isa, MiscIsa, SimdIsa};
+use label::LabelKind;
+use memory::{Limit, MemoryType, PAGE_SIZE};
 use module::{
   ExportDescriptor, ExternalInterface, ExternalInterfaces, ImportDescriptor, ModuleDescriptor,
 };
-use value_type::{ValueTypes, TYPE_F32, TYPE_F64, TYPE_I32, TYPE_I64};
+use value_type::{
+  BlockType, ValueTypes, BLOCK_TYPE_INDEX, TYPE_F32, TYPE_F64, TYPE_I32, TYPE_I64,
+};
+
+type ResultType = Vec<ValueTypes>;
+
+#[derive(Debug, Clone)]
+struct LabelType {
+  source_instruction: LabelKind,
+  parameters: ResultType,
+  returns: ResultType,
+}
+
+impl LabelType {
+  fn new(source_instruction: LabelKind, parameters: ResultType, returns: ResultType) -> Self {
+    LabelType {
+      source_instruction,
+      parameters,
+      returns,
+    }
+  }
 
-type ResultType = [ValueTypes; 1];
+  // NOTE: Branch to loop re-enters it, so that it takes parameters instead of results.
+  fn branch(&self) -> &ResultType {
+    match self.source_instruction {
+      LabelKind::Loop => &self.parameters,
+      _ => &self.returns,
+    }
+  }
+}
 
 #[derive(Debug, Clone)]
 enum Entry {
@@ -54,12 +83,28 @@ impl TypeStack {
     }
   }
 
-  fn pop_until_label(&self) -> Result<Vec<Entry>> {
-    let mut buf = Vec::new();
-    while let Some(Entry::Type(ty)) = self.pop() {
-      buf.push(Entry::Type(ty));
+  fn pop_types(&self, expect: &[ValueTypes]) -> Result<()> {
+    for ty in expect.iter().rev() {
+      if &self.pop_type()? != ty {
+        return Err(WasmError::TypeError(TypeError::TypeMismatch));
+      }
+    }
+    Ok(())
+  }
+
+  // NOTE: Results of a block must be the only values left above its label.
+  fn pop_results(&self, expect: &[ValueTypes]) -> Result<()> {
+    self.pop_types(expect)?;
+    match self.pop() {
+      Some(Entry::Label) => Ok(()),
+      _ => Err(WasmError::TypeError(TypeError::TypeMismatch)),
+    }
+  }
+
+  fn push_types(&self, types: &[ValueTypes]) {
+    for ty in types.iter() {
+      self.push(ty.clone());
     }
-    Ok(buf)
   }
 
   fn pop_i32(&self) -> Result<ValueTypes> {
@@ -100,10 +145,10 @@ impl<'a> Function<'a> {
     self.body.get(ptr)
   }
 
-  fn pop_value_type(&self) -> Option<ValueTypes> {
-    match self.pop() {
-      Some(byte) => Some(ValueTypes::from(*byte)),
-      _ => None,
+  fn pop_block_type(&self) -> Result<BlockType> {
+    match *self.pop()? {
+      BLOCK_TYPE_INDEX => Ok(BlockType::Index(self.pop_raw_u32()?)),
+      byte => Ok(BlockType::Value(ValueTypes::from(byte))),
     }
   }
 
@@ -124,6 +169,14 @@ impl<'a> Function<'a> {
     let idx = unsafe { core::mem::transmute::<_, u64>(buf) };
     Ok(idx)
   }
+
+  fn pop_raw_u128(&self) -> Result<u128> {
+    let mut buf = [0; 16];
+    for i in 0..buf.len() {
+      buf[i] = *self.pop()?;
+    }
+    Ok(unsafe { core::mem::transmute::<_, u128>(buf) })
+  }
 }
 
 pub struct Context<'a> {
@@ -132,13 +185,19 @@ pub struct Context<'a> {
   exports: &'a ExternalInterfaces,
   imports: &'a ExternalInterfaces,
   datas: &'a Vec<Data>,
-  limits: &'a Vec<Limit>,
-  tables: &'a Vec<TableType>,
+  data_count: &'a Option<u32>,
+  // NOTE: Imported memories and tables precede ones defined in the module.
+  limits: Vec<MemoryType>,
+  tables: Vec<TableType>,
   globals: &'a Vec<(GlobalType, Vec<u8>)>,
+  // NOTE: Types of globals, including imported ones.
+  global_types: Vec<GlobalType>,
   elements: &'a Vec<Element>,
+  // NOTE: Indices of function types of tags, including imported ones.
+  tags: Vec<u32>,
   start: &'a Option<u32>,
   locals: RefCell<Vec<ValueTypes>>,
-  labels: RefCell<VecDeque<ResultType>>,
+  labels: RefCell<VecDeque<LabelType>>,
   return_type: RefCell<ResultType>,
 }
 
@@ -176,7 +235,7 @@ impl<'a> Context<'a> {
           let idx = module.functions.get(idx).map(|n| Indice::from(*n))?;
           let function_type = module.function_types.get(idx.to_usize())?;
           let (body, locals) = match code {
-            Ok((body, locals)) => Ok((body, locals)),
+            Ok((body, locals, _)) => Ok((body, locals)),
             Err(ref err) => Err(err.to_owned()),
           }?;
           Ok(Function::new(function_type, locals, body))
@@ -185,15 +244,52 @@ impl<'a> Context<'a> {
       exports: &module.exports,
       imports: &module.imports,
       datas: &module.datas,
+      data_count: &module.data_count,
       globals: &module.globals,
-      tables: &module.tables,
+      global_types: module
+        .imports
+        .iter()
+        .filter_map(|import| match &import.descriptor {
+          ModuleDescriptor::ImportDescriptor(ImportDescriptor::Global(ty)) => Some(ty.clone()),
+          _ => None,
+        })
+        .chain(module.globals.iter().map(|(ty, _)| ty.clone()))
+        .collect(),
+      tables: module
+        .imports
+        .iter()
+        .filter_map(|import| match &import.descriptor {
+          ModuleDescriptor::ImportDescriptor(ImportDescriptor::Table(ty)) => Some(ty.clone()),
+          _ => None,
+        })
+        .chain(module.tables.iter().cloned())
+        .collect(),
       elements: &module.elements,
-      limits: &module.limits,
+      tags: module
+        .imports
+        .iter()
+        .filter_map(|import| match &import.descriptor {
+          ModuleDescriptor::ImportDescriptor(ImportDescriptor::Tag(idx)) => Some(idx.to_u32()),
+          _ => None,
+        })
+        .chain(module.tags.iter().cloned())
+        .collect(),
+      limits: module
+        .imports
+        .iter()
+        .filter_map(|import| match &import.descriptor {
+          ModuleDescriptor::ImportDescriptor(ImportDescriptor::Memory(memory_type)) => {
+            Some(memory_type.clone())
+          }
+          _ => None,
+        })
+        .chain(module.limits.iter().cloned())
+        .collect(),
       start: &module.start,
 
       locals: RefCell::new(Vec::new()),
       labels: RefCell::new(VecDeque::new()),
-      return_type: RefCell::new([ValueTypes::Unit; 1]),
+      return_type: RefCell::new(Vec::new()),
     })
   }
 
@@ -222,18 +318,36 @@ impl<'a> Context<'a> {
         }
         Isa::GetGlobal => {
           let mut buf = [0; 4];
-          for i in 0..buf.len() {
-            idx += 1;
-            buf[i] = expr[idx];
-          }
+          buf.clone_from_slice(&expr[idx..idx + 4]);
+          idx += 4;
           let idx = Indice::from(unsafe { core::mem::transmute::<_, u32>(buf) });
-          match self.globals.get(idx.to_usize()) {
-            Some((GlobalType::Const(ty), _)) | Some((GlobalType::Var(ty), _)) => {
-              type_stack.push(ty.clone())
-            }
+          match self.global_types.get(idx.to_usize()) {
+            Some(GlobalType::Const(ty)) | Some(GlobalType::Var(ty)) => type_stack.push(ty.clone()),
             _ => return Err(WasmError::TypeError(TypeError::ConstantExpressionRequired)),
           }
         }
+        Isa::SimdPrefix => {
+          let mut buf = [0; 4];
+          buf.clone_from_slice(&expr[idx..idx + 4]);
+          idx += 4;
+          let code = unsafe { core::mem::transmute::<_, u32>(buf) };
+          if SimdIsa::try_from(code)? != SimdIsa::Const {
+            return Err(WasmError::TypeError(TypeError::ConstantExpressionRequired));
+          }
+          idx += 16;
+          type_stack.push(ValueTypes::V128);
+        }
+        Isa::RefNull => {
+          type_stack.push(ValueTypes::from(expr[idx]));
+          idx += 1;
+        }
+        Isa::RefFunc => {
+          let mut buf = [0; 4];
+          buf.clone_from_slice(&expr[idx..idx + 4]);
+          idx += 4;
+          self.validate_function_index(unsafe { core::mem::transmute::<_, u32>(buf) })?;
+          type_stack.push(ValueTypes::FuncRef);
+        }
         Isa::End => {
           break;
         }
@@ -252,8 +366,11 @@ impl<'a> Context<'a> {
         .limits
         .get(*memidx as usize)
         .ok_or(TypeError::UnknownMemory)?;
-      if ValueTypes::I32 != self.validate_constant(offset)? {
-        return Err(WasmError::TypeError(TypeError::TypeMismatch));
+      match offset {
+        Some(offset) if ValueTypes::I32 != self.validate_constant(offset)? => {
+          return Err(WasmError::TypeError(TypeError::TypeMismatch));
+        }
+        _ => {}
       }
     }
     Ok(())
@@ -261,24 +378,32 @@ impl<'a> Context<'a> {
 
   fn validate_elements(&self) -> Result<()> {
     for Element {
-      table_idx,
-      offset,
+      mode,
+      element_type,
       init,
     } in self.elements.iter()
     {
-      if self.tables.get(table_idx.to_usize()).is_none() {
-        return Err(WasmError::TypeError(TypeError::UnknownTable(
-          table_idx.to_u32(),
-        )));
-      }
-      if ValueTypes::I32 != self.validate_constant(offset)? {
-        return Err(WasmError::TypeError(TypeError::TypeMismatch));
+      if let ElementMode::Active(table_idx, offset) = mode {
+        if &self.table_type(table_idx.to_u32())?.element_type != element_type {
+          return Err(WasmError::TypeError(TypeError::TypeMismatch));
+        }
+        if ValueTypes::I32 != self.validate_constant(offset)? {
+          return Err(WasmError::TypeError(TypeError::TypeMismatch));
+        }
       }
-      for i in init.iter() {
-        self
-          .functions
-          .get(i.to_usize())
-          .ok_or_else(|| TypeError::UnknownFunction(i.to_u32()))?;
+      match init {
+        ElementInit::Functions(xs) => {
+          for i in xs.iter() {
+            self.validate_function_index(i.to_u32())?;
+          }
+        }
+        ElementInit::Expressions(xs) => {
+          for x in xs.iter() {
+            if ValueTypes::from(element_type) != self.validate_constant(x)? {
+              return Err(WasmError::TypeError(TypeError::TypeMismatch));
+            }
+          }
+        }
       }
     }
     Ok(())
@@ -311,6 +436,28 @@ impl<'a> Context<'a> {
           Isa::GetGlobal => {
             return Err(WasmError::TypeError(TypeError::ConstantExpressionRequired));
           }
+          Isa::SimdPrefix => {
+            let mut buf = [0; 4];
+            buf.clone_from_slice(&init[idx..idx + 4]);
+            idx += 4;
+            let code = unsafe { core::mem::transmute::<_, u32>(buf) };
+            if SimdIsa::try_from(code)? != SimdIsa::Const {
+              return Err(WasmError::TypeError(TypeError::ConstantExpressionRequired));
+            }
+            idx += 16;
+            type_stack.push(ValueTypes::V128);
+          }
+          Isa::RefNull => {
+            type_stack.push(ValueTypes::from(init[idx]));
+            idx += 1;
+          }
+          Isa::RefFunc => {
+            let mut buf = [0; 4];
+            buf.clone_from_slice(&init[idx..idx + 4]);
+            idx += 4;
+            self.validate_function_index(unsafe { core::mem::transmute::<_, u32>(buf) })?;
+            type_stack.push(ValueTypes::FuncRef);
+          }
           Isa::End => {
             break;
           }
@@ -360,10 +507,16 @@ impl<'a> Context<'a> {
         }
         ModuleDescriptor::ExportDescriptor(ExportDescriptor::Global(x)) => {
           self
-            .globals
+            .global_types
             .get(x.to_usize())
             .ok_or_else(|| TypeError::UnknownGlobal(x.to_u32()))?;
         }
+        ModuleDescriptor::ExportDescriptor(ExportDescriptor::Tag(x)) => {
+          self
+            .tags
+            .get(x.to_usize())
+            .ok_or_else(|| TypeError::UnknownTag(x.to_u32()))?;
+        }
         _ => unreachable!(),
       };
       names.push(name);
@@ -376,8 +529,6 @@ impl<'a> Context<'a> {
   }
 
   fn validate_imports(&self) -> Result<()> {
-    let mut tables = Vec::new();
-    let mut memories = Vec::new();
     for ExternalInterface { descriptor, .. } in self.imports.iter() {
       match descriptor {
         ModuleDescriptor::ImportDescriptor(ImportDescriptor::Function(x)) => {
@@ -386,59 +537,62 @@ impl<'a> Context<'a> {
             .get(x.to_usize())
             .ok_or_else(|| TypeError::UnknownFunction(x.to_u32()))?;
         }
-        ModuleDescriptor::ImportDescriptor(ImportDescriptor::Table(ty)) => {
-          if !self.tables.is_empty() {
-            return Err(WasmError::TypeError(TypeError::MultipleTables));
-          }
-          tables.push(ty);
-        }
-        ModuleDescriptor::ImportDescriptor(ImportDescriptor::Memory(limit)) => {
-          if !self.limits.is_empty() {
-            return Err(WasmError::TypeError(TypeError::MultipleMemories));
-          }
-          memories.push(limit);
-        }
+        ModuleDescriptor::ImportDescriptor(ImportDescriptor::Table(_ty)) => {}
+        ModuleDescriptor::ImportDescriptor(ImportDescriptor::Memory(_limit)) => {}
         ModuleDescriptor::ImportDescriptor(ImportDescriptor::Global(_ty)) => {}
+        ModuleDescriptor::ImportDescriptor(ImportDescriptor::Tag(_idx)) => {}
         _ => unreachable!(),
       };
     }
-    if tables.len() > 1 {
-      return Err(WasmError::TypeError(TypeError::MultipleTables));
-    }
-    if memories.len() > 1 {
-      return Err(WasmError::TypeError(TypeError::MultipleMemories));
-    }
-    Ok(())
-  }
-
-  fn validate_tables(&self) -> Result<()> {
-    if self.tables.len() > 1 {
-      return Err(WasmError::TypeError(TypeError::MultipleTables));
-    }
     Ok(())
   }
 
+  // NOTE: Memory can't be larger than 4GiB, which is 65536 pages by default.
   fn validate_memories(&self) -> Result<()> {
-    for limit in self.limits.iter() {
+    for MemoryType { limit, page_size } in self.limits.iter() {
+      if *page_size != 1 && *page_size != PAGE_SIZE {
+        return Err(WasmError::TypeError(TypeError::InvalidPageSize));
+      }
+      let maximum = (1u64 << 32) / u64::from(*page_size);
       match limit {
         Limit::NoUpperLimit(min) => {
-          if *min > 65536 {
+          if u64::from(*min) > maximum {
             return Err(WasmError::TypeError(TypeError::InvalidMemorySize));
           }
         }
-        Limit::HasUpperLimit(min, max) => {
-          if min > max || *min > 65536 || *max > 65536 {
+        Limit::HasUpperLimit(min, max) | Limit::Shared(min, max) => {
+          if min > max || u64::from(*max) > maximum {
             return Err(WasmError::TypeError(TypeError::InvalidMemorySize));
           }
         }
       }
     }
-    if self.limits.len() > 1 {
-      return Err(WasmError::TypeError(TypeError::MultipleMemories));
+    Ok(())
+  }
+
+  // NOTE: Exceptions carry parameters of the type, and never return.
+  fn validate_tags(&self) -> Result<()> {
+    for idx in 0..self.tags.len() {
+      if !self.tag_type(idx as u32)?.returns().is_empty() {
+        return Err(WasmError::TypeError(TypeError::TypeMismatch));
+      }
     }
     Ok(())
   }
 
+  fn tag_type(&self, idx: u32) -> Result<&FunctionType> {
+    let type_idx = *self
+      .tags
+      .get(idx as usize)
+      .ok_or_else(|| TypeError::UnknownTag(idx))?;
+    Ok(
+      self
+        .function_types
+        .get(type_idx as usize)
+        .ok_or_else(|| TypeError::UnknownFunctionType(type_idx))?,
+    )
+  }
+
   fn validate_start(&self) -> Result<()> {
     if let Some(idx) = self.start {
       let func = self
@@ -453,13 +607,21 @@ impl<'a> Context<'a> {
     Ok(())
   }
 
-  fn validate_function_types(&self) -> Result<()> {
-    for fy in self.function_types.iter() {
-      if fy.returns().len() > 1 {
-        return Err(WasmError::TypeError(TypeError::InvalidResultArity));
+  fn get_block_signature(&self, block_type: BlockType) -> Result<(ResultType, ResultType)> {
+    Ok(match block_type {
+      BlockType::Value(ValueTypes::Unit) => (vec![], vec![]),
+      BlockType::Value(ty) => (vec![], vec![ty]),
+      BlockType::Index(idx) => {
+        let function_type = self
+          .function_types
+          .get(idx as usize)
+          .ok_or_else(|| TypeError::UnknownFunctionType(idx))?;
+        (
+          function_type.parameters().to_owned(),
+          function_type.returns().to_owned(),
+        )
       }
-    }
-    Ok(())
+    })
   }
 
   fn validate_functions(&self) -> Result<()> {
@@ -478,7 +640,7 @@ impl<'a> Context<'a> {
   ) -> Result<()> {
     let align = function.pop_raw_u32()?;
     let _offset = function.pop_raw_u32()?;
-    self.limits.first().ok_or(TypeError::UnknownMemory)?;
+    self.validate_memory_index(function.pop_raw_u32()?)?;
     if 2u32.pow(align) > bit_width / 8 {
       return Err(WasmError::TypeError(TypeError::InvalidAlignment));
     };
@@ -487,6 +649,81 @@ impl<'a> Context<'a> {
     Ok(())
   }
 
+  // NOTE: Instructions referring data segments are valid only when DataCount section exists.
+  fn validate_data_index(&self, idx: u32) -> Result<()> {
+    let data_count = self.data_count.ok_or(TypeError::DataCountRequired)?;
+    if idx >= data_count {
+      return Err(WasmError::TypeError(TypeError::UnknownData(idx)));
+    }
+    Ok(())
+  }
+
+  fn validate_function_index(&self, idx: u32) -> Result<()> {
+    self
+      .functions
+      .get(idx as usize)
+      .ok_or_else(|| TypeError::UnknownFunction(idx))?;
+    Ok(())
+  }
+
+  // NOTE: Function referred by `ref.func` in function bodies must be declared
+  // by element segments, exports or initializers of globals.
+  fn is_declared_function(&self, idx: u32) -> bool {
+    let is_ref_func = |expr: &[u8]| match expr.first() {
+      Some(code) if Isa::from(*code) == Isa::RefFunc => {
+        let mut buf = [0; 4];
+        buf.clone_from_slice(&expr[1..5]);
+        let function_idx = unsafe { core::mem::transmute::<_, u32>(buf) };
+        function_idx == idx
+      }
+      _ => false,
+    };
+    let in_elements = self.elements.iter().any(|el| match &el.init {
+      ElementInit::Functions(xs) => xs.iter().any(|x| x.to_u32() == idx),
+      ElementInit::Expressions(xs) => xs.iter().any(|x| is_ref_func(x)),
+    });
+    let in_exports = self.exports.iter().any(|export| match &export.descriptor {
+      ModuleDescriptor::ExportDescriptor(ExportDescriptor::Function(x)) => x.to_u32() == idx,
+      _ => false,
+    });
+    let in_globals = self.globals.iter().any(|(_, init)| is_ref_func(init));
+    in_elements || in_exports || in_globals
+  }
+
+  fn table_type(&self, idx: u32) -> Result<&TableType> {
+    Ok(
+      self
+        .tables
+        .get(idx as usize)
+        .ok_or_else(|| TypeError::UnknownTable(idx))?,
+    )
+  }
+
+  fn element_type(&self, idx: u32) -> Result<&ElementType> {
+    Ok(
+      &self
+        .elements
+        .get(idx as usize)
+        .ok_or_else(|| TypeError::UnknownElement(idx))?
+        .element_type,
+    )
+  }
+
+  fn validate_memory_index(&self, idx: u32) -> Result<()> {
+    self
+      .limits
+      .get(idx as usize)
+      .ok_or(TypeError::UnknownMemory)?;
+    Ok(())
+  }
+
+  fn validate_bulk_memory(&self, cxt: &TypeStack) -> Result<()> {
+    cxt.pop_i32()?;
+    cxt.pop_i32()?;
+    cxt.pop_i32()?;
+    Ok(())
+  }
+
   fn validate_store(
     &self,
     cxt: &TypeStack,
@@ -496,7 +733,7 @@ impl<'a> Context<'a> {
   ) -> Result<()> {
     let align = function.pop_raw_u32()?;
     let _offset = function.pop_raw_u32()?;
-    self.limits.first().ok_or(TypeError::UnknownMemory)?;
+    self.validate_memory_index(function.pop_raw_u32()?)?;
     if 2u32.pow(align) > bit_width / 8 {
       return Err(WasmError::TypeError(TypeError::InvalidAlignment));
     };
@@ -508,6 +745,118 @@ impl<'a> Context<'a> {
     Ok(())
   }
 
+  // NOTE: Atomic instructions require exactly the natural alignment.
+  fn validate_atomic(&self, cxt: &TypeStack, atomic: AtomicIsa, function: &Function) -> Result<()> {
+    if atomic == AtomicIsa::Fence {
+      return Ok(());
+    }
+    let align = function.pop_raw_u32()?;
+    let _offset = function.pop_raw_u32()?;
+    self.validate_memory_index(function.pop_raw_u32()?)?;
+    let (bit_width, parameters, result) = match atomic {
+      AtomicIsa::Notify => (32, vec![ValueTypes::I32, ValueTypes::I32], Some(ValueTypes::I32)),
+      AtomicIsa::Wait32 => (
+        32,
+        vec![ValueTypes::I32, ValueTypes::I32, ValueTypes::I64],
+        Some(ValueTypes::I32),
+      ),
+      AtomicIsa::Wait64 => (
+        64,
+        vec![ValueTypes::I32, ValueTypes::I64, ValueTypes::I64],
+        Some(ValueTypes::I32),
+      ),
+      AtomicIsa::Fence => unreachable!(),
+      AtomicIsa::Load(ty, width) => (width, vec![ValueTypes::I32], Some(ty)),
+      AtomicIsa::Store(ty, width) => (width, vec![ValueTypes::I32, ty], None),
+      AtomicIsa::Rmw(AtomicRmw::Cmpxchg, ty, width) => {
+        (width, vec![ValueTypes::I32, ty.clone(), ty.clone()], Some(ty))
+      }
+      AtomicIsa::Rmw(_, ty, width) => (width, vec![ValueTypes::I32, ty.clone()], Some(ty)),
+    };
+    if 2u32.pow(align) != bit_width / 8 {
+      return Err(WasmError::TypeError(TypeError::InvalidAlignment));
+    };
+    cxt.pop_types(&parameters)?;
+    if let Some(ty) = result {
+      cxt.push(ty);
+    }
+    Ok(())
+  }
+
+  fn validate_lane_index(&self, idx: u32, lanes: u32) -> Result<()> {
+    if idx >= lanes {
+      return Err(WasmError::TypeError(TypeError::InvalidLaneIndex));
+    }
+    Ok(())
+  }
+
+  fn validate_simd(&self, cxt: &TypeStack, simd_isa: SimdIsa, function: &Function) -> Result<()> {
+    use value_type::ValueTypes::{I32, V128};
+    // NOTE: Bit width of memory accessed, which bounds alignment.
+    let bit_width = match simd_isa {
+      SimdIsa::Load | SimdIsa::Store => Some(128),
+      SimdIsa::LoadExtend(_, _) => Some(64),
+      SimdIsa::LoadSplat(width)
+      | SimdIsa::LoadZero(width)
+      | SimdIsa::LoadLane(width)
+      | SimdIsa::StoreLane(width) => Some(width),
+      _ => None,
+    };
+    if let Some(bit_width) = bit_width {
+      let align = function.pop_raw_u32()?;
+      let _offset = function.pop_raw_u32()?;
+      self.validate_memory_index(function.pop_raw_u32()?)?;
+      if 2u32.pow(align) > bit_width / 8 {
+        return Err(WasmError::TypeError(TypeError::InvalidAlignment));
+      };
+    }
+    let (parameters, result) = match simd_isa {
+      SimdIsa::Load
+      | SimdIsa::LoadExtend(_, _)
+      | SimdIsa::LoadSplat(_)
+      | SimdIsa::LoadZero(_) => (vec![I32], Some(V128)),
+      SimdIsa::Store => (vec![I32, V128], None),
+      SimdIsa::LoadLane(width) => {
+        self.validate_lane_index(function.pop_raw_u32()?, 128 / width)?;
+        (vec![I32, V128], Some(V128))
+      }
+      SimdIsa::StoreLane(width) => {
+        self.validate_lane_index(function.pop_raw_u32()?, 128 / width)?;
+        (vec![I32, V128], None)
+      }
+      SimdIsa::Const => {
+        let _ = function.pop_raw_u128()?;
+        (vec![], Some(V128))
+      }
+      SimdIsa::Shuffle => {
+        let indices = function.pop_raw_u128()?.to_le_bytes();
+        for idx in indices.iter() {
+          self.validate_lane_index(u32::from(*idx), 32)?;
+        }
+        (vec![V128, V128], Some(V128))
+      }
+      SimdIsa::Splat(shape) => (vec![shape.scalar_type()], Some(V128)),
+      SimdIsa::ExtractLane(shape, _) => {
+        self.validate_lane_index(function.pop_raw_u32()?, shape.lanes())?;
+        (vec![V128], Some(shape.scalar_type()))
+      }
+      SimdIsa::ReplaceLane(shape) => {
+        self.validate_lane_index(function.pop_raw_u32()?, shape.lanes())?;
+        (vec![V128, shape.scalar_type()], Some(V128))
+      }
+      SimdIsa::Unary(_) => (vec![V128], Some(V128)),
+      SimdIsa::Binary(_) => (vec![V128, V128], Some(V128)),
+      SimdIsa::Bitselect => (vec![V128, V128, V128], Some(V128)),
+      SimdIsa::Shl(_) | SimdIsa::Shr(_, _) => (vec![V128, I32], Some(V128)),
+      SimdIsa::AnyTrue | SimdIsa::AllTrue(_) | SimdIsa::Bitmask(_) => (vec![V128], Some(I32)),
+    };
+    cxt.pop_types(&parameters)?;
+    if let Some(ty) = result {
+      cxt.push(ty);
+    }
+    Ok(())
+  }
+
   fn validate_unary(&self, cxt: &TypeStack) -> Result<()> {
     let t = cxt.pop_type()?;
     cxt.push(t);
@@ -543,17 +892,17 @@ impl<'a> Context<'a> {
     for local in function.locals.iter() {
       locals.push(local.clone());
     }
-    if let Some(ret) = function.function_type.returns().first() {
-      self.return_type.replace([ret.clone(); 1]);
-    };
+    self
+      .return_type
+      .replace(function.function_type.returns().to_owned());
     let return_type = &self.return_type.borrow();
 
-    labels.push_front(
-      [match function.function_type.returns().first() {
-        Some(ty) => ty.clone(),
-        None => ValueTypes::Unit,
-      }; 1],
-    );
+    labels.push_front(LabelType::new(
+      LabelKind::Frame,
+      vec![],
+      return_type.to_vec(),
+    ));
+    cxt.push_label();
 
     while let Some(inst) = function.pop() {
       match Isa::from(*inst) {
@@ -562,65 +911,84 @@ impl<'a> Context<'a> {
         Nop => {}
         Block => {
           let _ = function.pop_raw_u32()?; // Drop size of block.
-          let expect_type = function.pop_value_type()?;
-          labels.push_front([expect_type; 1]);
+          let (parameters, returns) = self.get_block_signature(function.pop_block_type()?)?;
+          cxt.pop_types(&parameters)?;
+          labels.push_front(LabelType::new(LabelKind::Block, parameters.clone(), returns));
           cxt.push_label();
+          cxt.push_types(&parameters);
         }
         Loop => {
-          let expect_type = function.pop_value_type()?;
-          labels.push_front([expect_type; 1]);
+          let (parameters, returns) = self.get_block_signature(function.pop_block_type()?)?;
+          cxt.pop_types(&parameters)?;
+          labels.push_front(LabelType::new(LabelKind::Loop, parameters.clone(), returns));
           cxt.push_label();
+          cxt.push_types(&parameters);
         }
         If => {
           let _ = cxt.pop_i32()?;
           let _ = function.pop_raw_u32()?; // Drop size of if.
           let _ = function.pop_raw_u32()?; // Drop size of else.
-          let expect_type = function.pop_value_type()?;
-          labels.push_front([expect_type; 1]);
+          let (parameters, returns) = self.get_block_signature(function.pop_block_type()?)?;
+          cxt.pop_types(&parameters)?;
+          labels.push_front(LabelType::new(LabelKind::If, parameters.clone(), returns));
           cxt.push_label();
+          cxt.push_types(&parameters);
         }
         Else => {
-          let expect = labels.pop_front().ok_or(TypeError::TypeMismatch)?[0].clone();
-          let actual = cxt.pop_type()?;
-          if expect != actual {
-            return Err(WasmError::TypeError(TypeError::TypeMismatch));
+          let label = labels.pop_front().ok_or(TypeError::TypeMismatch)?;
+          cxt.pop_results(&label.returns)?;
+          // NOTE: Else-branch starts with parameters of the block again.
+          cxt.push_label();
+          cxt.push_types(&label.parameters);
+          labels.push_front(LabelType::new(LabelKind::Else, label.parameters, label.returns));
+        }
+        Try => {
+          let _ = function.pop_raw_u32()?; // Drop size of try.
+          let (parameters, returns) = self.get_block_signature(function.pop_block_type()?)?;
+          // NOTE: Drop handlers and delegation, which are validated at each clause.
+          let count_of_handlers = function.pop_raw_u32()?;
+          for _ in 0..count_of_handlers * 2 + 1 {
+            let _ = function.pop_raw_u32()?;
           }
-          cxt.pop_until_label()?;
-          labels.push_front([expect; 1]);
-        }
-        End => {
-          let expect = labels.pop_front().ok_or(TypeError::TypeMismatch)?[0].clone();
-          match cxt.pop() {
-            Some(Entry::Type(actual)) => {
-              if expect != actual {
-                return Err(WasmError::TypeError(TypeError::TypeMismatch));
-              };
-              cxt.pop_until_label()?;
-            }
-            _ => {
-              if expect != ValueTypes::Unit {
-                return Err(WasmError::TypeError(TypeError::TypeMismatch));
-              }
-            }
+          cxt.pop_types(&parameters)?;
+          labels.push_front(LabelType::new(LabelKind::Try, parameters.clone(), returns));
+          cxt.push_label();
+          cxt.push_types(&parameters);
+        }
+        Catch | CatchAll => {
+          let parameters = match Isa::from(*inst) {
+            Catch => self.tag_type(function.pop_raw_u32()?)?.parameters().to_owned(),
+            _ => vec![],
           };
+          let label = labels.pop_front().ok_or(TypeError::TypeMismatch)?;
+          cxt.pop_results(&label.returns)?;
+          // NOTE: Handler starts with values which the exception carries.
+          cxt.push_label();
+          cxt.push_types(&parameters);
+          labels.push_front(LabelType::new(LabelKind::Catch, label.parameters, label.returns));
+        }
+        End | Delegate => {
+          let expect = labels.pop_front().ok_or(TypeError::TypeMismatch)?.returns;
+          if Isa::from(*inst) == Delegate {
+            let idx = function.pop_raw_u32()?;
+            labels.get(idx as usize).ok_or(TypeError::UnknownLabel)?;
+          }
+          cxt.pop_results(&expect)?;
+          // NOTE: Results of the block are handed over to the enclosing one.
+          cxt.push_types(&expect);
         }
 
         Br => {
           let idx = Indice::from(function.pop_raw_u32()?);
-          let expect = labels.get(idx.to_usize()).ok_or(TypeError::UnknownLabel)?[0].clone();
-          let actual = cxt.pop_type()?;
-          if expect != actual {
-            return Err(WasmError::TypeError(TypeError::TypeMismatch));
-          }
+          let expect = labels.get(idx.to_usize()).ok_or(TypeError::UnknownLabel)?.branch();
+          cxt.pop_types(expect)?;
         }
         BrIf => {
           let idx = Indice::from(function.pop_raw_u32()?);
-          let expect = labels.get(idx.to_usize()).ok_or(TypeError::UnknownLabel)?[0].clone();
-          let actual = cxt.pop_type()?;
+          let expect = labels.get(idx.to_usize()).ok_or(TypeError::UnknownLabel)?.branch();
           cxt.pop_i32()?;
-          if expect != actual {
-            return Err(WasmError::TypeError(TypeError::TypeMismatch));
-          }
+          cxt.pop_types(expect)?;
+          cxt.push_types(expect);
         }
         BrTable => {
           let len = function.pop_raw_u32()?;
@@ -630,34 +998,49 @@ impl<'a> Context<'a> {
             indices.push(Indice::from(idx));
           }
           let idx = Indice::from(function.pop_raw_u32()?);
-          let expect = labels.get(idx.to_usize()).ok_or(TypeError::UnknownLabel)?[0].clone();
+          let expect = labels.get(idx.to_usize()).ok_or(TypeError::UnknownLabel)?.branch();
           for i in indices.iter() {
-            let actual = labels.get(i.to_usize()).ok_or(TypeError::UnknownLabel)?[0].clone();
+            let actual = labels.get(i.to_usize()).ok_or(TypeError::UnknownLabel)?.branch();
             if expect != actual {
               return Err(WasmError::TypeError(TypeError::TypeMismatch));
             }
           }
-          let actual = cxt.pop_type()?;
           cxt.pop_i32()?;
-          if expect != actual {
-            return Err(WasmError::TypeError(TypeError::TypeMismatch));
-          }
+          cxt.pop_types(expect)?;
         }
         Return => {
-          let expect = return_type[0].clone();
-          let actual = cxt.pop_type()?;
-          if expect != actual {
-            return Err(WasmError::TypeError(TypeError::TypeMismatch));
-          }
-          cxt.push(actual);
+          cxt.pop_types(return_type)?;
+          cxt.push_types(return_type);
         }
-        Call => {
+        // NOTE: Same as return, values which the innermost label expects are supposed.
+        Throw => {
+          let tag_type = self.tag_type(function.pop_raw_u32()?)?;
+          cxt.pop_types(tag_type.parameters())?;
+          cxt.push_types(&labels.front().ok_or(TypeError::TypeMismatch)?.returns);
+        }
+        Rethrow => {
+          let idx = function.pop_raw_u32()?;
+          match labels.get(idx as usize) {
+            Some(LabelType {
+              source_instruction: LabelKind::Catch,
+              ..
+            }) => {}
+            _ => return Err(WasmError::TypeError(TypeError::InvalidRethrowLabel)),
+          };
+          cxt.push_types(&labels.front().ok_or(TypeError::TypeMismatch)?.returns);
+        }
+        Call | ReturnCall => {
           let idx = Indice::from(function.pop_raw_u32()?);
           let function_type = self
             .functions
             .get(idx.to_usize())
             .map(|f| f.function_type)
             .ok_or(TypeError::TypeMismatch)?;
+          // NOTE: Callee of tail call returns to the caller of current function.
+          let is_tail_call = Isa::from(*inst) == ReturnCall;
+          if is_tail_call && function_type.returns().as_slice() != return_type.as_slice() {
+            return Err(WasmError::TypeError(TypeError::TypeMismatch));
+          }
           let mut parameters = function_type.parameters().clone();
           while let Some(ty) = parameters.pop() {
             if ty != cxt.pop_type()? {
@@ -668,13 +1051,19 @@ impl<'a> Context<'a> {
             cxt.push(ty.clone());
           }
         }
-        CallIndirect => {
+        CallIndirect | ReturnCallIndirect => {
           let idx = Indice::from(function.pop_raw_u32()?);
-          self.tables.first().ok_or(TypeError::UnknownTable(0))?;
+          if self.table_type(function.pop_raw_u32()?)?.element_type != ElementType::AnyFunc {
+            return Err(WasmError::TypeError(TypeError::TypeMismatch));
+          }
           let function_type = self
             .function_types
             .get(idx.to_usize())
             .ok_or_else(|| TypeError::UnknownFunctionType(idx.to_u32()))?;
+          let is_tail_call = Isa::from(*inst) == ReturnCallIndirect;
+          if is_tail_call && function_type.returns().as_slice() != return_type.as_slice() {
+            return Err(WasmError::TypeError(TypeError::TypeMismatch));
+          }
           let mut parameters = function_type.parameters().clone();
           cxt.pop_i32()?;
           while let Some(ty) = parameters.pop() {
@@ -730,10 +1119,10 @@ impl<'a> Context<'a> {
         GetGlobal => {
           let idx = Indice::from(function.pop_raw_u32()?);
           let ty = self
-            .globals
+            .global_types
             .get(idx.to_usize())
             .ok_or_else(|| TypeError::UnknownGlobal(idx.to_u32()))
-            .map(|(global_type, _)| match global_type {
+            .map(|global_type| match global_type {
               GlobalType::Const(ty) | GlobalType::Var(ty) => ty,
             })?;
           cxt.push(ty.clone());
@@ -743,10 +1132,10 @@ impl<'a> Context<'a> {
           let idx = function.pop_raw_u32()?;
           let idx: Indice = From::from(idx);
           let ty = self
-            .globals
+            .global_types
             .get(idx.to_usize())
             .ok_or_else(|| TypeError::UnknownGlobal(idx.to_u32()))
-            .and_then(|(global_type, _)| match global_type {
+            .and_then(|global_type| match global_type {
               GlobalType::Var(ty) => Ok(ty),
               GlobalType::Const(_) => Err(TypeError::GlobalIsImmutable),
             })?;
@@ -781,11 +1170,11 @@ impl<'a> Context<'a> {
         I64Store32 => self.validate_store(cxt, 32, &TYPE_I64, function)?,
 
         MemorySize => {
-          self.limits.first().ok_or(TypeError::TypeMismatch)?;
+          self.validate_memory_index(function.pop_raw_u32()?)?;
           cxt.push(ValueTypes::I32);
         }
         MemoryGrow => {
-          self.limits.first().ok_or(TypeError::TypeMismatch)?;
+          self.validate_memory_index(function.pop_raw_u32()?)?;
           cxt.pop_i32()?;
           cxt.push(ValueTypes::I32);
         }
@@ -903,8 +1292,45 @@ impl<'a> Context<'a> {
           cxt.pop_type()?;
           cxt.pop_type()?;
           let operand = cxt.pop_type()?;
+          // NOTE: Reference types are allowed only for typed select.
+          if operand.is_reference() {
+            return Err(WasmError::TypeError(TypeError::TypeMismatch));
+          }
           cxt.push(operand);
         }
+        SelectTyped => {
+          if function.pop_raw_u32()? != 1 {
+            return Err(WasmError::TypeError(TypeError::InvalidResultArity));
+          }
+          let ty = ValueTypes::from(*function.pop()?);
+          cxt.pop_i32()?;
+          cxt.pop_types(&[ty.clone(), ty.clone()])?;
+          cxt.push(ty);
+        }
+        TableGet => {
+          let ty = ValueTypes::from(&self.table_type(function.pop_raw_u32()?)?.element_type);
+          cxt.pop_i32()?;
+          cxt.push(ty);
+        }
+        TableSet => {
+          let ty = ValueTypes::from(&self.table_type(function.pop_raw_u32()?)?.element_type);
+          cxt.pop_types(&[ValueTypes::I32, ty])?;
+        }
+        RefNull => cxt.push(ValueTypes::from(*function.pop()?)),
+        RefIsNull => {
+          if !cxt.pop_type()?.is_reference() {
+            return Err(WasmError::TypeError(TypeError::TypeMismatch));
+          }
+          cxt.push(ValueTypes::I32);
+        }
+        RefFunc => {
+          let idx = function.pop_raw_u32()?;
+          self.validate_function_index(idx)?;
+          if !self.is_declared_function(idx) {
+            return Err(WasmError::TypeError(TypeError::UndeclaredFunctionReference));
+          }
+          cxt.push(ValueTypes::FuncRef);
+        }
         DropInst => {
           cxt.pop_type()?;
         }
@@ -936,6 +1362,81 @@ impl<'a> Context<'a> {
         I64ReinterpretF64 => self.validate_convert(cxt, &TYPE_F64, ValueTypes::I64)?,
         F32ReinterpretI32 => self.validate_convert(cxt, &TYPE_I32, ValueTypes::F32)?,
         F64ReinterpretI64 => self.validate_convert(cxt, &TYPE_I64, ValueTypes::F64)?,
+        I32Extend8Sign => self.validate_convert(cxt, &TYPE_I32, ValueTypes::I32)?,
+        I32Extend16Sign => self.validate_convert(cxt, &TYPE_I32, ValueTypes::I32)?,
+        I64Extend8Sign => self.validate_convert(cxt, &TYPE_I64, ValueTypes::I64)?,
+        I64Extend16Sign => self.validate_convert(cxt, &TYPE_I64, ValueTypes::I64)?,
+        I64Extend32Sign => self.validate_convert(cxt, &TYPE_I64, ValueTypes::I64)?,
+
+        MiscPrefix => match MiscIsa::try_from(function.pop_raw_u32()?)? {
+          MiscIsa::I32TruncSatSignF32 | MiscIsa::I32TruncSatUnsignF32 => {
+            self.validate_convert(cxt, &TYPE_F32, ValueTypes::I32)?
+          }
+          MiscIsa::I32TruncSatSignF64 | MiscIsa::I32TruncSatUnsignF64 => {
+            self.validate_convert(cxt, &TYPE_F64, ValueTypes::I32)?
+          }
+          MiscIsa::I64TruncSatSignF32 | MiscIsa::I64TruncSatUnsignF32 => {
+            self.validate_convert(cxt, &TYPE_F32, ValueTypes::I64)?
+          }
+          MiscIsa::I64TruncSatSignF64 | MiscIsa::I64TruncSatUnsignF64 => {
+            self.validate_convert(cxt, &TYPE_F64, ValueTypes::I64)?
+          }
+          MiscIsa::MemoryInit => {
+            self.validate_data_index(function.pop_raw_u32()?)?;
+            self.validate_memory_index(function.pop_raw_u32()?)?;
+            self.validate_bulk_memory(cxt)?
+          }
+          MiscIsa::DataDrop => self.validate_data_index(function.pop_raw_u32()?)?,
+          MiscIsa::MemoryCopy => {
+            self.validate_memory_index(function.pop_raw_u32()?)?;
+            self.validate_memory_index(function.pop_raw_u32()?)?;
+            self.validate_bulk_memory(cxt)?
+          }
+          MiscIsa::MemoryFill => {
+            self.validate_memory_index(function.pop_raw_u32()?)?;
+            self.validate_bulk_memory(cxt)?
+          }
+          MiscIsa::TableInit => {
+            let element_type = self.element_type(function.pop_raw_u32()?)?;
+            if &self.table_type(function.pop_raw_u32()?)?.element_type != element_type {
+              return Err(WasmError::TypeError(TypeError::TypeMismatch));
+            }
+            cxt.pop_types(&[ValueTypes::I32, ValueTypes::I32, ValueTypes::I32])?;
+          }
+          MiscIsa::ElemDrop => {
+            self.element_type(function.pop_raw_u32()?)?;
+          }
+          MiscIsa::TableCopy => {
+            let destination = &self.table_type(function.pop_raw_u32()?)?.element_type;
+            if &self.table_type(function.pop_raw_u32()?)?.element_type != destination {
+              return Err(WasmError::TypeError(TypeError::TypeMismatch));
+            }
+            cxt.pop_types(&[ValueTypes::I32, ValueTypes::I32, ValueTypes::I32])?;
+          }
+          MiscIsa::TableGrow => {
+            let ty = ValueTypes::from(&self.table_type(function.pop_raw_u32()?)?.element_type);
+            cxt.pop_types(&[ty, ValueTypes::I32])?;
+            cxt.push(ValueTypes::I32);
+          }
+          MiscIsa::TableSize => {
+            self.table_type(function.pop_raw_u32()?)?;
+            cxt.push(ValueTypes::I32);
+          }
+          MiscIsa::TableFill => {
+            let ty = ValueTypes::from(&self.table_type(function.pop_raw_u32()?)?.element_type);
+            cxt.pop_types(&[ValueTypes::I32, ty, ValueTypes::I32])?;
+          }
+        },
+
+        SimdPrefix => {
+          let simd_isa = SimdIsa::try_from(function.pop_raw_u32()?)?;
+          self.validate_simd(cxt, simd_isa, function)?
+        }
+
+        AtomicPrefix => {
+          let atomic = AtomicIsa::try_from(function.pop_raw_u32()?)?;
+          self.validate_atomic(cxt, atomic, function)?
+        }
       }
     }
     Ok(())
@@ -945,11 +1446,10 @@ impl<'a> Context<'a> {
     self.validate_exports()?;
     self.validate_imports()?;
     self.validate_datas()?;
-    self.validate_tables()?;
     self.validate_memories()?;
     self.validate_elements()?;
     self.validate_globals()?;
-    self.validate_function_types()?;
+    self.validate_tags()?;
     self.validate_functions()?;
     self.validate_start()?;
     Ok(())
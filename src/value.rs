@@ -7,7 +7,8 @@ use core::ops::Rem;
 use core::ops::{BitAnd, BitOr, BitXor, Neg};
 use core::{f32, f64};
 use error::{Result, Trap, WasmError};
-#[cfg(not(test))]
+use function::FunctionInstance;
+#[cfg(not(any(test, feature = "std")))]
 use libm::{F32Ext, F64Ext};
 use value_type::ValueTypes;
 
@@ -17,6 +18,12 @@ pub enum Values {
   I64(i64),
   F32(f32),
   F64(f64),
+  // NOTE: Lanes are laid out in little endian, as same as linear memory.
+  V128(u128),
+  // NOTE: None represents null reference.
+  FuncRef(Option<FunctionInstance>),
+  // NOTE: Opaque handle given by host.
+  ExternRef(Option<u32>),
 }
 
 macro_rules! unary_inst {
@@ -27,6 +34,7 @@ macro_rules! unary_inst {
         Values::I64(l) => Values::I64(l.$op()),
         Values::F32(l) => Values::F32(l.$op()),
         Values::F64(l) => Values::F64(l.$op()),
+        x => unreachable!("{:?}", x),
       }
     }
   };
@@ -40,6 +48,7 @@ macro_rules! unary_logical_inst {
         Values::I64(l) => Values::I32(l.$op() as i32),
         Values::F32(l) => Values::I32(l.$op() as i32),
         Values::F64(l) => Values::I32(l.$op() as i32),
+        x => unreachable!("{:?}", x),
       }
     }
   };
@@ -479,6 +488,7 @@ macro_rules! impl_float_traits {
 
 trait TruncFloat<T> {
   fn try_trunc_to(&self) -> Result<T>;
+  fn saturating_trunc_to(&self) -> T;
 }
 
 macro_rules! impl_try_trunc {
@@ -498,6 +508,15 @@ macro_rules! impl_try_trunc {
         }
         Ok(result as $to)
       }
+
+      fn saturating_trunc_to(&self) -> $to {
+        match self.try_trunc_to() {
+          Ok(result) => result,
+          Err(_) if self.is_nan() => 0,
+          Err(_) if self.is_sign_negative() => <$to>::min_value(),
+          Err(_) => <$to>::max_value(),
+        }
+      }
     }
   };
 }
@@ -516,6 +535,20 @@ macro_rules! trunc_inst {
   };
 }
 
+macro_rules! trunc_sat_inst {
+  ($name: ident, $kind_from: path, $kind_to: path, $internal: ty, $to: ty) => {
+      pub fn $name(&self) -> Self {
+        match self {
+          $kind_from(n) => {
+            let result: $internal = n.saturating_trunc_to();
+            $kind_to(result as $to)
+          }
+          x => unreachable!("Got {:?}", x),
+        }
+      }
+  };
+}
+
 impl_traits!(i32);
 impl_traits!(i64);
 impl_traits!(f32);
@@ -579,6 +612,7 @@ impl Values {
       Values::I64(l) => Values::I64(l.abs()),
       Values::F32(l) => Values::F32(unsafe { fabsf32(*l) }),
       Values::F64(l) => Values::F64(unsafe { fabsf64(*l) }),
+      x => unreachable!("{:?}", x),
     }
   }
 
@@ -665,6 +699,14 @@ impl Values {
   trunc_inst!(trunc_f32_to_unsign_i64, Values::F32, Values::I64, u64, i64);
   trunc_inst!(trunc_f64_to_sign_i64, Values::F64, Values::I64, i64, i64);
   trunc_inst!(trunc_f64_to_unsign_i64, Values::F64, Values::I64, u64, i64);
+  trunc_sat_inst!(trunc_sat_f32_to_sign_i32, Values::F32, Values::I32, i32, i32);
+  trunc_sat_inst!(trunc_sat_f32_to_unsign_i32, Values::F32, Values::I32, u32, i32);
+  trunc_sat_inst!(trunc_sat_f64_to_sign_i32, Values::F64, Values::I32, i32, i32);
+  trunc_sat_inst!(trunc_sat_f64_to_unsign_i32, Values::F64, Values::I32, u32, i32);
+  trunc_sat_inst!(trunc_sat_f32_to_sign_i64, Values::F32, Values::I64, i64, i64);
+  trunc_sat_inst!(trunc_sat_f32_to_unsign_i64, Values::F32, Values::I64, u64, i64);
+  trunc_sat_inst!(trunc_sat_f64_to_sign_i64, Values::F64, Values::I64, i64, i64);
+  trunc_sat_inst!(trunc_sat_f64_to_unsign_i64, Values::F64, Values::I64, u64, i64);
 
   pub fn reinterpret(&self) -> Self {
     match self {
@@ -672,6 +714,14 @@ impl Values {
       Values::I64(n) => Values::F64(f64::from_bits(*n as u64)),
       Values::F32(n) => Values::I32(unsafe { transmute(*n) }),
       Values::F64(n) => Values::I64(unsafe { transmute(*n) }),
+      x => unreachable!("{:?}", x),
+    }
+  }
+
+  pub fn is_null(&self) -> bool {
+    match self {
+      Values::FuncRef(None) | Values::ExternRef(None) => true,
+      _ => false,
     }
   }
 
@@ -695,6 +745,29 @@ impl Values {
       _ => unimplemented!(),
     }
   }
+
+  pub fn extend8_sign(&self) -> Self {
+    match self {
+      Values::I32(l) => Values::I32(i32::from(*l as i8)),
+      Values::I64(l) => Values::I64(i64::from(*l as i8)),
+      _ => unimplemented!(),
+    }
+  }
+
+  pub fn extend16_sign(&self) -> Self {
+    match self {
+      Values::I32(l) => Values::I32(i32::from(*l as i16)),
+      Values::I64(l) => Values::I64(i64::from(*l as i16)),
+      _ => unimplemented!(),
+    }
+  }
+
+  pub fn extend32_sign(&self) -> Self {
+    match self {
+      Values::I64(l) => Values::I64(i64::from(*l as i32)),
+      _ => unimplemented!(),
+    }
+  }
   pub fn div_f(&self, other: &Self) -> Self {
     match (self, other) {
       (Values::F32(l), Values::F32(r)) => Values::F32(l / *r),
@@ -702,11 +775,14 @@ impl Values {
       _ => unimplemented!(),
     }
   }
+  // NOTE: Zeros compare equal, so that -0 is taken by its sign bit.
   pub fn min(&self, other: &Self) -> Self {
     match (self, other) {
       (Values::F32(l), Values::F32(r)) => {
         if l.is_nan() || r.is_nan() {
           Values::F32(f32::NAN)
+        } else if l == r {
+          Values::F32(f32::from_bits(l.to_bits() | r.to_bits()))
         } else {
           Values::F32(l.min(*r))
         }
@@ -714,6 +790,8 @@ impl Values {
       (Values::F64(l), Values::F64(r)) => {
         if l.is_nan() || r.is_nan() {
           Values::F64(f64::NAN)
+        } else if l == r {
+          Values::F64(f64::from_bits(l.to_bits() | r.to_bits()))
         } else {
           Values::F64(l.min(*r))
         }
@@ -727,6 +805,8 @@ impl Values {
       (Values::F32(l), Values::F32(r)) => {
         if l.is_nan() || r.is_nan() {
           Values::F32(f32::NAN)
+        } else if l == r {
+          Values::F32(f32::from_bits(l.to_bits() & r.to_bits()))
         } else {
           Values::F32(l.max(*r))
         }
@@ -734,6 +814,8 @@ impl Values {
       (Values::F64(l), Values::F64(r)) => {
         if l.is_nan() || r.is_nan() {
           Values::F64(f64::NAN)
+        } else if l == r {
+          Values::F64(f64::from_bits(l.to_bits() & r.to_bits()))
         } else {
           Values::F64(l.max(*r))
         }
@@ -777,8 +859,10 @@ impl Values {
   pub fn nearest(&self) -> Self {
     match self {
       Values::F32(l) => {
-        if (*l > 0.0 && *l <= 0.5) || (*l < 0.0 && *l >= -0.5) {
+        if *l > 0.0 && *l <= 0.5 {
           Values::F32(0.0)
+        } else if *l < 0.0 && *l >= -0.5 {
+          Values::F32(-0.0)
         } else {
           let round = l.round();
           let result = if round.rem(2.0).eq(&1.0) {
@@ -792,8 +876,10 @@ impl Values {
         }
       }
       Values::F64(l) => {
-        if (*l > 0.0 && *l <= 0.5) || (*l < 0.0 && *l >= -0.5) {
+        if *l > 0.0 && *l <= 0.5 {
           Values::F64(0.0)
+        } else if *l < 0.0 && *l >= -0.5 {
+          Values::F64(-0.0)
         } else {
           let round = l.round();
           let result = if round.rem(2.0).eq(&1.0) {
@@ -827,6 +913,11 @@ macro_rules! impl_from_values {
             let prefix = if n.is_nan() { "" } else { "f64:" };
             format!("{}{}", prefix, n)
           }
+          V128(n) => format!("v128:{:#034x}", n),
+          FuncRef(Some(f)) => format!("funcref:{}", f.name().unwrap_or_else(|| "_".to_owned())),
+          ExternRef(Some(n)) => format!("externref:{}", n),
+          FuncRef(None) => "funcref:null".to_owned(),
+          ExternRef(None) => "externref:null".to_owned(),
         }
         .to_owned()
       }
@@ -846,6 +937,9 @@ macro_rules! impl_from_valuetypes {
           ValueTypes::I64 => Values::I64(0),
           ValueTypes::F32 => Values::F32(0.0),
           ValueTypes::F64 => Values::F64(0.0),
+          ValueTypes::V128 => Values::V128(0),
+          ValueTypes::FuncRef => Values::FuncRef(None),
+          ValueTypes::ExternRef => Values::ExternRef(None),
           ValueTypes::Unit => unreachable!(),
         }
       }
@@ -855,3 +949,17 @@ macro_rules! impl_from_valuetypes {
 
 impl_from_valuetypes!(ValueTypes);
 impl_from_valuetypes!(&ValueTypes);
+
+impl From<&Values> for ValueTypes {
+  fn from(x: &Values) -> Self {
+    match x {
+      Values::I32(_) => ValueTypes::I32,
+      Values::I64(_) => ValueTypes::I64,
+      Values::F32(_) => ValueTypes::F32,
+      Values::F64(_) => ValueTypes::F64,
+      Values::V128(_) => ValueTypes::V128,
+      Values::FuncRef(_) => ValueTypes::FuncRef,
+      Values::ExternRef(_) => ValueTypes::ExternRef,
+    }
+  }
+}
@@ -8,14 +8,35 @@ pub enum ValueTypes {
   I64,
   F32,
   F64,
+  V128,
+  FuncRef,
+  ExternRef,
 }
 
-pub const TYPE_UNIT: ValueTypes = ValueTypes::Unit;
 pub const TYPE_I32: ValueTypes = ValueTypes::I32;
 pub const TYPE_I64: ValueTypes = ValueTypes::I64;
 pub const TYPE_F32: ValueTypes = ValueTypes::F32;
 pub const TYPE_F64: ValueTypes = ValueTypes::F64;
 
+// NOTE: Internal representation of block type which refers a function type,
+// followed by index of the type as raw u32.
+pub const BLOCK_TYPE_INDEX: u8 = 0x00;
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum BlockType {
+  Value(ValueTypes),
+  Index(u32),
+}
+
+impl ValueTypes {
+  pub fn is_reference(&self) -> bool {
+    match self {
+      ValueTypes::FuncRef | ValueTypes::ExternRef => true,
+      _ => false,
+    }
+  }
+}
+
 impl From<u8> for ValueTypes {
   fn from(code: u8) -> Self {
     match code {
@@ -24,11 +45,29 @@ impl From<u8> for ValueTypes {
       0x7e => ValueTypes::I64,
       0x7d => ValueTypes::F32,
       0x7c => ValueTypes::F64,
+      0x7b => ValueTypes::V128,
+      0x70 => ValueTypes::FuncRef,
+      0x6f => ValueTypes::ExternRef,
       x => unreachable!("Expected value type, got {:?}", x),
     }
   }
 }
 
+impl<'a> From<&'a ValueTypes> for u8 {
+  fn from(value_type: &'a ValueTypes) -> Self {
+    match value_type {
+      ValueTypes::Unit => 0x40,
+      ValueTypes::I32 => 0x7f,
+      ValueTypes::I64 => 0x7e,
+      ValueTypes::F32 => 0x7d,
+      ValueTypes::F64 => 0x7c,
+      ValueTypes::V128 => 0x7b,
+      ValueTypes::FuncRef => 0x70,
+      ValueTypes::ExternRef => 0x6f,
+    }
+  }
+}
+
 impl fmt::Debug for ValueTypes {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     use self::ValueTypes::*;
@@ -41,6 +80,9 @@ impl fmt::Debug for ValueTypes {
         I64 => "i64",
         F32 => "f32",
         F64 => "f64",
+        V128 => "v128",
+        FuncRef => "funcref",
+        ExternRef => "externref",
       }
     )
   }
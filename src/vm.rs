@@ -1,26 +1,32 @@
 #[cfg(not(test))]
 use alloc::prelude::*;
+use alloc::string::String;
 use alloc::vec::Vec;
+use caller::Caller;
+use core::convert::TryFrom;
+use core::fmt;
+use core::mem;
+use decode::Names;
 use error::{Result, Trap, WasmError};
 use frame::Frame;
 use function::FunctionInstance;
 use indice::Indice;
-use isa::Isa;
-use label::{Label, LabelKind};
-use memory::MemoryInstances;
-use module::{
-    ExportDescriptor, ExternalInterface, ExternalModule, ExternalModules, InternalModule,
-    ModuleDescriptor, ModuleName,
-};
+use global::GlobalInstance;
+use isa::{AtomicIsa, AtomicRmw, Half, Isa, MiscIsa, SimdIsa, SimdUnary};
+use label::{Handler, Label, LabelKind};
+use memory::{DataInstances, MemoryInstance};
+use module::{ExportDescriptor, ExternalModule, InternalModule};
+use simd;
 use stack::{Stack, StackEntry};
-use store::Store;
+use store::ModuleRef;
+use table::{ElementInstances, TableInstance};
+use tag::{Exception, TagInstance};
 use value::Values;
-use value_type::TYPE_UNIT;
+use value_type::{BlockType, ValueTypes};
 
 macro_rules! impl_load_inst {
     ($fn_name: ident, $load_fn: ident, $ty: ty) => {
-        fn $fn_name(&self, offset: u32, load_data_width: u32, source_of_frame: &ModuleName) -> Result<$ty> {
-            let memory_instances = self.get_memory_instances(source_of_frame)?;
+        fn $fn_name(&self, offset: u32, load_data_width: u32, memory_instance: &MemoryInstance) -> Result<$ty> {
             let width = load_data_width / 8;
             let i = self.stack.pop_value_ext_i32() as u32;
             let (effective_address, overflowed) = i.overflowing_add(offset);
@@ -28,10 +34,10 @@ macro_rules! impl_load_inst {
                 return Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds));
             };
             let (ptr, overflowed) = effective_address.overflowing_add(width);
-            if overflowed || memory_instances.data_size_small_than(ptr) {
+            if overflowed || memory_instance.data_size_smaller_than(ptr) {
                 return Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds));
             };
-            let data = memory_instances
+            let data = memory_instance
                 .$load_fn(effective_address, ptr);
             Ok(data)
         }
@@ -40,8 +46,8 @@ macro_rules! impl_load_inst {
 
 macro_rules! impl_load_to {
     ($fn_name: ident, $load_fn: ident, $path: path, $ty: ty) => {
-        fn $fn_name(&mut self, offset: u32, width: u32, sign: bool, source_of_frame: &ModuleName) -> Result<()> {
-            let mut value = self.$load_fn(offset, width, source_of_frame)?;
+        fn $fn_name(&mut self, offset: u32, width: u32, sign: bool, memory_instance: &MemoryInstance) -> Result<()> {
+            let mut value = self.$load_fn(offset, width, memory_instance)?;
             if sign {
                 let is_msb_one = value & (1 << (width - 1)) != 0;
                 if is_msb_one {
@@ -115,12 +121,90 @@ macro_rules! impl_try_binary_inst {
     };
 }
 
+// NOTE: Traps which keep frames to be resumed.
+fn is_suspension(err: &WasmError) -> bool {
+    match err {
+        WasmError::Trap(Trap::OutOfFuel) | WasmError::Trap(Trap::Yield) => true,
+        _ => false,
+    }
+}
+
+#[derive(Debug)]
+pub enum Invocation {
+    Returned(Vec<Values>),
+    Suspended(Suspended),
+}
+
+/// Invocation which ran out of fuel or was yielded by host function.
+/// It owns a stack of its own, so several invocations can be suspended at once.
+#[derive(Debug)]
+pub struct Suspended {
+    stack: Stack,
+    count_of_returns: u32,
+    reason: Trap,
+}
+
+impl Suspended {
+    pub fn reason(&self) -> &Trap {
+        &self.reason
+    }
+
+    /// Host function which yielded is called again with the same arguments.
+    pub fn resume(self, vm: &mut ModuleInstance) -> Result<Invocation> {
+        let count_of_returns = self.count_of_returns;
+        vm.evaluate_on(self.stack, |vm| vm.evaluate_invocation(count_of_returns))
+    }
+}
+
+/// Function of a frame on the call stack when a trap raised.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrapLocation {
+    pub function_index: u32,
+    pub name: Option<String>,
+    /// Offset of the instruction in the original module, None for host functions.
+    pub offset: Option<u32>,
+}
+
+impl fmt::Display for TrapLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "func[{}]", self.function_index),
+        }
+    }
+}
+
+/// Error raised by the last invocation and a backtrace of it, innermost frame first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrapInfo {
+    pub error: WasmError,
+    pub backtrace: Vec<TrapLocation>,
+}
+
+impl fmt::Display for TrapInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:?}", self.error)?;
+        write!(f, "wasm backtrace:")?;
+        for (idx, location) in self.backtrace.iter().enumerate() {
+            match location.offset {
+                Some(offset) => write!(f, "\n  {}: {:#x} - {}", idx, offset, location)?,
+                None => write!(f, "\n  {}: <host> - {}", idx, location)?,
+            };
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct ModuleInstance {
-    store: Store,
+    module: ModuleRef,
     pub(crate) stack: Stack,
     internal_module: InternalModule,
-    external_modules: ExternalModules,
+    // NOTE: None means unmetered.
+    fuel: Option<u64>,
+    // NOTE: Count of returns of an invocation which is waiting to be resumed.
+    suspended_returns: Option<u32>,
+    last_trap: Option<TrapInfo>,
 }
 
 impl ModuleInstance {
@@ -146,6 +230,9 @@ impl ModuleInstance {
     impl_unary_inst!(neg);
     impl_unary_inst!(extend_u32_to_i64);
     impl_unary_inst!(extend_i32_to_i64);
+    impl_unary_inst!(extend8_sign);
+    impl_unary_inst!(extend16_sign);
+    impl_unary_inst!(extend32_sign);
     impl_unary_inst!(convert_sign_i32_to_f32);
     impl_unary_inst!(convert_unsign_i32_to_f32);
     impl_unary_inst!(convert_sign_i64_to_f64);
@@ -166,6 +253,15 @@ impl ModuleInstance {
     impl_try_unary_inst!(trunc_f32_to_sign_i64);
     impl_try_unary_inst!(trunc_f32_to_unsign_i64);
 
+    impl_unary_inst!(trunc_sat_f32_to_sign_i32);
+    impl_unary_inst!(trunc_sat_f32_to_unsign_i32);
+    impl_unary_inst!(trunc_sat_f64_to_sign_i32);
+    impl_unary_inst!(trunc_sat_f64_to_unsign_i32);
+    impl_unary_inst!(trunc_sat_f32_to_sign_i64);
+    impl_unary_inst!(trunc_sat_f32_to_unsign_i64);
+    impl_unary_inst!(trunc_sat_f64_to_sign_i64);
+    impl_unary_inst!(trunc_sat_f64_to_unsign_i64);
+
     impl_binary_inst!(add);
     impl_binary_inst!(sub);
     impl_binary_inst!(mul);
@@ -197,8 +293,7 @@ impl ModuleInstance {
     impl_try_binary_inst!(rem_s);
     impl_try_binary_inst!(rem_u);
 
-    fn store(&self, data_width: u32, offset: u32, source_of_frame: &ModuleName) -> Result<()> {
-        let memory_instances = self.get_memory_instances(source_of_frame)?;
+    fn store(&self, data_width: u32, offset: u32, memory_instance: &MemoryInstance) -> Result<()> {
         let c = self.stack.pop_value_ext();
         let width = data_width / 8;
         let i = self.stack.pop_value_ext_i32() as u32;
@@ -207,37 +302,262 @@ impl ModuleInstance {
             return Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds));
         };
         let (ptr, overflowed) = effective_address.overflowing_add(width);
-        if overflowed || memory_instances.data_size_small_than(ptr) {
+        if overflowed || memory_instance.data_size_smaller_than(ptr) {
             return Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds));
         };
-        memory_instances.store_data(effective_address, ptr, &c);
+        memory_instance.store_data(effective_address, ptr, &c);
         Ok(())
     }
 
+    fn effective_address(
+        &self,
+        offset: u32,
+        data_width: u32,
+        memory_instance: &MemoryInstance,
+    ) -> Result<(u32, u32)> {
+        let width = data_width / 8;
+        let i = self.stack.pop_value_ext_i32() as u32;
+        let (effective_address, overflowed) = i.overflowing_add(offset);
+        if overflowed {
+            return Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds));
+        };
+        let (ptr, overflowed) = effective_address.overflowing_add(width);
+        if overflowed || memory_instance.data_size_smaller_than(ptr) {
+            return Err(WasmError::Trap(Trap::MemoryAccessOutOfBounds));
+        };
+        Ok((effective_address, ptr))
+    }
+
+    fn atomic_address(
+        &self,
+        offset: u32,
+        data_width: u32,
+        memory_instance: &MemoryInstance,
+    ) -> Result<(u32, u32)> {
+        let (effective_address, ptr) = self.effective_address(offset, data_width, memory_instance)?;
+        if effective_address % (data_width / 8) != 0 {
+            return Err(WasmError::Trap(Trap::UnalignedAtomic));
+        };
+        Ok((effective_address, ptr))
+    }
+
+    fn pop_atomic_operand(&self) -> u64 {
+        match self.stack.pop_value_ext() {
+            Values::I32(v) => u64::from(v as u32),
+            Values::I64(v) => v as u64,
+            x => unreachable!("Expected integer value, got {:?}", x),
+        }
+    }
+
+    fn push_atomic_result(&self, ty: &ValueTypes, value: u64) -> Result<()> {
+        let value = match ty {
+            ValueTypes::I32 => Values::I32(value as u32 as i32),
+            _ => Values::I64(value as i64),
+        };
+        self.stack.push(StackEntry::new_value(value))
+    }
+
+    fn atomic(&self, atomic: AtomicIsa, offset: u32, memory: &MemoryInstance) -> Result<()> {
+        match atomic {
+            AtomicIsa::Notify => {
+                let count = self.stack.pop_value_ext_i32() as u32;
+                let (address, _) = self.atomic_address(offset, 32, memory)?;
+                let woken = memory.notify(address, count);
+                self.stack
+                    .push(StackEntry::new_value(Values::I32(woken as i32)))?;
+            }
+            AtomicIsa::Wait32 | AtomicIsa::Wait64 => {
+                let timeout = match self.stack.pop_value_ext() {
+                    Values::I64(v) => v,
+                    x => unreachable!("Expected i64 value, got {:?}", x),
+                };
+                let expected = self.pop_atomic_operand();
+                let width = if atomic == AtomicIsa::Wait32 { 32 } else { 64 };
+                let (from, to) = self.atomic_address(offset, width, memory)?;
+                let result = memory.wait(from, to, expected, timeout)?;
+                self.stack
+                    .push(StackEntry::new_value(Values::I32(result as i32)))?;
+            }
+            AtomicIsa::Fence => {}
+            AtomicIsa::Load(ty, width) => {
+                let (from, to) = self.atomic_address(offset, width, memory)?;
+                self.push_atomic_result(&ty, memory.load_data_64(from, to))?;
+            }
+            AtomicIsa::Store(_, width) => {
+                let value = self.stack.pop_value_ext();
+                let (from, to) = self.atomic_address(offset, width, memory)?;
+                memory.store_data(from, to, &value);
+            }
+            AtomicIsa::Rmw(AtomicRmw::Cmpxchg, ty, width) => {
+                let replacement = self.pop_atomic_operand();
+                let expected = self.pop_atomic_operand();
+                let (from, to) = self.atomic_address(offset, width, memory)?;
+                // NOTE: Expected value is compared after wrapped to the accessed width.
+                let mask = if width == 64 { !0 } else { (1 << width) - 1 };
+                let previous = memory.read_modify_write(from, to, |previous| {
+                    if previous == expected & mask {
+                        replacement
+                    } else {
+                        previous
+                    }
+                });
+                self.push_atomic_result(&ty, previous)?;
+            }
+            AtomicIsa::Rmw(rmw, ty, width) => {
+                let operand = self.pop_atomic_operand();
+                let (from, to) = self.atomic_address(offset, width, memory)?;
+                let previous = memory.read_modify_write(from, to, |previous| match rmw {
+                    AtomicRmw::Add => previous.wrapping_add(operand),
+                    AtomicRmw::Sub => previous.wrapping_sub(operand),
+                    AtomicRmw::And => previous & operand,
+                    AtomicRmw::Or => previous | operand,
+                    AtomicRmw::Xor => previous ^ operand,
+                    AtomicRmw::Xchg | AtomicRmw::Cmpxchg => operand,
+                });
+                self.push_atomic_result(&ty, previous)?;
+            }
+        };
+        Ok(())
+    }
+
+    fn simd_memory(
+        &self,
+        simd_isa: SimdIsa,
+        frame: &Frame,
+        source_of_frame: &ModuleRef,
+    ) -> Result<()> {
+        let _align = frame.pop_raw_u32()?;
+        let offset = frame.pop_raw_u32()?;
+        let memory = self.get_memory_instance(source_of_frame, frame.pop_raw_u32()?)?;
+        let value = match simd_isa {
+            SimdIsa::Load => {
+                let (from, to) = self.effective_address(offset, 128, &memory)?;
+                memory.load_data_128(from, to)
+            }
+            SimdIsa::LoadExtend(shape, sign) => {
+                let (from, to) = self.effective_address(offset, 64, &memory)?;
+                let half = u128::from(memory.load_data_64(from, to));
+                simd::unary(&SimdUnary::Extend(shape, Half::Low, sign), half)
+            }
+            SimdIsa::LoadSplat(width) => {
+                let (from, to) = self.effective_address(offset, width, &memory)?;
+                simd::splat_bits(width, memory.load_data_64(from, to))
+            }
+            SimdIsa::LoadZero(width) => {
+                let (from, to) = self.effective_address(offset, width, &memory)?;
+                u128::from(memory.load_data_64(from, to))
+            }
+            SimdIsa::LoadLane(width) => {
+                let idx = frame.pop_raw_u32()?;
+                let v = self.stack.pop_value_ext_v128();
+                let (from, to) = self.effective_address(offset, width, &memory)?;
+                simd::replace_lane_bits(v, width, idx, memory.load_data_64(from, to))
+            }
+            SimdIsa::Store => {
+                let v = self.stack.pop_value_ext();
+                let (from, to) = self.effective_address(offset, 128, &memory)?;
+                memory.store_data(from, to, &v);
+                return Ok(());
+            }
+            SimdIsa::StoreLane(width) => {
+                let idx = frame.pop_raw_u32()?;
+                let v = self.stack.pop_value_ext_v128();
+                let (from, to) = self.effective_address(offset, width, &memory)?;
+                let bits = simd::lane_bits(v, width, idx);
+                memory.store_data(from, to, &Values::I64(bits as i64));
+                return Ok(());
+            }
+            x => unreachable!("Expected memory instruction of SIMD, got {:?}", x),
+        };
+        self.stack.push(StackEntry::new_value(Values::V128(value)))
+    }
+
+    fn simd(&self, simd_isa: SimdIsa, frame: &Frame, source_of_frame: &ModuleRef) -> Result<()> {
+        let value = match simd_isa {
+            SimdIsa::Load
+            | SimdIsa::LoadExtend(_, _)
+            | SimdIsa::LoadSplat(_)
+            | SimdIsa::LoadZero(_)
+            | SimdIsa::LoadLane(_)
+            | SimdIsa::Store
+            | SimdIsa::StoreLane(_) => return self.simd_memory(simd_isa, frame, source_of_frame),
+            SimdIsa::Const => Values::V128(frame.pop_raw_u128()?),
+            SimdIsa::Shuffle => {
+                let indices = frame.pop_raw_u128()?;
+                let b = self.stack.pop_value_ext_v128();
+                let a = self.stack.pop_value_ext_v128();
+                Values::V128(simd::shuffle(a, b, indices))
+            }
+            SimdIsa::Splat(shape) => Values::V128(simd::splat(shape, &self.stack.pop_value_ext())),
+            SimdIsa::ExtractLane(shape, sign) => {
+                let idx = frame.pop_raw_u32()?;
+                let v = self.stack.pop_value_ext_v128();
+                simd::extract_lane(v, shape, sign, idx)
+            }
+            SimdIsa::ReplaceLane(shape) => {
+                let idx = frame.pop_raw_u32()?;
+                let value = self.stack.pop_value_ext();
+                let v = self.stack.pop_value_ext_v128();
+                Values::V128(simd::replace_lane(v, shape, idx, &value))
+            }
+            SimdIsa::Unary(op) => Values::V128(simd::unary(&op, self.stack.pop_value_ext_v128())),
+            SimdIsa::Binary(op) => {
+                let b = self.stack.pop_value_ext_v128();
+                let a = self.stack.pop_value_ext_v128();
+                Values::V128(simd::binary(&op, a, b))
+            }
+            SimdIsa::Bitselect => {
+                let c = self.stack.pop_value_ext_v128();
+                let b = self.stack.pop_value_ext_v128();
+                let a = self.stack.pop_value_ext_v128();
+                Values::V128(simd::bitselect(a, b, c))
+            }
+            SimdIsa::Shl(shape) => {
+                let amount = self.stack.pop_value_ext_i32() as u32;
+                let v = self.stack.pop_value_ext_v128();
+                Values::V128(simd::shift_left(shape, v, amount))
+            }
+            SimdIsa::Shr(shape, sign) => {
+                let amount = self.stack.pop_value_ext_i32() as u32;
+                let v = self.stack.pop_value_ext_v128();
+                Values::V128(simd::shift_right(shape, sign, v, amount))
+            }
+            SimdIsa::AnyTrue => Values::I32((self.stack.pop_value_ext_v128() != 0) as i32),
+            SimdIsa::AllTrue(shape) => {
+                Values::I32(simd::all_true(shape, self.stack.pop_value_ext_v128()) as i32)
+            }
+            SimdIsa::Bitmask(shape) => {
+                Values::I32(simd::bitmask(shape, self.stack.pop_value_ext_v128()) as i32)
+            }
+        };
+        self.stack.push(StackEntry::new_value(value))
+    }
+
     pub fn start_index(&self) -> &Option<Indice> {
         &self.internal_module.start
     }
 
     pub(crate) fn new_from(
-        store: Store,
+        module: ModuleRef,
         internal_module: InternalModule,
-        external_modules: ExternalModules,
         stack_height: usize,
     ) -> Result<Self> {
         Ok(ModuleInstance {
-            store,
+            module,
             internal_module,
             stack: Stack::new(stack_height),
-            external_modules,
+            fuel: None,
+            suspended_returns: None,
+            last_trap: None,
         })
     }
 
     pub fn get_function_instance(&self, idx: &Indice) -> Option<FunctionInstance> {
-        self.store.get_function_instance(idx)
+        self.module.function_instance(idx.to_u32())
     }
 
     pub fn export_module(&self) -> ExternalModule {
-        ExternalModule::from(&self.store)
+        ExternalModule::from(&self.module)
     }
 
     fn get_local(&self, idx: &Indice) -> Result<()> {
@@ -263,29 +583,118 @@ impl ModuleInstance {
         Ok(())
     }
 
-    fn get_global(&self, idx: &Indice) -> Result<()> {
-        let value = self.store.get_global(idx)?;
-        self.stack.push(StackEntry::new_value(value))?;
-        Ok(())
+    fn get_memory_instance(&self, source_of_frame: &ModuleRef, idx: u32) -> Result<MemoryInstance> {
+        Ok(source_of_frame.memory_instance(idx)?)
     }
 
-    fn set_global(&mut self, idx: &Indice) -> Result<()> {
-        let value = self.stack.pop_value_ext();
-        self.store.set_global(idx, value);
-        Ok(())
+    fn get_data_instances(&self, source_of_frame: &ModuleRef) -> DataInstances {
+        source_of_frame.data_instances()
+    }
+
+    fn get_element_instances(&self, source_of_frame: &ModuleRef) -> ElementInstances {
+        source_of_frame.element_instances()
+    }
+
+    fn get_table_instance(&self, source_of_frame: &ModuleRef, idx: u32) -> Result<TableInstance> {
+        Ok(source_of_frame.table_instance(idx)?)
     }
 
-    fn get_memory_instances(&self, source_of_frame: &ModuleName) -> Result<MemoryInstances> {
-        Ok(match source_of_frame {
-            Some(module_name) => {
-                self.external_modules
-                    .get(&Some(module_name.to_string()))?
-                    .memory_instances
+    fn get_tag_instance(&self, source_of_frame: &ModuleRef, idx: u32) -> Result<TagInstance> {
+        Ok(source_of_frame.tag_instance(idx)?)
+    }
+
+    fn get_global_instance(&self, source_of_frame: &ModuleRef, idx: u32) -> Result<GlobalInstance> {
+        Ok(source_of_frame.global_instance(idx)?)
+    }
+
+    fn get_block_signature(
+        &self,
+        block_type: BlockType,
+        source_of_frame: &ModuleRef,
+    ) -> Result<(Vec<ValueTypes>, Vec<ValueTypes>)> {
+        Ok(match block_type {
+            BlockType::Value(ValueTypes::Unit) => (vec![], vec![]),
+            BlockType::Value(ty) => (vec![], vec![ty]),
+            BlockType::Index(idx) => {
+                let function_type = source_of_frame.function_type(idx)?;
+                (
+                    function_type.parameters().to_owned(),
+                    function_type.returns().to_owned(),
+                )
             }
-            None => self.store.memory_instances.clone(),
         })
     }
 
+    fn push_label(
+        &self,
+        continuation: u32,
+        block_type: BlockType,
+        source_instruction: LabelKind,
+        source_of_frame: &ModuleRef,
+    ) -> Result<()> {
+        self.push_label_with_handler(
+            continuation,
+            block_type,
+            source_instruction,
+            None,
+            source_of_frame,
+        )
+    }
+
+    // NOTE: Parameters of a block are placed above its label.
+    fn push_label_with_handler(
+        &self,
+        continuation: u32,
+        block_type: BlockType,
+        source_instruction: LabelKind,
+        handler: Option<Handler>,
+        source_of_frame: &ModuleRef,
+    ) -> Result<()> {
+        let (parameter_type, return_type) = self.get_block_signature(block_type, source_of_frame)?;
+        let mut parameters = vec![];
+        for _ in 0..parameter_type.len() {
+            parameters.push(self.stack.pop()?);
+        }
+        let label = StackEntry::new_label_with_handler(
+            continuation,
+            parameter_type,
+            return_type,
+            source_instruction,
+            handler,
+        );
+        self.stack.push(label)?;
+        self.stack.push_entries(&mut parameters)?;
+        Ok(())
+    }
+
+    // NOTE: Callee of tail call takes over the place of its caller on the stack.
+    fn call(
+        &self,
+        caller: &Frame,
+        function_instance: FunctionInstance,
+        mut arguments: Vec<StackEntry>,
+        is_tail_call: bool,
+    ) -> Result<()> {
+        let callee = if is_tail_call {
+            caller.tail_called();
+            self.stack.stack_ptr.set(caller.return_ptr);
+            Frame::new(
+                caller.return_ptr,
+                caller.prev_return_ptr,
+                function_instance,
+                &mut arguments,
+            )
+        } else {
+            Frame::new(
+                self.stack.stack_ptr(),
+                self.stack.frame_ptr(),
+                function_instance,
+                &mut arguments,
+            )
+        };
+        self.stack.push_frame(callee)
+    }
+
     fn evaluate_instructions(&mut self, frame: &Frame) -> Result<()> {
         use self::Isa::*;
         if let FunctionInstance::HostFn(ref f) = &frame.function_instance {
@@ -295,14 +704,27 @@ impl ModuleInstance {
                 self.get_local(&Indice::from(i))?;
                 arguments.push(self.stack.pop_value_ext());
             }
-            let results = f.call(arguments.as_slice());
+            // NOTE: Host function invoked directly is evaluated with this module.
+            let caller_module = self
+                .stack
+                .get_caller_module()
+                .unwrap_or_else(|| self.module.clone());
+            let caller = Caller::new(caller_module);
+            let results = f.call(&caller, arguments.as_slice())?;
             for r in results.into_iter() {
                 self.stack.push(StackEntry::new_value(r))?;
             }
             return Ok(());
         }
-        let source_of_frame = frame.function_instance.get_source_module_name();
+        let source_of_frame = frame.function_instance.module()?;
         while let Some(expression) = frame.pop_ref() {
+            if let Some(fuel) = self.fuel {
+                if fuel == 0 {
+                    frame.rewind();
+                    return Err(WasmError::Trap(Trap::OutOfFuel));
+                }
+                self.fuel = Some(fuel - 1);
+            }
             match Isa::from(*expression) {
                 Reserved => unreachable!(),
                 Unreachable => return Err(WasmError::Trap(Trap::Unreachable)),
@@ -310,19 +732,30 @@ impl ModuleInstance {
                     frame.jump_to_last();
                     break;
                 }
-                Else | End => {
+                // NOTE: Reaching to the next clause of try means the previous one has completed.
+                Else | End | Catch | CatchAll | Delegate => {
                     if frame.is_next_empty() {
                         break;
                     } else {
                         let mut buf_values = self.stack.pop_until_label()?;
                         let label = self.stack.pop_label_ext();
-                        if let Label {
-                            source_instruction: LabelKind::If,
-                            continuation,
-                            ..
-                        } = &label
-                        {
-                            frame.jump_to(*continuation);
+                        match label {
+                            Label {
+                                source_instruction: LabelKind::If,
+                                continuation,
+                                ..
+                            }
+                            | Label {
+                                source_instruction: LabelKind::Try,
+                                continuation,
+                                ..
+                            }
+                            | Label {
+                                source_instruction: LabelKind::Catch,
+                                continuation,
+                                ..
+                            } => frame.jump_to(continuation),
+                            _ => {}
                         };
                         self.stack.push_entries(&mut buf_values)?;
                     }
@@ -341,17 +774,14 @@ impl ModuleInstance {
                     // [19] NextInstruction         |  <- continuation
                     let start_of_label = frame.get_start_of_label();
                     let size = frame.pop_raw_u32()?;
-                    let block_type = frame.pop_runtime_type()?;
+                    let block_type = frame.pop_block_type()?;
                     let continuation = start_of_label + size;
-                    let label = StackEntry::new_label(continuation, block_type, LabelKind::Block);
-                    self.stack.push(label)?;
+                    self.push_label(continuation, block_type, LabelKind::Block, &source_of_frame)?;
                 }
                 Loop => {
                     let start_of_label = frame.get_start_of_label();
-                    let block_type = frame.pop_runtime_type()?;
-                    let label_continue =
-                        StackEntry::new_label(start_of_label, block_type, LabelKind::Loop);
-                    self.stack.push(label_continue)?;
+                    let block_type = frame.pop_block_type()?;
+                    self.push_label(start_of_label, block_type, LabelKind::Loop, &source_of_frame)?;
                 }
                 If => {
                     let cond = &self.stack.pop_value_ext();
@@ -359,14 +789,16 @@ impl ModuleInstance {
                     let if_size = frame.pop_raw_u32()?;
                     let else_size = frame.pop_raw_u32()?;
                     let continuation = start_of_label + if_size + else_size;
-                    let block_type = frame.pop_runtime_type()?;
+                    let block_type = frame.pop_block_type()?;
                     if cond.is_truthy() {
-                        let label = StackEntry::new_label(continuation, block_type, LabelKind::If);
-                        self.stack.push(label)?;
+                        self.push_label(continuation, block_type, LabelKind::If, &source_of_frame)?;
                     } else {
-                        let label =
-                            StackEntry::new_label(continuation, block_type, LabelKind::Else);
-                        self.stack.push(label)?;
+                        self.push_label(
+                            continuation,
+                            block_type,
+                            LabelKind::Else,
+                            &source_of_frame,
+                        )?;
                         let start_of_else = start_of_label + if_size;
                         if else_size > 0 {
                             frame.jump_to(start_of_else);
@@ -375,6 +807,49 @@ impl ModuleInstance {
                         }
                     }
                 }
+                Try => {
+                    let start_of_label = frame.get_start_of_label();
+                    let size = frame.pop_raw_u32()?;
+                    let block_type = frame.pop_block_type()?;
+                    let mut handlers = vec![];
+                    for _ in 0..frame.pop_raw_u32()? {
+                        let tag_idx = match frame.pop_raw_u32()? {
+                            idx if idx == u32::max_value() => None,
+                            idx => Some(idx),
+                        };
+                        let start_of_handler = start_of_label + frame.pop_raw_u32()?;
+                        handlers.push((tag_idx, start_of_handler));
+                    }
+                    let handler = match frame.pop_raw_u32()? {
+                        depth if depth == u32::max_value() => Handler::Catch(handlers),
+                        depth => Handler::Delegate(depth),
+                    };
+                    self.push_label_with_handler(
+                        start_of_label + size,
+                        block_type,
+                        LabelKind::Try,
+                        Some(handler),
+                        &source_of_frame,
+                    )?;
+                }
+                Throw => {
+                    let tag = self.get_tag_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    let mut values = vec![];
+                    for _ in 0..tag.function_type().parameters().len() {
+                        values.push(self.stack.pop_value_ext());
+                    }
+                    values.reverse();
+                    return Err(WasmError::Trap(Trap::Exception(Exception::new(tag, values))));
+                }
+                Rethrow => {
+                    let label = self.stack.get_label(&Indice::from(frame.pop_raw_u32()?))?;
+                    return match label.handler {
+                        Some(Handler::Caught(exception)) => {
+                            Err(WasmError::Trap(Trap::Exception(exception)))
+                        }
+                        _ => Err(WasmError::Trap(Trap::Unknown)),
+                    };
+                }
                 Br => {
                     let label = Indice::from(frame.pop_raw_u32()?);
                     let continuation = self.stack.jump_to_label(&label)?;
@@ -405,53 +880,30 @@ impl ModuleInstance {
                     let continuation = self.stack.jump_to_label(l)?;
                     frame.jump_to(continuation);
                 }
-                Call => {
+                Call | ReturnCall => {
+                    let is_tail_call = Isa::from(*expression) == ReturnCall;
                     let idx = Indice::from(frame.pop_raw_u32()?);
-                    let function_instance = match &source_of_frame {
-                        Some(module_name) => self
-                            .external_modules
-                            // FIXME: Drop owning of name to search something.
-                            .get_function_instance(&Some(module_name.to_owned()), idx.to_usize())
-                            .map(|x| x.clone())?,
-                        None => self.store.get_function_instance(&idx)?,
-                    };
+                    let function_instance = source_of_frame.function_instance(idx.to_u32())?;
                     let arity = function_instance.get_arity();
                     let mut arguments = vec![];
                     for _ in 0..arity {
                         arguments.push(self.stack.pop()?);
                     }
-                    let frame = Frame::new(
-                        self.stack.stack_ptr(),
-                        self.stack.frame_ptr(),
-                        function_instance,
-                        &mut arguments,
-                    );
-                    self.stack.push_frame(frame)?;
+                    self.call(frame, function_instance, arguments, is_tail_call)?;
                     break;
                 }
-                CallIndirect => {
+                CallIndirect | ReturnCallIndirect => {
+                    let is_tail_call = Isa::from(*expression) == ReturnCallIndirect;
                     let idx = Indice::from(frame.pop_raw_u32()?);
-                    // NOTE: Due to only single table instance allowed, `ta` always equal to 0.
-                    let ta = frame.get_table_address();
-                    let table = match &source_of_frame {
-                        Some(module_name) => self
-                            .external_modules
-                            .get_table_instance(&Some(module_name.to_owned()), &ta)?,
-                        None => self.store.get_table_at(&ta)?,
-                    };
+                    let table = self.get_table_instance(&source_of_frame, frame.pop_raw_u32()?)?;
                     let i = self.stack.pop_value_ext_i32();
                     if i > table.len() as i32 {
                         return Err(WasmError::Trap(Trap::UndefinedElement));
                     }
                     let function_instance = table.get_function_instance(i as u32)?;
-                    let mut arguments = {
+                    let arguments = {
                         let actual_fn_ty = function_instance.function_type_ref();
-                        let expect_fn_ty = &match &source_of_frame {
-                            Some(module_name) => self
-                                .external_modules
-                                .get_function_type(&Some(module_name.to_owned()), idx.to_u32())?,
-                            None => self.store.get_function_type(&idx)?.clone(),
-                        };
+                        let expect_fn_ty = &source_of_frame.function_type(idx.to_u32())?;
                         if actual_fn_ty != expect_fn_ty {
                             return Err(WasmError::Trap(Trap::IndirectCallTypeMismatch));
                         }
@@ -461,13 +913,7 @@ impl ModuleInstance {
                         }
                         arg
                     };
-                    let frame = Frame::new(
-                        self.stack.stack_ptr(),
-                        self.stack.frame_ptr(),
-                        function_instance,
-                        &mut arguments,
-                    );
-                    self.stack.push_frame(frame)?;
+                    self.call(frame, function_instance, arguments, is_tail_call)?;
                     break;
                 }
                 GetLocal => {
@@ -484,11 +930,42 @@ impl ModuleInstance {
                 }
                 GetGlobal => {
                     let idx = Indice::from(frame.pop_raw_u32()?);
-                    self.get_global(&idx)?;
+                    let value = self
+                        .get_global_instance(&source_of_frame, idx.to_u32())?
+                        .get_value();
+                    self.stack.push(StackEntry::new_value(value))?;
                 }
                 SetGlobal => {
                     let idx = Indice::from(frame.pop_raw_u32()?);
-                    self.set_global(&idx)?;
+                    let value = self.stack.pop_value_ext();
+                    self.get_global_instance(&source_of_frame, idx.to_u32())?
+                        .set_value(value);
+                }
+                TableGet => {
+                    let table = self.get_table_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    let i = self.stack.pop_value_ext_i32() as u32;
+                    self.stack.push(StackEntry::new_value(table.get(i)?))?;
+                }
+                TableSet => {
+                    let table = self.get_table_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    let value = self.stack.pop_value_ext();
+                    let i = self.stack.pop_value_ext_i32() as u32;
+                    table.set(i, value)?;
+                }
+                RefNull => {
+                    let value = Values::from(ValueTypes::from(*frame.pop_ref()?));
+                    self.stack.push(StackEntry::new_value(value))?;
+                }
+                RefIsNull => {
+                    let is_null = self.stack.pop_value_ext().is_null();
+                    self.stack
+                        .push(StackEntry::new_value(Values::I32(is_null as i32)))?;
+                }
+                RefFunc => {
+                    let idx = Indice::from(frame.pop_raw_u32()?);
+                    let function_instance = source_of_frame.function_instance(idx.to_u32())?;
+                    self.stack
+                        .push(StackEntry::new_value(Values::FuncRef(Some(function_instance))))?;
                 }
                 I32Const => {
                     let n = frame.pop_raw_u32()? as i32;
@@ -549,6 +1026,20 @@ impl ModuleInstance {
                 I32RotateRight | I64RotateRight => self.wasm_rotate_right()?,
                 F32Copysign | F64Copysign => self.copy_sign()?,
 
+                SelectTyped => {
+                    let len = frame.pop_raw_u32()?;
+                    for _ in 0..len {
+                        frame.pop_ref()?;
+                    }
+                    let cond = &self.stack.pop_value_ext();
+                    let false_br = self.stack.pop_value_ext();
+                    let true_br = self.stack.pop_value_ext();
+                    if cond.is_truthy() {
+                        self.stack.push(StackEntry::new_value(true_br))?;
+                    } else {
+                        self.stack.push(StackEntry::new_value(false_br))?;
+                    }
+                }
                 Select => {
                     let cond = &self.stack.pop_value_ext();
                     let false_br = self.stack.pop_value_ext();
@@ -592,69 +1083,82 @@ impl ModuleInstance {
                 I32Load => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.load_data_to_i32(offset, 32, true, &source_of_frame)?;
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.load_data_to_i32(offset, 32, true, &memory)?;
                 }
                 I32Load8Unsign => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.load_data_to_i32(offset, 8, false, &source_of_frame)?
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.load_data_to_i32(offset, 8, false, &memory)?
                 }
                 I32Load8Sign => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.load_data_to_i32(offset, 8, true, &source_of_frame)?
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.load_data_to_i32(offset, 8, true, &memory)?
                 }
                 I32Load16Unsign => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.load_data_to_i32(offset, 16, false, &source_of_frame)?
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.load_data_to_i32(offset, 16, false, &memory)?
                 }
                 I32Load16Sign => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.load_data_to_i32(offset, 16, true, &source_of_frame)?
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.load_data_to_i32(offset, 16, true, &memory)?
                 }
 
                 I64Load => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.load_data_to_i64(offset, 64, true, &source_of_frame)?;
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.load_data_to_i64(offset, 64, true, &memory)?;
                 }
                 I64Load8Unsign => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.load_data_to_i64(offset, 8, false, &source_of_frame)?
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.load_data_to_i64(offset, 8, false, &memory)?
                 }
                 I64Load8Sign => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.load_data_to_i64(offset, 8, true, &source_of_frame)?
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.load_data_to_i64(offset, 8, true, &memory)?
                 }
                 I64Load16Unsign => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.load_data_to_i64(offset, 16, false, &source_of_frame)?
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.load_data_to_i64(offset, 16, false, &memory)?
                 }
                 I64Load16Sign => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.load_data_to_i64(offset, 16, true, &source_of_frame)?
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.load_data_to_i64(offset, 16, true, &memory)?
                 }
                 I64Load32Unsign => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.load_data_to_i64(offset, 32, false, &source_of_frame)?
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.load_data_to_i64(offset, 32, false, &memory)?
                 }
                 I64Load32Sign => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.load_data_to_i64(offset, 32, true, &source_of_frame)?
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.load_data_to_i64(offset, 32, true, &memory)?
                 }
 
                 F32Load => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    let value = self.load_data_f32(offset, 32, &source_of_frame)?;
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    let value = self.load_data_f32(offset, 32, &memory)?;
                     self.stack
                         .push(StackEntry::new_value(Values::F32(value as f32)))?;
                 }
@@ -662,7 +1166,8 @@ impl ModuleInstance {
                 F64Load => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    let value = self.load_data_f64(offset, 64, &source_of_frame)?;
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    let value = self.load_data_f64(offset, 64, &memory)?;
                     self.stack
                         .push(StackEntry::new_value(Values::F64(value as f64)))?;
                 }
@@ -670,60 +1175,69 @@ impl ModuleInstance {
                 I32Store => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.store(32, offset, &source_of_frame)?;
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.store(32, offset, &memory)?;
                 }
                 F32Store => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.store(32, offset, &source_of_frame)?;
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.store(32, offset, &memory)?;
                 }
                 I64Store => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.store(64, offset, &source_of_frame)?;
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.store(64, offset, &memory)?;
                 }
                 F64Store => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.store(64, offset, &source_of_frame)?;
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.store(64, offset, &memory)?;
                 }
                 I32Store8 => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.store(8, offset, &source_of_frame)?;
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.store(8, offset, &memory)?;
                 }
                 I32Store16 => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.store(16, offset, &source_of_frame)?;
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.store(16, offset, &memory)?;
                 }
                 I64Store8 => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.store(8, offset, &source_of_frame)?;
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.store(8, offset, &memory)?;
                 }
                 I64Store16 => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.store(16, offset, &source_of_frame)?;
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.store(16, offset, &memory)?;
                 }
                 I64Store32 => {
                     let _align = frame.pop_raw_u32()?;
                     let offset = frame.pop_raw_u32()?;
-                    self.store(32, offset, &source_of_frame)?;
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    self.store(32, offset, &memory)?;
                 }
 
                 MemorySize => {
-                    let memory_instances = self.get_memory_instances(&source_of_frame)?;
-                    let page_size = memory_instances.size_by_pages();
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    let page_size = memory.size_by_pages();
                     self.stack
                         .push(StackEntry::new_value(Values::I32(page_size as i32)))?;
                 }
                 MemoryGrow => {
-                    let memory_instances = self.get_memory_instances(&source_of_frame)?;
-                    let page_size = memory_instances.size_by_pages();
+                    let memory = self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                    let page_size = memory.size_by_pages();
                     let n = self.stack.pop_value_ext_i32() as u32;
-                    let result = match memory_instances.memory_grow(n) {
+                    let result = match memory.memory_grow(n) {
                         Ok(()) => (page_size as i32),
                         Err(WasmError::Trap(Trap::FailToGrow)) => -1,
                         _ => unreachable!(),
@@ -734,6 +1248,9 @@ impl ModuleInstance {
 
                 I64ExtendUnsignI32 => self.extend_u32_to_i64()?,
                 I64ExtendSignI32 => self.extend_i32_to_i64()?,
+                I32Extend8Sign | I64Extend8Sign => self.extend8_sign()?,
+                I32Extend16Sign | I64Extend16Sign => self.extend16_sign()?,
+                I64Extend32Sign => self.extend32_sign()?,
                 F32ConvertSignI32 => self.convert_sign_i32_to_f32()?,
                 F32ConvertUnsignI32 => self.convert_unsign_i32_to_f32()?,
                 F64ConvertSignI64 => self.convert_sign_i64_to_f64()?,
@@ -753,6 +1270,125 @@ impl ModuleInstance {
                 I32TruncUnsignF64 => self.trunc_f64_to_unsign_i32()?,
                 I64TruncSignF32 => self.trunc_f32_to_sign_i64()?,
                 I64TruncUnsignF32 => self.trunc_f32_to_unsign_i64()?,
+
+                MiscPrefix => match MiscIsa::try_from(frame.pop_raw_u32()?)? {
+                    MiscIsa::I32TruncSatSignF32 => self.trunc_sat_f32_to_sign_i32()?,
+                    MiscIsa::I32TruncSatUnsignF32 => self.trunc_sat_f32_to_unsign_i32()?,
+                    MiscIsa::I32TruncSatSignF64 => self.trunc_sat_f64_to_sign_i32()?,
+                    MiscIsa::I32TruncSatUnsignF64 => self.trunc_sat_f64_to_unsign_i32()?,
+                    MiscIsa::I64TruncSatSignF32 => self.trunc_sat_f32_to_sign_i64()?,
+                    MiscIsa::I64TruncSatUnsignF32 => self.trunc_sat_f32_to_unsign_i64()?,
+                    MiscIsa::I64TruncSatSignF64 => self.trunc_sat_f64_to_sign_i64()?,
+                    MiscIsa::I64TruncSatUnsignF64 => self.trunc_sat_f64_to_unsign_i64()?,
+                    MiscIsa::MemoryInit => {
+                        let idx = frame.pop_raw_u32()?;
+                        let memory =
+                            self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                        let length = self.stack.pop_value_ext_i32() as u32;
+                        let source = self.stack.pop_value_ext_i32() as u32;
+                        let destination = self.stack.pop_value_ext_i32() as u32;
+                        self.get_data_instances(&source_of_frame).init(
+                            &memory,
+                            idx,
+                            destination,
+                            source,
+                            length,
+                        )?;
+                    }
+                    MiscIsa::DataDrop => {
+                        let idx = frame.pop_raw_u32()?;
+                        self.get_data_instances(&source_of_frame).drop_data(idx)?;
+                    }
+                    MiscIsa::MemoryCopy => {
+                        let destination_memory =
+                            self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                        let source_memory =
+                            self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                        let length = self.stack.pop_value_ext_i32() as u32;
+                        let source = self.stack.pop_value_ext_i32() as u32;
+                        let destination = self.stack.pop_value_ext_i32() as u32;
+                        destination_memory.copy(destination, &source_memory, source, length)?;
+                    }
+                    MiscIsa::MemoryFill => {
+                        let memory =
+                            self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                        let length = self.stack.pop_value_ext_i32() as u32;
+                        let value = self.stack.pop_value_ext_i32() as u8;
+                        let offset = self.stack.pop_value_ext_i32() as u32;
+                        memory.fill(offset, value, length)?;
+                    }
+                    MiscIsa::TableInit => {
+                        let idx = frame.pop_raw_u32()?;
+                        let table =
+                            self.get_table_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                        let length = self.stack.pop_value_ext_i32() as u32;
+                        let source = self.stack.pop_value_ext_i32() as u32;
+                        let destination = self.stack.pop_value_ext_i32() as u32;
+                        self.get_element_instances(&source_of_frame).init(
+                            &table,
+                            idx,
+                            destination,
+                            source,
+                            length,
+                        )?;
+                    }
+                    MiscIsa::ElemDrop => {
+                        let idx = frame.pop_raw_u32()?;
+                        self.get_element_instances(&source_of_frame)
+                            .drop_element(idx)?;
+                    }
+                    MiscIsa::TableCopy => {
+                        let destination_table =
+                            self.get_table_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                        let source_table =
+                            self.get_table_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                        let length = self.stack.pop_value_ext_i32() as u32;
+                        let source = self.stack.pop_value_ext_i32() as u32;
+                        let destination = self.stack.pop_value_ext_i32() as u32;
+                        destination_table.copy(destination, &source_table, source, length)?;
+                    }
+                    MiscIsa::TableGrow => {
+                        let table =
+                            self.get_table_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                        let delta = self.stack.pop_value_ext_i32() as u32;
+                        let init = self.stack.pop_value_ext();
+                        let result = match table.grow(delta, init) {
+                            Some(size) => size as i32,
+                            None => -1,
+                        };
+                        self.stack.push(StackEntry::new_value(Values::I32(result)))?;
+                    }
+                    MiscIsa::TableSize => {
+                        let table =
+                            self.get_table_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                        self.stack
+                            .push(StackEntry::new_value(Values::I32(table.len() as i32)))?;
+                    }
+                    MiscIsa::TableFill => {
+                        let table =
+                            self.get_table_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                        let length = self.stack.pop_value_ext_i32() as u32;
+                        let value = self.stack.pop_value_ext();
+                        let offset = self.stack.pop_value_ext_i32() as u32;
+                        table.fill(offset, value, length)?;
+                    }
+                },
+
+                SimdPrefix => {
+                    let simd_isa = SimdIsa::try_from(frame.pop_raw_u32()?)?;
+                    self.simd(simd_isa, frame, &source_of_frame)?
+                }
+
+                AtomicPrefix => match AtomicIsa::try_from(frame.pop_raw_u32()?)? {
+                    AtomicIsa::Fence => {}
+                    atomic => {
+                        let _align = frame.pop_raw_u32()?;
+                        let offset = frame.pop_raw_u32()?;
+                        let memory =
+                            self.get_memory_instance(&source_of_frame, frame.pop_raw_u32()?)?;
+                        self.atomic(atomic, offset, &memory)?;
+                    }
+                },
             };
         }
         Ok(())
@@ -763,22 +1399,40 @@ impl ModuleInstance {
             let frame = self.stack.pop_frame()?;
             // NOTE: Only fresh frame should be initialization.
             if frame.is_fresh() {
-                let return_type = frame
-                    .get_return_type()
-                    .first()
-                    .map_or(TYPE_UNIT, |x| x.to_owned());
-                let label = StackEntry::new_label(frame.last_ptr, return_type, LabelKind::Frame);
+                let label = StackEntry::new_label(
+                    frame.last_ptr,
+                    vec![],
+                    frame.get_return_type().to_owned(),
+                    LabelKind::Frame,
+                );
                 self.stack.frame_ptr.set(frame.return_ptr);
                 self.stack.push_entries(&mut frame.get_local_variables())?;
                 self.stack.push(label)?;
+                frame.initialized();
+            }
+            if let Err(err) = self.evaluate_instructions(&frame) {
+                if is_suspension(&err) {
+                    self.stack.push_frame(frame)?;
+                    return Err(err);
+                }
+                let trap_info = self.collect_trap_info(&err, &frame);
+                if let WasmError::Trap(Trap::Exception(ref exception)) = err {
+                    if self.unwind(frame, exception)? {
+                        continue;
+                    }
+                }
+                self.last_trap = Some(trap_info);
+                return Err(err);
             }
-            self.evaluate_instructions(&frame)?;
 
             let is_completed = frame.is_completed();
             if !is_completed {
                 self.stack.push_back_frame(frame);
                 continue;
             }
+            if frame.is_tail_called() {
+                continue;
+            }
             let count_of_returns = frame.get_return_count();
             let mut returns = vec![];
             for _ in 0..count_of_returns {
@@ -790,22 +1444,115 @@ impl ModuleInstance {
         Ok(())
     }
 
-    fn run_internal(&mut self, invoke: &str, mut arguments: Vec<Values>) -> Result<Values> {
-        match self
-            .internal_module
-            .get_export_by_key(invoke)
-            // FIXME: Remove to owning.
-            .map(|x| x.to_owned())
-        {
-            Some(ExternalInterface {
-                descriptor: ModuleDescriptor::ExportDescriptor(ExportDescriptor::Function(idx)),
-                ..
-            }) => {
+    // NOTE: Unwind labels and frames until a handler catches the exception,
+    // then the frame which has the handler is resumed from it.
+    fn unwind(&mut self, mut frame: Frame, exception: &Exception) -> Result<bool> {
+        let mut count_of_delegated = 0;
+        loop {
+            // NOTE: Frame of host function has no handler, so that it doesn't need the module.
+            let source_of_frame = frame.function_instance.module();
+            loop {
+                self.stack.pop_until_label()?;
+                let label = self.stack.pop_label_ext();
+                let handlers = match label.handler {
+                    _ if label.source_instruction == LabelKind::Frame => break,
+                    _ if count_of_delegated > 0 => {
+                        count_of_delegated -= 1;
+                        continue;
+                    }
+                    Some(Handler::Catch(ref handlers)) => handlers,
+                    Some(Handler::Delegate(depth)) => {
+                        count_of_delegated = depth;
+                        continue;
+                    }
+                    _ => continue,
+                };
+                for (tag_idx, start_of_handler) in handlers.iter() {
+                    // NOTE: Handler of catch_all doesn't receive values.
+                    let values = match tag_idx {
+                        Some(idx) => {
+                            let tag = self.get_tag_instance(source_of_frame.as_ref()?, *idx)?;
+                            if &tag != exception.tag() {
+                                continue;
+                            }
+                            exception.values()
+                        }
+                        None => &[],
+                    };
+                    self.stack.push(StackEntry::new_label_with_handler(
+                        label.continuation,
+                        label.parameter_type.clone(),
+                        label.return_type.clone(),
+                        LabelKind::Catch,
+                        Some(Handler::Caught(exception.clone())),
+                    ))?;
+                    for value in values.iter() {
+                        self.stack.push(StackEntry::new_value(value.clone()))?;
+                    }
+                    frame.jump_to(*start_of_handler);
+                    self.stack.push_frame(frame)?;
+                    return Ok(true);
+                }
+            }
+            // NOTE: Delegation to the label of function propagates the exception to its caller.
+            count_of_delegated = 0;
+            self.stack.update_frame_ptr(&frame);
+            frame = match self.stack.pop_frame() {
+                Some(caller) => caller,
+                None => return Ok(false),
+            };
+        }
+    }
+
+    fn collect_trap_info(&self, err: &WasmError, frame: &Frame) -> TrapInfo {
+        let backtrace = Some(frame)
+            .into_iter()
+            .chain(self.stack.frames().iter().rev())
+            .filter_map(|frame| self.trap_location(frame))
+            .collect();
+        TrapInfo {
+            error: err.clone(),
+            backtrace,
+        }
+    }
+
+    // NOTE: Frames of functions which belong to other modules are omitted.
+    fn trap_location(&self, frame: &Frame) -> Option<TrapLocation> {
+        let function_instances = &self.module.function_instances();
+        let function_index = function_instances
+            .iter()
+            .position(|f| f.ptr_eq(&frame.function_instance))?;
+        let name = match self.internal_module.names.function(function_index as u32) {
+            Some(name) => Some(name.to_owned()),
+            None => frame.function_instance.name(),
+        };
+        let source_maps = &self.internal_module.source_maps;
+        let count_of_imports = function_instances.len() - source_maps.len();
+        // NOTE: Pointer of the frame has already passed the opcode being evaluated.
+        let offset = match &frame.function_instance {
+            FunctionInstance::LocalFn(_) => function_index
+                .checked_sub(count_of_imports)
+                .and_then(|idx| source_maps.get(idx))
+                .and_then(|source_map| source_map.original_offset(frame.ptr().saturating_sub(1))),
+            FunctionInstance::HostFn(_) => None,
+        };
+        Some(TrapLocation {
+            function_index: function_index as u32,
+            name,
+            offset,
+        })
+    }
+
+    fn run_internal(&mut self, invoke: &str, mut arguments: Vec<Values>) -> Result<Vec<Values>> {
+        self.last_trap = None;
+        match self.module.export(invoke) {
+            Some(ExportDescriptor::Function(idx)) => {
                 let mut argument_entries = vec![];
                 while let Some(argument) = arguments.pop() {
                     argument_entries.push(StackEntry::new_value(argument));
                 }
-                let function_instance = self.store.get_function_instance(&idx).unwrap();
+                let function_instance = self.module.function_instance(idx.to_u32()).unwrap();
+                let count_of_returns = function_instance.get_return_count();
                 let frame = Frame::new(
                     self.stack.stack_ptr(),
                     self.stack.frame_ptr(),
@@ -813,32 +1560,157 @@ impl ModuleInstance {
                     &mut argument_entries,
                 );
                 let _ = self.stack.push_frame(frame);
-                match self.evaluate() {
-                    Ok(_) => match self.stack.pop_value() {
-                        Ok(v) => Ok(v),
-                        Err(_) => Ok(Values::I32(0)),
-                    },
-                    // Err(WasmError::Trap(Trap::StackUnderflow)) => Ok(Values::I32(0)),
-                    Err(err) => Err(err),
-                }
-            }
-            Some(ExternalInterface {
-                descriptor: ModuleDescriptor::ExportDescriptor(ExportDescriptor::Global(idx)),
-                ..
-            }) => self.store.get_global(&idx),
+                self.evaluate_invocation(count_of_returns)
+            }
+            Some(ExportDescriptor::Global(idx)) => {
+                Ok(vec![self.module.global_instance(idx.to_u32())?.get_value()])
+            }
             None => Err(WasmError::Trap(Trap::Notfound)),
             x => unimplemented!("{:?}", x),
         }
     }
 
+    fn evaluate_invocation(&mut self, count_of_returns: u32) -> Result<Vec<Values>> {
+        if let Err(err) = self.evaluate() {
+            if is_suspension(&err) {
+                self.suspended_returns = Some(count_of_returns);
+            }
+            return Err(err);
+        }
+        let mut returns = vec![];
+        for _ in 0..count_of_returns {
+            returns.push(self.stack.pop_value()?);
+        }
+        returns.reverse();
+        Ok(returns)
+    }
+
+    // NOTE: Discard frames which trapped invocation left behind,
+    // except an invocation suspended to be resumed.
+    fn settle(&mut self, result: Result<Vec<Values>>) -> Result<Vec<Values>> {
+        match result {
+            Err(ref err) if !is_suspension(err) => {
+                self.stack = Stack::new(self.stack.stack_size);
+            }
+            _ => {}
+        };
+        result
+    }
+
+    // NOTE: Evaluate on a stack of an invocation while keeping the one of the instance.
+    fn evaluate_on<F>(&mut self, stack: Stack, f: F) -> Result<Invocation>
+    where
+        F: FnOnce(&mut ModuleInstance) -> Result<Vec<Values>>,
+    {
+        let stack_of_instance = mem::replace(&mut self.stack, stack);
+        let suspended_returns = self.suspended_returns.take();
+        let result = f(self);
+        let stack = mem::replace(&mut self.stack, stack_of_instance);
+        let count_of_returns = mem::replace(&mut self.suspended_returns, suspended_returns);
+        match (result, count_of_returns) {
+            (Ok(values), _) => Ok(Invocation::Returned(values)),
+            (Err(WasmError::Trap(reason)), Some(count_of_returns)) => {
+                Ok(Invocation::Suspended(Suspended {
+                    stack,
+                    count_of_returns,
+                    reason,
+                }))
+            }
+            (Err(err), _) => Err(err),
+        }
+    }
+
+    /// Same as `run`, but invocation is suspended instead of trapping
+    /// when fuel runs out or host function yields.
+    pub fn invoke_resumable(
+        &mut self,
+        invoke: &str,
+        arguments: Vec<Values>,
+    ) -> Result<Invocation> {
+        let stack = Stack::new(self.stack.stack_size);
+        self.evaluate_on(stack, |vm| vm.run_internal(invoke, arguments))
+    }
+
+    /// Names given by "name" custom section of the module.
+    pub fn names(&self) -> &Names {
+        &self.internal_module.names
+    }
+
+    pub fn last_trap_location(&self) -> Option<&TrapLocation> {
+        self.last_trap.as_ref()?.backtrace.first()
+    }
+
+    /// Backtrace of the trap which the last invocation raised, apart from the error it returned.
+    pub fn trap_info(&self) -> Option<&TrapInfo> {
+        self.last_trap.as_ref()
+    }
+
+    pub fn has_export(&self, name: &str) -> bool {
+        self.module.export(name).is_some()
+    }
+
+    /// Find a global which the module exports by its name.
+    /// It is the same instance as other modules importing it observe.
+    pub fn global_instance(&self, name: &str) -> Option<GlobalInstance> {
+        match self.module.export(name)? {
+            ExportDescriptor::Global(idx) => self.module.global_instance(idx.to_u32()),
+            _ => None,
+        }
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Values> {
+        self.global_instance(name)
+            .map(|global_instance| global_instance.get_value())
+    }
+
+    /// Write the value to the exported global, which must be mutable.
+    pub fn set_global(&self, name: &str, value: Values) -> Result<()> {
+        self.global_instance(name)
+            .ok_or(WasmError::Trap(Trap::Notfound))?
+            .set(value)
+    }
+
+    /// Find a linear memory which the module exports by its name,
+    /// to exchange data with the module before and after calls.
+    pub fn memory_instance(&self, name: &str) -> Option<MemoryInstance> {
+        match self.module.export(name)? {
+            ExportDescriptor::Memory(idx) => self.module.memory_instance(idx.to_u32()),
+            _ => None,
+        }
+    }
+
+    /// Enables metering if it hasn't been yet.
+    /// Each instruction consumes a fuel,
+    /// and evaluation traps with `Trap::OutOfFuel` when it runs out.
+    pub fn add_fuel(&mut self, fuel: u64) {
+        self.fuel = Some(self.fuel.unwrap_or(0).saturating_add(fuel));
+    }
+
+    pub fn remaining_fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    /// Continue an invocation of `run` which ran out of fuel or yielded.
+    pub fn resume(&mut self) -> Result<Vec<Values>> {
+        let count_of_returns = self.suspended_returns.take().ok_or(Trap::NotSuspended)?;
+        let result = self.evaluate_invocation(count_of_returns);
+        self.settle(result)
+    }
+
     #[cfg(not(debug_assertions))]
-    pub fn run(&mut self, invoke: &str, arguments: Vec<Values>) -> Result<Values> {
-        self.run_internal(invoke, arguments)
+    pub fn run(&mut self, invoke: &str, arguments: Vec<Values>) -> Result<Vec<Values>> {
+        if self.suspended_returns.take().is_some() {
+            self.stack = Stack::new(self.stack.stack_size);
+        }
+        let result = self.run_internal(invoke, arguments);
+        self.settle(result)
     }
 
     #[cfg(debug_assertions)]
-    pub fn run(&mut self, invoke: &str, arguments: Vec<Values>) -> Result<Values> {
+    pub fn run(&mut self, invoke: &str, arguments: Vec<Values>) -> Result<Vec<Values>> {
+        self.suspended_returns = None;
         self.stack = Stack::new(self.stack.stack_size);
-        self.run_internal(invoke, arguments)
+        let result = self.run_internal(invoke, arguments);
+        self.settle(result)
     }
 }
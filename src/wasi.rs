@@ -0,0 +1,650 @@
+use alloc::rc::Rc;
+use caller::Caller;
+use core::cell::RefCell;
+use error::{HostError, Trap};
+use function::{FunctionInstance, FunctionType};
+use module::ExternalModule;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use value::Values;
+use value_type::{ValueTypes, TYPE_I32, TYPE_I64};
+
+pub const WASI_MODULE_NAME: &str = "wasi_snapshot_preview1";
+
+/// Raised through `Trap::HostError` when a command module calls `proc_exit`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProcExit(pub u32);
+
+type Errno = u16;
+
+const ERRNO_SUCCESS: Errno = 0;
+const ERRNO_ACCES: Errno = 2;
+const ERRNO_BADF: Errno = 8;
+const ERRNO_EXIST: Errno = 20;
+const ERRNO_FAULT: Errno = 21;
+const ERRNO_INVAL: Errno = 28;
+const ERRNO_IO: Errno = 29;
+const ERRNO_ISDIR: Errno = 31;
+const ERRNO_NOENT: Errno = 44;
+const ERRNO_NOTDIR: Errno = 54;
+const ERRNO_NOTSUP: Errno = 58;
+const ERRNO_OVERFLOW: Errno = 61;
+const ERRNO_SPIPE: Errno = 70;
+const ERRNO_NOTCAPABLE: Errno = 76;
+
+const CLOCK_REALTIME: u32 = 0;
+const CLOCK_MONOTONIC: u32 = 1;
+
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+const FILETYPE_DIRECTORY: u8 = 3;
+const FILETYPE_REGULAR_FILE: u8 = 4;
+
+const FDFLAGS_APPEND: u32 = 1;
+
+const OFLAGS_CREAT: u32 = 1;
+const OFLAGS_DIRECTORY: u32 = 2;
+const OFLAGS_EXCL: u32 = 4;
+const OFLAGS_TRUNC: u32 = 8;
+
+const RIGHTS_FD_READ: u64 = 1 << 1;
+const RIGHTS_FD_SEEK: u64 = 1 << 2;
+const RIGHTS_FD_WRITE: u64 = 1 << 6;
+const RIGHTS_PATH_OPEN: u64 = 1 << 13;
+// NOTE: Every rights defined by preview1, which preopened directories are granted.
+const RIGHTS_ALL: u64 = (1 << 29) - 1;
+
+const PREOPENTYPE_DIR: u8 = 0;
+
+enum Descriptor {
+  Stdin,
+  Stdout,
+  Stderr,
+  Directory {
+    preopened_name: Option<String>,
+    path: PathBuf,
+  },
+  File(File),
+}
+
+// NOTE: Rights of a descriptor, and ones which descriptors opened through it can have.
+#[derive(Clone, Copy)]
+struct Rights {
+  base: u64,
+  inheriting: u64,
+}
+
+impl Rights {
+  fn new(base: u64) -> Self {
+    Rights {
+      base,
+      inheriting: 0,
+    }
+  }
+}
+
+struct WasiContext {
+  arguments: Vec<String>,
+  environment: Vec<String>,
+  descriptors: BTreeMap<u32, (Descriptor, Rights)>,
+  next_fd: u32,
+  started_at: Instant,
+}
+
+impl WasiContext {
+  fn new(
+    arguments: Vec<String>,
+    environment: Vec<(String, String)>,
+    preopens: Vec<(String, PathBuf)>,
+  ) -> Self {
+    let mut descriptors = BTreeMap::new();
+    descriptors.insert(0, (Descriptor::Stdin, Rights::new(RIGHTS_FD_READ)));
+    descriptors.insert(1, (Descriptor::Stdout, Rights::new(RIGHTS_FD_WRITE)));
+    descriptors.insert(2, (Descriptor::Stderr, Rights::new(RIGHTS_FD_WRITE)));
+    let mut next_fd = 3;
+    for (name, path) in preopens {
+      let descriptor = Descriptor::Directory {
+        preopened_name: Some(name),
+        path,
+      };
+      let rights = Rights {
+        base: RIGHTS_ALL,
+        inheriting: RIGHTS_ALL,
+      };
+      descriptors.insert(next_fd, (descriptor, rights));
+      next_fd += 1;
+    }
+    WasiContext {
+      arguments,
+      environment: environment
+        .into_iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect(),
+      descriptors,
+      next_fd,
+      started_at: Instant::now(),
+    }
+  }
+
+  // NOTE: Descriptor which must have been granted all of the rights.
+  fn get_descriptor(&mut self, fd: u32, rights: u64) -> Result<&mut Descriptor, Errno> {
+    match self.descriptors.get_mut(&fd).ok_or(ERRNO_BADF)? {
+      (descriptor, granted) if granted.base & rights == rights => Ok(descriptor),
+      _ => Err(ERRNO_NOTCAPABLE),
+    }
+  }
+
+  fn get_rights(&self, fd: u32) -> Result<Rights, Errno> {
+    self
+      .descriptors
+      .get(&fd)
+      .map(|(_, rights)| *rights)
+      .ok_or(ERRNO_BADF)
+  }
+
+  fn insert_descriptor(&mut self, descriptor: Descriptor, rights: Rights) -> u32 {
+    let fd = self.next_fd;
+    self.descriptors.insert(fd, (descriptor, rights));
+    self.next_fd += 1;
+    fd
+  }
+}
+
+fn from_io_error(error: &io::Error) -> Errno {
+  match error.kind() {
+    io::ErrorKind::NotFound => ERRNO_NOENT,
+    io::ErrorKind::PermissionDenied => ERRNO_ACCES,
+    io::ErrorKind::AlreadyExists => ERRNO_EXIST,
+    io::ErrorKind::InvalidInput => ERRNO_INVAL,
+    _ => ERRNO_IO,
+  }
+}
+
+fn argument_u32(arguments: &[Values], idx: usize) -> Result<u32, Errno> {
+  match arguments.get(idx) {
+    Some(Values::I32(v)) => Ok(*v as u32),
+    _ => Err(ERRNO_INVAL),
+  }
+}
+
+fn argument_u64(arguments: &[Values], idx: usize) -> Result<u64, Errno> {
+  match arguments.get(idx) {
+    Some(Values::I64(v)) => Ok(*v as u64),
+    _ => Err(ERRNO_INVAL),
+  }
+}
+
+// NOTE: Checked before buffers are allocated by lengths which modules pass.
+fn check_range(caller: &Caller, ptr: u32, len: u32) -> Result<(), Errno> {
+  let end = ptr.checked_add(len).ok_or(ERRNO_FAULT)?;
+  match caller.memory_data_size(0) {
+    Some(size) if end <= size => Ok(()),
+    _ => Err(ERRNO_FAULT),
+  }
+}
+
+fn offset(ptr: u32, idx: u32, size: u32) -> Result<u32, Errno> {
+  idx
+    .checked_mul(size)
+    .and_then(|x| ptr.checked_add(x))
+    .ok_or(ERRNO_FAULT)
+}
+
+fn read_bytes(caller: &Caller, ptr: u32, len: u32) -> Result<Vec<u8>, Errno> {
+  check_range(caller, ptr, len)?;
+  let mut buf = vec![0; len as usize];
+  caller
+    .read_memory(0, ptr, &mut buf)
+    .map_err(|_| ERRNO_FAULT)?;
+  Ok(buf)
+}
+
+fn read_u32(caller: &Caller, ptr: u32) -> Result<u32, Errno> {
+  let mut buf = [0; 4];
+  caller
+    .read_memory(0, ptr, &mut buf)
+    .map_err(|_| ERRNO_FAULT)?;
+  Ok(u32::from_le_bytes(buf))
+}
+
+fn write_bytes(caller: &Caller, ptr: u32, bytes: &[u8]) -> Result<(), Errno> {
+  caller.write_memory(0, ptr, bytes).map_err(|_| ERRNO_FAULT)
+}
+
+fn write_u32(caller: &Caller, ptr: u32, value: u32) -> Result<(), Errno> {
+  write_bytes(caller, ptr, &value.to_le_bytes())
+}
+
+fn write_u64(caller: &Caller, ptr: u32, value: u64) -> Result<(), Errno> {
+  write_bytes(caller, ptr, &value.to_le_bytes())
+}
+
+// NOTE: Pairs of (buf, buf_len) which iovec/ciovec array points to.
+fn read_iovecs(caller: &Caller, iovs: u32, iovs_len: u32) -> Result<Vec<(u32, u32)>, Errno> {
+  check_range(caller, iovs, iovs_len.checked_mul(8).ok_or(ERRNO_FAULT)?)?;
+  let mut iovecs = vec![];
+  for i in 0..iovs_len {
+    let ptr = offset(iovs, i, 8)?;
+    iovecs.push((read_u32(caller, ptr)?, read_u32(caller, offset(ptr, 1, 4)?)?));
+  }
+  Ok(iovecs)
+}
+
+// NOTE: Resolve a path relative to a directory, which must not escape from it
+// even through symbolic links under the directory.
+fn resolve_path(directory: &Path, path: &str) -> Result<PathBuf, Errno> {
+  let mut resolved = directory.to_path_buf();
+  let mut depth = 0;
+  for component in Path::new(path).components() {
+    match component {
+      Component::Normal(name) => {
+        resolved.push(name);
+        depth += 1;
+      }
+      Component::CurDir => {}
+      Component::ParentDir if depth > 0 => {
+        resolved.pop();
+        depth -= 1;
+      }
+      Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+        return Err(ERRNO_NOTCAPABLE)
+      }
+    }
+  }
+  let directory = directory.canonicalize().map_err(|e| from_io_error(&e))?;
+  let canonical = match resolved.symlink_metadata() {
+    Ok(_) => resolved.canonicalize(),
+    // NOTE: A file to be created doesn't exist yet, so that its parent is resolved instead.
+    Err(_) => match (resolved.parent(), resolved.file_name()) {
+      (Some(parent), Some(name)) => parent.canonicalize().map(|parent| parent.join(name)),
+      _ => return Err(ERRNO_NOENT),
+    },
+  }
+  .map_err(|e| from_io_error(&e))?;
+  if canonical.starts_with(&directory) {
+    Ok(canonical)
+  } else {
+    Err(ERRNO_NOTCAPABLE)
+  }
+}
+
+fn write_strings(
+  caller: &Caller,
+  strings: &[String],
+  pointers_ptr: u32,
+  buf_ptr: u32,
+) -> Result<(), Errno> {
+  let mut buf_ptr = buf_ptr;
+  for (i, string) in strings.iter().enumerate() {
+    write_u32(caller, offset(pointers_ptr, i as u32, 4)?, buf_ptr)?;
+    write_bytes(caller, buf_ptr, string.as_bytes())?;
+    let end = offset(buf_ptr, string.len() as u32, 1)?;
+    write_bytes(caller, end, &[0])?;
+    buf_ptr = offset(end, 1, 1)?;
+  }
+  Ok(())
+}
+
+fn write_sizes_of_strings(
+  caller: &Caller,
+  strings: &[String],
+  count_ptr: u32,
+  size_ptr: u32,
+) -> Result<(), Errno> {
+  let size = strings.iter().map(|s| s.len() as u32 + 1).sum();
+  write_u32(caller, count_ptr, strings.len() as u32)?;
+  write_u32(caller, size_ptr, size)
+}
+
+fn args_get(context: &mut WasiContext, caller: &Caller, arguments: &[Values]) -> Result<(), Errno> {
+  let argv = argument_u32(arguments, 0)?;
+  let argv_buf = argument_u32(arguments, 1)?;
+  write_strings(caller, &context.arguments, argv, argv_buf)
+}
+
+fn args_sizes_get(
+  context: &mut WasiContext,
+  caller: &Caller,
+  arguments: &[Values],
+) -> Result<(), Errno> {
+  let argc = argument_u32(arguments, 0)?;
+  let argv_buf_size = argument_u32(arguments, 1)?;
+  write_sizes_of_strings(caller, &context.arguments, argc, argv_buf_size)
+}
+
+fn environ_get(
+  context: &mut WasiContext,
+  caller: &Caller,
+  arguments: &[Values],
+) -> Result<(), Errno> {
+  let environ = argument_u32(arguments, 0)?;
+  let environ_buf = argument_u32(arguments, 1)?;
+  write_strings(caller, &context.environment, environ, environ_buf)
+}
+
+fn environ_sizes_get(
+  context: &mut WasiContext,
+  caller: &Caller,
+  arguments: &[Values],
+) -> Result<(), Errno> {
+  let environc = argument_u32(arguments, 0)?;
+  let environ_buf_size = argument_u32(arguments, 1)?;
+  write_sizes_of_strings(caller, &context.environment, environc, environ_buf_size)
+}
+
+fn clock_time_get(
+  context: &mut WasiContext,
+  caller: &Caller,
+  arguments: &[Values],
+) -> Result<(), Errno> {
+  let clock_id = argument_u32(arguments, 0)?;
+  let time = argument_u32(arguments, 2)?;
+  let elapsed = match clock_id {
+    CLOCK_REALTIME => SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map_err(|_| ERRNO_IO)?,
+    CLOCK_MONOTONIC => context.started_at.elapsed(),
+    _ => return Err(ERRNO_NOTSUP),
+  };
+  let nanos = elapsed.as_secs() * 1_000_000_000 + u64::from(elapsed.subsec_nanos());
+  write_u64(caller, time, nanos)
+}
+
+fn random_get(_: &mut WasiContext, caller: &Caller, arguments: &[Values]) -> Result<(), Errno> {
+  let buf = argument_u32(arguments, 0)?;
+  let buf_len = argument_u32(arguments, 1)?;
+  check_range(caller, buf, buf_len)?;
+  let mut bytes = vec![0; buf_len as usize];
+  File::open("/dev/urandom")
+    .and_then(|mut f| f.read_exact(&mut bytes))
+    .map_err(|e| from_io_error(&e))?;
+  write_bytes(caller, buf, &bytes)
+}
+
+fn fd_write(context: &mut WasiContext, caller: &Caller, arguments: &[Values]) -> Result<(), Errno> {
+  let fd = argument_u32(arguments, 0)?;
+  let iovs = argument_u32(arguments, 1)?;
+  let iovs_len = argument_u32(arguments, 2)?;
+  let nwritten = argument_u32(arguments, 3)?;
+  let descriptor = context.get_descriptor(fd, RIGHTS_FD_WRITE)?;
+  let mut written: u32 = 0;
+  for (buf, buf_len) in read_iovecs(caller, iovs, iovs_len)? {
+    written = written.checked_add(buf_len).ok_or(ERRNO_OVERFLOW)?;
+    let bytes = read_bytes(caller, buf, buf_len)?;
+    let result = match descriptor {
+      Descriptor::Stdout => io::stdout().write_all(&bytes),
+      Descriptor::Stderr => io::stderr().write_all(&bytes),
+      Descriptor::File(file) => file.write_all(&bytes),
+      Descriptor::Stdin => return Err(ERRNO_BADF),
+      Descriptor::Directory { .. } => return Err(ERRNO_ISDIR),
+    };
+    result.map_err(|e| from_io_error(&e))?;
+  }
+  write_u32(caller, nwritten, written)
+}
+
+fn fd_read(context: &mut WasiContext, caller: &Caller, arguments: &[Values]) -> Result<(), Errno> {
+  let fd = argument_u32(arguments, 0)?;
+  let iovs = argument_u32(arguments, 1)?;
+  let iovs_len = argument_u32(arguments, 2)?;
+  let nread = argument_u32(arguments, 3)?;
+  let descriptor = context.get_descriptor(fd, RIGHTS_FD_READ)?;
+  let mut read: u32 = 0;
+  for (buf, buf_len) in read_iovecs(caller, iovs, iovs_len)? {
+    check_range(caller, buf, buf_len)?;
+    let mut bytes = vec![0; buf_len as usize];
+    let result = match descriptor {
+      Descriptor::Stdin => io::stdin().read(&mut bytes),
+      Descriptor::File(file) => file.read(&mut bytes),
+      Descriptor::Stdout | Descriptor::Stderr => return Err(ERRNO_BADF),
+      Descriptor::Directory { .. } => return Err(ERRNO_ISDIR),
+    };
+    let len = result.map_err(|e| from_io_error(&e))?;
+    write_bytes(caller, buf, &bytes[..len])?;
+    read = read.checked_add(len as u32).ok_or(ERRNO_OVERFLOW)?;
+    if len < bytes.len() {
+      break;
+    }
+  }
+  write_u32(caller, nread, read)
+}
+
+fn fd_close(context: &mut WasiContext, _: &Caller, arguments: &[Values]) -> Result<(), Errno> {
+  let fd = argument_u32(arguments, 0)?;
+  context.descriptors.remove(&fd).ok_or(ERRNO_BADF)?;
+  Ok(())
+}
+
+fn fd_seek(context: &mut WasiContext, caller: &Caller, arguments: &[Values]) -> Result<(), Errno> {
+  let fd = argument_u32(arguments, 0)?;
+  let offset = argument_u64(arguments, 1)? as i64;
+  let whence = argument_u32(arguments, 2)?;
+  let newoffset = argument_u32(arguments, 3)?;
+  let position = match whence {
+    0 if offset >= 0 => SeekFrom::Start(offset as u64),
+    1 => SeekFrom::Current(offset),
+    2 => SeekFrom::End(offset),
+    _ => return Err(ERRNO_INVAL),
+  };
+  let position = match context.get_descriptor(fd, RIGHTS_FD_SEEK)? {
+    Descriptor::File(file) => file.seek(position).map_err(|e| from_io_error(&e))?,
+    Descriptor::Directory { .. } => return Err(ERRNO_ISDIR),
+    _ => return Err(ERRNO_SPIPE),
+  };
+  write_u64(caller, newoffset, position)
+}
+
+fn fd_fdstat_get(
+  context: &mut WasiContext,
+  caller: &Caller,
+  arguments: &[Values],
+) -> Result<(), Errno> {
+  let fd = argument_u32(arguments, 0)?;
+  let stat = argument_u32(arguments, 1)?;
+  let filetype = match context.get_descriptor(fd, 0)? {
+    Descriptor::Stdin | Descriptor::Stdout | Descriptor::Stderr => FILETYPE_CHARACTER_DEVICE,
+    Descriptor::Directory { .. } => FILETYPE_DIRECTORY,
+    Descriptor::File(_) => FILETYPE_REGULAR_FILE,
+  };
+  // NOTE: Layout of fdstat is
+  // {fs_filetype: u8, fs_flags: u16, fs_rights_base: u64, fs_rights_inheriting: u64}
+  let mut bytes = [0; 24];
+  bytes[0] = filetype;
+  let rights = context.get_rights(fd)?;
+  bytes[8..16].copy_from_slice(&rights.base.to_le_bytes());
+  bytes[16..24].copy_from_slice(&rights.inheriting.to_le_bytes());
+  write_bytes(caller, stat, &bytes)
+}
+
+fn fd_prestat_get(
+  context: &mut WasiContext,
+  caller: &Caller,
+  arguments: &[Values],
+) -> Result<(), Errno> {
+  let fd = argument_u32(arguments, 0)?;
+  let prestat = argument_u32(arguments, 1)?;
+  match context.get_descriptor(fd, 0)? {
+    Descriptor::Directory {
+      preopened_name: Some(name),
+      ..
+    } => {
+      write_bytes(caller, prestat, &[PREOPENTYPE_DIR, 0, 0, 0])?;
+      write_u32(caller, offset(prestat, 1, 4)?, name.len() as u32)
+    }
+    _ => Err(ERRNO_BADF),
+  }
+}
+
+fn fd_prestat_dir_name(
+  context: &mut WasiContext,
+  caller: &Caller,
+  arguments: &[Values],
+) -> Result<(), Errno> {
+  let fd = argument_u32(arguments, 0)?;
+  let path = argument_u32(arguments, 1)?;
+  let path_len = argument_u32(arguments, 2)?;
+  match context.get_descriptor(fd, 0)? {
+    Descriptor::Directory {
+      preopened_name: Some(name),
+      ..
+    } => {
+      if (path_len as usize) < name.len() {
+        return Err(ERRNO_INVAL);
+      }
+      write_bytes(caller, path, name.as_bytes())
+    }
+    _ => Err(ERRNO_BADF),
+  }
+}
+
+fn path_open(
+  context: &mut WasiContext,
+  caller: &Caller,
+  arguments: &[Values],
+) -> Result<(), Errno> {
+  let dirfd = argument_u32(arguments, 0)?;
+  let path = argument_u32(arguments, 2)?;
+  let path_len = argument_u32(arguments, 3)?;
+  let oflags = argument_u32(arguments, 4)?;
+  let rights_base = argument_u64(arguments, 5)?;
+  let rights_inheriting = argument_u64(arguments, 6)?;
+  let fdflags = argument_u32(arguments, 7)?;
+  let opened_fd = argument_u32(arguments, 8)?;
+
+  let path = String::from_utf8(read_bytes(caller, path, path_len)?).map_err(|_| ERRNO_INVAL)?;
+  let path = match context.get_descriptor(dirfd, RIGHTS_PATH_OPEN)? {
+    Descriptor::Directory { path: directory, .. } => resolve_path(directory, &path)?,
+    _ => return Err(ERRNO_NOTDIR),
+  };
+  // NOTE: Opened descriptor can't have rights which the directory doesn't pass on.
+  let inheritable = context.get_rights(dirfd)?.inheriting;
+  if (rights_base | rights_inheriting) & !inheritable != 0 {
+    return Err(ERRNO_NOTCAPABLE);
+  }
+  let rights = Rights {
+    base: rights_base,
+    inheriting: rights_inheriting,
+  };
+
+  let descriptor = if oflags & OFLAGS_DIRECTORY != 0 || path.is_dir() {
+    if !path.is_dir() {
+      return Err(ERRNO_NOTDIR);
+    }
+    Descriptor::Directory {
+      preopened_name: None,
+      path,
+    }
+  } else {
+    let writable = rights_base & RIGHTS_FD_WRITE != 0;
+    let file = OpenOptions::new()
+      .read(rights_base & RIGHTS_FD_READ != 0 || !writable)
+      .write(writable)
+      .append(fdflags & FDFLAGS_APPEND != 0)
+      .create(oflags & OFLAGS_CREAT != 0)
+      .create_new(oflags & OFLAGS_CREAT != 0 && oflags & OFLAGS_EXCL != 0)
+      .truncate(oflags & OFLAGS_TRUNC != 0)
+      .open(path)
+      .map_err(|e| from_io_error(&e))?;
+    Descriptor::File(file)
+  };
+  let fd = context.insert_descriptor(descriptor, rights);
+  write_u32(caller, opened_fd, fd)
+}
+
+fn define<F>(
+  context: &Rc<RefCell<WasiContext>>,
+  name: &str,
+  parameters: Vec<ValueTypes>,
+  f: F,
+) -> FunctionInstance
+where
+  F: Fn(&mut WasiContext, &Caller, &[Values]) -> Result<(), Errno> + 'static,
+{
+  let context = context.clone();
+  FunctionInstance::new_host_fn(
+    Some(name.to_owned()),
+    FunctionType::new(parameters, vec![TYPE_I32]),
+    move |caller: &Caller, arguments: &[Values]| {
+      let errno = match f(&mut context.borrow_mut(), caller, arguments) {
+        Ok(()) => ERRNO_SUCCESS,
+        Err(errno) => errno,
+      };
+      Ok(vec![Values::I32(i32::from(errno))])
+    },
+  )
+}
+
+fn proc_exit(_: &Caller, arguments: &[Values]) -> Result<Vec<Values>, Trap> {
+  match arguments {
+    [Values::I32(code)] => Err(HostError::new(ProcExit(*code as u32)).into()),
+    _ => Err(Trap::TypeMismatch),
+  }
+}
+
+/// Host module of `wasi_snapshot_preview1`.
+/// `preopens` are pairs of a name seen from the module and a directory of host,
+/// which will be accessible under it.
+pub fn create_wasi(
+  arguments: Vec<String>,
+  environment: Vec<(String, String)>,
+  preopens: Vec<(String, PathBuf)>,
+) -> ExternalModule {
+  let context = Rc::new(RefCell::new(WasiContext::new(
+    arguments,
+    environment,
+    preopens,
+  )));
+  let i32x2 = || vec![TYPE_I32, TYPE_I32];
+  let i32x4 = || vec![TYPE_I32, TYPE_I32, TYPE_I32, TYPE_I32];
+  ExternalModule::new(
+    vec![
+      define(&context, "args_get", i32x2(), args_get),
+      define(&context, "args_sizes_get", i32x2(), args_sizes_get),
+      define(&context, "environ_get", i32x2(), environ_get),
+      define(&context, "environ_sizes_get", i32x2(), environ_sizes_get),
+      define(
+        &context,
+        "clock_time_get",
+        vec![TYPE_I32, TYPE_I64, TYPE_I32],
+        clock_time_get,
+      ),
+      define(&context, "random_get", i32x2(), random_get),
+      define(&context, "fd_write", i32x4(), fd_write),
+      define(&context, "fd_read", i32x4(), fd_read),
+      define(&context, "fd_close", vec![TYPE_I32], fd_close),
+      define(
+        &context,
+        "fd_seek",
+        vec![TYPE_I32, TYPE_I64, TYPE_I32, TYPE_I32],
+        fd_seek,
+      ),
+      define(&context, "fd_fdstat_get", i32x2(), fd_fdstat_get),
+      define(&context, "fd_prestat_get", i32x2(), fd_prestat_get),
+      define(
+        &context,
+        "fd_prestat_dir_name",
+        vec![TYPE_I32, TYPE_I32, TYPE_I32],
+        fd_prestat_dir_name,
+      ),
+      define(
+        &context,
+        "path_open",
+        vec![
+          TYPE_I32, TYPE_I32, TYPE_I32, TYPE_I32, TYPE_I32, TYPE_I64, TYPE_I64, TYPE_I32, TYPE_I32,
+        ],
+        path_open,
+      ),
+      FunctionInstance::new_host_fn(
+        Some("proc_exit".to_owned()),
+        FunctionType::new(vec![TYPE_I32], vec![]),
+        proc_exit,
+      ),
+    ],
+    vec![],
+    vec![],
+    vec![],
+    vec![],
+  )
+}
@@ -0,0 +1,191 @@
+use super::instruction::{Immediate, Opcode};
+use alloc::vec::Vec;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Index<'a> {
+  Num(u32),
+  Id(&'a str),
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct FunctionSignature<'a> {
+  pub parameters: Vec<(Option<&'a str>, u8)>,
+  pub results: Vec<u8>,
+}
+
+// NOTE: Either of reference to a type or inline signature can be omitted.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct TypeUse<'a> {
+  pub index: Option<Index<'a>>,
+  pub signature: Option<FunctionSignature<'a>>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BlockKind {
+  Block,
+  Loop,
+  If,
+  Try,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Operand<'a> {
+  Nothing,
+  Index(Index<'a>),
+  LabelTable(Vec<Index<'a>>),
+  CallIndirect(Option<Index<'a>>, TypeUse<'a>),
+  // NOTE: Memory index, and alignment kept as it is written, not by power of 2.
+  Memory(Index<'a>, Option<u32>, u32),
+  // NOTE: Memory argument followed by lane index.
+  MemoryLane(Index<'a>, Option<u32>, u32, u8),
+  Lane(u8),
+  // NOTE: Operand of `v128.const` or lanes of `i8x16.shuffle`, as bytes in little endian.
+  V128(u128),
+  // NOTE: Pair of indices, like destination and source of `table.copy`.
+  Indices(Index<'a>, Index<'a>),
+  ReferenceType(u8),
+  ValueTypes(Vec<u8>),
+  I32(i32),
+  I64(i64),
+  F32(u32),
+  F64(u64),
+}
+
+// NOTE: Folded instructions are flattened while parsing,
+// so structured instructions are represented by its begin and end.
+#[derive(Debug, PartialEq, Clone)]
+pub enum InstructionKind<'a> {
+  Begin(BlockKind, Option<&'a str>, TypeUse<'a>),
+  Else(Option<&'a str>),
+  // NOTE: Clauses of try, where delegate ends the block instead of end.
+  Catch(Index<'a>),
+  CatchAll,
+  Delegate(Index<'a>),
+  End(Option<&'a str>),
+  Plain(Opcode, Immediate, Operand<'a>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Instruction<'a> {
+  pub kind: InstructionKind<'a>,
+  pub line: u32,
+}
+
+pub type Expression<'a> = Vec<Instruction<'a>>;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Limits {
+  pub min: u32,
+  pub max: Option<u32>,
+  pub shared: bool,
+  // NOTE: Only memories can customize their page size.
+  pub page_size: Option<u32>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct GlobalType {
+  pub value_type: u8,
+  pub is_mutable: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ImportDescriptor<'a> {
+  Function(TypeUse<'a>),
+  Table(Limits, u8),
+  Memory(Limits),
+  Global(GlobalType),
+  Tag(TypeUse<'a>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Import<'a> {
+  pub module: Vec<u8>,
+  pub name: Vec<u8>,
+  pub descriptor: ImportDescriptor<'a>,
+  pub line: u32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Function<'a> {
+  pub type_use: TypeUse<'a>,
+  pub locals: Vec<(Option<&'a str>, u8)>,
+  pub body: Expression<'a>,
+  pub line: u32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Global<'a> {
+  pub global_type: GlobalType,
+  pub init: Expression<'a>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExportKind {
+  Function,
+  Table,
+  Memory,
+  Global,
+  Tag,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Export<'a> {
+  pub name: Vec<u8>,
+  pub kind: ExportKind,
+  pub index: Index<'a>,
+  pub line: u32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ElementMode<'a> {
+  Active(Index<'a>, Expression<'a>),
+  Passive,
+  Declarative,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ElementInit<'a> {
+  Functions(Vec<Index<'a>>),
+  Expressions(Vec<Expression<'a>>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Element<'a> {
+  pub mode: ElementMode<'a>,
+  pub element_type: u8,
+  pub init: ElementInit<'a>,
+  pub line: u32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Data<'a> {
+  pub memory: Index<'a>,
+  // NOTE: Passive segments don't have offset.
+  pub offset: Option<Expression<'a>>,
+  pub bytes: Vec<u8>,
+  pub line: u32,
+}
+
+// NOTE: Identifiers of each index space are placed in order of index.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Module<'a> {
+  pub types: Vec<FunctionSignature<'a>>,
+  pub type_ids: Vec<Option<&'a str>>,
+  pub function_ids: Vec<Option<&'a str>>,
+  pub table_ids: Vec<Option<&'a str>>,
+  pub memory_ids: Vec<Option<&'a str>>,
+  pub global_ids: Vec<Option<&'a str>>,
+  pub tag_ids: Vec<Option<&'a str>>,
+  pub element_ids: Vec<Option<&'a str>>,
+  pub data_ids: Vec<Option<&'a str>>,
+  pub imports: Vec<Import<'a>>,
+  pub functions: Vec<Function<'a>>,
+  pub tables: Vec<(Limits, u8)>,
+  pub memories: Vec<Limits>,
+  pub globals: Vec<Global<'a>>,
+  pub tags: Vec<(TypeUse<'a>, u32)>,
+  pub exports: Vec<Export<'a>>,
+  pub start: Option<(Index<'a>, u32)>,
+  pub elements: Vec<Element<'a>>,
+  pub datas: Vec<Data<'a>>,
+}
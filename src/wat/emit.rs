@@ -0,0 +1,548 @@
+use super::ast::*;
+use super::instruction::{Immediate, Opcode};
+use super::malformed;
+use alloc::vec::Vec;
+use encode::{write_bytes, write_sleb128, write_uleb128};
+use error::Result;
+
+type Signature = (Vec<u8>, Vec<u8>);
+
+fn write_limits(buf: &mut Vec<u8>, limits: &Limits) {
+  // NOTE: Bit 1 of the flag tells the memory is shared,
+  // and bit 3 tells the exponent of its page size follows.
+  let shared = if limits.shared { 0x02 } else { 0x00 };
+  let page_size = if limits.page_size.is_some() { 0x08 } else { 0x00 };
+  match limits.max {
+    Some(max) => {
+      buf.push(0x01 | shared | page_size);
+      write_uleb128(buf, u64::from(limits.min));
+      write_uleb128(buf, u64::from(max));
+    }
+    None => {
+      buf.push(shared | page_size);
+      write_uleb128(buf, u64::from(limits.min));
+    }
+  }
+  if let Some(page_size) = limits.page_size {
+    write_uleb128(buf, u64::from(page_size.trailing_zeros()));
+  }
+}
+
+fn write_global_type(buf: &mut Vec<u8>, global_type: &GlobalType) {
+  buf.push(global_type.value_type);
+  buf.push(if global_type.is_mutable { 0x01 } else { 0x00 });
+}
+
+// NOTE: Empty sections are omitted.
+fn write_section(buf: &mut Vec<u8>, id: u8, count: usize, content: &[u8]) {
+  if count == 0 {
+    return;
+  }
+  let mut section = vec![];
+  write_uleb128(&mut section, count as u64);
+  section.extend_from_slice(content);
+  buf.push(id);
+  write_bytes(buf, &section);
+}
+
+fn resolve(ids: &[Option<&str>], index: &Index, line: u32) -> Result<u32> {
+  match index {
+    Index::Num(idx) => Ok(*idx),
+    Index::Id(id) => ids
+      .iter()
+      .position(|x| *x == Some(id))
+      .map(|idx| idx as u32)
+      .ok_or_else(|| malformed(line)),
+  }
+}
+
+// NOTE: Label is referred by its depth from the innermost block.
+fn resolve_label(labels: &[Option<&str>], index: &Index, line: u32) -> Result<u32> {
+  match index {
+    Index::Num(idx) => Ok(*idx),
+    Index::Id(id) => labels
+      .iter()
+      .rposition(|x| *x == Some(id))
+      .map(|idx| (labels.len() - 1 - idx) as u32)
+      .ok_or_else(|| malformed(line)),
+  }
+}
+
+fn signature_of(signature: &FunctionSignature) -> Signature {
+  (
+    signature.parameters.iter().map(|(_, x)| *x).collect(),
+    signature.results.clone(),
+  )
+}
+
+struct Emitter<'a, 'b> {
+  module: &'b Module<'a>,
+  types: Vec<Signature>,
+  uses_data_count: bool,
+}
+
+impl<'a, 'b> Emitter<'a, 'b> {
+  // NOTE: Type of inline signature is appended unless the same one is defined.
+  fn type_use(&mut self, type_use: &TypeUse, line: u32) -> Result<u32> {
+    match (&type_use.index, &type_use.signature) {
+      (Some(index), signature) => {
+        let idx = resolve(&self.module.type_ids, index, line)?;
+        let defined = self.types.get(idx as usize).ok_or_else(|| malformed(line))?;
+        match signature {
+          Some(signature) if *defined != signature_of(signature) => Err(malformed(line)),
+          _ => Ok(idx),
+        }
+      }
+      (None, signature) => {
+        let signature = signature
+          .as_ref()
+          .map(signature_of)
+          .unwrap_or_default();
+        match self.types.iter().position(|x| *x == signature) {
+          Some(idx) => Ok(idx as u32),
+          None => {
+            self.types.push(signature);
+            Ok(self.types.len() as u32 - 1)
+          }
+        }
+      }
+    }
+  }
+
+  fn block_type(&mut self, buf: &mut Vec<u8>, type_use: &TypeUse, line: u32) -> Result<()> {
+    if type_use.index.is_none() {
+      match &type_use.signature {
+        None => {
+          buf.push(0x40);
+          return Ok(());
+        }
+        Some(signature) if signature.parameters.is_empty() && signature.results.len() <= 1 => {
+          buf.push(*signature.results.first().unwrap_or(&0x40));
+          return Ok(());
+        }
+        Some(_) => {}
+      }
+    }
+    let idx = self.type_use(type_use, line)?;
+    write_sleb128(buf, i64::from(idx));
+    Ok(())
+  }
+
+  fn memory_argument(
+    &self,
+    buf: &mut Vec<u8>,
+    natural_align: u32,
+    memory: &Index,
+    align: Option<u32>,
+    offset: u32,
+    line: u32,
+  ) -> Result<()> {
+    let align = align.map(u32::trailing_zeros).unwrap_or(natural_align);
+    let memory = resolve(&self.module.memory_ids, memory, line)?;
+    // NOTE: Bit 6 of alignment tells an explicit memory index follows.
+    if memory == 0 {
+      write_uleb128(buf, u64::from(align));
+    } else {
+      write_uleb128(buf, u64::from(align | 0x40));
+      write_uleb128(buf, u64::from(memory));
+    }
+    write_uleb128(buf, u64::from(offset));
+    Ok(())
+  }
+
+  fn operand(
+    &mut self,
+    buf: &mut Vec<u8>,
+    immediate: Immediate,
+    operand: &Operand,
+    locals: &[Option<&str>],
+    labels: &[Option<&str>],
+    line: u32,
+  ) -> Result<()> {
+    let label = |index: &Index| resolve_label(labels, index, line);
+    match (immediate, operand) {
+      (Immediate::Nothing, _) => {}
+      (Immediate::Reserved, _) => buf.push(0x00),
+      (Immediate::Select, Operand::Nothing) => {}
+      (Immediate::Select, Operand::ValueTypes(value_types)) => write_bytes(buf, value_types),
+      (Immediate::ReferenceType, Operand::ReferenceType(reference_type)) => {
+        buf.push(*reference_type)
+      }
+      (Immediate::MemoryIndex, Operand::Index(index)) => {
+        let idx = resolve(&self.module.memory_ids, index, line)?;
+        write_uleb128(buf, u64::from(idx));
+      }
+      (Immediate::MemoryIndices, Operand::Indices(destination, source)) => {
+        let destination = resolve(&self.module.memory_ids, destination, line)?;
+        let source = resolve(&self.module.memory_ids, source, line)?;
+        write_uleb128(buf, u64::from(destination));
+        write_uleb128(buf, u64::from(source));
+      }
+      (Immediate::Data, Operand::Index(index)) => {
+        self.uses_data_count = true;
+        let idx = resolve(&self.module.data_ids, index, line)?;
+        write_uleb128(buf, u64::from(idx));
+      }
+      (Immediate::MemoryInit, Operand::Indices(memory, data)) => {
+        self.uses_data_count = true;
+        let data = resolve(&self.module.data_ids, data, line)?;
+        let memory = resolve(&self.module.memory_ids, memory, line)?;
+        write_uleb128(buf, u64::from(data));
+        write_uleb128(buf, u64::from(memory));
+      }
+      (Immediate::Table, Operand::Index(index)) => {
+        let idx = resolve(&self.module.table_ids, index, line)?;
+        write_uleb128(buf, u64::from(idx));
+      }
+      (Immediate::TableIndices, Operand::Indices(destination, source)) => {
+        let destination = resolve(&self.module.table_ids, destination, line)?;
+        let source = resolve(&self.module.table_ids, source, line)?;
+        write_uleb128(buf, u64::from(destination));
+        write_uleb128(buf, u64::from(source));
+      }
+      (Immediate::TableInit, Operand::Indices(table, element)) => {
+        let element = resolve(&self.module.element_ids, element, line)?;
+        let table = resolve(&self.module.table_ids, table, line)?;
+        write_uleb128(buf, u64::from(element));
+        write_uleb128(buf, u64::from(table));
+      }
+      (Immediate::Element, Operand::Index(index)) => {
+        let idx = resolve(&self.module.element_ids, index, line)?;
+        write_uleb128(buf, u64::from(idx));
+      }
+      (Immediate::Label, Operand::Index(index)) => write_uleb128(buf, u64::from(label(index)?)),
+      (Immediate::LabelTable, Operand::LabelTable(indices)) => {
+        write_uleb128(buf, indices.len() as u64 - 1);
+        for index in indices {
+          write_uleb128(buf, u64::from(label(index)?));
+        }
+      }
+      (Immediate::Function, Operand::Index(index)) => {
+        let idx = resolve(&self.module.function_ids, index, line)?;
+        write_uleb128(buf, u64::from(idx));
+      }
+      (Immediate::CallIndirect, Operand::CallIndirect(table, type_use)) => {
+        let type_idx = self.type_use(type_use, line)?;
+        let table_idx = match table {
+          Some(table) => resolve(&self.module.table_ids, table, line)?,
+          None => 0,
+        };
+        write_uleb128(buf, u64::from(type_idx));
+        write_uleb128(buf, u64::from(table_idx));
+      }
+      (Immediate::Local, Operand::Index(index)) => {
+        write_uleb128(buf, u64::from(resolve(locals, index, line)?));
+      }
+      (Immediate::Global, Operand::Index(index)) => {
+        let idx = resolve(&self.module.global_ids, index, line)?;
+        write_uleb128(buf, u64::from(idx));
+      }
+      (Immediate::Tag, Operand::Index(index)) => {
+        let idx = resolve(&self.module.tag_ids, index, line)?;
+        write_uleb128(buf, u64::from(idx));
+      }
+      (Immediate::Memory(natural_align), Operand::Memory(memory, align, offset)) => {
+        self.memory_argument(buf, natural_align, memory, *align, *offset, line)?
+      }
+      (Immediate::MemoryLane(natural_align), Operand::MemoryLane(memory, align, offset, lane)) => {
+        self.memory_argument(buf, natural_align, memory, *align, *offset, line)?;
+        buf.push(*lane);
+      }
+      (Immediate::Lane, Operand::Lane(lane)) => buf.push(*lane),
+      (Immediate::V128, Operand::V128(bits)) | (Immediate::Shuffle, Operand::V128(bits)) => {
+        buf.extend_from_slice(&bits.to_le_bytes())
+      }
+      (Immediate::I32, Operand::I32(value)) => write_sleb128(buf, i64::from(*value)),
+      (Immediate::I64, Operand::I64(value)) => write_sleb128(buf, *value),
+      (Immediate::F32, Operand::F32(bits)) => buf.extend_from_slice(&bits.to_le_bytes()),
+      (Immediate::F64, Operand::F64(bits)) => buf.extend_from_slice(&bits.to_le_bytes()),
+      _ => unreachable!("{:?} {:?}", immediate, operand),
+    };
+    Ok(())
+  }
+
+  fn expression(
+    &mut self,
+    buf: &mut Vec<u8>,
+    expression: &[Instruction],
+    locals: &[Option<&str>],
+  ) -> Result<()> {
+    let mut labels: Vec<Option<&str>> = vec![];
+    for Instruction { kind, line } in expression {
+      match kind {
+        InstructionKind::Begin(kind, label, type_use) => {
+          buf.push(match kind {
+            BlockKind::Block => 0x02,
+            BlockKind::Loop => 0x03,
+            BlockKind::If => 0x04,
+            BlockKind::Try => 0x06,
+          });
+          self.block_type(buf, type_use, *line)?;
+          labels.push(*label);
+        }
+        InstructionKind::Else(_) => buf.push(0x05),
+        InstructionKind::Catch(index) => {
+          buf.push(0x07);
+          let idx = resolve(&self.module.tag_ids, index, *line)?;
+          write_uleb128(buf, u64::from(idx));
+        }
+        InstructionKind::CatchAll => buf.push(0x19),
+        // NOTE: Label of delegate is counted from outside of the try block.
+        InstructionKind::Delegate(index) => {
+          labels.pop();
+          buf.push(0x18);
+          write_uleb128(buf, u64::from(resolve_label(&labels, index, *line)?));
+        }
+        InstructionKind::End(_) => {
+          labels.pop();
+          buf.push(0x0b);
+        }
+        InstructionKind::Plain(opcode, immediate, operand) => {
+          match opcode {
+            Opcode::Single(code) => buf.push(*code),
+            Opcode::Prefixed(prefix, code) => {
+              buf.push(*prefix);
+              write_uleb128(buf, u64::from(*code));
+            }
+          };
+          self.operand(buf, *immediate, operand, locals, &labels, *line)?;
+        }
+      }
+    }
+    buf.push(0x0b);
+    Ok(())
+  }
+
+  fn function_body(&mut self, buf: &mut Vec<u8>, function: &Function, type_idx: u32) -> Result<()> {
+    let count_of_parameters = self.types[type_idx as usize].0.len();
+    let mut locals: Vec<Option<&str>> = match &function.type_use.signature {
+      Some(signature) => signature.parameters.iter().map(|(id, _)| *id).collect(),
+      None => vec![None; count_of_parameters],
+    };
+    for (id, _) in function.locals.iter() {
+      if id.is_some() && locals.contains(id) {
+        return Err(malformed(function.line));
+      }
+      locals.push(*id);
+    }
+
+    let mut body = vec![];
+    let mut groups: Vec<(u32, u8)> = vec![];
+    for (_, value_type) in function.locals.iter() {
+      if let Some((count, last)) = groups.last_mut() {
+        if last == value_type {
+          *count += 1;
+          continue;
+        }
+      }
+      groups.push((1, *value_type));
+    }
+    write_uleb128(&mut body, groups.len() as u64);
+    for (count, value_type) in groups {
+      write_uleb128(&mut body, u64::from(count));
+      body.push(value_type);
+    }
+    self.expression(&mut body, &function.body, &locals)?;
+    write_bytes(buf, &body);
+    Ok(())
+  }
+
+  fn emit(mut self) -> Result<Vec<u8>> {
+    let module = self.module;
+
+    let mut imports = vec![];
+    for import in module.imports.iter() {
+      write_bytes(&mut imports, &import.module);
+      write_bytes(&mut imports, &import.name);
+      match &import.descriptor {
+        ImportDescriptor::Function(type_use) => {
+          imports.push(0x00);
+          let idx = self.type_use(type_use, import.line)?;
+          write_uleb128(&mut imports, u64::from(idx));
+        }
+        ImportDescriptor::Table(limits, element_type) => {
+          imports.push(0x01);
+          imports.push(*element_type);
+          write_limits(&mut imports, limits);
+        }
+        ImportDescriptor::Memory(limits) => {
+          imports.push(0x02);
+          write_limits(&mut imports, limits);
+        }
+        ImportDescriptor::Global(global_type) => {
+          imports.push(0x03);
+          write_global_type(&mut imports, global_type);
+        }
+        ImportDescriptor::Tag(type_use) => {
+          imports.push(0x04);
+          imports.push(0x00);
+          let idx = self.type_use(type_use, import.line)?;
+          write_uleb128(&mut imports, u64::from(idx));
+        }
+      }
+    }
+
+    let mut functions = vec![];
+    let mut type_indices = vec![];
+    for function in module.functions.iter() {
+      let idx = self.type_use(&function.type_use, function.line)?;
+      write_uleb128(&mut functions, u64::from(idx));
+      type_indices.push(idx);
+    }
+
+    let mut tables = vec![];
+    for (limits, element_type) in module.tables.iter() {
+      tables.push(*element_type);
+      write_limits(&mut tables, limits);
+    }
+
+    let mut memories = vec![];
+    for limits in module.memories.iter() {
+      write_limits(&mut memories, limits);
+    }
+
+    let mut tags = vec![];
+    for (type_use, line) in module.tags.iter() {
+      tags.push(0x00);
+      let idx = self.type_use(type_use, *line)?;
+      write_uleb128(&mut tags, u64::from(idx));
+    }
+
+    let mut globals = vec![];
+    for global in module.globals.iter() {
+      write_global_type(&mut globals, &global.global_type);
+      self.expression(&mut globals, &global.init, &[])?;
+    }
+
+    let mut exports = vec![];
+    for export in module.exports.iter() {
+      write_bytes(&mut exports, &export.name);
+      let (kind, ids) = match export.kind {
+        ExportKind::Function => (0x00, &module.function_ids),
+        ExportKind::Table => (0x01, &module.table_ids),
+        ExportKind::Memory => (0x02, &module.memory_ids),
+        ExportKind::Global => (0x03, &module.global_ids),
+        ExportKind::Tag => (0x04, &module.tag_ids),
+      };
+      exports.push(kind);
+      write_uleb128(&mut exports, u64::from(resolve(ids, &export.index, export.line)?));
+    }
+
+    let mut start = vec![];
+    if let Some((index, line)) = &module.start {
+      write_uleb128(&mut start, u64::from(resolve(&module.function_ids, index, *line)?));
+    }
+
+    let mut elements = vec![];
+    for element in module.elements.iter() {
+      // NOTE: Flags of segment are chosen by its mode and how elements are given.
+      let (expressions_flag, element_kind) = match element.init {
+        ElementInit::Functions(_) => (0b000, 0x00),
+        ElementInit::Expressions(_) => (0b100, element.element_type),
+      };
+      match &element.mode {
+        ElementMode::Active(table, offset) => {
+          let idx = resolve(&module.table_ids, table, element.line)?;
+          if idx == 0 && element.element_type == 0x70 {
+            write_uleb128(&mut elements, expressions_flag);
+            self.expression(&mut elements, offset, &[])?;
+          } else {
+            write_uleb128(&mut elements, 0b010 | expressions_flag);
+            write_uleb128(&mut elements, u64::from(idx));
+            self.expression(&mut elements, offset, &[])?;
+            elements.push(element_kind);
+          }
+        }
+        ElementMode::Passive => {
+          write_uleb128(&mut elements, 0b001 | expressions_flag);
+          elements.push(element_kind);
+        }
+        ElementMode::Declarative => {
+          write_uleb128(&mut elements, 0b011 | expressions_flag);
+          elements.push(element_kind);
+        }
+      }
+      match &element.init {
+        ElementInit::Functions(functions) => {
+          write_uleb128(&mut elements, functions.len() as u64);
+          for function in functions.iter() {
+            let idx = resolve(&module.function_ids, function, element.line)?;
+            write_uleb128(&mut elements, u64::from(idx));
+          }
+        }
+        ElementInit::Expressions(expressions) => {
+          write_uleb128(&mut elements, expressions.len() as u64);
+          for expression in expressions.iter() {
+            self.expression(&mut elements, expression, &[])?;
+          }
+        }
+      }
+    }
+
+    let mut codes = vec![];
+    for (function, idx) in module.functions.iter().zip(type_indices) {
+      self.function_body(&mut codes, function, idx)?;
+    }
+
+    let mut datas = vec![];
+    for data in module.datas.iter() {
+      match &data.offset {
+        Some(offset) => {
+          let idx = resolve(&module.memory_ids, &data.memory, data.line)?;
+          if idx == 0 {
+            datas.push(0x00);
+          } else {
+            datas.push(0x02);
+            write_uleb128(&mut datas, u64::from(idx));
+          }
+          self.expression(&mut datas, offset, &[])?;
+        }
+        None => datas.push(0x01),
+      }
+      write_bytes(&mut datas, &data.bytes);
+    }
+
+    // NOTE: Type section is written at last, because implicit types are appended while emitting.
+    let mut types = vec![];
+    for (parameters, results) in self.types.iter() {
+      types.push(0x60);
+      write_bytes(&mut types, parameters);
+      write_bytes(&mut types, results);
+    }
+
+    let mut buf = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+    write_section(&mut buf, 1, self.types.len(), &types);
+    write_section(&mut buf, 2, module.imports.len(), &imports);
+    write_section(&mut buf, 3, module.functions.len(), &functions);
+    write_section(&mut buf, 4, module.tables.len(), &tables);
+    write_section(&mut buf, 5, module.memories.len(), &memories);
+    write_section(&mut buf, 13, module.tags.len(), &tags);
+    write_section(&mut buf, 6, module.globals.len(), &globals);
+    write_section(&mut buf, 7, module.exports.len(), &exports);
+    if module.start.is_some() {
+      buf.push(8);
+      write_bytes(&mut buf, &start);
+    }
+    write_section(&mut buf, 9, module.elements.len(), &elements);
+    // NOTE: DataCount section is emitted only when instructions refer data segments.
+    if self.uses_data_count {
+      buf.push(12);
+      let mut data_count = vec![];
+      write_uleb128(&mut data_count, module.datas.len() as u64);
+      write_bytes(&mut buf, &data_count);
+    }
+    write_section(&mut buf, 10, module.functions.len(), &codes);
+    write_section(&mut buf, 11, module.datas.len(), &datas);
+    Ok(buf)
+  }
+}
+
+pub fn emit(module: &Module) -> Result<Vec<u8>> {
+  let types = module.types.iter().map(signature_of).collect();
+  Emitter {
+    module,
+    types,
+    uses_data_count: false,
+  }
+  .emit()
+}
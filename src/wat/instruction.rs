@@ -0,0 +1,652 @@
+use alloc::string::String;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Opcode {
+  Single(u8),
+  // NOTE: Prefix byte followed by u32 which identifies an instruction.
+  Prefixed(u8, u32),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Immediate {
+  Nothing,
+  Label,
+  LabelTable,
+  Function,
+  CallIndirect,
+  Local,
+  Global,
+  Tag,
+  // NOTE: Natural alignment by power of 2.
+  Memory(u32),
+  // NOTE: Memory index which can be omitted for the first memory.
+  MemoryIndex,
+  // NOTE: Destination and source of `memory.copy`.
+  MemoryIndices,
+  Data,
+  // NOTE: Index of data segment followed by memory index.
+  MemoryInit,
+  // NOTE: Table index which can be omitted for the first table.
+  Table,
+  TableIndices,
+  // NOTE: Index of element segment followed by table index.
+  TableInit,
+  Element,
+  ReferenceType,
+  // NOTE: Typed select is given by types of result.
+  Select,
+  // NOTE: Reserved zero byte of `atomic.fence`.
+  Reserved,
+  // NOTE: Natural alignment by power of 2, and lane index follows memory argument.
+  MemoryLane(u32),
+  Lane,
+  V128,
+  Shuffle,
+  I32,
+  I64,
+  F32,
+  F64,
+}
+
+// NOTE: Instructions without immediate, which are placed in order of opcode from 0x45.
+const NUMERIC_INSTRUCTIONS: [&str; 128] = [
+  "i32.eqz",
+  "i32.eq",
+  "i32.ne",
+  "i32.lt_s",
+  "i32.lt_u",
+  "i32.gt_s",
+  "i32.gt_u",
+  "i32.le_s",
+  "i32.le_u",
+  "i32.ge_s",
+  "i32.ge_u",
+  "i64.eqz",
+  "i64.eq",
+  "i64.ne",
+  "i64.lt_s",
+  "i64.lt_u",
+  "i64.gt_s",
+  "i64.gt_u",
+  "i64.le_s",
+  "i64.le_u",
+  "i64.ge_s",
+  "i64.ge_u",
+  "f32.eq",
+  "f32.ne",
+  "f32.lt",
+  "f32.gt",
+  "f32.le",
+  "f32.ge",
+  "f64.eq",
+  "f64.ne",
+  "f64.lt",
+  "f64.gt",
+  "f64.le",
+  "f64.ge",
+  "i32.clz",
+  "i32.ctz",
+  "i32.popcnt",
+  "i32.add",
+  "i32.sub",
+  "i32.mul",
+  "i32.div_s",
+  "i32.div_u",
+  "i32.rem_s",
+  "i32.rem_u",
+  "i32.and",
+  "i32.or",
+  "i32.xor",
+  "i32.shl",
+  "i32.shr_s",
+  "i32.shr_u",
+  "i32.rotl",
+  "i32.rotr",
+  "i64.clz",
+  "i64.ctz",
+  "i64.popcnt",
+  "i64.add",
+  "i64.sub",
+  "i64.mul",
+  "i64.div_s",
+  "i64.div_u",
+  "i64.rem_s",
+  "i64.rem_u",
+  "i64.and",
+  "i64.or",
+  "i64.xor",
+  "i64.shl",
+  "i64.shr_s",
+  "i64.shr_u",
+  "i64.rotl",
+  "i64.rotr",
+  "f32.abs",
+  "f32.neg",
+  "f32.ceil",
+  "f32.floor",
+  "f32.trunc",
+  "f32.nearest",
+  "f32.sqrt",
+  "f32.add",
+  "f32.sub",
+  "f32.mul",
+  "f32.div",
+  "f32.min",
+  "f32.max",
+  "f32.copysign",
+  "f64.abs",
+  "f64.neg",
+  "f64.ceil",
+  "f64.floor",
+  "f64.trunc",
+  "f64.nearest",
+  "f64.sqrt",
+  "f64.add",
+  "f64.sub",
+  "f64.mul",
+  "f64.div",
+  "f64.min",
+  "f64.max",
+  "f64.copysign",
+  "i32.wrap_i64",
+  "i32.trunc_f32_s",
+  "i32.trunc_f32_u",
+  "i32.trunc_f64_s",
+  "i32.trunc_f64_u",
+  "i64.extend_i32_s",
+  "i64.extend_i32_u",
+  "i64.trunc_f32_s",
+  "i64.trunc_f32_u",
+  "i64.trunc_f64_s",
+  "i64.trunc_f64_u",
+  "f32.convert_i32_s",
+  "f32.convert_i32_u",
+  "f32.convert_i64_s",
+  "f32.convert_i64_u",
+  "f32.demote_f64",
+  "f64.convert_i32_s",
+  "f64.convert_i32_u",
+  "f64.convert_i64_s",
+  "f64.convert_i64_u",
+  "f64.promote_f32",
+  "i32.reinterpret_f32",
+  "i64.reinterpret_f64",
+  "f32.reinterpret_i32",
+  "f64.reinterpret_i64",
+  "i32.extend8_s",
+  "i32.extend16_s",
+  "i64.extend8_s",
+  "i64.extend16_s",
+  "i64.extend32_s",
+];
+
+// NOTE: Translate names used before the spec settled, like `get_local` or `i32.trunc_s/f32`.
+fn normalize(name: &str) -> Option<String> {
+  let normalized = match name {
+    "get_local" => "local.get",
+    "set_local" => "local.set",
+    "tee_local" => "local.tee",
+    "get_global" => "global.get",
+    "set_global" => "global.set",
+    "current_memory" => "memory.size",
+    "grow_memory" => "memory.grow",
+    _ => {
+      let idx = name.find('/')?;
+      let (operator, source) = (&name[..idx], &name[idx + 1..]);
+      return Some(if operator.ends_with("_s") || operator.ends_with("_u") {
+        let (operator, sign) = operator.split_at(operator.len() - 2);
+        format!("{}_{}{}", operator, source, sign)
+      } else {
+        format!("{}_{}", operator, source)
+      });
+    }
+  };
+  Some(normalized.into())
+}
+
+fn lookup_normalized(name: &str) -> Option<(u8, Immediate)> {
+  use self::Immediate::*;
+  let instruction = match name {
+    "unreachable" => (0x00, Nothing),
+    "nop" => (0x01, Nothing),
+    "throw" => (0x08, Tag),
+    "rethrow" => (0x09, Label),
+    "br" => (0x0c, Label),
+    "br_if" => (0x0d, Label),
+    "br_table" => (0x0e, LabelTable),
+    "return" => (0x0f, Nothing),
+    "call" => (0x10, Function),
+    "call_indirect" => (0x11, CallIndirect),
+    "return_call" => (0x12, Function),
+    "return_call_indirect" => (0x13, CallIndirect),
+    "drop" => (0x1a, Nothing),
+    "select" => (0x1b, Select),
+    "local.get" => (0x20, Local),
+    "local.set" => (0x21, Local),
+    "local.tee" => (0x22, Local),
+    "global.get" => (0x23, Global),
+    "global.set" => (0x24, Global),
+    "table.get" => (0x25, Table),
+    "table.set" => (0x26, Table),
+    "i32.load" => (0x28, Memory(2)),
+    "i64.load" => (0x29, Memory(3)),
+    "f32.load" => (0x2a, Memory(2)),
+    "f64.load" => (0x2b, Memory(3)),
+    "i32.load8_s" => (0x2c, Memory(0)),
+    "i32.load8_u" => (0x2d, Memory(0)),
+    "i32.load16_s" => (0x2e, Memory(1)),
+    "i32.load16_u" => (0x2f, Memory(1)),
+    "i64.load8_s" => (0x30, Memory(0)),
+    "i64.load8_u" => (0x31, Memory(0)),
+    "i64.load16_s" => (0x32, Memory(1)),
+    "i64.load16_u" => (0x33, Memory(1)),
+    "i64.load32_s" => (0x34, Memory(2)),
+    "i64.load32_u" => (0x35, Memory(2)),
+    "i32.store" => (0x36, Memory(2)),
+    "i64.store" => (0x37, Memory(3)),
+    "f32.store" => (0x38, Memory(2)),
+    "f64.store" => (0x39, Memory(3)),
+    "i32.store8" => (0x3a, Memory(0)),
+    "i32.store16" => (0x3b, Memory(1)),
+    "i64.store8" => (0x3c, Memory(0)),
+    "i64.store16" => (0x3d, Memory(1)),
+    "i64.store32" => (0x3e, Memory(2)),
+    "memory.size" => (0x3f, MemoryIndex),
+    "memory.grow" => (0x40, MemoryIndex),
+    "i32.const" => (0x41, I32),
+    "i64.const" => (0x42, I64),
+    "f32.const" => (0x43, F32),
+    "f64.const" => (0x44, F64),
+    "ref.null" => (0xd0, ReferenceType),
+    "ref.is_null" => (0xd1, Nothing),
+    "ref.func" => (0xd2, Function),
+    _ => {
+      let idx = NUMERIC_INSTRUCTIONS.iter().position(|x| *x == name)?;
+      (0x45 + idx as u8, Nothing)
+    }
+  };
+  Some(instruction)
+}
+
+fn lookup_prefixed(name: &str) -> Option<(Opcode, Immediate)> {
+  use self::Immediate::*;
+  use self::Opcode::*;
+  let instruction = match name {
+    "i32.trunc_sat_f32_s" => (Prefixed(0xfc, 0x00), Nothing),
+    "i32.trunc_sat_f32_u" => (Prefixed(0xfc, 0x01), Nothing),
+    "i32.trunc_sat_f64_s" => (Prefixed(0xfc, 0x02), Nothing),
+    "i32.trunc_sat_f64_u" => (Prefixed(0xfc, 0x03), Nothing),
+    "i64.trunc_sat_f32_s" => (Prefixed(0xfc, 0x04), Nothing),
+    "i64.trunc_sat_f32_u" => (Prefixed(0xfc, 0x05), Nothing),
+    "i64.trunc_sat_f64_s" => (Prefixed(0xfc, 0x06), Nothing),
+    "i64.trunc_sat_f64_u" => (Prefixed(0xfc, 0x07), Nothing),
+    "memory.init" => (Prefixed(0xfc, 0x08), MemoryInit),
+    "data.drop" => (Prefixed(0xfc, 0x09), Data),
+    "memory.copy" => (Prefixed(0xfc, 0x0a), MemoryIndices),
+    "memory.fill" => (Prefixed(0xfc, 0x0b), MemoryIndex),
+    "table.init" => (Prefixed(0xfc, 0x0c), TableInit),
+    "elem.drop" => (Prefixed(0xfc, 0x0d), Element),
+    "table.copy" => (Prefixed(0xfc, 0x0e), TableIndices),
+    "table.grow" => (Prefixed(0xfc, 0x0f), Table),
+    "table.size" => (Prefixed(0xfc, 0x10), Table),
+    "table.fill" => (Prefixed(0xfc, 0x11), Table),
+    "memory.atomic.notify" => (Prefixed(0xfe, 0x00), Memory(2)),
+    "memory.atomic.wait32" => (Prefixed(0xfe, 0x01), Memory(2)),
+    "memory.atomic.wait64" => (Prefixed(0xfe, 0x02), Memory(3)),
+    "atomic.fence" => (Prefixed(0xfe, 0x03), Reserved),
+    _ if name.contains(".atomic.") => {
+      let (code, natural_align) = lookup_atomic(name)?;
+      (Prefixed(0xfe, code), Memory(natural_align))
+    }
+    _ => {
+      let code = SIMD_INSTRUCTIONS.iter().position(|x| *x == name)? as u32;
+      (Prefixed(0xfd, code), simd_immediate(code))
+    }
+  };
+  Some(instruction)
+}
+
+// NOTE: Operands of atomic accessing instructions, in order of opcode within each group.
+const ATOMIC_OPERANDS: [(&str, u32); 7] = [
+  ("i32", 32),
+  ("i64", 64),
+  ("i32", 8),
+  ("i32", 16),
+  ("i64", 8),
+  ("i64", 16),
+  ("i64", 32),
+];
+
+const ATOMIC_RMW_OPERATORS: [&str; 7] = ["add", "sub", "and", "or", "xor", "xchg", "cmpxchg"];
+
+// NOTE: Names are like `i32.atomic.load16_u`, `i64.atomic.store8` or `i64.atomic.rmw32.add_u`.
+fn lookup_atomic(name: &str) -> Option<(u32, u32)> {
+  let (ty, accessor) = match name.find(".atomic.") {
+    Some(idx) => (&name[..idx], &name[idx + 8..]),
+    None => return None,
+  };
+  let full_width = match ty {
+    "i32" => 32,
+    "i64" => 64,
+    _ => return None,
+  };
+  let parse_width = |width: &str| -> Option<u32> {
+    if width.is_empty() {
+      Some(full_width)
+    } else {
+      width.parse().ok().filter(|width| *width < full_width)
+    }
+  };
+  let (group, width) = if accessor.starts_with("load") {
+    let width = &accessor[4..];
+    match width.len() {
+      0 => (0, full_width),
+      _ if width.ends_with("_u") => (0, parse_width(&width[..width.len() - 2])?),
+      _ => return None,
+    }
+  } else if accessor.starts_with("store") {
+    (1, parse_width(&accessor[5..])?)
+  } else if accessor.starts_with("rmw") {
+    let idx = accessor.find('.')?;
+    let width = parse_width(&accessor[3..idx])?;
+    let operator = &accessor[idx + 1..];
+    let operator = if width == full_width {
+      operator
+    } else if operator.ends_with("_u") {
+      &operator[..operator.len() - 2]
+    } else {
+      return None;
+    };
+    let group = ATOMIC_RMW_OPERATORS.iter().position(|x| *x == operator)?;
+    (2 + group as u32, width)
+  } else {
+    return None;
+  };
+  let operand = ATOMIC_OPERANDS.iter().position(|x| *x == (ty, width))? as u32;
+  Some((0x10 + group * 7 + operand, (width / 8).trailing_zeros()))
+}
+
+// NOTE: SIMD instructions in order of opcode, where reserved ones are left empty.
+const SIMD_INSTRUCTIONS: [&str; 256] = [
+  "v128.load",
+  "v128.load8x8_s",
+  "v128.load8x8_u",
+  "v128.load16x4_s",
+  "v128.load16x4_u",
+  "v128.load32x2_s",
+  "v128.load32x2_u",
+  "v128.load8_splat",
+  "v128.load16_splat",
+  "v128.load32_splat",
+  "v128.load64_splat",
+  "v128.store",
+  "v128.const",
+  "i8x16.shuffle",
+  "i8x16.swizzle",
+  "i8x16.splat",
+  "i16x8.splat",
+  "i32x4.splat",
+  "i64x2.splat",
+  "f32x4.splat",
+  "f64x2.splat",
+  "i8x16.extract_lane_s",
+  "i8x16.extract_lane_u",
+  "i8x16.replace_lane",
+  "i16x8.extract_lane_s",
+  "i16x8.extract_lane_u",
+  "i16x8.replace_lane",
+  "i32x4.extract_lane",
+  "i32x4.replace_lane",
+  "i64x2.extract_lane",
+  "i64x2.replace_lane",
+  "f32x4.extract_lane",
+  "f32x4.replace_lane",
+  "f64x2.extract_lane",
+  "f64x2.replace_lane",
+  "i8x16.eq",
+  "i8x16.ne",
+  "i8x16.lt_s",
+  "i8x16.lt_u",
+  "i8x16.gt_s",
+  "i8x16.gt_u",
+  "i8x16.le_s",
+  "i8x16.le_u",
+  "i8x16.ge_s",
+  "i8x16.ge_u",
+  "i16x8.eq",
+  "i16x8.ne",
+  "i16x8.lt_s",
+  "i16x8.lt_u",
+  "i16x8.gt_s",
+  "i16x8.gt_u",
+  "i16x8.le_s",
+  "i16x8.le_u",
+  "i16x8.ge_s",
+  "i16x8.ge_u",
+  "i32x4.eq",
+  "i32x4.ne",
+  "i32x4.lt_s",
+  "i32x4.lt_u",
+  "i32x4.gt_s",
+  "i32x4.gt_u",
+  "i32x4.le_s",
+  "i32x4.le_u",
+  "i32x4.ge_s",
+  "i32x4.ge_u",
+  "f32x4.eq",
+  "f32x4.ne",
+  "f32x4.lt",
+  "f32x4.gt",
+  "f32x4.le",
+  "f32x4.ge",
+  "f64x2.eq",
+  "f64x2.ne",
+  "f64x2.lt",
+  "f64x2.gt",
+  "f64x2.le",
+  "f64x2.ge",
+  "v128.not",
+  "v128.and",
+  "v128.andnot",
+  "v128.or",
+  "v128.xor",
+  "v128.bitselect",
+  "v128.any_true",
+  "v128.load8_lane",
+  "v128.load16_lane",
+  "v128.load32_lane",
+  "v128.load64_lane",
+  "v128.store8_lane",
+  "v128.store16_lane",
+  "v128.store32_lane",
+  "v128.store64_lane",
+  "v128.load32_zero",
+  "v128.load64_zero",
+  "f32x4.demote_f64x2_zero",
+  "f64x2.promote_low_f32x4",
+  "i8x16.abs",
+  "i8x16.neg",
+  "i8x16.popcnt",
+  "i8x16.all_true",
+  "i8x16.bitmask",
+  "i8x16.narrow_i16x8_s",
+  "i8x16.narrow_i16x8_u",
+  "f32x4.ceil",
+  "f32x4.floor",
+  "f32x4.trunc",
+  "f32x4.nearest",
+  "i8x16.shl",
+  "i8x16.shr_s",
+  "i8x16.shr_u",
+  "i8x16.add",
+  "i8x16.add_sat_s",
+  "i8x16.add_sat_u",
+  "i8x16.sub",
+  "i8x16.sub_sat_s",
+  "i8x16.sub_sat_u",
+  "f64x2.ceil",
+  "f64x2.floor",
+  "i8x16.min_s",
+  "i8x16.min_u",
+  "i8x16.max_s",
+  "i8x16.max_u",
+  "f64x2.trunc",
+  "i8x16.avgr_u",
+  "i16x8.extadd_pairwise_i8x16_s",
+  "i16x8.extadd_pairwise_i8x16_u",
+  "i32x4.extadd_pairwise_i16x8_s",
+  "i32x4.extadd_pairwise_i16x8_u",
+  "i16x8.abs",
+  "i16x8.neg",
+  "i16x8.q15mulr_sat_s",
+  "i16x8.all_true",
+  "i16x8.bitmask",
+  "i16x8.narrow_i32x4_s",
+  "i16x8.narrow_i32x4_u",
+  "i16x8.extend_low_i8x16_s",
+  "i16x8.extend_high_i8x16_s",
+  "i16x8.extend_low_i8x16_u",
+  "i16x8.extend_high_i8x16_u",
+  "i16x8.shl",
+  "i16x8.shr_s",
+  "i16x8.shr_u",
+  "i16x8.add",
+  "i16x8.add_sat_s",
+  "i16x8.add_sat_u",
+  "i16x8.sub",
+  "i16x8.sub_sat_s",
+  "i16x8.sub_sat_u",
+  "f64x2.nearest",
+  "i16x8.mul",
+  "i16x8.min_s",
+  "i16x8.min_u",
+  "i16x8.max_s",
+  "i16x8.max_u",
+  "",
+  "i16x8.avgr_u",
+  "i16x8.extmul_low_i8x16_s",
+  "i16x8.extmul_high_i8x16_s",
+  "i16x8.extmul_low_i8x16_u",
+  "i16x8.extmul_high_i8x16_u",
+  "i32x4.abs",
+  "i32x4.neg",
+  "",
+  "i32x4.all_true",
+  "i32x4.bitmask",
+  "",
+  "",
+  "i32x4.extend_low_i16x8_s",
+  "i32x4.extend_high_i16x8_s",
+  "i32x4.extend_low_i16x8_u",
+  "i32x4.extend_high_i16x8_u",
+  "i32x4.shl",
+  "i32x4.shr_s",
+  "i32x4.shr_u",
+  "i32x4.add",
+  "",
+  "",
+  "i32x4.sub",
+  "",
+  "",
+  "",
+  "i32x4.mul",
+  "i32x4.min_s",
+  "i32x4.min_u",
+  "i32x4.max_s",
+  "i32x4.max_u",
+  "i32x4.dot_i16x8_s",
+  "",
+  "i32x4.extmul_low_i16x8_s",
+  "i32x4.extmul_high_i16x8_s",
+  "i32x4.extmul_low_i16x8_u",
+  "i32x4.extmul_high_i16x8_u",
+  "i64x2.abs",
+  "i64x2.neg",
+  "",
+  "i64x2.all_true",
+  "i64x2.bitmask",
+  "",
+  "",
+  "i64x2.extend_low_i32x4_s",
+  "i64x2.extend_high_i32x4_s",
+  "i64x2.extend_low_i32x4_u",
+  "i64x2.extend_high_i32x4_u",
+  "i64x2.shl",
+  "i64x2.shr_s",
+  "i64x2.shr_u",
+  "i64x2.add",
+  "",
+  "",
+  "i64x2.sub",
+  "",
+  "",
+  "",
+  "i64x2.mul",
+  "i64x2.eq",
+  "i64x2.ne",
+  "i64x2.lt_s",
+  "i64x2.gt_s",
+  "i64x2.le_s",
+  "i64x2.ge_s",
+  "i64x2.extmul_low_i32x4_s",
+  "i64x2.extmul_high_i32x4_s",
+  "i64x2.extmul_low_i32x4_u",
+  "i64x2.extmul_high_i32x4_u",
+  "f32x4.abs",
+  "f32x4.neg",
+  "",
+  "f32x4.sqrt",
+  "f32x4.add",
+  "f32x4.sub",
+  "f32x4.mul",
+  "f32x4.div",
+  "f32x4.min",
+  "f32x4.max",
+  "f32x4.pmin",
+  "f32x4.pmax",
+  "f64x2.abs",
+  "f64x2.neg",
+  "",
+  "f64x2.sqrt",
+  "f64x2.add",
+  "f64x2.sub",
+  "f64x2.mul",
+  "f64x2.div",
+  "f64x2.min",
+  "f64x2.max",
+  "f64x2.pmin",
+  "f64x2.pmax",
+  "i32x4.trunc_sat_f32x4_s",
+  "i32x4.trunc_sat_f32x4_u",
+  "f32x4.convert_i32x4_s",
+  "f32x4.convert_i32x4_u",
+  "i32x4.trunc_sat_f64x2_s_zero",
+  "i32x4.trunc_sat_f64x2_u_zero",
+  "f64x2.convert_low_i32x4_s",
+  "f64x2.convert_low_i32x4_u",
+];
+
+// NOTE: Immediates of SIMD instructions are determined by ranges of opcode.
+fn simd_immediate(code: u32) -> Immediate {
+  use self::Immediate::*;
+  match code {
+    0x00 | 0x0b => Memory(4),
+    0x01...0x06 | 0x0a | 0x5d => Memory(3),
+    0x07 => Memory(0),
+    0x08 => Memory(1),
+    0x09 | 0x5c => Memory(2),
+    0x0c => V128,
+    0x0d => Shuffle,
+    0x15...0x22 => Lane,
+    0x54...0x5b => MemoryLane((code - 0x54) % 4),
+    _ => Nothing,
+  }
+}
+
+/// Opcode and kind of immediate of an instruction except structured ones.
+pub fn lookup(name: &str) -> Option<(Opcode, Immediate)> {
+  lookup_normalized(name)
+    .or_else(|| lookup_normalized(&normalize(name)?))
+    .map(|(opcode, immediate)| (Opcode::Single(opcode), immediate))
+    .or_else(|| lookup_prefixed(name))
+}
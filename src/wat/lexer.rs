@@ -0,0 +1,227 @@
+use super::malformed;
+use alloc::vec::Vec;
+use error::Result;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TokenKind<'a> {
+  LeftParen,
+  RightParen,
+  Keyword(&'a str),
+  Id(&'a str),
+  // NOTE: Numbers are kept as text, because how to read it depends on the context.
+  Reserved(&'a str),
+  String(Vec<u8>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Token<'a> {
+  pub kind: TokenKind<'a>,
+  pub line: u32,
+}
+
+fn is_idchar(c: u8) -> bool {
+  match c {
+    b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' => true,
+    b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'*' | b'+' | b'-' | b'.' | b'/' => true,
+    b':' | b'<' | b'=' | b'>' | b'?' | b'@' | b'\\' | b'^' | b'_' | b'`' | b'|' | b'~' => true,
+    _ => false,
+  }
+}
+
+fn hex_digit(c: u8) -> Option<u8> {
+  match c {
+    b'0'..=b'9' => Some(c - b'0'),
+    b'a'..=b'f' => Some(c - b'a' + 10),
+    b'A'..=b'F' => Some(c - b'A' + 10),
+    _ => None,
+  }
+}
+
+struct Lexer<'a> {
+  source: &'a str,
+  bytes: &'a [u8],
+  ptr: usize,
+  line: u32,
+}
+
+impl<'a> Lexer<'a> {
+  fn peek_at(&self, offset: usize) -> Option<u8> {
+    self.bytes.get(self.ptr + offset).cloned()
+  }
+
+  fn skip_line_comment(&mut self) {
+    while let Some(c) = self.peek_at(0) {
+      if c == b'\n' {
+        break;
+      }
+      self.ptr += 1;
+    }
+  }
+
+  // NOTE: Block comments can be nested.
+  fn skip_block_comment(&mut self) -> Result<()> {
+    let mut depth = 0;
+    loop {
+      match (self.peek_at(0), self.peek_at(1)) {
+        (Some(b'('), Some(b';')) => {
+          depth += 1;
+          self.ptr += 2;
+        }
+        (Some(b';'), Some(b')')) => {
+          depth -= 1;
+          self.ptr += 2;
+          if depth == 0 {
+            return Ok(());
+          }
+        }
+        (Some(c), _) => {
+          if c == b'\n' {
+            self.line += 1;
+          }
+          self.ptr += 1;
+        }
+        (None, _) => return Err(malformed(self.line)),
+      }
+    }
+  }
+
+  fn string(&mut self) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    self.ptr += 1;
+    loop {
+      let c = self.peek_at(0).ok_or_else(|| malformed(self.line))?;
+      self.ptr += 1;
+      match c {
+        b'"' => return Ok(buf),
+        b'\\' => {
+          let escaped = self.peek_at(0).ok_or_else(|| malformed(self.line))?;
+          self.ptr += 1;
+          match escaped {
+            b't' => buf.push(b'\t'),
+            b'n' => buf.push(b'\n'),
+            b'r' => buf.push(b'\r'),
+            b'"' => buf.push(b'"'),
+            b'\'' => buf.push(b'\''),
+            b'\\' => buf.push(b'\\'),
+            b'u' => self.unicode_escape(&mut buf)?,
+            c => {
+              let high = hex_digit(c).ok_or_else(|| malformed(self.line))?;
+              let low = self
+                .peek_at(0)
+                .and_then(hex_digit)
+                .ok_or_else(|| malformed(self.line))?;
+              self.ptr += 1;
+              buf.push(high * 16 + low);
+            }
+          }
+        }
+        b'\n' => return Err(malformed(self.line)),
+        c if c < 0x20 || c == 0x7f => return Err(malformed(self.line)),
+        c => buf.push(c),
+      }
+    }
+  }
+
+  // NOTE: \u{hex} is encoded into UTF-8.
+  fn unicode_escape(&mut self, buf: &mut Vec<u8>) -> Result<()> {
+    if self.peek_at(0) != Some(b'{') {
+      return Err(malformed(self.line));
+    }
+    self.ptr += 1;
+    let mut code_point: u32 = 0;
+    let mut has_digit = false;
+    loop {
+      match self.peek_at(0) {
+        Some(b'}') if has_digit => break,
+        Some(b'_') if has_digit => {}
+        Some(c) => {
+          let digit = hex_digit(c).ok_or_else(|| malformed(self.line))?;
+          code_point = code_point
+            .checked_mul(16)
+            .and_then(|x| x.checked_add(u32::from(digit)))
+            .ok_or_else(|| malformed(self.line))?;
+          has_digit = true;
+        }
+        None => return Err(malformed(self.line)),
+      }
+      self.ptr += 1;
+    }
+    self.ptr += 1;
+    let c = core::char::from_u32(code_point).ok_or_else(|| malformed(self.line))?;
+    let mut encoded = [0; 4];
+    buf.extend_from_slice(c.encode_utf8(&mut encoded).as_bytes());
+    Ok(())
+  }
+
+  fn next_token(&mut self) -> Result<Option<Token<'a>>> {
+    loop {
+      let c = match self.peek_at(0) {
+        Some(c) => c,
+        None => return Ok(None),
+      };
+      let line = self.line;
+      let kind = match c {
+        b'\n' => {
+          self.line += 1;
+          self.ptr += 1;
+          continue;
+        }
+        b' ' | b'\t' | b'\r' => {
+          self.ptr += 1;
+          continue;
+        }
+        b';' if self.peek_at(1) == Some(b';') => {
+          self.skip_line_comment();
+          continue;
+        }
+        b'(' if self.peek_at(1) == Some(b';') => {
+          self.skip_block_comment()?;
+          continue;
+        }
+        b'(' => {
+          self.ptr += 1;
+          TokenKind::LeftParen
+        }
+        b')' => {
+          self.ptr += 1;
+          TokenKind::RightParen
+        }
+        b'"' => TokenKind::String(self.string()?),
+        c if is_idchar(c) => {
+          let start = self.ptr;
+          while self.peek_at(0).map(is_idchar).unwrap_or(false) {
+            self.ptr += 1;
+          }
+          let text = &self.source[start..self.ptr];
+          match c {
+            b'$' if text.len() > 1 => TokenKind::Id(&text[1..]),
+            b'a'..=b'z' => TokenKind::Keyword(text),
+            _ => TokenKind::Reserved(text),
+          }
+        }
+        _ => return Err(malformed(line)),
+      };
+      // NOTE: Tokens must be separated by whitespace or parentheses.
+      match (&kind, self.peek_at(0)) {
+        (TokenKind::LeftParen, _) | (TokenKind::RightParen, _) => {}
+        (_, Some(c)) if is_idchar(c) || c == b'"' => return Err(malformed(line)),
+        _ => {}
+      }
+      return Ok(Some(Token { kind, line }));
+    }
+  }
+}
+
+pub fn tokenize(source: &str) -> Result<Vec<Token>> {
+  let mut lexer = Lexer {
+    source,
+    bytes: source.as_bytes(),
+    ptr: 0,
+    line: 1,
+  };
+  let mut tokens = vec![];
+  while let Some(token) = lexer.next_token()? {
+    tokens.push(token);
+  }
+  Ok(tokens)
+}
@@ -0,0 +1,266 @@
+mod ast;
+mod emit;
+mod instruction;
+mod lexer;
+mod number;
+mod parser;
+pub mod script;
+
+use self::parser::{Parsed, Parser};
+use alloc::vec::Vec;
+use error::{Result, Trap, WasmError};
+
+fn malformed(line: u32) -> WasmError {
+  WasmError::Trap(Trap::MalformedTextform(line))
+}
+
+// NOTE: Text format begins with a module field or a comment.
+pub fn is_textform(bytes: &[u8]) -> bool {
+  match bytes.iter().find(|c| !c.is_ascii_whitespace()) {
+    Some(b'(') | Some(b';') => true,
+    _ => false,
+  }
+}
+
+fn encode(parsed: Parsed) -> Result<Vec<u8>> {
+  match parsed {
+    Parsed::Text(module) => emit::emit(&module),
+    Parsed::Binary(bytes) => Ok(bytes),
+    Parsed::Quote(bytes) => {
+      let source = core::str::from_utf8(&bytes).map_err(|_| malformed(1))?;
+      parse_wat(source)
+    }
+  }
+}
+
+/// Translate a module in text format into binary format.
+pub fn parse_wat(source: &str) -> Result<Vec<u8>> {
+  let tokens = lexer::tokenize(source)?;
+  encode(Parser::new(tokens).parse()?)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs::File;
+  use std::io::Read;
+
+  fn read(path: &str) -> Vec<u8> {
+    let mut buf = vec![];
+    File::open(path).unwrap().read_to_end(&mut buf).unwrap();
+    buf
+  }
+
+  macro_rules! test_fixture {
+    ($fn_name:ident, $file_name:expr) => {
+      #[test]
+      fn $fn_name() {
+        let source = read(&format!("./dist/{}.wat", $file_name));
+        let source = core::str::from_utf8(&source).unwrap();
+        assert_eq!(
+          parse_wat(source).unwrap(),
+          read(&format!("./dist/{}.wasm", $file_name))
+        );
+      }
+    };
+  }
+
+  test_fixture!(parse_fib, "fib");
+  test_fixture!(parse_if_eq, "if_eq");
+  test_fixture!(parse_caller, "caller");
+  test_fixture!(parse_host_error, "host_error");
+  test_fixture!(parse_wasi, "wasi");
+  test_fixture!(parse_multi_value, "multi_value");
+
+  #[test]
+  fn parse_folded_instructions() {
+    let flat = parse_wat(
+      r#"(module
+        (func $f (param $x i32) (result i32)
+          get_local $x
+          if (result i32)
+            i32.const 1
+          else
+            local.get $x
+            i32.const 2
+            i32.add
+          end))"#,
+    );
+    let folded = parse_wat(
+      r#"(func $f (param $x i32) (result i32)
+        (if (result i32) (local.get $x)
+          (then (i32.const 1))
+          (else (i32.add (local.get $x) (i32.const 2)))))"#,
+    );
+    assert_eq!(flat, folded);
+  }
+
+  #[test]
+  fn parse_labels() {
+    let named = parse_wat(
+      r#"(func
+        (block $outer
+          (loop $inner
+            (br_if $inner (i32.const 0))
+            (br_table $inner $outer $outer (i32.const 1)))))"#,
+    );
+    let numbered = parse_wat(
+      r#"(func
+        (block
+          (loop
+            (br_if 0 (i32.const 0))
+            (br_table 0 1 1 (i32.const 1)))))"#,
+    );
+    assert_eq!(named, numbered);
+  }
+
+  #[test]
+  fn parse_inline_abbreviations() {
+    let inline = parse_wat(
+      r#"(module
+        (func $double (import "env" "double") (param i32) (result i32))
+        (memory (export "memory") (data "\00\01"))
+        (table funcref (elem $f))
+        (func $f (export "f") (export "g") (result i32)
+          (call $double (i32.const 21))))"#,
+    );
+    let expanded = parse_wat(
+      r#"(module
+        (type (func (param i32) (result i32)))
+        (type (func (result i32)))
+        (import "env" "double" (func (type 0)))
+        (func (type 1) (call 0 (i32.const 21)))
+        (table 1 1 anyfunc)
+        (memory 1 1)
+        (export "memory" (memory 0))
+        (export "f" (func 1))
+        (export "g" (func 1))
+        (elem (i32.const 0) 1)
+        (data (i32.const 0) "\00\01"))"#,
+    );
+    assert_eq!(inline, expanded);
+  }
+
+  #[test]
+  fn parse_numbers() {
+    use self::number::*;
+    assert_eq!(parse_i32("0xffff_ffff"), Some(-1));
+    assert_eq!(parse_i32("-0x8000_0000"), Some(i32::min_value()));
+    assert_eq!(parse_i32("4294967296"), None);
+    assert_eq!(parse_i64("-9223372036854775808"), Some(i64::min_value()));
+    assert_eq!(parse_f32("0x1p-149"), Some(1));
+    assert_eq!(parse_f32("0x1.fffffep127"), Some(0x7f7f_ffff));
+    assert_eq!(parse_f32("0x1.ffffffp127"), None);
+    assert_eq!(parse_f32("-nan:0x200000"), Some(0xffa0_0000));
+    assert_eq!(parse_f64("0x1.00000000000008p0"), Some(0x3ff0_0000_0000_0000));
+    assert_eq!(parse_f64("1e400"), None);
+    assert_eq!(parse_f64("-inf"), Some(0xfff0_0000_0000_0000));
+  }
+
+  #[test]
+  fn parse_malformed() {
+    assert_eq!(
+      parse_wat("(module\n  (func (i32.const 0x)))"),
+      Err(WasmError::Trap(Trap::MalformedTextform(2)))
+    );
+    assert!(parse_wat("(func $f) (func $f)").is_err());
+    assert!(parse_wat("(func) (import \"m\" \"n\" (func))").is_err());
+    assert!(parse_wat("(func block $a end $b)").is_err());
+    assert!(parse_wat("(func (local.get $x))").is_err());
+    assert!(parse_wat("(func (i32.load align=3))").is_err());
+    assert!(parse_wat("(module (func (type 5)))").is_err());
+    assert!(parse_wat("(func (call_indirect (type 1) (i32.const 0)))").is_err());
+  }
+
+  #[test]
+  fn parse_script() {
+    use self::script::*;
+    use value::Values;
+    let commands = script::parse_wast(
+      r#"(module $m (func (export "f") (param i32) (result i32) (local.get 0)))
+      (register "m" $m)
+      (assert_return (invoke $m "f" (i32.const -1)) (i32.const -1))
+      (assert_return (invoke "g") (f32.const nan:canonical) (ref.null extern))
+      (assert_trap (invoke "h" (f64.const -0x1p0)) "unreachable")
+      (assert_malformed (module quote "(func (i32.const 0x))") "unknown operator")
+      (assert_invalid (module binary "\00asm" "\02\00\00\00") "unknown binary version")
+      (get "x")"#,
+    )
+    .unwrap();
+    let lines = commands.iter().map(|command| command.line).collect::<Vec<_>>();
+    assert_eq!(lines, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+    match &commands[0].kind {
+      CommandKind::Module(ScriptModule {
+        name: Some(name),
+        bytes: Ok(bytes),
+      }) => {
+        assert_eq!(name, "m");
+        assert_eq!(
+          bytes,
+          &parse_wat(r#"(module (func (export "f") (param i32) (result i32) (local.get 0)))"#)
+            .unwrap()
+        );
+      }
+      x => unreachable!("{:?}", x),
+    }
+    match &commands[1].kind {
+      CommandKind::Register { name, as_name } => {
+        assert_eq!(name, &Some("m".to_owned()));
+        assert_eq!(as_name, "m");
+      }
+      x => unreachable!("{:?}", x),
+    }
+    match &commands[2].kind {
+      CommandKind::AssertReturn {
+        action: Action::Invoke { module, field, args },
+        expected,
+      } => {
+        assert_eq!(module, &Some("m".to_owned()));
+        assert_eq!(field, "f");
+        assert_eq!(args, &vec![Values::I32(-1)]);
+        match &expected[..] {
+          [Expected::Value(Values::I32(-1))] => {}
+          x => unreachable!("{:?}", x),
+        }
+      }
+      x => unreachable!("{:?}", x),
+    }
+    match &commands[3].kind {
+      CommandKind::AssertReturn { expected, .. } => match &expected[..] {
+        [Expected::CanonicalNan, Expected::Value(Values::ExternRef(None))] => {}
+        x => unreachable!("{:?}", x),
+      },
+      x => unreachable!("{:?}", x),
+    }
+    match &commands[4].kind {
+      CommandKind::AssertTrap {
+        action: Action::Invoke { args, .. },
+        message,
+      } => {
+        assert_eq!(args, &vec![Values::F64(-1.0)]);
+        assert_eq!(message, "unreachable");
+      }
+      x => unreachable!("{:?}", x),
+    }
+    match &commands[5].kind {
+      CommandKind::AssertMalformed {
+        module: ScriptModule { bytes: Err(_), .. },
+        ..
+      } => {}
+      x => unreachable!("{:?}", x),
+    }
+    match &commands[6].kind {
+      CommandKind::AssertInvalid {
+        module: ScriptModule { bytes: Ok(bytes), .. },
+        ..
+      } => assert_eq!(bytes, &vec![0, 97, 115, 109, 2, 0, 0, 0]),
+      x => unreachable!("{:?}", x),
+    }
+    match &commands[7].kind {
+      CommandKind::Action(Action::Get { module: None, field }) => assert_eq!(field, "x"),
+      x => unreachable!("{:?}", x),
+    }
+    assert!(script::parse_wast("(assert_return (invoke \"f\")").is_err());
+    assert!(script::parse_wast("(assert_unknown (invoke \"f\"))").is_err());
+  }
+}
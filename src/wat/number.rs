@@ -0,0 +1,298 @@
+use alloc::string::String;
+
+fn strip_sign(text: &str) -> (bool, &str) {
+  if text.starts_with('-') {
+    (true, &text[1..])
+  } else if text.starts_with('+') {
+    (false, &text[1..])
+  } else {
+    (false, text)
+  }
+}
+
+// NOTE: Underscores are allowed only between digits.
+fn strip_underscores(text: &str, is_digit: fn(char) -> bool) -> Option<String> {
+  let mut buf = String::new();
+  let mut prev_is_digit = false;
+  for c in text.chars() {
+    if c == '_' {
+      if !prev_is_digit {
+        return None;
+      }
+      prev_is_digit = false;
+      continue;
+    }
+    prev_is_digit = is_digit(c);
+    buf.push(c);
+  }
+  if text.ends_with('_') {
+    return None;
+  }
+  Some(buf)
+}
+
+fn is_decimal_digit(c: char) -> bool {
+  c.is_digit(10)
+}
+
+fn is_hex_digit(c: char) -> bool {
+  c.is_digit(16)
+}
+
+fn unsigned(text: &str) -> Option<u64> {
+  let (digits, radix) = if text.starts_with("0x") {
+    (&text[2..], 16)
+  } else {
+    (text, 10)
+  };
+  if digits.is_empty() || !digits.chars().next()?.is_digit(radix) {
+    return None;
+  }
+  let is_digit = if radix == 16 {
+    is_hex_digit
+  } else {
+    is_decimal_digit
+  };
+  let digits = strip_underscores(digits, is_digit)?;
+  u64::from_str_radix(&digits, radix).ok()
+}
+
+// NOTE: Both of signed and unsigned representation are accepted.
+pub fn parse_i32(text: &str) -> Option<i32> {
+  let (is_negative, magnitude) = strip_sign(text);
+  let magnitude = unsigned(magnitude)?;
+  if is_negative {
+    if magnitude > 1 << 31 {
+      return None;
+    }
+    Some((magnitude as i64).wrapping_neg() as i32)
+  } else {
+    if magnitude > u64::from(u32::max_value()) {
+      return None;
+    }
+    Some(magnitude as u32 as i32)
+  }
+}
+
+pub fn parse_i64(text: &str) -> Option<i64> {
+  let (is_negative, magnitude) = strip_sign(text);
+  let magnitude = unsigned(magnitude)?;
+  if is_negative {
+    if magnitude > 1 << 63 {
+      return None;
+    }
+    Some((magnitude as i64).wrapping_neg())
+  } else {
+    Some(magnitude as i64)
+  }
+}
+
+pub fn parse_u32(text: &str) -> Option<u32> {
+  if text.starts_with('+') || text.starts_with('-') {
+    return None;
+  }
+  let value = unsigned(text)?;
+  if value > u64::from(u32::max_value()) {
+    return None;
+  }
+  Some(value as u32)
+}
+
+// NOTE: Lane of `v128.const` accepts both of signed and unsigned representation of its width.
+pub fn parse_integer_lane(text: &str, width: u32) -> Option<u64> {
+  let value = parse_i64(text)?;
+  if width < 64 && (value < -(1 << (width - 1)) || value >= 1 << width) {
+    return None;
+  }
+  Some(value as u64)
+}
+
+struct FloatFormat {
+  significand_bits: u32,
+  exponent_bits: u32,
+}
+
+const F32_FORMAT: FloatFormat = FloatFormat {
+  significand_bits: 23,
+  exponent_bits: 8,
+};
+
+const F64_FORMAT: FloatFormat = FloatFormat {
+  significand_bits: 52,
+  exponent_bits: 11,
+};
+
+impl FloatFormat {
+  fn sign_bit(&self) -> u64 {
+    1 << (self.significand_bits + self.exponent_bits)
+  }
+
+  fn infinity(&self) -> u64 {
+    ((1 << self.exponent_bits) - 1) << self.significand_bits
+  }
+
+  fn canonical_nan(&self) -> u64 {
+    self.infinity() | 1 << (self.significand_bits - 1)
+  }
+
+  // NOTE: Round mantissa * 2^exponent to nearest, ties to even.
+  fn round(&self, mantissa: u64, exponent: i32, is_inexact: bool) -> Option<u64> {
+    if mantissa == 0 {
+      return Some(0);
+    }
+    let precision = self.significand_bits as i32 + 1;
+    let bias = (1 << (self.exponent_bits - 1)) - 1;
+    let min_exponent = 1 - bias;
+    let msb = 63 - mantissa.leading_zeros() as i32;
+    let mut quantum = (msb + exponent - (precision - 1)).max(min_exponent - (precision - 1));
+    let shift = quantum - exponent;
+    let mut rounded = if shift <= 0 {
+      u128::from(mantissa) << -shift
+    } else if shift > 65 {
+      0
+    } else {
+      let mantissa = u128::from(mantissa);
+      let kept = mantissa >> shift;
+      let rest = mantissa & ((1 << shift) - 1);
+      let half = 1 << (shift - 1);
+      let is_round_up = rest > half || (rest == half && (is_inexact || kept & 1 == 1));
+      kept + if is_round_up { 1 } else { 0 }
+    };
+    if rounded >> precision != 0 {
+      rounded >>= 1;
+      quantum += 1;
+    }
+    let rounded = rounded as u64;
+    if rounded >> (precision - 1) == 0 {
+      return Some(rounded);
+    }
+    let biased_exponent = quantum + precision - 1 + bias;
+    if biased_exponent >= (1 << self.exponent_bits) - 1 {
+      return None;
+    }
+    let fraction = rounded & ((1 << self.significand_bits) - 1);
+    Some((biased_exponent as u64) << self.significand_bits | fraction)
+  }
+
+  fn hex_float(&self, text: &str) -> Option<u64> {
+    let (significand, exponent) = match text.find(|c| c == 'p' || c == 'P') {
+      Some(idx) => (&text[..idx], Some(&text[idx + 1..])),
+      None => (text, None),
+    };
+    let (integer, fraction) = match significand.find('.') {
+      Some(idx) => (&significand[..idx], &significand[idx + 1..]),
+      None => (significand, ""),
+    };
+    if !integer.chars().next()?.is_digit(16) {
+      return None;
+    }
+    let integer = strip_underscores(integer, is_hex_digit)?;
+    let fraction = if fraction.is_empty() {
+      String::new()
+    } else {
+      if !fraction.chars().next()?.is_digit(16) {
+        return None;
+      }
+      strip_underscores(fraction, is_hex_digit)?
+    };
+    let mut exponent = match exponent {
+      Some(exponent) => {
+        let (is_negative, digits) = strip_sign(exponent);
+        if !digits.chars().next()?.is_digit(10) {
+          return None;
+        }
+        let digits = strip_underscores(digits, is_decimal_digit)?;
+        // NOTE: Saturate huge exponent, which is rounded to zero or infinity anyway.
+        let value = digits.parse::<i32>().unwrap_or(100_000).min(100_000);
+        if is_negative {
+          -value
+        } else {
+          value
+        }
+      }
+      None => 0,
+    };
+    let mut mantissa: u64 = 0;
+    let mut is_inexact = false;
+    for (i, c) in integer.chars().chain(fraction.chars()).enumerate() {
+      let digit = u64::from(c.to_digit(16)?);
+      let is_fraction = i >= integer.len();
+      if mantissa >> 60 == 0 {
+        mantissa = mantissa * 16 + digit;
+        if is_fraction {
+          exponent -= 4;
+        }
+      } else {
+        is_inexact |= digit != 0;
+        if !is_fraction {
+          exponent += 4;
+        }
+      }
+    }
+    self.round(mantissa, exponent, is_inexact)
+  }
+
+  fn parse(&self, text: &str, decimal: fn(&str) -> Option<u64>) -> Option<u64> {
+    let (is_negative, magnitude) = strip_sign(text);
+    let sign = if is_negative { self.sign_bit() } else { 0 };
+    let bits = if magnitude == "inf" {
+      self.infinity()
+    } else if magnitude == "nan" {
+      self.canonical_nan()
+    } else if magnitude.starts_with("nan:0x") {
+      let payload = unsigned(&magnitude[4..])?;
+      if payload == 0 || payload >> self.significand_bits != 0 {
+        return None;
+      }
+      self.infinity() | payload
+    } else if magnitude.starts_with("0x") {
+      self.hex_float(&magnitude[2..])?
+    } else {
+      if !magnitude.chars().next()?.is_digit(10) {
+        return None;
+      }
+      let is_digit = |c: char| c.is_digit(10);
+      let mut prev = None;
+      for c in magnitude.chars() {
+        // NOTE: Underscores must be surrounded by digits.
+        if c == '_' && !prev.map(is_digit).unwrap_or(false) {
+          return None;
+        }
+        if prev == Some('_') && !is_digit(c) {
+          return None;
+        }
+        prev = Some(c);
+      }
+      if prev == Some('_') {
+        return None;
+      }
+      let digits = magnitude.replace('_', "");
+      decimal(&digits)?
+    };
+    Some(sign | bits)
+  }
+}
+
+fn decimal_f32(text: &str) -> Option<u64> {
+  let value = text.parse::<f32>().ok()?;
+  if value.is_infinite() {
+    return None;
+  }
+  Some(u64::from(value.to_bits()))
+}
+
+fn decimal_f64(text: &str) -> Option<u64> {
+  let value = text.parse::<f64>().ok()?;
+  if value.is_infinite() {
+    return None;
+  }
+  Some(value.to_bits())
+}
+
+pub fn parse_f32(text: &str) -> Option<u32> {
+  F32_FORMAT.parse(text, decimal_f32).map(|bits| bits as u32)
+}
+
+pub fn parse_f64(text: &str) -> Option<u64> {
+  F64_FORMAT.parse(text, decimal_f64)
+}
@@ -0,0 +1,1153 @@
+use super::ast::*;
+use super::instruction::{lookup, Immediate, Opcode};
+use super::lexer::{Token, TokenKind};
+use super::malformed;
+use super::number::{parse_f32, parse_f64, parse_i32, parse_i64, parse_integer_lane, parse_u32};
+use alloc::vec::Vec;
+use error::Result;
+
+const PAGE_SIZE: usize = 65536;
+
+pub enum Parsed<'a> {
+  Text(Module<'a>),
+  Binary(Vec<u8>),
+  Quote(Vec<u8>),
+}
+
+pub struct Parser<'a> {
+  tokens: Vec<Token<'a>>,
+  ptr: usize,
+  module: Module<'a>,
+  // NOTE: Imports must precede any definitions to keep order of index spaces.
+  has_definition: bool,
+}
+
+impl<'a> Parser<'a> {
+  pub fn new(tokens: Vec<Token<'a>>) -> Self {
+    Parser {
+      tokens,
+      ptr: 0,
+      module: Module::default(),
+      has_definition: false,
+    }
+  }
+
+  pub(super) fn line(&self) -> u32 {
+    self
+      .tokens
+      .get(self.ptr)
+      .or_else(|| self.tokens.last())
+      .map(|token| token.line)
+      .unwrap_or(1)
+  }
+
+  // NOTE: Token is cloned to release borrowing of the parser.
+  pub(super) fn peek_at(&self, offset: usize) -> Option<TokenKind<'a>> {
+    self.tokens.get(self.ptr + offset).map(|token| token.kind.clone())
+  }
+
+  pub(super) fn peek(&self) -> Option<TokenKind<'a>> {
+    self.peek_at(0)
+  }
+
+  pub(super) fn advance(&mut self) {
+    self.ptr += 1;
+  }
+
+  pub(super) fn is_keyword(&self, keyword: &str) -> bool {
+    match self.peek() {
+      Some(TokenKind::Keyword(x)) => x == keyword,
+      _ => false,
+    }
+  }
+
+  pub(super) fn is_field(&self, keyword: &str) -> bool {
+    match (self.peek(), self.peek_at(1)) {
+      (Some(TokenKind::LeftParen), Some(TokenKind::Keyword(x))) => x == keyword,
+      _ => false,
+    }
+  }
+
+  pub(super) fn is_left_paren(&self) -> bool {
+    self.peek() == Some(TokenKind::LeftParen)
+  }
+
+  fn is_index(&self) -> bool {
+    match self.peek() {
+      Some(TokenKind::Id(_)) => true,
+      Some(TokenKind::Reserved(x)) => parse_u32(x).is_some(),
+      _ => false,
+    }
+  }
+
+  pub(super) fn expect_left_paren(&mut self) -> Result<()> {
+    match self.peek() {
+      Some(TokenKind::LeftParen) => {
+        self.advance();
+        Ok(())
+      }
+      _ => Err(malformed(self.line())),
+    }
+  }
+
+  pub(super) fn expect_right_paren(&mut self) -> Result<()> {
+    match self.peek() {
+      Some(TokenKind::RightParen) => {
+        self.advance();
+        Ok(())
+      }
+      _ => Err(malformed(self.line())),
+    }
+  }
+
+  fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+    if self.is_keyword(keyword) {
+      self.advance();
+      Ok(())
+    } else {
+      Err(malformed(self.line()))
+    }
+  }
+
+  pub(super) fn keyword(&mut self) -> Result<&'a str> {
+    match self.peek() {
+      Some(TokenKind::Keyword(x)) => {
+        let keyword = x;
+        self.advance();
+        Ok(keyword)
+      }
+      _ => Err(malformed(self.line())),
+    }
+  }
+
+  // NOTE: Number and keywords like `inf` or `nan` are read by the context.
+  pub(super) fn number(&mut self) -> Result<&'a str> {
+    match self.peek() {
+      Some(TokenKind::Reserved(x)) | Some(TokenKind::Keyword(x)) => {
+        let number = x;
+        self.advance();
+        Ok(number)
+      }
+      _ => Err(malformed(self.line())),
+    }
+  }
+
+  pub(super) fn optional_id(&mut self) -> Option<&'a str> {
+    match self.peek() {
+      Some(TokenKind::Id(x)) => {
+        let id = x;
+        self.advance();
+        Some(id)
+      }
+      _ => None,
+    }
+  }
+
+  fn index(&mut self) -> Result<Index<'a>> {
+    let line = self.line();
+    match self.peek() {
+      Some(TokenKind::Id(x)) => {
+        let id = x;
+        self.advance();
+        Ok(Index::Id(id))
+      }
+      Some(TokenKind::Reserved(x)) => {
+        let idx = parse_u32(x).ok_or_else(|| malformed(line))?;
+        self.advance();
+        Ok(Index::Num(idx))
+      }
+      _ => Err(malformed(line)),
+    }
+  }
+
+  fn u32(&mut self) -> Result<u32> {
+    let line = self.line();
+    parse_u32(self.number()?).ok_or_else(|| malformed(line))
+  }
+
+  fn string(&mut self) -> Result<Vec<u8>> {
+    match self.peek() {
+      Some(TokenKind::String(x)) => {
+        let string = x;
+        self.advance();
+        Ok(string)
+      }
+      _ => Err(malformed(self.line())),
+    }
+  }
+
+  fn strings(&mut self) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    while let Some(TokenKind::String(_)) = self.peek() {
+      bytes.append(&mut self.string()?);
+    }
+    Ok(bytes)
+  }
+
+  pub(super) fn name(&mut self) -> Result<Vec<u8>> {
+    let line = self.line();
+    let name = self.string()?;
+    core::str::from_utf8(&name).map_err(|_| malformed(line))?;
+    Ok(name)
+  }
+
+  fn value_type(&mut self) -> Result<u8> {
+    let line = self.line();
+    match self.keyword()? {
+      "i32" => Ok(0x7f),
+      "i64" => Ok(0x7e),
+      "f32" => Ok(0x7d),
+      "f64" => Ok(0x7c),
+      "v128" => Ok(0x7b),
+      "funcref" => Ok(0x70),
+      "externref" => Ok(0x6f),
+      _ => Err(malformed(line)),
+    }
+  }
+
+  fn element_type(&mut self) -> Result<u8> {
+    let line = self.line();
+    match self.keyword()? {
+      "funcref" | "anyfunc" => Ok(0x70),
+      "externref" => Ok(0x6f),
+      _ => Err(malformed(line)),
+    }
+  }
+
+  pub(super) fn heap_type(&mut self) -> Result<u8> {
+    let line = self.line();
+    match self.keyword()? {
+      "func" => Ok(0x70),
+      "extern" => Ok(0x6f),
+      _ => Err(malformed(line)),
+    }
+  }
+
+  fn is_element_type(&self) -> bool {
+    match self.peek() {
+      Some(TokenKind::Keyword(x)) => x == "funcref" || x == "anyfunc" || x == "externref",
+      _ => false,
+    }
+  }
+
+  fn optional_index(&mut self) -> Result<Index<'a>> {
+    if self.is_index() {
+      self.index()
+    } else {
+      Ok(Index::Num(0))
+    }
+  }
+
+  fn limits(&mut self) -> Result<Limits> {
+    let min = self.u32()?;
+    let max = match self.peek() {
+      Some(TokenKind::Reserved(_)) => Some(self.u32()?),
+      _ => None,
+    };
+    let shared = self.is_keyword("shared");
+    if shared {
+      self.advance();
+    }
+    Ok(Limits {
+      min,
+      max,
+      shared,
+      page_size: None,
+    })
+  }
+
+  // NOTE: Page size must be a power of 2.
+  fn memory_limits(&mut self) -> Result<Limits> {
+    let mut limits = self.limits()?;
+    if self.is_field("pagesize") {
+      self.advance();
+      self.advance();
+      let page_size = self.u32()?;
+      if !page_size.is_power_of_two() {
+        return Err(malformed(self.line()));
+      }
+      self.expect_right_paren()?;
+      limits.page_size = Some(page_size);
+    }
+    Ok(limits)
+  }
+
+  fn global_type(&mut self) -> Result<GlobalType> {
+    if self.is_field("mut") {
+      self.advance();
+      self.advance();
+      let value_type = self.value_type()?;
+      self.expect_right_paren()?;
+      Ok(GlobalType {
+        value_type,
+        is_mutable: true,
+      })
+    } else {
+      Ok(GlobalType {
+        value_type: self.value_type()?,
+        is_mutable: false,
+      })
+    }
+  }
+
+  // NOTE: Returns None if neither of params nor results is written.
+  fn signature(&mut self) -> Result<Option<FunctionSignature<'a>>> {
+    let mut signature = FunctionSignature::default();
+    let mut is_written = false;
+    while self.is_field("param") {
+      self.advance();
+      self.advance();
+      is_written = true;
+      if let Some(id) = self.optional_id() {
+        let value_type = self.value_type()?;
+        signature.parameters.push((Some(id), value_type));
+      } else {
+        while let Some(TokenKind::Keyword(_)) = self.peek() {
+          let value_type = self.value_type()?;
+          signature.parameters.push((None, value_type));
+        }
+      }
+      self.expect_right_paren()?;
+    }
+    while self.is_field("result") {
+      self.advance();
+      self.advance();
+      is_written = true;
+      while let Some(TokenKind::Keyword(_)) = self.peek() {
+        let value_type = self.value_type()?;
+        signature.results.push(value_type);
+      }
+      self.expect_right_paren()?;
+    }
+    Ok(if is_written { Some(signature) } else { None })
+  }
+
+  fn type_use(&mut self) -> Result<TypeUse<'a>> {
+    let index = if self.is_field("type") {
+      self.advance();
+      self.advance();
+      let index = self.index()?;
+      self.expect_right_paren()?;
+      Some(index)
+    } else {
+      None
+    };
+    Ok(TypeUse {
+      index,
+      signature: self.signature()?,
+    })
+  }
+
+  fn memory_argument(&mut self) -> Result<Operand<'a>> {
+    let line = self.line();
+    let memory = self.optional_index()?;
+    let mut offset = 0;
+    let mut align = None;
+    if let Some(TokenKind::Keyword(x)) = self.peek() {
+      if x.starts_with("offset=") {
+        offset = parse_u32(&x[7..]).ok_or_else(|| malformed(line))?;
+        self.advance();
+      }
+    }
+    if let Some(TokenKind::Keyword(x)) = self.peek() {
+      if x.starts_with("align=") {
+        let value = parse_u32(&x[6..]).ok_or_else(|| malformed(line))?;
+        if !value.is_power_of_two() {
+          return Err(malformed(line));
+        }
+        align = Some(value);
+        self.advance();
+      }
+    }
+    Ok(Operand::Memory(memory, align, offset))
+  }
+
+  fn lane(&mut self) -> Result<u8> {
+    let line = self.line();
+    match self.peek() {
+      Some(TokenKind::Reserved(x)) => {
+        let lane = parse_u32(x).filter(|lane| *lane <= 0xff).ok_or_else(|| malformed(line))?;
+        self.advance();
+        Ok(lane as u8)
+      }
+      _ => Err(malformed(line)),
+    }
+  }
+
+  // NOTE: Lane index can't be distinguished from memory index until it is followed by another one.
+  fn memory_lane(&mut self) -> Result<Operand<'a>> {
+    let line = self.line();
+    let argument = self.memory_argument()?;
+    match (argument, self.is_index()) {
+      (Operand::Memory(memory, align, offset), true) => {
+        Ok(Operand::MemoryLane(memory, align, offset, self.lane()?))
+      }
+      (Operand::Memory(Index::Num(lane), None, 0), false) if lane <= 0xff => {
+        Ok(Operand::MemoryLane(Index::Num(0), None, 0, lane as u8))
+      }
+      _ => Err(malformed(line)),
+    }
+  }
+
+  // NOTE: Lanes are packed into bytes in little endian.
+  pub(super) fn v128(&mut self) -> Result<u128> {
+    let line = self.line();
+    let shape = self.keyword()?;
+    let (width, lanes) = match shape {
+      "i8x16" => (8, 16),
+      "i16x8" => (16, 8),
+      "i32x4" | "f32x4" => (32, 4),
+      "i64x2" | "f64x2" => (64, 2),
+      _ => return Err(malformed(line)),
+    };
+    let mut bits = 0u128;
+    for idx in 0..lanes {
+      let number = self.number()?;
+      let lane = match shape {
+        "f32x4" => parse_f32(number).map(u64::from),
+        "f64x2" => parse_f64(number),
+        _ => parse_integer_lane(number, width),
+      }
+      .ok_or_else(|| malformed(line))?;
+      let mask = u128::max_value() >> (128 - width);
+      bits |= (u128::from(lane) & mask) << (idx * width);
+    }
+    Ok(bits)
+  }
+
+  fn plain_instruction(&mut self) -> Result<Instruction<'a>> {
+    let line = self.line();
+    let (mut opcode, immediate) = lookup(self.keyword()?).ok_or_else(|| malformed(line))?;
+    let operand = match immediate {
+      Immediate::Nothing | Immediate::Reserved => Operand::Nothing,
+      Immediate::Label
+      | Immediate::Function
+      | Immediate::Local
+      | Immediate::Global
+      | Immediate::Tag
+      | Immediate::Data
+      | Immediate::Element => {
+        Operand::Index(self.index()?)
+      }
+      Immediate::Table | Immediate::MemoryIndex => Operand::Index(self.optional_index()?),
+      Immediate::TableIndices | Immediate::MemoryIndices => {
+        let destination = self.optional_index()?;
+        Operand::Indices(destination, self.optional_index()?)
+      }
+      Immediate::TableInit | Immediate::MemoryInit => {
+        let index = self.index()?;
+        if self.is_index() {
+          Operand::Indices(index, self.index()?)
+        } else {
+          Operand::Indices(Index::Num(0), index)
+        }
+      }
+      Immediate::ReferenceType => Operand::ReferenceType(self.heap_type()?),
+      Immediate::Select if self.is_field("result") => {
+        let mut value_types = vec![];
+        while self.is_field("result") {
+          self.advance();
+          self.advance();
+          while let Some(TokenKind::Keyword(_)) = self.peek() {
+            value_types.push(self.value_type()?);
+          }
+          self.expect_right_paren()?;
+        }
+        opcode = Opcode::Single(0x1c);
+        Operand::ValueTypes(value_types)
+      }
+      Immediate::Select => Operand::Nothing,
+      Immediate::LabelTable => {
+        let mut indices = vec![self.index()?];
+        while self.is_index() {
+          indices.push(self.index()?);
+        }
+        Operand::LabelTable(indices)
+      }
+      Immediate::CallIndirect => {
+        let table = if self.is_index() {
+          Some(self.index()?)
+        } else {
+          None
+        };
+        Operand::CallIndirect(table, self.type_use()?)
+      }
+      Immediate::Memory(_) => self.memory_argument()?,
+      Immediate::MemoryLane(_) => self.memory_lane()?,
+      Immediate::Lane => Operand::Lane(self.lane()?),
+      Immediate::V128 => Operand::V128(self.v128()?),
+      Immediate::Shuffle => {
+        let mut bits = 0u128;
+        for idx in 0..16 {
+          bits |= u128::from(self.lane()?) << (idx * 8);
+        }
+        Operand::V128(bits)
+      }
+      Immediate::I32 => Operand::I32(parse_i32(self.number()?).ok_or_else(|| malformed(line))?),
+      Immediate::I64 => Operand::I64(parse_i64(self.number()?).ok_or_else(|| malformed(line))?),
+      Immediate::F32 => Operand::F32(parse_f32(self.number()?).ok_or_else(|| malformed(line))?),
+      Immediate::F64 => Operand::F64(parse_f64(self.number()?).ok_or_else(|| malformed(line))?),
+    };
+    Ok(Instruction {
+      kind: InstructionKind::Plain(opcode, immediate, operand),
+      line,
+    })
+  }
+
+  // NOTE: Label after `end` or `else` must be same as the one of its block.
+  fn closing_label(&mut self, label: Option<&'a str>) -> Result<Option<&'a str>> {
+    let line = self.line();
+    match self.optional_id() {
+      Some(id) if Some(id) != label => Err(malformed(line)),
+      id => Ok(id),
+    }
+  }
+
+  fn block_kind(keyword: &str) -> Option<BlockKind> {
+    match keyword {
+      "block" => Some(BlockKind::Block),
+      "loop" => Some(BlockKind::Loop),
+      "if" => Some(BlockKind::If),
+      "try" => Some(BlockKind::Try),
+      _ => None,
+    }
+  }
+
+  // NOTE: Keywords which terminate instructions of a block.
+  fn is_terminator(keyword: &str) -> bool {
+    match keyword {
+      "end" | "else" | "catch" | "catch_all" | "delegate" => true,
+      _ => false,
+    }
+  }
+
+  fn instructions(&mut self, expression: &mut Expression<'a>) -> Result<()> {
+    loop {
+      let line = self.line();
+      match self.peek() {
+        Some(TokenKind::LeftParen) => self.folded_instruction(expression)?,
+        Some(TokenKind::Keyword(x)) if !Parser::is_terminator(x) => {
+          let kind = match Parser::block_kind(x) {
+            Some(kind) => kind,
+            None => {
+              expression.push(self.plain_instruction()?);
+              continue;
+            }
+          };
+          self.advance();
+          let label = self.optional_id();
+          let block_type = self.type_use()?;
+          expression.push(Instruction {
+            kind: InstructionKind::Begin(kind, label, block_type),
+            line,
+          });
+          self.instructions(expression)?;
+          if kind == BlockKind::If && self.is_keyword("else") {
+            let line = self.line();
+            self.advance();
+            let id = self.closing_label(label)?;
+            expression.push(Instruction {
+              kind: InstructionKind::Else(id),
+              line,
+            });
+            self.instructions(expression)?;
+          }
+          if kind == BlockKind::Try && self.is_keyword("delegate") {
+            let line = self.line();
+            self.advance();
+            expression.push(Instruction {
+              kind: InstructionKind::Delegate(self.index()?),
+              line,
+            });
+            continue;
+          }
+          while kind == BlockKind::Try
+            && (self.is_keyword("catch") || self.is_keyword("catch_all"))
+          {
+            let line = self.line();
+            let clause = if self.is_keyword("catch") {
+              self.advance();
+              InstructionKind::Catch(self.index()?)
+            } else {
+              self.advance();
+              InstructionKind::CatchAll
+            };
+            expression.push(Instruction { kind: clause, line });
+            self.instructions(expression)?;
+          }
+          let line = self.line();
+          self.expect_keyword("end")?;
+          let id = self.closing_label(label)?;
+          expression.push(Instruction {
+            kind: InstructionKind::End(id),
+            line,
+          });
+        }
+        _ => return Ok(()),
+      }
+    }
+  }
+
+  fn folded_instruction(&mut self, expression: &mut Expression<'a>) -> Result<()> {
+    self.expect_left_paren()?;
+    let line = self.line();
+    let kind = match self.peek() {
+      Some(TokenKind::Keyword(x)) => Parser::block_kind(x),
+      _ => return Err(malformed(line)),
+    };
+    match kind {
+      Some(kind) => {
+        self.advance();
+        let label = self.optional_id();
+        let block_type = self.type_use()?;
+        let begin = Instruction {
+          kind: InstructionKind::Begin(kind, label, block_type),
+          line,
+        };
+        if kind == BlockKind::If {
+          while self.is_left_paren() && !self.is_field("then") {
+            self.folded_instruction(expression)?;
+          }
+          expression.push(begin);
+          self.expect_left_paren()?;
+          self.expect_keyword("then")?;
+          self.instructions(expression)?;
+          self.expect_right_paren()?;
+          if self.is_field("else") {
+            let line = self.line();
+            self.advance();
+            self.advance();
+            expression.push(Instruction {
+              kind: InstructionKind::Else(None),
+              line,
+            });
+            self.instructions(expression)?;
+            self.expect_right_paren()?;
+          }
+        } else if kind == BlockKind::Try {
+          expression.push(begin);
+          self.expect_left_paren()?;
+          self.expect_keyword("do")?;
+          self.instructions(expression)?;
+          self.expect_right_paren()?;
+          while self.is_field("catch") || self.is_field("catch_all") {
+            let line = self.line();
+            self.advance();
+            let clause = if self.is_keyword("catch") {
+              self.advance();
+              InstructionKind::Catch(self.index()?)
+            } else {
+              self.advance();
+              InstructionKind::CatchAll
+            };
+            expression.push(Instruction { kind: clause, line });
+            self.instructions(expression)?;
+            self.expect_right_paren()?;
+          }
+          if self.is_field("delegate") {
+            let line = self.line();
+            self.advance();
+            self.advance();
+            expression.push(Instruction {
+              kind: InstructionKind::Delegate(self.index()?),
+              line,
+            });
+            self.expect_right_paren()?;
+            return self.expect_right_paren();
+          }
+        } else {
+          expression.push(begin);
+          self.instructions(expression)?;
+        }
+        let line = self.line();
+        self.expect_right_paren()?;
+        expression.push(Instruction {
+          kind: InstructionKind::End(None),
+          line,
+        });
+      }
+      None => {
+        let instruction = self.plain_instruction()?;
+        while self.is_left_paren() {
+          self.folded_instruction(expression)?;
+        }
+        self.expect_right_paren()?;
+        expression.push(instruction);
+      }
+    }
+    Ok(())
+  }
+
+  // NOTE: Offset is written either by `(offset expr)` or a folded instruction.
+  fn offset(&mut self) -> Result<Expression<'a>> {
+    let mut expression = vec![];
+    if self.is_field("offset") {
+      self.advance();
+      self.advance();
+      self.instructions(&mut expression)?;
+      self.expect_right_paren()?;
+    } else {
+      self.folded_instruction(&mut expression)?;
+    }
+    Ok(expression)
+  }
+
+  fn zero_offset(line: u32) -> Expression<'a> {
+    vec![Instruction {
+      kind: InstructionKind::Plain(Opcode::Single(0x41), Immediate::I32, Operand::I32(0)),
+      line,
+    }]
+  }
+
+  fn bind(ids: &mut Vec<Option<&'a str>>, id: Option<&'a str>, line: u32) -> Result<u32> {
+    if id.is_some() && ids.contains(&id) {
+      return Err(malformed(line));
+    }
+    ids.push(id);
+    Ok(ids.len() as u32 - 1)
+  }
+
+  fn inline_exports(&mut self, kind: ExportKind, idx: u32) -> Result<()> {
+    while self.is_field("export") {
+      let line = self.line();
+      self.advance();
+      self.advance();
+      let name = self.name()?;
+      self.expect_right_paren()?;
+      self.module.exports.push(Export {
+        name,
+        kind,
+        index: Index::Num(idx),
+        line,
+      });
+    }
+    Ok(())
+  }
+
+  fn inline_import(&mut self) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+    if !self.is_field("import") {
+      return Ok(None);
+    }
+    self.advance();
+    self.advance();
+    let module = self.name()?;
+    let name = self.name()?;
+    self.expect_right_paren()?;
+    Ok(Some((module, name)))
+  }
+
+  fn push_import(
+    &mut self,
+    (module, name): (Vec<u8>, Vec<u8>),
+    descriptor: ImportDescriptor<'a>,
+    line: u32,
+  ) -> Result<()> {
+    if self.has_definition {
+      return Err(malformed(line));
+    }
+    self.module.imports.push(Import {
+      module,
+      name,
+      descriptor,
+      line,
+    });
+    Ok(())
+  }
+
+  fn type_field(&mut self, line: u32) -> Result<()> {
+    let id = self.optional_id();
+    Parser::bind(&mut self.module.type_ids, id, line)?;
+    self.expect_left_paren()?;
+    self.expect_keyword("func")?;
+    let signature = self.signature()?.unwrap_or_default();
+    self.expect_right_paren()?;
+    self.module.types.push(signature);
+    Ok(())
+  }
+
+  fn import_field(&mut self, line: u32) -> Result<()> {
+    let module = self.name()?;
+    let name = self.name()?;
+    self.expect_left_paren()?;
+    let kind_line = self.line();
+    let descriptor = match self.keyword()? {
+      "func" => {
+        let id = self.optional_id();
+        Parser::bind(&mut self.module.function_ids, id, line)?;
+        ImportDescriptor::Function(self.type_use()?)
+      }
+      "table" => {
+        let id = self.optional_id();
+        Parser::bind(&mut self.module.table_ids, id, line)?;
+        let limits = self.limits()?;
+        ImportDescriptor::Table(limits, self.element_type()?)
+      }
+      "memory" => {
+        let id = self.optional_id();
+        Parser::bind(&mut self.module.memory_ids, id, line)?;
+        ImportDescriptor::Memory(self.memory_limits()?)
+      }
+      "global" => {
+        let id = self.optional_id();
+        Parser::bind(&mut self.module.global_ids, id, line)?;
+        ImportDescriptor::Global(self.global_type()?)
+      }
+      "tag" => {
+        let id = self.optional_id();
+        Parser::bind(&mut self.module.tag_ids, id, line)?;
+        ImportDescriptor::Tag(self.type_use()?)
+      }
+      _ => return Err(malformed(kind_line)),
+    };
+    self.expect_right_paren()?;
+    self.push_import((module, name), descriptor, line)
+  }
+
+  fn function_field(&mut self, line: u32) -> Result<()> {
+    let id = self.optional_id();
+    let idx = Parser::bind(&mut self.module.function_ids, id, line)?;
+    self.inline_exports(ExportKind::Function, idx)?;
+    if let Some(import) = self.inline_import()? {
+      let type_use = self.type_use()?;
+      return self.push_import(import, ImportDescriptor::Function(type_use), line);
+    }
+    self.has_definition = true;
+    let type_use = self.type_use()?;
+    let mut locals = vec![];
+    while self.is_field("local") {
+      self.advance();
+      self.advance();
+      if let Some(id) = self.optional_id() {
+        locals.push((Some(id), self.value_type()?));
+      } else {
+        while let Some(TokenKind::Keyword(_)) = self.peek() {
+          locals.push((None, self.value_type()?));
+        }
+      }
+      self.expect_right_paren()?;
+    }
+    let mut body = vec![];
+    self.instructions(&mut body)?;
+    self.module.functions.push(Function {
+      type_use,
+      locals,
+      body,
+      line,
+    });
+    Ok(())
+  }
+
+  fn table_field(&mut self, line: u32) -> Result<()> {
+    let id = self.optional_id();
+    let idx = Parser::bind(&mut self.module.table_ids, id, line)?;
+    self.inline_exports(ExportKind::Table, idx)?;
+    if let Some(import) = self.inline_import()? {
+      let limits = self.limits()?;
+      let element_type = self.element_type()?;
+      return self.push_import(import, ImportDescriptor::Table(limits, element_type), line);
+    }
+    self.has_definition = true;
+    if let Some(TokenKind::Keyword(_)) = self.peek() {
+      let element_type = self.element_type()?;
+      self.expect_left_paren()?;
+      self.expect_keyword("elem")?;
+      let init = if self.is_left_paren() {
+        self.element_expressions()?
+      } else {
+        self.element_functions()?
+      };
+      self.expect_right_paren()?;
+      let count = match &init {
+        ElementInit::Functions(xs) => xs.len(),
+        ElementInit::Expressions(xs) => xs.len(),
+      } as u32;
+      self.module.tables.push((
+        Limits {
+          min: count,
+          max: Some(count),
+          shared: false,
+          page_size: None,
+        },
+        element_type,
+      ));
+      Parser::bind(&mut self.module.element_ids, None, line)?;
+      self.module.elements.push(Element {
+        mode: ElementMode::Active(Index::Num(idx), Parser::zero_offset(line)),
+        element_type,
+        init,
+        line,
+      });
+    } else {
+      let limits = self.limits()?;
+      let element_type = self.element_type()?;
+      self.module.tables.push((limits, element_type));
+    }
+    Ok(())
+  }
+
+  fn memory_field(&mut self, line: u32) -> Result<()> {
+    let id = self.optional_id();
+    let idx = Parser::bind(&mut self.module.memory_ids, id, line)?;
+    self.inline_exports(ExportKind::Memory, idx)?;
+    if let Some(import) = self.inline_import()? {
+      let limits = self.memory_limits()?;
+      return self.push_import(import, ImportDescriptor::Memory(limits), line);
+    }
+    self.has_definition = true;
+    if self.is_field("data") {
+      self.advance();
+      self.advance();
+      let bytes = self.strings()?;
+      self.expect_right_paren()?;
+      Parser::bind(&mut self.module.data_ids, None, line)?;
+      let pages = ((bytes.len() + PAGE_SIZE - 1) / PAGE_SIZE) as u32;
+      self.module.memories.push(Limits {
+        min: pages,
+        max: Some(pages),
+        shared: false,
+        page_size: None,
+      });
+      self.module.datas.push(Data {
+        memory: Index::Num(idx),
+        offset: Some(Parser::zero_offset(line)),
+        bytes,
+        line,
+      });
+    } else {
+      let limits = self.memory_limits()?;
+      self.module.memories.push(limits);
+    }
+    Ok(())
+  }
+
+  fn global_field(&mut self, line: u32) -> Result<()> {
+    let id = self.optional_id();
+    let idx = Parser::bind(&mut self.module.global_ids, id, line)?;
+    self.inline_exports(ExportKind::Global, idx)?;
+    if let Some(import) = self.inline_import()? {
+      let global_type = self.global_type()?;
+      return self.push_import(import, ImportDescriptor::Global(global_type), line);
+    }
+    self.has_definition = true;
+    let global_type = self.global_type()?;
+    let mut init = vec![];
+    self.instructions(&mut init)?;
+    self.module.globals.push(Global { global_type, init });
+    Ok(())
+  }
+
+  fn tag_field(&mut self, line: u32) -> Result<()> {
+    let id = self.optional_id();
+    let idx = Parser::bind(&mut self.module.tag_ids, id, line)?;
+    self.inline_exports(ExportKind::Tag, idx)?;
+    if let Some(import) = self.inline_import()? {
+      let type_use = self.type_use()?;
+      return self.push_import(import, ImportDescriptor::Tag(type_use), line);
+    }
+    self.has_definition = true;
+    let type_use = self.type_use()?;
+    self.module.tags.push((type_use, line));
+    Ok(())
+  }
+
+  fn export_field(&mut self, line: u32) -> Result<()> {
+    let name = self.name()?;
+    self.expect_left_paren()?;
+    let kind = match self.keyword()? {
+      "func" => ExportKind::Function,
+      "table" => ExportKind::Table,
+      "memory" => ExportKind::Memory,
+      "global" => ExportKind::Global,
+      "tag" => ExportKind::Tag,
+      _ => return Err(malformed(line)),
+    };
+    let index = self.index()?;
+    self.expect_right_paren()?;
+    self.module.exports.push(Export {
+      name,
+      kind,
+      index,
+      line,
+    });
+    Ok(())
+  }
+
+  fn start_field(&mut self, line: u32) -> Result<()> {
+    if self.module.start.is_some() {
+      return Err(malformed(line));
+    }
+    let index = self.index()?;
+    self.module.start = Some((index, line));
+    Ok(())
+  }
+
+  // NOTE: An index prior to offset refers a table or memory,
+  // unless `(table x)` or `(memory x)` follows it.
+  fn segment_target(&mut self, keyword: &str) -> Result<Index<'a>> {
+    let mut target = Index::Num(0);
+    if self.is_index() {
+      let index = self.index()?;
+      if !self.is_field(keyword) {
+        target = index;
+      }
+    }
+    if self.is_field(keyword) {
+      self.advance();
+      self.advance();
+      target = self.index()?;
+      self.expect_right_paren()?;
+    }
+    Ok(target)
+  }
+
+  fn element_functions(&mut self) -> Result<ElementInit<'a>> {
+    let mut functions = vec![];
+    while self.is_index() {
+      functions.push(self.index()?);
+    }
+    Ok(ElementInit::Functions(functions))
+  }
+
+  // NOTE: Each item is written either by `(item expr)` or a folded instruction.
+  fn element_expressions(&mut self) -> Result<ElementInit<'a>> {
+    let mut expressions = vec![];
+    while self.is_left_paren() {
+      let mut expression = vec![];
+      if self.is_field("item") {
+        self.advance();
+        self.advance();
+        self.instructions(&mut expression)?;
+        self.expect_right_paren()?;
+      } else {
+        self.folded_instruction(&mut expression)?;
+      }
+      expressions.push(expression);
+    }
+    Ok(ElementInit::Expressions(expressions))
+  }
+
+  fn element_field(&mut self, line: u32) -> Result<()> {
+    let id = self.optional_id();
+    Parser::bind(&mut self.module.element_ids, id, line)?;
+    let mode = if self.is_keyword("declare") {
+      self.advance();
+      ElementMode::Declarative
+    } else if self.is_left_paren() || self.is_index() {
+      let table = self.segment_target("table")?;
+      ElementMode::Active(table, self.offset()?)
+    } else {
+      ElementMode::Passive
+    };
+    let (element_type, init) = if self.is_element_type() {
+      let element_type = self.element_type()?;
+      (element_type, self.element_expressions()?)
+    } else {
+      if self.is_keyword("func") {
+        self.advance();
+      }
+      (0x70, self.element_functions()?)
+    };
+    self.module.elements.push(Element {
+      mode,
+      element_type,
+      init,
+      line,
+    });
+    Ok(())
+  }
+
+  fn data_field(&mut self, line: u32) -> Result<()> {
+    let id = self.optional_id();
+    Parser::bind(&mut self.module.data_ids, id, line)?;
+    let (memory, offset) = if self.is_left_paren() || self.is_index() {
+      (self.segment_target("memory")?, Some(self.offset()?))
+    } else {
+      (Index::Num(0), None)
+    };
+    let bytes = self.strings()?;
+    self.module.datas.push(Data {
+      memory,
+      offset,
+      bytes,
+      line,
+    });
+    Ok(())
+  }
+
+  fn field(&mut self) -> Result<()> {
+    self.expect_left_paren()?;
+    let line = self.line();
+    match self.keyword()? {
+      "type" => self.type_field(line)?,
+      "import" => self.import_field(line)?,
+      "func" => self.function_field(line)?,
+      "table" => self.table_field(line)?,
+      "memory" => self.memory_field(line)?,
+      "global" => self.global_field(line)?,
+      "tag" => self.tag_field(line)?,
+      "export" => self.export_field(line)?,
+      "start" => self.start_field(line)?,
+      "elem" => self.element_field(line)?,
+      "data" => self.data_field(line)?,
+      _ => return Err(malformed(line)),
+    };
+    self.expect_right_paren()
+  }
+
+  fn fields(&mut self) -> Result<()> {
+    while self.is_left_paren() {
+      self.field()?;
+    }
+    Ok(())
+  }
+
+  // NOTE: Expression from the left parenthesis to the matching one,
+  // so that a module in a script is parsed on its own.
+  pub(super) fn expression(&mut self) -> Result<Parser<'a>> {
+    let start = self.ptr;
+    let mut depth = 0;
+    loop {
+      match self.peek() {
+        Some(TokenKind::LeftParen) => depth += 1,
+        Some(TokenKind::RightParen) if depth > 0 => depth -= 1,
+        Some(_) if depth > 0 => {}
+        _ => return Err(malformed(self.line())),
+      }
+      self.advance();
+      if depth == 0 {
+        return Ok(Parser::new(self.tokens[start..self.ptr].to_vec()));
+      }
+    }
+  }
+
+  // NOTE: A module consists of only fields can omit `(module ...)`.
+  pub fn parse(mut self) -> Result<Parsed<'a>> {
+    let parsed = if self.is_field("module") {
+      self.advance();
+      self.advance();
+      self.optional_id();
+      let parsed = if self.is_keyword("binary") {
+        self.advance();
+        Parsed::Binary(self.strings()?)
+      } else if self.is_keyword("quote") {
+        self.advance();
+        Parsed::Quote(self.strings()?)
+      } else {
+        self.fields()?;
+        Parsed::Text(Module::default())
+      };
+      self.expect_right_paren()?;
+      parsed
+    } else {
+      self.fields()?;
+      Parsed::Text(Module::default())
+    };
+    if self.peek().is_some() {
+      return Err(malformed(self.line()));
+    }
+    Ok(match parsed {
+      Parsed::Text(_) => Parsed::Text(self.module),
+      parsed => parsed,
+    })
+  }
+}
@@ -0,0 +1,296 @@
+use super::lexer::{tokenize, TokenKind};
+use super::malformed;
+use super::number::{parse_f32, parse_f64, parse_i32, parse_i64, parse_u32};
+use super::parser::Parser;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::f32;
+use core::f64;
+use error::Result;
+use value::Values;
+
+/// Module defined by a script, which is translated into binary format.
+#[derive(Debug)]
+pub struct ScriptModule {
+  pub name: Option<String>,
+  // NOTE: Error of the text is kept to be asserted as malformed or invalid.
+  pub bytes: Result<Vec<u8>>,
+}
+
+#[derive(Debug)]
+pub enum Action {
+  Invoke {
+    module: Option<String>,
+    field: String,
+    args: Vec<Values>,
+  },
+  Get {
+    module: Option<String>,
+    field: String,
+  },
+}
+
+#[derive(Debug)]
+pub enum Expected {
+  Value(Values),
+  CanonicalNan,
+  ArithmeticNan,
+  // NOTE: Lanes of floating point vectors may be NaN respectively.
+  F32x4(Vec<Expected>),
+  F64x2(Vec<Expected>),
+  // NOTE: Any null reference, or any non-null function reference.
+  RefNull,
+  RefFunc,
+  Either(Vec<Expected>),
+}
+
+#[derive(Debug)]
+pub enum CommandKind {
+  Module(ScriptModule),
+  Register {
+    name: Option<String>,
+    as_name: String,
+  },
+  Action(Action),
+  AssertReturn {
+    action: Action,
+    expected: Vec<Expected>,
+  },
+  AssertTrap {
+    action: Action,
+    message: String,
+  },
+  AssertExhaustion {
+    action: Action,
+    message: String,
+  },
+  AssertException {
+    action: Action,
+  },
+  AssertMalformed {
+    module: ScriptModule,
+    message: String,
+  },
+  AssertInvalid {
+    module: ScriptModule,
+    message: String,
+  },
+  AssertUnlinkable {
+    module: ScriptModule,
+    message: String,
+  },
+  AssertUninstantiable {
+    module: ScriptModule,
+    message: String,
+  },
+}
+
+#[derive(Debug)]
+pub struct Command {
+  pub kind: CommandKind,
+  pub line: u32,
+}
+
+fn id(parser: &mut Parser) -> Option<String> {
+  parser.optional_id().map(String::from)
+}
+
+fn text(parser: &mut Parser) -> Result<String> {
+  let line = parser.line();
+  String::from_utf8(parser.name()?).map_err(|_| malformed(line))
+}
+
+fn module(parser: &mut Parser) -> Result<ScriptModule> {
+  let name = match parser.peek_at(2) {
+    Some(TokenKind::Id(x)) => Some(String::from(x)),
+    _ => None,
+  };
+  let bytes = parser.expression().and_then(|module| super::encode(module.parse()?));
+  Ok(ScriptModule { name, bytes })
+}
+
+fn float_lane(parser: &mut Parser, parse: fn(&str) -> Option<Values>) -> Result<Expected> {
+  let line = parser.line();
+  match parser.number()? {
+    "nan:canonical" => Ok(Expected::CanonicalNan),
+    "nan:arithmetic" => Ok(Expected::ArithmeticNan),
+    number => parse(number)
+      .map(Expected::Value)
+      .ok_or_else(|| malformed(line)),
+  }
+}
+
+fn f32_value(number: &str) -> Option<Values> {
+  parse_f32(number).map(|bits| Values::F32(f32::from_bits(bits)))
+}
+
+fn f64_value(number: &str) -> Option<Values> {
+  parse_f64(number).map(|bits| Values::F64(f64::from_bits(bits)))
+}
+
+// NOTE: Values are written as constant instructions, such as `(i32.const 0)`.
+fn value(parser: &mut Parser) -> Result<Expected> {
+  parser.expect_left_paren()?;
+  let line = parser.line();
+  let expected = match parser.keyword()? {
+    "i32.const" => {
+      let value = parse_i32(parser.number()?).ok_or_else(|| malformed(line))?;
+      Expected::Value(Values::I32(value))
+    }
+    "i64.const" => {
+      let value = parse_i64(parser.number()?).ok_or_else(|| malformed(line))?;
+      Expected::Value(Values::I64(value))
+    }
+    "f32.const" => float_lane(parser, f32_value)?,
+    "f64.const" => float_lane(parser, f64_value)?,
+    "v128.const" if parser.is_keyword("f32x4") => {
+      parser.advance();
+      let mut lanes = vec![];
+      for _ in 0..4 {
+        lanes.push(float_lane(parser, f32_value)?);
+      }
+      Expected::F32x4(lanes)
+    }
+    "v128.const" if parser.is_keyword("f64x2") => {
+      parser.advance();
+      let mut lanes = vec![];
+      for _ in 0..2 {
+        lanes.push(float_lane(parser, f64_value)?);
+      }
+      Expected::F64x2(lanes)
+    }
+    "v128.const" => Expected::Value(Values::V128(parser.v128()?)),
+    "ref.null" if parser.peek() == Some(TokenKind::RightParen) => Expected::RefNull,
+    "ref.null" => Expected::Value(match parser.heap_type()? {
+      0x70 => Values::FuncRef(None),
+      _ => Values::ExternRef(None),
+    }),
+    "ref.extern" => {
+      let value = parse_u32(parser.number()?).ok_or_else(|| malformed(line))?;
+      Expected::Value(Values::ExternRef(Some(value)))
+    }
+    "ref.func" => Expected::RefFunc,
+    "either" => {
+      let mut expected = vec![];
+      while parser.is_left_paren() {
+        expected.push(value(parser)?);
+      }
+      Expected::Either(expected)
+    }
+    _ => return Err(malformed(line)),
+  };
+  parser.expect_right_paren()?;
+  Ok(expected)
+}
+
+fn action(parser: &mut Parser) -> Result<Action> {
+  parser.expect_left_paren()?;
+  let line = parser.line();
+  let action = match parser.keyword()? {
+    "invoke" => {
+      let module = id(parser);
+      let field = text(parser)?;
+      let mut args = vec![];
+      while parser.is_left_paren() {
+        match value(parser)? {
+          Expected::Value(value) => args.push(value),
+          _ => return Err(malformed(line)),
+        }
+      }
+      Action::Invoke {
+        module,
+        field,
+        args,
+      }
+    }
+    "get" => {
+      let module = id(parser);
+      Action::Get {
+        module,
+        field: text(parser)?,
+      }
+    }
+    _ => return Err(malformed(line)),
+  };
+  parser.expect_right_paren()?;
+  Ok(action)
+}
+
+fn command(parser: &mut Parser) -> Result<Command> {
+  let line = parser.line();
+  if parser.is_field("module") {
+    let kind = CommandKind::Module(module(parser)?);
+    return Ok(Command { kind, line });
+  }
+  if parser.is_field("invoke") || parser.is_field("get") {
+    let kind = CommandKind::Action(action(parser)?);
+    return Ok(Command { kind, line });
+  }
+  parser.expect_left_paren()?;
+  let kind = match parser.keyword()? {
+    "register" => {
+      let as_name = text(parser)?;
+      CommandKind::Register {
+        name: id(parser),
+        as_name,
+      }
+    }
+    "assert_return" => {
+      let action = action(parser)?;
+      let mut expected = vec![];
+      while parser.is_left_paren() {
+        expected.push(value(parser)?);
+      }
+      CommandKind::AssertReturn { action, expected }
+    }
+    // NOTE: Assertions of NaN in former specs.
+    "assert_return_canonical_nan" => CommandKind::AssertReturn {
+      action: action(parser)?,
+      expected: vec![Expected::CanonicalNan],
+    },
+    "assert_return_arithmetic_nan" => CommandKind::AssertReturn {
+      action: action(parser)?,
+      expected: vec![Expected::ArithmeticNan],
+    },
+    "assert_trap" if parser.is_field("module") => CommandKind::AssertUninstantiable {
+      module: module(parser)?,
+      message: text(parser)?,
+    },
+    "assert_trap" => CommandKind::AssertTrap {
+      action: action(parser)?,
+      message: text(parser)?,
+    },
+    "assert_exhaustion" => CommandKind::AssertExhaustion {
+      action: action(parser)?,
+      message: text(parser)?,
+    },
+    "assert_exception" => CommandKind::AssertException {
+      action: action(parser)?,
+    },
+    "assert_malformed" => CommandKind::AssertMalformed {
+      module: module(parser)?,
+      message: text(parser)?,
+    },
+    "assert_invalid" => CommandKind::AssertInvalid {
+      module: module(parser)?,
+      message: text(parser)?,
+    },
+    "assert_unlinkable" => CommandKind::AssertUnlinkable {
+      module: module(parser)?,
+      message: text(parser)?,
+    },
+    _ => return Err(malformed(line)),
+  };
+  parser.expect_right_paren()?;
+  Ok(Command { kind, line })
+}
+
+/// Parse a script of the spec interpreter into its commands.
+pub fn parse_wast(source: &str) -> Result<Vec<Command>> {
+  let mut parser = Parser::new(tokenize(source)?);
+  let mut commands = vec![];
+  while parser.peek().is_some() {
+    commands.push(command(&mut parser)?);
+  }
+  Ok(commands)
+}
@@ -1,5 +1,3 @@
-extern crate wabt;
-
 #[cfg(test)]
 extern crate wasvm;
 use std::cell::RefCell;
@@ -8,38 +6,56 @@ use std::fs::File;
 use std::io::Read;
 use std::rc::Rc;
 use std::{f32, f64};
-use wabt::script::{Action, Command, CommandKind, ModuleBinary, ScriptParser, Value};
+use wasvm::script::{parse_wast, Action, Command, CommandKind, Expected, ScriptModule};
 use wasvm::{
   create_spectest, decode_module, init_store, instantiate_module, validate_module, ExternalModules,
-  ModuleInstance, Trap, Values, WasmError,
+  ModuleInstance, Store, Values, WasmError,
 };
 
-fn get_args(args: &[Value<f32, f64>]) -> Vec<Values> {
-  args
-    .iter()
-    .map(|v| match v {
-      Value::I32(value) => Values::I32(*value),
-      Value::I64(value) => Values::I64(*value),
-      Value::F32(value) => Values::F32(*value),
-      Value::F64(value) => Values::F64(*value),
-    })
-    .collect()
+fn is_expected(actual: &Values, expected: &Expected) -> bool {
+  match (actual, expected) {
+    // NOTE: Payload of NaN is not compared, as same as the former harness.
+    (Values::F32(n), Expected::Value(Values::F32(m))) if n.is_nan() => m.is_nan(),
+    (Values::F64(n), Expected::Value(Values::F64(m))) if n.is_nan() => m.is_nan(),
+    // NOTE: Floating point numbers are compared by bits to tell signs of zero.
+    (Values::F32(n), Expected::Value(Values::F32(m))) => n.to_bits() == m.to_bits(),
+    (Values::F64(n), Expected::Value(Values::F64(m))) => n.to_bits() == m.to_bits(),
+    (actual, Expected::Value(value)) => actual == value,
+    (Values::F32(n), Expected::CanonicalNan) => n.to_bits() & 0x7fff_ffff == 0x7fc0_0000,
+    (Values::F64(n), Expected::CanonicalNan) => {
+      n.to_bits() & 0x7fff_ffff_ffff_ffff == 0x7ff8_0000_0000_0000
+    }
+    (Values::F32(n), Expected::ArithmeticNan) => n.to_bits() & 0x7fc0_0000 == 0x7fc0_0000,
+    (Values::F64(n), Expected::ArithmeticNan) => {
+      n.to_bits() & 0x7ff8_0000_0000_0000 == 0x7ff8_0000_0000_0000
+    }
+    (Values::V128(v), Expected::F32x4(lanes)) => lanes.iter().enumerate().all(|(idx, lane)| {
+      let lane_value = Values::F32(f32::from_bits((v >> (idx * 32)) as u32));
+      is_expected(&lane_value, lane)
+    }),
+    (Values::V128(v), Expected::F64x2(lanes)) => lanes.iter().enumerate().all(|(idx, lane)| {
+      let lane_value = Values::F64(f64::from_bits((v >> (idx * 64)) as u64));
+      is_expected(&lane_value, lane)
+    }),
+    (Values::FuncRef(None), Expected::RefNull) | (Values::ExternRef(None), Expected::RefNull) => {
+      true
+    }
+    (Values::FuncRef(Some(_)), Expected::RefFunc) => true,
+    (actual, Expected::Either(expected)) => expected.iter().any(|e| is_expected(actual, e)),
+    _ => false,
+  }
 }
 
-fn get_expectation(expected: &[Value]) -> Values {
-  match expected.get(0) {
-    Some(Value::I32(v)) => Values::I32(*v),
-    Some(Value::I64(v)) => Values::I64(*v),
-    Some(Value::F32(v)) => Values::F32(*v),
-    Some(Value::F64(v)) => Values::F64(*v),
-    None => Values::I32(0),
-  }
+fn module_bytes(module: &ScriptModule) -> &[u8] {
+  module.bytes.as_ref().unwrap()
 }
 
 struct E2ETest<'a> {
-  parser: ScriptParser<f32, f64>,
+  commands: Vec<Command>,
   modules: HashMap<Option<String>, Rc<RefCell<ModuleInstance>>>,
   external_modules: ExternalModules,
+  // NOTE: Modules of a script are instantiated in the same store.
+  store: Store,
   file_name: &'a str,
 }
 
@@ -47,9 +63,9 @@ impl<'a> E2ETest<'a> {
   fn new(file_name: &'a str) -> Self {
     let mut buf = String::new();
     let test_filename = format!("./testsuite/{}.wast", file_name);
-    let mut json = File::open(&test_filename).unwrap();
-    json.read_to_string(&mut buf).unwrap();
-    let parser = ScriptParser::from_str(&buf).unwrap();
+    let mut file = File::open(&test_filename).unwrap();
+    file.read_to_string(&mut buf).unwrap();
+    let commands = parse_wast(&buf).unwrap();
     let modules = HashMap::new();
     let mut external_modules = ExternalModules::default();
     external_modules
@@ -57,29 +73,44 @@ impl<'a> E2ETest<'a> {
       .unwrap();
 
     E2ETest {
-      parser,
+      commands,
       modules,
       external_modules,
+      store: init_store(),
       file_name,
     }
   }
 
-  fn do_instantiate(&mut self, module: &ModuleBinary, name: &Option<String>) {
-    let bytes = module.clone().into_vec();
-    let store = init_store();
-    let section = decode_module(&bytes);
+  fn do_instantiate(&mut self, module: &ScriptModule) {
+    let store = self.store.clone();
+    let section = decode_module(module_bytes(module));
     let vm_ref = Rc::new(RefCell::new(
       instantiate_module(store, section, self.external_modules.clone(), 65536).unwrap(),
     ));
     self.modules.insert(None, vm_ref.clone());
-    self.modules.insert(name.clone(), vm_ref.clone());
+    self.modules.insert(module.name.clone(), vm_ref.clone());
   }
 
-  fn do_action(&mut self, field: &str, args: &[Value], module: &Option<String>, line: u64) {
-    println!("Perform action at {}:{}.", field, line);
+  fn perform(&self, action: &Action) -> Result<Vec<Values>, WasmError> {
+    let (field, args, module) = match action {
+      Action::Invoke {
+        ref field,
+        ref args,
+        ref module,
+      } => (field, args.clone(), module),
+      Action::Get {
+        ref field,
+        ref module,
+      } => (field, vec![], module),
+    };
     let vm_ref: Rc<RefCell<ModuleInstance>> = self.modules[module].clone();
     let mut vm = vm_ref.borrow_mut();
-    vm.run(field, get_args(args)).unwrap();
+    vm.run(field.as_ref(), args)
+  }
+
+  fn do_action(&mut self, action: &Action, line: u32) {
+    println!("Perform action at line:{}.", line);
+    self.perform(action).unwrap();
   }
 
   fn do_register(&mut self, name: &Option<String>, as_name: &str) {
@@ -96,60 +127,38 @@ impl<'a> E2ETest<'a> {
       .unwrap();
   }
 
-  fn assert_return(&self, action: &Action, expected: &[Value], line: u64) {
-    let (field, args, module) = match action {
-      Action::Invoke {
-        ref field,
-        ref args,
-        ref module,
-      } => (field, get_args(args), module),
-      Action::Get {
-        ref field,
-        ref module,
-      } => (field, vec![], module),
-    };
-    println!("Assert return at {}:{}.", field, line);
-    let vm_ref: Rc<RefCell<ModuleInstance>> = self.modules[module].clone();
-    let mut vm = vm_ref.borrow_mut();
-    let actual = vm.run(field.as_ref(), args).unwrap();
-    let expectation = get_expectation(expected);
-    match actual {
-      Values::F32(n) if n.is_nan() => match expectation {
-        Values::F32(m) => assert!(m.is_nan()),
-        _ => unreachable!(),
-      },
-      Values::F64(n) if n.is_nan() => match expectation {
-        Values::F64(m) => assert!(m.is_nan()),
-        _ => unreachable!(),
-      },
-      _ => assert_eq!(actual, expectation),
-    };
-  }
-  fn assert_trap(&mut self, action: &Action, _message: &str, line: u64) {
-    match action {
-      Action::Invoke {
-        ref field,
-        ref args,
-        ref module,
-      } => {
-        println!("Assert trap at {}:{}.", field, line,);
-        let vm_ref: Rc<RefCell<ModuleInstance>> = self.modules[module].clone();
-        let mut vm = vm_ref.borrow_mut();
-        vm.run(field, get_args(args)).unwrap_err();
-      }
-      x => unreachable!("{:?}", x),
+  fn assert_return(&self, action: &Action, expected: &[Expected], line: u32) {
+    println!("Assert return at line:{}.", line);
+    let actual = self.perform(action).unwrap();
+    assert_eq!(actual.len(), expected.len());
+    for (actual, expected) in actual.iter().zip(expected.iter()) {
+      assert!(
+        is_expected(actual, expected),
+        "{:?} is expected, but got {:?}",
+        expected,
+        actual
+      );
     }
   }
 
-  fn assert_uninstantiable(&mut self, module: &ModuleBinary, _message: &str, line: u64) {
+  fn assert_trap(&mut self, action: &Action, _message: &str, line: u32) {
+    println!("Assert trap at line:{}.", line);
+    self.perform(action).unwrap_err();
+  }
+
+  fn assert_exception(&mut self, action: &Action, line: u32) {
+    println!("Assert exception at line:{}.", line);
+    self.perform(action).unwrap_err();
+  }
+
+  fn assert_uninstantiable(&mut self, module: &ScriptModule, _message: &str, line: u32) {
     println!("Assert uninstantiable at line:{}.", line);
-    let bytes = module.clone().into_vec();
     let store = init_store();
-    let module = decode_module(&bytes);
-    instantiate_module(store, module, Default::default(), 65536).unwrap_err();
+    let section = decode_module(module_bytes(module));
+    instantiate_module(store, section, self.external_modules.clone(), 65536).unwrap_err();
   }
 
-  fn assert_malformed(&self, module: &ModuleBinary, _message: &str, line: u64) {
+  fn assert_malformed(&self, module: &ScriptModule, _message: &str, line: u32) {
     if (self.file_name == "custom_section" && line == 77)
       || (self.file_name == "custom_section" && line == 94)
       || (self.file_name == "custom" && line == 85)
@@ -157,75 +166,40 @@ impl<'a> E2ETest<'a> {
       println!("Skip {}, it seems can't resolvable yet...", line);
       return;
     };
-    let bytes = module.clone().into_vec();
-    let store = init_store();
-    let module = decode_module(&bytes);
-    let err = instantiate_module(store, module, Default::default(), 65536).unwrap_err();
-    if let WasmError::Trap(Trap::UnsupportedTextform) = err {
-      println!("Skip malformed text form at line:{}.", line);
-      return;
-    };
-    println!("Assert malformed at {}.", line,);
+    println!("Assert malformed at {}.", line);
+    // NOTE: Malformed text is rejected by the parser of scripts.
+    if let Ok(ref bytes) = module.bytes {
+      let store = init_store();
+      let section = decode_module(bytes);
+      instantiate_module(store, section, Default::default(), 65536).unwrap_err();
+    }
   }
 
-  fn assert_invalid(&self, message: &str, module: &ModuleBinary, line: u64) {
+  fn assert_invalid(&self, module: &ScriptModule, message: &str, line: u32) {
     println!("Assert invalid at {}:{}.", message, line);
-    let bytes = module.clone().into_vec();
-    let section = decode_module(&bytes);
-    validate_module(&section).unwrap_err();
-  }
-
-  fn assert_nan(&self, action: &Action, line: u64) {
-    match action {
-      Action::Invoke {
-        ref field,
-        ref args,
-        ref module,
-      } => {
-        println!("Assert NaN at '{}:{}'.", field, line);
-        let vm_ref: Rc<RefCell<ModuleInstance>> = self.modules[module].clone();
-        let mut vm = vm_ref.borrow_mut();
-        let actual = vm.run(field.as_ref(), get_args(args)).unwrap();
-        match actual {
-          Values::F32(n) => assert!(n.is_nan()),
-          Values::F64(n) => assert!(n.is_nan()),
-          _ => unreachable!(),
-        };
-      }
-      x => unreachable!("{:?}", x),
+    if let Ok(ref bytes) = module.bytes {
+      let section = decode_module(bytes);
+      validate_module(&section).unwrap_err();
     }
   }
 
-  fn assert_unlinkable(&self, module: &ModuleBinary, _message: &str, line: u64) {
-    let bytes = module.clone().into_vec();
-    let tmp_bytes = bytes.clone();
-    let (magic_numbers, _) = tmp_bytes.split_at(8);
-    if magic_numbers == [0u8, 97, 115, 109, 1, 0, 0, 0] {
-      println!("Assert unlinkable at {}.", line,);
-      let store = init_store();
-      let section = decode_module(&bytes);
-      instantiate_module(store, section, self.external_modules.clone(), 65536).unwrap_err();
-    } else {
-      println!("Skip unlinkable text form at line:{}.", line);
-    };
+  fn assert_unlinkable(&self, module: &ScriptModule, _message: &str, line: u32) {
+    println!("Assert unlinkable at {}.", line);
+    let store = init_store();
+    let section = decode_module(module_bytes(module));
+    instantiate_module(store, section, self.external_modules.clone(), 65536).unwrap_err();
   }
 
   fn do_test(&mut self) {
-    while let Ok(Some(Command { kind, line, .. })) = self.parser.next() {
+    let commands = std::mem::replace(&mut self.commands, vec![]);
+    for Command { kind, line } in commands.iter() {
+      let line = *line;
       match kind {
-        CommandKind::Module {
-          ref module,
-          ref name,
-        } => self.do_instantiate(module, name),
-        CommandKind::PerformAction(Action::Invoke {
-          ref field,
-          ref args,
-          ref module,
-        }) => self.do_action(field, args, module, line),
+        CommandKind::Module(ref module) => self.do_instantiate(module),
+        CommandKind::Action(ref action) => self.do_action(action, line),
         CommandKind::Register {
           ref name,
           ref as_name,
-          ..
         } => self.do_register(name, as_name),
         CommandKind::AssertReturn {
           ref action,
@@ -235,6 +209,7 @@ impl<'a> E2ETest<'a> {
           ref action,
           ref message,
         } => self.assert_trap(action, message, line),
+        CommandKind::AssertException { ref action } => self.assert_exception(action, line),
         CommandKind::AssertUninstantiable {
           ref module,
           ref message,
@@ -243,24 +218,16 @@ impl<'a> E2ETest<'a> {
           ref module,
           ref message,
         } => self.assert_malformed(module, message, line),
-        CommandKind::AssertReturnCanonicalNan { ref action } => self.assert_nan(action, line),
-        CommandKind::AssertReturnArithmeticNan { ref action } => self.assert_nan(action, line),
         CommandKind::AssertUnlinkable {
           ref module,
           ref message,
         } => self.assert_unlinkable(module, message, line),
         // FIXME: Enable specs
-        CommandKind::AssertExhaustion {
-          action: Action::Invoke { ref field, .. },
-        } => println!("Skip exhaustion line:{}:{}.", field, line),
+        CommandKind::AssertExhaustion { .. } => println!("Skip exhaustion line:{}.", line),
         CommandKind::AssertInvalid {
-          ref message,
           ref module,
-        } => self.assert_invalid(message, module, line),
-        x => unreachable!(
-          "there are no other commands apart from that defined above {:?}",
-          x
-        ),
+          ref message,
+        } => self.assert_invalid(module, message, line),
       }
     }
   }
@@ -287,14 +254,16 @@ impl_e2e!(test_br_if, "br_if");
 impl_e2e!(test_br_table, "br_table");
 impl_e2e!(test_br_only, "br");
 impl_e2e!(test_break_drop, "break-drop");
+impl_e2e!(test_bulk, "bulk");
 impl_e2e!(test_call_indirect, "call_indirect");
 impl_e2e!(test_call, "call");
 impl_e2e!(test_comments, "comments");
 impl_e2e!(test_const, "const"); /* All specs suppose Text-format */
-impl_e2e!(test_conversions, "conversions");
+impl_e2e!(test_conversions, "conversions"); /* Includes saturating truncations */
 impl_e2e!(test_custom_section, "custom_section");
 impl_e2e!(test_custom_simple, "custom");
 impl_e2e!(test_data, "data");
+impl_e2e!(test_delegate, "proposals/exception-handling/delegate");
 impl_e2e!(test_elem, "elem");
 impl_e2e!(test_endianness, "endianness");
 impl_e2e!(test_exports, "exports");
@@ -314,8 +283,8 @@ impl_e2e!(test_func_ptrs, "func_ptrs");
 impl_e2e!(test_func, "func");
 impl_e2e!(test_get_local, "get_local");
 impl_e2e!(test_globals, "globals");
-impl_e2e!(test_i32, "i32");
-impl_e2e!(test_i64, "i64");
+impl_e2e!(test_i32, "i32"); /* Includes sign-extension operators */
+impl_e2e!(test_i64, "i64"); /* Includes sign-extension operators */
 impl_e2e!(test_if, "if");
 impl_e2e!(test_imports, "imports");
 impl_e2e!(test_inline_module, "inline-module");
@@ -325,24 +294,100 @@ impl_e2e!(test_labels, "labels");
 impl_e2e!(test_left_to_right, "left-to-right");
 impl_e2e!(test_linking, "linking");
 impl_e2e!(test_loop, "loop");
+impl_e2e!(test_memory_copy, "memory_copy");
+impl_e2e!(test_memory_fill, "memory_fill");
 impl_e2e!(test_memory_grow, "memory_grow");
+impl_e2e!(test_memory_init, "memory_init");
 impl_e2e!(test_memory_redundancy, "memory_redundancy");
 impl_e2e!(test_memory_trap, "memory_trap");
 impl_e2e!(test_memory_only, "memory");
 impl_e2e!(test_names, "names");
 impl_e2e!(test_nop, "nop");
+impl_e2e!(test_ref_func, "ref_func");
+impl_e2e!(test_ref_is_null, "ref_is_null");
+impl_e2e!(test_ref_null, "ref_null");
 impl_e2e!(test_resizing, "resizing");
+impl_e2e!(test_rethrow, "proposals/exception-handling/rethrow");
 impl_e2e!(test_return, "return");
+impl_e2e!(test_return_call, "proposals/tail-call/return_call");
+impl_e2e!(test_return_call_indirect, "proposals/tail-call/return_call_indirect");
 impl_e2e!(test_select, "select");
 impl_e2e!(test_set_local, "set_local");
+impl_e2e!(test_simd_address, "simd_address");
+impl_e2e!(test_simd_align, "simd_align");
+impl_e2e!(test_simd_bit_shift, "simd_bit_shift");
+impl_e2e!(test_simd_bitwise, "simd_bitwise");
+impl_e2e!(test_simd_boolean, "simd_boolean");
+impl_e2e!(test_simd_const, "simd_const");
+impl_e2e!(test_simd_conversions, "simd_conversions");
+impl_e2e!(test_simd_f32x4, "simd_f32x4");
+impl_e2e!(test_simd_f32x4_arith, "simd_f32x4_arith");
+impl_e2e!(test_simd_f32x4_cmp, "simd_f32x4_cmp");
+impl_e2e!(test_simd_f32x4_pmin_pmax, "simd_f32x4_pmin_pmax");
+impl_e2e!(test_simd_f32x4_rounding, "simd_f32x4_rounding");
+impl_e2e!(test_simd_f64x2, "simd_f64x2");
+impl_e2e!(test_simd_f64x2_arith, "simd_f64x2_arith");
+impl_e2e!(test_simd_f64x2_cmp, "simd_f64x2_cmp");
+impl_e2e!(test_simd_f64x2_pmin_pmax, "simd_f64x2_pmin_pmax");
+impl_e2e!(test_simd_f64x2_rounding, "simd_f64x2_rounding");
+impl_e2e!(test_simd_i16x8_arith, "simd_i16x8_arith");
+impl_e2e!(test_simd_i16x8_arith2, "simd_i16x8_arith2");
+impl_e2e!(test_simd_i16x8_cmp, "simd_i16x8_cmp");
+impl_e2e!(test_simd_i16x8_extadd_pairwise_i8x16, "simd_i16x8_extadd_pairwise_i8x16");
+impl_e2e!(test_simd_i16x8_extmul_i8x16, "simd_i16x8_extmul_i8x16");
+impl_e2e!(test_simd_i16x8_q15mulr_sat_s, "simd_i16x8_q15mulr_sat_s");
+impl_e2e!(test_simd_i16x8_sat_arith, "simd_i16x8_sat_arith");
+impl_e2e!(test_simd_i32x4_arith, "simd_i32x4_arith");
+impl_e2e!(test_simd_i32x4_arith2, "simd_i32x4_arith2");
+impl_e2e!(test_simd_i32x4_cmp, "simd_i32x4_cmp");
+impl_e2e!(test_simd_i32x4_dot_i16x8, "simd_i32x4_dot_i16x8");
+impl_e2e!(test_simd_i32x4_extadd_pairwise_i16x8, "simd_i32x4_extadd_pairwise_i16x8");
+impl_e2e!(test_simd_i32x4_extmul_i16x8, "simd_i32x4_extmul_i16x8");
+impl_e2e!(test_simd_i32x4_trunc_sat_f32x4, "simd_i32x4_trunc_sat_f32x4");
+impl_e2e!(test_simd_i32x4_trunc_sat_f64x2, "simd_i32x4_trunc_sat_f64x2");
+impl_e2e!(test_simd_i64x2_arith, "simd_i64x2_arith");
+impl_e2e!(test_simd_i64x2_arith2, "simd_i64x2_arith2");
+impl_e2e!(test_simd_i64x2_cmp, "simd_i64x2_cmp");
+impl_e2e!(test_simd_i64x2_extmul_i32x4, "simd_i64x2_extmul_i32x4");
+impl_e2e!(test_simd_i8x16_arith, "simd_i8x16_arith");
+impl_e2e!(test_simd_i8x16_arith2, "simd_i8x16_arith2");
+impl_e2e!(test_simd_i8x16_cmp, "simd_i8x16_cmp");
+impl_e2e!(test_simd_i8x16_sat_arith, "simd_i8x16_sat_arith");
+impl_e2e!(test_simd_int_to_int_extend, "simd_int_to_int_extend");
+impl_e2e!(test_simd_lane, "simd_lane");
+impl_e2e!(test_simd_linking, "simd_linking");
+impl_e2e!(test_simd_load, "simd_load");
+impl_e2e!(test_simd_load16_lane, "simd_load16_lane");
+impl_e2e!(test_simd_load32_lane, "simd_load32_lane");
+impl_e2e!(test_simd_load64_lane, "simd_load64_lane");
+impl_e2e!(test_simd_load8_lane, "simd_load8_lane");
+impl_e2e!(test_simd_load_extend, "simd_load_extend");
+impl_e2e!(test_simd_load_splat, "simd_load_splat");
+impl_e2e!(test_simd_load_zero, "simd_load_zero");
+impl_e2e!(test_simd_splat, "simd_splat");
+impl_e2e!(test_simd_store, "simd_store");
+impl_e2e!(test_simd_store16_lane, "simd_store16_lane");
+impl_e2e!(test_simd_store32_lane, "simd_store32_lane");
+impl_e2e!(test_simd_store64_lane, "simd_store64_lane");
+impl_e2e!(test_simd_store8_lane, "simd_store8_lane");
 impl_e2e!(test_skip_stack_guard_page, "skip-stack-guard-page");
 impl_e2e!(test_stack, "stack");
 impl_e2e!(test_start, "start");
 impl_e2e!(test_store_retval, "store_retval");
 impl_e2e!(test_switch, "switch");
+impl_e2e!(test_table_only, "table");
+impl_e2e!(test_table_copy, "table_copy");
+impl_e2e!(test_table_fill, "table_fill");
+impl_e2e!(test_table_get, "table_get");
+impl_e2e!(test_table_grow, "table_grow");
+impl_e2e!(test_table_init, "table_init");
+impl_e2e!(test_table_set, "table_set");
+impl_e2e!(test_table_size, "table_size");
 impl_e2e!(test_tee_local, "tee_local");
+impl_e2e!(test_throw, "proposals/exception-handling/throw");
 impl_e2e!(test_token, "token");
 impl_e2e!(test_traps, "traps");
+impl_e2e!(test_try_catch, "proposals/exception-handling/try_catch");
 impl_e2e!(test_type, "type");
 impl_e2e!(test_typecheck, "typecheck");
 impl_e2e!(test_unreachable, "unreachable");